@@ -1,8 +1,9 @@
-use selfhash::{HashFunctionT, SelfHashableJSON, SelfHashableT};
+use selfhash::{HashFunctionT, SelfHashableJSON, SelfHashableT, SelfHashableText, SelfHashableXML};
 use std::{
     borrow::Cow,
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     io::{Read, Write},
+    path::{Path, PathBuf},
 };
 
 /// Operate on JSON as self-hashable data -- data which is self-validating.  In particular, self-hashable data is
@@ -11,65 +12,815 @@ use std::{
 /// placeholder value which encodes which hash function will be used, the data is serialized into JCS (JSON
 /// Canonicalization Scheme), and then hashed.  This hash value is then used to set all the self-hash slots.
 /// The data is then serialized into JCS again, and at this point is self-hashed and fully self-verifiable.
+/// YAML is also supported (see --format), and is self-hashed exactly as the corresponding JSON would be.
+/// XML is also supported, and is self-hashed using Exclusive XML Canonicalization instead of JCS.  Text files
+/// are also supported, and are self-hashed over their raw bytes, with self-hash slots found by marker.
 #[derive(clap::Parser)]
-#[clap(version, about)]
+#[clap(version, about, after_help = EXIT_STATUS_HELP)]
 enum CLI {
-    /// Read JSON from stdin, compute its self-hash, and output canonical JSON (JCS) with its self-hash [URL] path(s)
-    /// set (see --self-hash-paths and --self-hash-url-paths), overwriting any existing self-hash [URL] path(s).
+    /// Read JSON from stdin (or the given files), compute its self-hash, and output canonical JSON (JCS) with its
+    /// self-hash [URL] path(s) set (see --self-hash-paths and --self-hash-url-paths), overwriting any existing
+    /// self-hash [URL] path(s).
     Compute(Compute),
-    /// Read JSON from stdin, verify its self-hash(es) (see --self-hash-paths and --self-hash-url-paths),
-    /// and print the verified self-hash.
+    /// Read JSON from stdin (or the given files), verify its self-hash(es) (see --self-hash-paths and
+    /// --self-hash-url-paths), and print the verified self-hash.
     Verify(Verify),
+    /// Compute or verify the self-hashed manifest of a directory tree.
+    #[command(subcommand)]
+    Tree(Tree),
+    /// Print the supported hash functions (see --hash-function), one per line, along with their multicodec
+    /// codes and placeholder hashes in the given base.
+    ListHashFunctions(ListHashFunctions),
 }
 
+/// Describes the exit status (see ErrorKind) in the help message.
+const EXIT_STATUS_HELP: &str = "Exit status: 0 on success, 1 for an I/O error, 2 for a configuration error (e.g. invalid arguments), 3 for a parse error, 4 for a missing self-hash slot, 5 for a self-hash mismatch, and 6 for an unsupported hash function.";
+
 impl CLI {
-    fn handle(self) {
+    fn handle(self) -> Result<(), CLIError> {
         match self {
             Self::Compute(x) => x.handle(),
             Self::Verify(x) => x.handle(),
+            Self::Tree(x) => x.handle(),
+            Self::ListHashFunctions(x) => x.handle(),
         }
     }
 }
 
 #[derive(clap::Args)]
 struct SelfHashArgs {
-    /// Optionally specify a comma-delimited list of JSONPath queries that are considered self-hash slots.
-    /// Note that while each self-hash field (i.e. self-hash path query result) doesn't have to exist already,
-    /// its parent must exist.  Each self-hash path must end with a plain field name (not a wildcard and not
-    /// a bracket-enclosed field name).  See https://en.wikipedia.org/wiki/JSONPath for details on JSONPath.
-    #[arg(short, long, default_value = "$.selfHash", value_name = "PATHS")]
-    self_hash_paths: String,
-    /// Optionally specify a comma-delimited list of JSONPath queries that are considered self-hash URL slots.
+    /// Optionally specify JSONPath queries that are considered self-hash slots.  This argument may be
+    /// repeated, and each value may be a comma-delimited list, where only commas outside of brackets,
+    /// parentheses, and quotes are delimiters (so e.g. `$['a,b']` is a single query).  Note that while
+    /// each self-hash field (i.e. self-hash path query result) doesn't have to exist already, its parent
+    /// must exist.  Each self-hash path is an RFC 9535 query, e.g. `$.selfHash`, `$['@id']`, or
+    /// `$.verificationMethod[*].controller`; a missing field is only inserted if its parent is unambiguous.
+    /// See https://www.rfc-editor.org/rfc/rfc9535.html for details on JSONPath.  For XML, each self-hash
+    /// path is instead an XPath-like selector for an attribute or element, e.g. `/record/@selfHash` or
+    /// `/record/header/selfHash`, and only a missing attribute is inserted.  [default: $.selfHash (or
+    /// /*/@selfHash for XML), unless --self-hash-pointers, --self-hash-url-pointers, or
+    /// --self-hash-template is specified]
+    #[arg(short, long, action = clap::ArgAction::Append, value_name = "PATHS")]
+    self_hash_paths: Vec<String>,
+    /// Optionally specify JSONPath queries that are considered self-hash URL slots.  As with
+    /// --self-hash-paths, this argument may be repeated, and each value may be a comma-delimited list.
     /// Note that each self-hash URL field (i.e. self-hash URL path query result) must already exist and be a
-    /// valid self-hash URL (a valid default is "vjson:///").  Each self-hash URL path must end with a
-    /// plain field name (not a wildcard and not a bracket-enclosed field name).  See
-    /// https://en.wikipedia.org/wiki/JSONPath for details on JSONPath.
-    #[arg(short = 'u', long, default_value = "", value_name = "PATHS")]
-    self_hash_url_paths: String,
+    /// valid self-hash URL, which is either of the form "vjson:///<self-hash>" or an absolute URL with a
+    /// `selfHash` query parameter, e.g. "https://api.example.com/records?selfHash=&v=1" (valid defaults are
+    /// "vjson:///" or an empty `selfHash` query parameter).  Each self-hash URL path is an RFC 9535 query,
+    /// e.g. `$.selfHashURL`, `$['$id']`, or `$.service[*].id`.  See
+    /// https://www.rfc-editor.org/rfc/rfc9535.html for details on JSONPath.  For XML, each self-hash URL
+    /// path is instead an XPath-like selector, as with --self-hash-paths.
+    #[arg(short = 'u', long, action = clap::ArgAction::Append, value_name = "PATHS")]
+    self_hash_url_paths: Vec<String>,
+    /// Optionally specify JSON Pointers (e.g. `/selfHash` or `/proof/0/id`) that are considered self-hash
+    /// slots.  This argument may be repeated, and each value may be a comma-delimited list, where only
+    /// commas followed by `/` are delimiters (so e.g. `/a,b` is a single pointer).  As with
+    /// --self-hash-paths, each self-hash field doesn't have to exist already, but its parent must exist.
+    /// See https://www.rfc-editor.org/rfc/rfc6901 for details on JSON Pointer.
+    #[arg(long, action = clap::ArgAction::Append, value_name = "POINTERS")]
+    self_hash_pointers: Vec<String>,
+    /// Optionally specify JSON Pointers that are considered self-hash URL slots.  As with
+    /// --self-hash-pointers, this argument may be repeated, and each value may be a comma-delimited list.
+    /// As with --self-hash-url-paths, each self-hash URL field must already exist and be a valid self-hash
+    /// URL.  See https://www.rfc-editor.org/rfc/rfc6901 for details on JSON Pointer.
+    #[arg(long, action = clap::ArgAction::Append, value_name = "POINTERS")]
+    self_hash_url_pointers: Vec<String>,
+    /// Optionally specify a self-hash template slot, given by a selector (a JSONPath query if it begins
+    /// with `$`, otherwise a JSON Pointer) and a template containing exactly one `{selfHash}` placeholder,
+    /// e.g. `--self-hash-template '$.id' 'did:example:{selfHash}'`.  Each selected field must be a string
+    /// matching the template (with an empty self-hash meaning unset) or null, or may be missing.  This
+    /// argument may be repeated.
+    #[arg(long, num_args = 2, value_names = ["SELECTOR", "TEMPLATE"])]
+    self_hash_template: Vec<String>,
+    /// Specify the format of the input (and for compute, of the output).  YAML is parsed into the JSON
+    /// data model (tags, anchors, aliases, and non-string keys are rejected) and self-hashed exactly as
+    /// the corresponding JSON would be, so a YAML document and its JSON counterpart have the same self-hash.
+    /// XML is self-hashed over its Exclusive XML Canonicalization, and is output in that form; JSON
+    /// Pointers and self-hash templates don't apply to it.  Text is self-hashed over its raw bytes (see
+    /// --self-hash-regex, --self-hash-text-template, and --line-endings), and is output as it was input,
+    /// except for its self-hash slots (and without a trailing newline being added).
+    #[arg(short, long, value_enum, default_value_t = Format::JSON)]
+    format: Format,
+    /// For text, optionally specify a regex whose matches are self-hash slots.  The regex must contain a
+    /// capture group named `selfHash` (or exactly one capture group) delimiting the self-hash, where an
+    /// empty capture means unset, e.g. `(?m)^// selfhash: (?P<selfHash>\S*)$`.  This argument may be
+    /// repeated.  [default: --self-hash-text-template 'selfhash: {selfHash}', unless --self-hash-regex is
+    /// specified]
+    #[arg(long, value_name = "REGEX")]
+    self_hash_regex: Vec<String>,
+    /// For text, optionally specify a template whose matches are self-hash slots, containing exactly one
+    /// `{selfHash}` placeholder, e.g. `selfhash: {selfHash}`.  All other characters are matched literally,
+    /// and the placeholder matches a (possibly empty) self-hash.  This argument may be repeated.
+    #[arg(long, value_name = "TEMPLATE")]
+    self_hash_text_template: Vec<String>,
+    /// For text, specify how line endings are normalized when computing the digest.  The text itself is
+    /// never modified other than to set its self-hash slots.
+    #[arg(long, value_enum, default_value_t = LineEndings::None)]
+    line_endings: LineEndings,
+}
+
+#[derive(clap::Args)]
+struct HashFunctionArgs {
+    /// Specify the hash function to self-hash with, by name (see the list-hash-functions subcommand).
+    #[arg(long, default_value = "BLAKE3", value_name = "NAME")]
+    hash_function: String,
+    #[command(flatten)]
+    base_args: BaseArgs,
+}
+
+impl HashFunctionArgs {
+    fn mb_hash_function(&self) -> Result<selfhash::MBHashFunction, CLIError> {
+        Ok(parse_named_hash_function(&self.hash_function)?
+            .as_mb_hash_function(self.base_args.base.into()))
+    }
+}
+
+#[derive(clap::Args)]
+struct BaseArgs {
+    /// Specify the multibase encoding of the self-hash, by name or by multibase prefix.  A base which isn't
+    /// URL-safe (e.g. base64) can't be used with self-hash URL slots.
+    #[arg(long, value_enum, default_value_t = Base::Base64Url)]
+    base: Base,
+}
+
+/// Parse a hash function name, which must be one whose feature is enabled.
+fn parse_named_hash_function(s: &str) -> Result<selfhash::NamedHashFunction, CLIError> {
+    let named_hash_function = s
+        .parse::<selfhash::NamedHashFunction>()
+        .map_err(|e| CLIError::new(ErrorKind::UnsupportedAlgorithm, e))?;
+    if !named_hash_function.is_enabled() {
+        return Err(CLIError::new(
+            ErrorKind::UnsupportedAlgorithm,
+            format!(
+                "hash function {} is not supported by this build",
+                named_hash_function
+            ),
+        ));
+    }
+    Ok(named_hash_function)
+}
+
+/// The multibase encodings of a self-hash, which are all of mbx::Base except Identity, since a self-hash
+/// is text and the identity "encoding" is raw bytes.  A base which isn't URL-safe can't be used with
+/// self-hash URL slots (e.g. `vjson:///<self-hash>` or `did:selfhash:<self-hash>`).
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum Base {
+    /// Binary (multibase prefix `0`).
+    #[value(name = "base2", alias = "0")]
+    Base2,
+    /// Octal (multibase prefix `7`).
+    #[value(name = "base8", alias = "7")]
+    Base8,
+    /// Decimal (multibase prefix `9`).
+    #[value(name = "base10", alias = "9")]
+    Base10,
+    /// Lowercase hexadecimal (multibase prefix `f`).
+    #[value(name = "base16-lower", alias = "f")]
+    Base16Lower,
+    /// Uppercase hexadecimal (multibase prefix `F`).
+    #[value(name = "base16-upper", alias = "F")]
+    Base16Upper,
+    /// Lowercase RFC 4648 base32hex without padding (multibase prefix `v`).
+    #[value(name = "base32-hex-lower", alias = "v")]
+    Base32HexLower,
+    /// Uppercase RFC 4648 base32hex without padding (multibase prefix `V`).
+    #[value(name = "base32-hex-upper", alias = "V")]
+    Base32HexUpper,
+    /// Lowercase RFC 4648 base32hex with padding (multibase prefix `t`).  Not URL-safe.
+    #[value(name = "base32-hex-pad-lower", alias = "t")]
+    Base32HexPadLower,
+    /// Uppercase RFC 4648 base32hex with padding (multibase prefix `T`).  Not URL-safe.
+    #[value(name = "base32-hex-pad-upper", alias = "T")]
+    Base32HexPadUpper,
+    /// Lowercase RFC 4648 base32 without padding (multibase prefix `b`).
+    #[value(name = "base32-lower", alias = "b")]
+    Base32Lower,
+    /// Uppercase RFC 4648 base32 without padding (multibase prefix `B`).
+    #[value(name = "base32-upper", alias = "B")]
+    Base32Upper,
+    /// Lowercase RFC 4648 base32 with padding (multibase prefix `c`).  Not URL-safe.
+    #[value(name = "base32-pad-lower", alias = "c")]
+    Base32PadLower,
+    /// Uppercase RFC 4648 base32 with padding (multibase prefix `C`).  Not URL-safe.
+    #[value(name = "base32-pad-upper", alias = "C")]
+    Base32PadUpper,
+    /// z-base-32 (multibase prefix `h`).
+    #[value(name = "base32-z", alias = "h")]
+    Base32Z,
+    /// Lowercase base36 (multibase prefix `k`).
+    #[value(name = "base36-lower", alias = "k")]
+    Base36Lower,
+    /// Uppercase base36 (multibase prefix `K`).
+    #[value(name = "base36-upper", alias = "K")]
+    Base36Upper,
+    /// Flickr's base58 (multibase prefix `Z`).
+    #[value(name = "base58-flickr", alias = "Z")]
+    Base58Flickr,
+    /// Bitcoin's base58 (multibase prefix `z`).
+    #[value(name = "base58-btc", alias = "z")]
+    Base58Btc,
+    /// RFC 4648 base64 without padding (multibase prefix `m`).  Not URL-safe.
+    #[value(name = "base64", alias = "m")]
+    Base64,
+    /// RFC 4648 base64 with padding (multibase prefix `M`).  Not URL-safe.
+    #[value(name = "base64-pad", alias = "M")]
+    Base64Pad,
+    /// URL-safe RFC 4648 base64 without padding (multibase prefix `u`).
+    #[value(name = "base64-url", alias = "u")]
+    Base64Url,
+    /// URL-safe RFC 4648 base64 with padding (multibase prefix `U`).  Not URL-safe, since `=` is reserved in URLs.
+    #[value(name = "base64-url-pad", alias = "U")]
+    Base64UrlPad,
+}
+
+impl Base {
+    /// Returns the name of the given base as accepted by --base, or its debug form if it's not one of them
+    /// (i.e. if it's Identity).
+    fn name_of(base: mbx::Base) -> String {
+        use clap::ValueEnum;
+        Self::value_variants()
+            .iter()
+            .find(|variant| mbx::Base::from(**variant) == base)
+            .and_then(|variant| variant.to_possible_value())
+            .map(|possible_value| possible_value.get_name().to_string())
+            .unwrap_or_else(|| format!("{:?}", base))
+    }
+}
+
+impl From<Base> for mbx::Base {
+    fn from(base: Base) -> Self {
+        match base {
+            Base::Base2 => Self::Base2,
+            Base::Base8 => Self::Base8,
+            Base::Base10 => Self::Base10,
+            Base::Base16Lower => Self::Base16Lower,
+            Base::Base16Upper => Self::Base16Upper,
+            Base::Base32HexLower => Self::Base32HexLower,
+            Base::Base32HexUpper => Self::Base32HexUpper,
+            Base::Base32HexPadLower => Self::Base32HexPadLower,
+            Base::Base32HexPadUpper => Self::Base32HexPadUpper,
+            Base::Base32Lower => Self::Base32Lower,
+            Base::Base32Upper => Self::Base32Upper,
+            Base::Base32PadLower => Self::Base32PadLower,
+            Base::Base32PadUpper => Self::Base32PadUpper,
+            Base::Base32Z => Self::Base32Z,
+            Base::Base36Lower => Self::Base36Lower,
+            Base::Base36Upper => Self::Base36Upper,
+            Base::Base58Flickr => Self::Base58Flickr,
+            Base::Base58Btc => Self::Base58Btc,
+            Base::Base64 => Self::Base64,
+            Base::Base64Pad => Self::Base64Pad,
+            Base::Base64Url => Self::Base64Url,
+            Base::Base64UrlPad => Self::Base64UrlPad,
+        }
+    }
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum Format {
+    #[value(name = "json")]
+    JSON,
+    #[value(name = "yaml")]
+    YAML,
+    #[value(name = "xml")]
+    XML,
+    #[value(name = "text")]
+    Text,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum LineEndings {
+    /// Line endings are hashed as they are.
+    #[value(name = "none")]
+    None,
+    /// CRLF and CR line endings are hashed as LF.
+    #[value(name = "lf")]
+    LF,
+    /// LF and CR line endings are hashed as CRLF.
+    #[value(name = "crlf")]
+    CRLF,
+}
+
+impl From<LineEndings> for selfhash::LineEndingNormalization {
+    fn from(line_endings: LineEndings) -> Self {
+        match line_endings {
+            LineEndings::None => Self::None,
+            LineEndings::LF => Self::LF,
+            LineEndings::CRLF => Self::CRLF,
+        }
+    }
+}
+
+impl Format {
+    /// Parse the input in this format into the JSON data model.
+    fn parse(self, input: &str) -> selfhash::Result<serde_json::Value> {
+        match self {
+            Self::JSON => serde_json::from_str(input)
+                .map_err(|e| selfhash::Error::from(format!("malformed JSON; error was {}", e))),
+            Self::YAML => selfhash::yaml_to_json_value(input),
+            Self::XML | Self::Text => Err(selfhash::Error::from(
+                "XML and text input is not parsed into the JSON data model",
+            )),
+        }
+    }
+    /// Write the value in this format (canonical JSON (JCS) in the case of JSON).
+    fn write(self, value: &serde_json::Value, writer: &mut impl Write) -> selfhash::Result<()> {
+        match self {
+            Self::JSON => serde_json_canonicalizer::to_writer(value, writer).map_err(|e| {
+                selfhash::Error::from(format!("failed to write JSON; error was {}", e))
+            }),
+            Self::YAML => {
+                let yaml_string = selfhash::json_value_to_yaml_string(value)?;
+                // The YAML document already ends with a newline, so drop it; whether a trailing
+                // newline is printed is determined by --no-newline.
+                writer
+                    .write_all(yaml_string.trim_end_matches('\n').as_bytes())
+                    .map_err(|e| {
+                        selfhash::Error::from(format!("failed to write YAML; error was {}", e))
+                    })
+            }
+            Self::XML | Self::Text => Err(selfhash::Error::from(
+                "XML and text output is not written from the JSON data model",
+            )),
+        }
+    }
 }
 
 impl SelfHashArgs {
     fn parse_self_hash_paths(&self) -> HashSet<Cow<'_, str>> {
-        let self_hash_paths = self.self_hash_paths.trim();
-        if self_hash_paths.is_empty() {
-            maplit::hashset! {}
+        if !self.self_hash_paths.is_empty() {
+            parse_path_list(&self.self_hash_paths)
+        } else if matches!(self.format, Format::XML) {
+            maplit::hashset! { Cow::Borrowed("/*/@selfHash") }
+        } else if self.parse_self_hash_pointers().is_empty()
+            && self.parse_self_hash_url_pointers().is_empty()
+            && self.self_hash_template.is_empty()
+        {
+            maplit::hashset! { Cow::Borrowed("$.selfHash") }
         } else {
-            self_hash_paths
-                .split(',')
-                .map(|s| Cow::Borrowed(s))
-                .collect::<HashSet<_>>()
+            maplit::hashset! {}
         }
     }
     fn parse_self_hash_url_paths(&self) -> HashSet<Cow<'_, str>> {
-        let self_hash_url_paths = self.self_hash_url_paths.trim();
-        if self_hash_url_paths.is_empty() {
-            maplit::hashset! {}
+        parse_path_list(&self.self_hash_url_paths)
+    }
+    fn parse_self_hash_pointers(&self) -> HashSet<Cow<'_, str>> {
+        parse_pointer_list(&self.self_hash_pointers)
+    }
+    fn parse_self_hash_url_pointers(&self) -> HashSet<Cow<'_, str>> {
+        parse_pointer_list(&self.self_hash_url_pointers)
+    }
+    fn parse_self_hash_regexes(&self) -> HashSet<Cow<'_, str>> {
+        self.self_hash_regex
+            .iter()
+            .map(|self_hash_regex| Cow::Borrowed(self_hash_regex.as_str()))
+            .collect()
+    }
+    fn parse_self_hash_text_templates(&self) -> HashSet<Cow<'_, str>> {
+        if self.self_hash_regex.is_empty() && self.self_hash_text_template.is_empty() {
+            maplit::hashset! { Cow::Borrowed("selfhash: {selfHash}") }
+        } else {
+            self.self_hash_text_template
+                .iter()
+                .map(|self_hash_text_template| Cow::Borrowed(self_hash_text_template.as_str()))
+                .collect()
+        }
+    }
+    fn parse_self_hash_templates(&self) -> HashMap<Cow<'_, str>, Cow<'_, str>> {
+        // clap guarantees that the values come in (selector, template) pairs.
+        self.self_hash_template
+            .chunks_exact(2)
+            .map(|pair| {
+                (
+                    Cow::Borrowed(pair[0].as_str()),
+                    Cow::Borrowed(pair[1].as_str()),
+                )
+            })
+            .collect()
+    }
+    /// Returns the selectors of all the self-hash [URL] slots (or for text, the slot markers), sorted.  Note
+    /// that these may select any number of slots in a given input (see SelfHashableDocument::slot_path_v).
+    fn slot_selector_v(&self) -> Vec<String> {
+        let mut slot_selector_v = if matches!(self.format, Format::Text) {
+            self.parse_self_hash_regexes()
+                .into_iter()
+                .chain(self.parse_self_hash_text_templates())
+                .map(Cow::into_owned)
+                .collect::<Vec<_>>()
         } else {
-            self_hash_url_paths
-                .split(',')
-                .map(|s| Cow::Borrowed(s))
-                .collect::<HashSet<_>>()
+            self.parse_self_hash_paths()
+                .into_iter()
+                .chain(self.parse_self_hash_url_paths())
+                .chain(self.parse_self_hash_pointers())
+                .chain(self.parse_self_hash_url_pointers())
+                .chain(self.parse_self_hash_templates().into_keys())
+                .map(Cow::into_owned)
+                .collect::<Vec<_>>()
+        };
+        slot_selector_v.sort();
+        slot_selector_v
+    }
+    /// Check that these arguments are valid on their own, independently of any input, so that invalid
+    /// arguments are reported as a configuration error rather than as a problem with the input.
+    fn validate(&self) -> Result<(), CLIError> {
+        let configuration_error = |e: selfhash::Error| CLIError::new(ErrorKind::Configuration, e);
+        let ensure = |condition: bool, message: &str| {
+            if condition {
+                Ok(())
+            } else {
+                Err(CLIError::new(ErrorKind::Configuration, message))
+            }
+        };
+        match self.format {
+            Format::Text => {
+                ensure(
+                    self.self_hash_paths.is_empty()
+                        && self.parse_self_hash_url_paths().is_empty()
+                        && self.parse_self_hash_pointers().is_empty()
+                        && self.parse_self_hash_url_pointers().is_empty()
+                        && self.self_hash_template.is_empty(),
+                    "only --self-hash-regex and --self-hash-text-template apply to text",
+                )?;
+                for self_hash_regex in self.parse_self_hash_regexes() {
+                    selfhash::TextSlotMarker::from_regex(&self_hash_regex)
+                        .map_err(configuration_error)?;
+                }
+                for self_hash_text_template in self.parse_self_hash_text_templates() {
+                    selfhash::TextSlotMarker::from_template(&self_hash_text_template)
+                        .map_err(configuration_error)?;
+                }
+            }
+            Format::XML => {
+                ensure(
+                    self.parse_self_hash_pointers().is_empty()
+                        && self.parse_self_hash_url_pointers().is_empty()
+                        && self.self_hash_template.is_empty(),
+                    "--self-hash-pointers, --self-hash-url-pointers, and --self-hash-template don't apply to XML",
+                )?;
+                let self_hash_path_s = self.parse_self_hash_paths();
+                let self_hash_url_path_s = self.parse_self_hash_url_paths();
+                ensure(
+                    self_hash_path_s.is_disjoint(&self_hash_url_path_s),
+                    "self-hash paths and self-hash URL paths must be disjoint",
+                )?;
+                for path in self_hash_path_s.iter().chain(self_hash_url_path_s.iter()) {
+                    selfhash::XMLPath::parse(path).map_err(configuration_error)?;
+                }
+            }
+            Format::JSON | Format::YAML => {
+                let self_hash_path_s = self.parse_self_hash_paths();
+                let self_hash_url_path_s = self.parse_self_hash_url_paths();
+                let self_hash_pointer_s = self.parse_self_hash_pointers();
+                let self_hash_url_pointer_s = self.parse_self_hash_url_pointers();
+                ensure(
+                    self_hash_path_s.is_disjoint(&self_hash_url_path_s),
+                    "self-hash paths and self-hash URL paths must be disjoint",
+                )?;
+                ensure(
+                    self_hash_pointer_s.is_disjoint(&self_hash_url_pointer_s),
+                    "self-hash pointers and self-hash URL pointers must be disjoint",
+                )?;
+                for path in self_hash_path_s.iter().chain(self_hash_url_path_s.iter()) {
+                    selfhash::JSONPath::parse(path).map_err(configuration_error)?;
+                }
+                for pointer in self_hash_pointer_s
+                    .iter()
+                    .chain(self_hash_url_pointer_s.iter())
+                {
+                    selfhash::JSONPointer::parse(pointer).map_err(configuration_error)?;
+                }
+                for (selector, template) in self.parse_self_hash_templates() {
+                    if selector.starts_with('$') {
+                        selfhash::JSONPath::parse(&selector).map_err(configuration_error)?;
+                    } else {
+                        selfhash::JSONPointer::parse(&selector).map_err(configuration_error)?;
+                    }
+                    selfhash::SelfHashTemplate::parse(&template).map_err(configuration_error)?;
+                }
+            }
+        }
+        ensure(
+            !self.slot_selector_v().is_empty(),
+            "no self-hash slots were specified",
+        )
+    }
+    /// Parse the input in the specified format and set up the context for self-hashing it using these
+    /// arguments.  Since the arguments are validated first, any error in setting up the context is due to
+    /// the input, and is reported as a missing slot error if a self-hash slot is missing (see
+    /// ErrorKind::of_input_error), and otherwise as a parse error.
+    fn self_hashable_document<'v, 'w: 'v>(
+        &'w self,
+        input: &str,
+    ) -> Result<SelfHashableDocument<'v, 'w>, CLIError> {
+        self.validate()?;
+        let parse_error = |e: selfhash::Error| CLIError::new(ErrorKind::Parse, e);
+        let input_error = |e: selfhash::Error| CLIError::new(ErrorKind::of_input_error(&e), e);
+        match self.format {
+            Format::Text => Ok(SelfHashableDocument::Text(
+                SelfHashableText::new(
+                    input,
+                    Cow::Owned(self.parse_self_hash_regexes()),
+                    Cow::Owned(self.parse_self_hash_text_templates()),
+                )
+                .map_err(input_error)?
+                .with_line_ending_normalization(self.line_endings.into()),
+            )),
+            Format::XML => Ok(SelfHashableDocument::XML(
+                SelfHashableXML::new(
+                    input,
+                    Cow::Owned(self.parse_self_hash_paths()),
+                    Cow::Owned(self.parse_self_hash_url_paths()),
+                )
+                .map_err(input_error)?,
+            )),
+            Format::JSON | Format::YAML => Ok(SelfHashableDocument::JSON(
+                SelfHashableJSON::new_with_templates(
+                    self.format.parse(input).map_err(parse_error)?,
+                    Cow::Owned(self.parse_self_hash_paths()),
+                    Cow::Owned(self.parse_self_hash_url_paths()),
+                    Cow::Owned(self.parse_self_hash_pointers()),
+                    Cow::Owned(self.parse_self_hash_url_pointers()),
+                    Cow::Owned(self.parse_self_hash_templates()),
+                )
+                .map_err(input_error)?,
+                self.format,
+            )),
+        }
+    }
+}
+
+/// Self-hashable data in one of the supported formats.
+enum SelfHashableDocument<'v, 'w: 'v> {
+    /// JSON or YAML (in the given format), which are both self-hashed in the JSON data model.
+    JSON(SelfHashableJSON<'v, 'w>, Format),
+    XML(SelfHashableXML<'v, 'w>),
+    Text(SelfHashableText<'v, 'w>),
+}
+
+impl SelfHashableDocument<'_, '_> {
+    fn self_hashable(&self) -> &dyn SelfHashableT<mbx::MBHashStr> {
+        match self {
+            Self::JSON(json, _) => json,
+            Self::XML(xml) => xml,
+            Self::Text(text) => text,
+        }
+    }
+    fn self_hashable_mut(&mut self) -> &mut dyn SelfHashableT<mbx::MBHashStr> {
+        match self {
+            Self::JSON(json, _) => json,
+            Self::XML(xml) => xml,
+            Self::Text(text) => text,
+        }
+    }
+    /// Returns the resolved self-hash [URL] slots, for reporting: the normalized path of each slot for JSON
+    /// and YAML (e.g. `$['selfHash']`), the absolute XMLPath of each slot for XML (e.g. `/record/@selfHash`),
+    /// and the byte range of each self-hash for text (e.g. `10..63`).
+    fn slot_path_v(&self) -> selfhash::Result<Vec<String>> {
+        match self {
+            Self::JSON(json, _) => json.self_hash_slot_normalized_paths(),
+            Self::XML(xml) => xml.self_hash_slot_xml_paths(),
+            Self::Text(text) => Ok(text
+                .self_hash_slot_ranges()?
+                .into_iter()
+                .map(|self_hash_range| {
+                    format!("{}..{}", self_hash_range.start, self_hash_range.end)
+                })
+                .collect()),
+        }
+    }
+    /// Returns true iff the data is text, which is output exactly as self-hashed, so a trailing newline
+    /// can't be added to it.
+    fn is_text(&self) -> bool {
+        matches!(self, Self::Text(_))
+    }
+    /// Write the data in its format (canonical JSON (JCS) in the case of JSON, and canonical XML (Exclusive
+    /// XML Canonicalization) in the case of XML, and the raw bytes in the case of text).
+    fn write(&self, writer: &mut impl Write) -> selfhash::Result<()> {
+        match self {
+            Self::JSON(json, format) => format.write(json.value(), writer),
+            Self::XML(xml) => writer
+                .write_all(xml.to_c14n_string().as_bytes())
+                .map_err(|e| {
+                    selfhash::Error::from(format!("failed to write XML; error was {}", e))
+                }),
+            Self::Text(text) => writer.write_all(text.as_bytes()).map_err(|e| {
+                selfhash::Error::from(format!("failed to write text; error was {}", e))
+            }),
+        }
+    }
+}
+
+/// Parse the given (possibly repeated) argument values, each of which is a comma-delimited list of JSONPath
+/// queries (or for XML, XPath-like selectors).  Only commas outside of brackets, parentheses, and quoted
+/// strings are delimiters, since e.g. `$['a,b']`, `$['a','b']`, and `$[?@.x in ['a','b']]` each contain
+/// commas that are part of the query.
+fn parse_path_list(value_v: &[String]) -> HashSet<Cow<'_, str>> {
+    let mut path_s = HashSet::new();
+    for value in value_v.iter().map(|value| value.trim()) {
+        if value.is_empty() {
+            continue;
+        }
+        let mut depth = 0usize;
+        let mut quote_o = None;
+        let mut escaped = false;
+        let mut start = 0;
+        for (i, c) in value.char_indices() {
+            if let Some(quote) = quote_o {
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' {
+                    escaped = true;
+                } else if c == quote {
+                    quote_o = None;
+                }
+                continue;
+            }
+            match c {
+                '\'' | '"' => quote_o = Some(c),
+                '[' | '(' => depth += 1,
+                ']' | ')' => depth = depth.saturating_sub(1),
+                ',' if depth == 0 => {
+                    path_s.insert(Cow::Borrowed(&value[start..i]));
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+        path_s.insert(Cow::Borrowed(&value[start..]));
+    }
+    path_s
+}
+
+/// Parse the given (possibly repeated) argument values, each of which is a comma-delimited list of JSON
+/// Pointers.  Since a JSON Pointer may contain a comma but each nonempty one begins with `/`, only commas
+/// followed by `/` are delimiters.
+fn parse_pointer_list(value_v: &[String]) -> HashSet<Cow<'_, str>> {
+    let mut pointer_s = HashSet::new();
+    for value in value_v.iter().map(|value| value.trim()) {
+        if value.is_empty() {
+            continue;
+        }
+        let mut start = 0;
+        for (i, _) in value.match_indices(",/") {
+            pointer_s.insert(Cow::Borrowed(&value[start..i]));
+            start = i + 1;
+        }
+        pointer_s.insert(Cow::Borrowed(&value[start..]));
+    }
+    pointer_s
+}
+
+/// Expand the given file arguments, each of which is either a path or a glob pattern (e.g. `fixtures/*.json`
+/// or `fixtures/**/*.json`), into the list of files to process, in order and without duplicates.  A glob
+/// pattern which matches no files is an error, so that a typo doesn't go unnoticed, as is a path which can't
+/// be read while matching a glob pattern.  An argument which names an existing path is taken literally, even
+/// if it contains glob metacharacters (e.g. `fixtures/[draft].json`).
+fn expand_file_args(file_arg_v: &[String]) -> Result<Vec<PathBuf>, CLIError> {
+    let mut path_v = Vec::new();
+    let mut path_s = HashSet::new();
+    for file_arg in file_arg_v.iter() {
+        let matched_path_v = if file_arg.contains(['*', '?', '[']) && !Path::new(file_arg).exists()
+        {
+            let matched_path_v = glob::glob(file_arg)
+                .map_err(|e| {
+                    CLIError::new(
+                        ErrorKind::Configuration,
+                        format!("malformed glob pattern {:?}; error was {}", file_arg, e),
+                    )
+                })?
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| {
+                    CLIError::new(
+                        ErrorKind::IO,
+                        format!(
+                            "failed to read {:?} while matching glob pattern {:?}; error was {}",
+                            e.path(),
+                            file_arg,
+                            e.error()
+                        ),
+                    )
+                })?
+                .into_iter()
+                .filter(|path| path.is_file())
+                .collect::<Vec<_>>();
+            if matched_path_v.is_empty() {
+                return Err(CLIError::new(
+                    ErrorKind::Configuration,
+                    format!("glob pattern {:?} matched no files", file_arg),
+                ));
+            }
+            matched_path_v
+        } else {
+            vec![PathBuf::from(file_arg)]
+        };
+        for path in matched_path_v {
+            if path_s.insert(path.clone()) {
+                path_v.push(path);
+            }
+        }
+    }
+    Ok(path_v)
+}
+
+fn read_stdin() -> Result<String, CLIError> {
+    let mut input = String::new();
+    std::io::stdin().read_to_string(&mut input).map_err(|e| {
+        CLIError::new(
+            ErrorKind::IO,
+            format!("failed to read stdin; error was {}", e),
+        )
+    })?;
+    Ok(input)
+}
+
+fn read_file(path: &Path) -> Result<String, CLIError> {
+    std::fs::read_to_string(path).map_err(|e| {
+        CLIError::new(
+            ErrorKind::IO,
+            format!("failed to read file; error was {}", e),
+        )
+    })
+}
+
+fn write_stdout(output: &[u8]) -> Result<(), CLIError> {
+    std::io::stdout().write_all(output).map_err(|e| {
+        CLIError::new(
+            ErrorKind::IO,
+            format!("failed to write stdout; error was {}", e),
+        )
+    })
+}
+
+/// Write the contents to the given file atomically, by writing them to a temporary file in the same
+/// directory and then renaming it over the file, so that the file is never left partially written.  The
+/// permissions of an existing file are preserved.
+fn write_file_atomically(path: &Path, contents: &[u8]) -> Result<(), CLIError> {
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let file_name = path.file_name().ok_or_else(|| {
+        CLIError::new(
+            ErrorKind::Configuration,
+            format!("path {:?} has no file name", path),
+        )
+    })?;
+    let temp_path = dir.join(format!(
+        ".{}.selfhash-{}.tmp",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+    let write_r = (|| -> std::io::Result<()> {
+        let mut temp_file = std::fs::File::create(&temp_path)?;
+        temp_file.write_all(contents)?;
+        if let Ok(metadata) = std::fs::metadata(path) {
+            temp_file.set_permissions(metadata.permissions())?;
         }
+        temp_file.sync_all()?;
+        std::fs::rename(&temp_path, path)
+    })();
+    if let Err(e) = write_r {
+        let _ = std::fs::remove_file(&temp_path);
+        return Err(CLIError::new(
+            ErrorKind::IO,
+            format!("failed to write {:?}; error was {}", path, e),
+        ));
+    }
+    Ok(())
+}
+
+/// Process each of the given files, printing the result for each (a success to stdout as
+/// `<file>: <result>`, and a failure to stderr), and continuing past failures.  If any fail, the returned
+/// error has the kind (and therefore the exit status) of the first failure.
+fn process_files<T: std::fmt::Display>(
+    path_v: &[PathBuf],
+    verb: &str,
+    mut process: impl FnMut(&Path) -> Result<T, CLIError>,
+) -> Result<(), CLIError> {
+    let mut first_error_kind_o = None;
+    let mut failure_count = 0usize;
+    for path in path_v.iter() {
+        match process(path) {
+            Ok(result) => println!("{}: {}", path.display(), result),
+            Err(e) => {
+                eprintln!("{}: {}", path.display(), e);
+                first_error_kind_o.get_or_insert(e.kind);
+                failure_count += 1;
+            }
+        }
+    }
+    match first_error_kind_o {
+        Some(error_kind) => Err(CLIError::new(
+            error_kind,
+            format!(
+                "failed to {} {} of {} files",
+                verb,
+                failure_count,
+                path_v.len()
+            ),
+        )),
+        None => Ok(()),
     }
 }
 
@@ -78,105 +829,570 @@ struct Compute {
     /// If specified, don't print a trailing newline in the output [default: print newline].
     #[arg(short, long)]
     no_newline: bool,
+    /// If specified, overwrite each of the given files with its self-hashed output.  Each file is written
+    /// atomically, via a temporary file which is renamed over it.
+    #[arg(short, long, requires = "files", conflicts_with = "output_dir")]
+    in_place: bool,
+    /// If specified, write the self-hashed output of each of the given files to a file of the same name in
+    /// this directory, which is created if necessary.
+    #[arg(long, requires = "files", value_name = "DIR")]
+    output_dir: Option<PathBuf>,
+    #[command(flatten)]
+    hash_function_args: HashFunctionArgs,
     #[command(flatten)]
     self_hash_args: SelfHashArgs,
+    /// Optionally specify files (or glob patterns, e.g. `fixtures/*.json`) to read instead of stdin.  If
+    /// more than one file is given, then --in-place or --output-dir must be specified, and the self-hash
+    /// of each file is printed as `<file>: <self-hash>`.
+    #[arg(value_name = "FILES")]
+    files: Vec<String>,
 }
 
 impl Compute {
-    fn handle(self) {
-        // Read all of stdin into a String and parse it as JSON.
-        let mut input = String::new();
-        std::io::stdin().read_to_string(&mut input).unwrap();
-        let value = serde_json::from_str(&input).unwrap();
-
-        // Parse the self-hash related arguments.
-        let self_hash_path_s = self.self_hash_args.parse_self_hash_paths();
-        let self_hash_url_path_s = self.self_hash_args.parse_self_hash_url_paths();
-
-        // Set up the context for self-hashable JSON.
-        let mut json = SelfHashableJSON::new(
-            value,
-            Cow::Borrowed(&self_hash_path_s),
-            Cow::Borrowed(&self_hash_url_path_s),
-        )
-        .unwrap();
+    fn handle(self) -> Result<(), CLIError> {
+        if self.files.is_empty() {
+            // Read all of stdin into a String, self-hash it, and print the output.
+            let (output, _self_hash) = self.compute(&read_stdin()?)?;
+            return write_stdout(&output);
+        }
 
-        // Self-hash the JSON.
-        // TODO: Arg to specify the hash function
-        let mb_hash_function = selfhash::MBHashFunction::blake3(mbx::Base::Base64Url);
-        json.self_hash(mb_hash_function.new_hasher())
-            .expect("self-hash failed");
+        let path_v = expand_file_args(&self.files)?;
+        if !self.in_place && self.output_dir.is_none() {
+            if path_v.len() > 1 {
+                return Err(CLIError::new(
+                    ErrorKind::Configuration,
+                    "--in-place or --output-dir must be specified when self-hashing more than one file",
+                ));
+            }
+            // A single file is output to stdout, just as stdin would be.
+            let (output, _self_hash) = read_file(&path_v[0])
+                .and_then(|input| self.compute(&input))
+                .map_err(|e| e.in_file(&path_v[0]))?;
+            return write_stdout(&output);
+        }
+        if let Some(output_dir) = self.output_dir.as_deref() {
+            // Files with the same name (from different directories) would overwrite one another.
+            let mut file_name_s = HashSet::new();
+            for path in path_v.iter() {
+                if !file_name_s.insert(path.file_name()) {
+                    return Err(CLIError::new(
+                        ErrorKind::Configuration,
+                        format!(
+                            "more than one of the given files is named {:?}, so they can't all be written to --output-dir",
+                            path.file_name().unwrap_or_default()
+                        ),
+                    ));
+                }
+            }
+            std::fs::create_dir_all(output_dir).map_err(|e| {
+                CLIError::new(
+                    ErrorKind::IO,
+                    format!(
+                        "failed to create output directory {:?}; error was {}",
+                        output_dir, e
+                    ),
+                )
+            })?;
+        }
+
+        process_files(&path_v, "self-hash", |path| self.compute_file(path))
+    }
+    /// Self-hash the given file and write the output to the file itself (--in-place) or to the output
+    /// directory (--output-dir), returning its self-hash.
+    fn compute_file(&self, path: &Path) -> Result<mbx::MBHash, CLIError> {
+        let (output, self_hash) = self.compute(&read_file(path)?)?;
+        let output_path = match self.output_dir.as_deref() {
+            Some(output_dir) => output_dir.join(path.file_name().ok_or_else(|| {
+                CLIError::new(
+                    ErrorKind::Configuration,
+                    format!("path {:?} has no file name", path),
+                )
+            })?),
+            None => path.to_path_buf(),
+        };
+        write_file_atomically(&output_path, &output)?;
+        Ok(self_hash)
+    }
+    /// Parse the input in the specified format, self-hash it, and return the self-hashed output (with an
+    /// optional trailing newline) along with its self-hash.
+    fn compute(&self, input: &str) -> Result<(Vec<u8>, mbx::MBHash), CLIError> {
+        // Determine the hash function first, so that an unsupported one is reported as such regardless of
+        // the input.
+        let mb_hash_function = self.hash_function_args.mb_hash_function()?;
+
+        // Parse the input in the specified format and set up the context for self-hashing it.
+        let mut document = self.self_hash_args.self_hashable_document(input)?;
+
+        // Self-hash the data.  Having validated the arguments and parsed the input, this fails if the
+        // self-hash slots can't be found or set in the input.
+        document
+            .self_hashable_mut()
+            .self_hash(mb_hash_function.new_hasher())
+            .map_err(|e| {
+                CLIError::new(
+                    ErrorKind::of_input_error(&e),
+                    format!("failed to set self-hash slots; error was {}", e),
+                )
+            })?;
 
         // Verify the self-hash.  This is mostly a sanity check.
-        json.verify_self_hashes()
-            .expect("programmer error: self-hash verification failed");
+        let self_hash = document
+            .self_hashable()
+            .verify_self_hashes()
+            .map_err(|e| {
+                CLIError::new(
+                    ErrorKind::HashMismatch,
+                    format!("self-hashed output failed verification; error was {}", e),
+                )
+            })?
+            .to_owned();
 
-        // Print the self-hashed JSON and optional newline.
-        serde_json_canonicalizer::to_writer(json.value(), &mut std::io::stdout()).unwrap();
-        if !self.no_newline {
-            std::io::stdout().write("\n".as_bytes()).unwrap();
+        // Write the self-hashed data in the specified format and optional newline (which is never added
+        // to text, since it would change the self-hashed bytes).
+        let mut output = Vec::new();
+        document
+            .write(&mut output)
+            .map_err(|e| CLIError::new(ErrorKind::IO, e))?;
+        if !self.no_newline && !document.is_text() {
+            output.push(b'\n');
         }
+        Ok((output, self_hash))
     }
 }
 
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum Output {
+    /// Print the verified self-hash, or for files, `<file>: <self-hash>`; errors are printed to stderr.
+    #[value(name = "text")]
+    Text,
+    /// Print a verification report for each input as a line of canonical JSON (JCS), with the fields
+    /// `file` (null for stdin), `verified`, `claimedHash`, `computedHash`, `hashFunction`, `base`,
+    /// `slotPaths`, and `error` (null, or an object with `kind`, `exitCode`, and `message`).  `slotPaths`
+    /// lists the self-hash [URL] slots found in the input: the normalized path of each for JSON and YAML
+    /// (e.g. `$['selfHash']`), the absolute XMLPath of each for XML, and the byte range of each self-hash
+    /// for text.  Fields which couldn't be determined (e.g. because the input couldn't be parsed) are null.
+    #[value(name = "json")]
+    JSON,
+}
+
 #[derive(clap::Args)]
 struct Verify {
     /// If specified, don't print a trailing newline in the output [default: print newline].
     #[arg(short, long)]
     no_newline: bool,
+    /// Specify the output format.
+    #[arg(short, long, value_enum, default_value_t = Output::Text)]
+    output: Output,
     #[command(flatten)]
     self_hash_args: SelfHashArgs,
+    /// Optionally specify files (or glob patterns, e.g. `fixtures/*.json`) to verify instead of stdin.  The
+    /// result for each file is printed as `<file>: <self-hash>` (or to stderr, if verification fails), and
+    /// the exit status is nonzero if any file fails verification.
+    #[arg(value_name = "FILES")]
+    files: Vec<String>,
 }
 
 impl Verify {
-    fn handle(self) {
-        // Read all of stdin into a String and parse it as JSON.
-        let mut input = String::new();
-        std::io::stdin().read_to_string(&mut input).unwrap();
-        let value: serde_json::Value = serde_json::from_str(&input).unwrap();
-
-        // Parse the self-hash related arguments.
-        let self_hash_path_s = self.self_hash_args.parse_self_hash_paths();
-        let self_hash_url_path_s = self.self_hash_args.parse_self_hash_url_paths();
-
-        // TODO: Add this check
-        // // Check for the existence of the self-hash [URL] path(s).  This is to produce a better error
-        // // message than the one that would be produced by verify_self_hashes.
-        // for self_hash_path in self_hash_path_s.iter().map(std::ops::Deref::deref) {
-        //     if value.get(self_hash_path).is_none() {
-        //         eprintln!("Input JSON has no {:?} field (expected because of argument --self-hash-field-names {:?}), and therefore can't be verified.", self_hash_path, self.self_hash_args.self_hash_paths);
-        //         std::process::exit(1);
-        //     }
-        // }
-        // for self_hash_url_path in self_hash_url_path_s.iter().map(std::ops::Deref::deref) {
-        //     if value.get(self_hash_url_path).is_none() {
-        //         eprintln!("Input JSON has no {:?} field (expected because of argument --self-hash-url-field-names {:?}), and therefore can't be verified.", self_hash_url_path, self.self_hash_args.self_hash_url_paths);
-        //         std::process::exit(1);
-        //     }
-        // }
-
-        // Set up the context for self-hashable JSON.
-        let json = SelfHashableJSON::new(
-            value,
-            Cow::Borrowed(&self_hash_path_s),
-            Cow::Borrowed(&self_hash_url_path_s),
-        )
-        .unwrap();
+    fn handle(self) -> Result<(), CLIError> {
+        if let Output::JSON = self.output {
+            return self.handle_json();
+        }
+        if self.files.is_empty() {
+            // Read all of stdin into a String, verify it, and print the verified self-hash with optional
+            // newline.
+            let self_hash = self.verify(&read_stdin()?).into_result()?;
+            print!("{}", self_hash);
+            if !self.no_newline {
+                println!();
+            }
+            return Ok(());
+        }
 
-        // Verify the self-hash.
-        let self_hash = json
-            .verify_self_hashes()
-            .expect("self-hash verification failed");
+        let path_v = expand_file_args(&self.files)?;
+        process_files(&path_v, "verify", |path| {
+            self.verify(&read_file(path)?).into_result()
+        })
+    }
+    /// Print a verification report for stdin or each of the given files, returning an error with the kind
+    /// of the first failure, if any.
+    fn handle_json(&self) -> Result<(), CLIError> {
+        let input_v = if self.files.is_empty() {
+            vec![(None, read_stdin())]
+        } else {
+            expand_file_args(&self.files)?
+                .into_iter()
+                .map(|path| {
+                    let input_r = read_file(&path);
+                    (Some(path), input_r)
+                })
+                .collect()
+        };
+        let mut first_error_o = None;
+        for (path_o, input_r) in input_v {
+            let verification_report = match input_r {
+                Ok(input) => self.verify(&input),
+                Err(e) => VerificationReport::from(e),
+            };
+            println!("{}", verification_report.to_json_string(path_o.as_deref()));
+            if let Err(e) = verification_report.into_result() {
+                first_error_o.get_or_insert(e);
+            }
+        }
+        match first_error_o {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+    /// Parse the input in the specified format and verify its self-hash(es) via
+    /// SelfHashableT::verify_self_hashes, reporting as much as could be determined about the claimed and
+    /// computed self-hashes.
+    fn verify(&self, input: &str) -> VerificationReport {
+        let mut verification_report = VerificationReport::default();
+
+        // Parse the input in the specified format and set up the context for self-hashing it.
+        let document = match self.self_hash_args.self_hashable_document(input) {
+            Ok(document) => document,
+            Err(e) => return verification_report.with_error(e),
+        };
+        verification_report.slot_path_vo = document.slot_path_v().ok();
+        let self_hashable = document.self_hashable();
 
-        // Print the verified self-hash with optional newline.
-        std::io::stdout().write(self_hash.as_bytes()).unwrap();
-        if !self.no_newline {
-            std::io::stdout().write("\n".as_bytes()).unwrap();
+        // Determine the claimed self-hash, so that it can be reported, and so that its hash function can
+        // be checked for support by this build before verifying.
+        let claimed_self_hash = match self_hashable.get_unverified_self_hash() {
+            Ok(Some(claimed_self_hash)) => claimed_self_hash,
+            Ok(None) => {
+                return verification_report.with_error(CLIError::new(
+                    ErrorKind::MissingSlot,
+                    "the input's self-hash slots are unset, so it hasn't been self-hashed",
+                ))
+            }
+            Err(e) => {
+                return verification_report
+                    .with_error(CLIError::new(ErrorKind::of_input_error(&e), e))
+            }
+        };
+        verification_report.claimed_hash_o = Some(claimed_self_hash.to_owned());
+        verification_report.base_o = Some(claimed_self_hash.base());
+
+        // Determine the hash function from the claimed self-hash.  NOTE: Use 64 bytes as max supported
+        // digest size, as HashRefT for MBHashStr does.
+        let codec = match claimed_self_hash.decoded::<64>() {
+            Ok(multihash) => multihash.code(),
+            Err(_) => {
+                return verification_report.with_error(CLIError::new(
+                    ErrorKind::Parse,
+                    format!(
+                        "claimed self-hash {} is not a valid multihash",
+                        claimed_self_hash
+                    ),
+                ))
+            }
+        };
+        let named_hash_function = match selfhash::NamedHashFunction::ALL
+            .iter()
+            .find(|named_hash_function| named_hash_function.codec() == codec)
+        {
+            Some(named_hash_function) if named_hash_function.is_enabled() => *named_hash_function,
+            _ => {
+                return verification_report.with_error(CLIError::new(
+                    ErrorKind::UnsupportedAlgorithm,
+                    format!(
+                        "claimed self-hash uses hash function with multicodec code 0x{:02x}, which is not supported by this build",
+                        codec
+                    ),
+                ))
+            }
+        };
+        verification_report.named_hash_function_o = Some(named_hash_function);
+
+        // Verify the self-hash, classifying any failure by the library's ErrorKind.
+        match self_hashable.verify_self_hashes() {
+            Ok(verified_self_hash) => {
+                verification_report.computed_hash_o = Some(verified_self_hash.to_owned());
+            }
+            Err(e) if e.kind() == selfhash::ErrorKind::HashMismatch => {
+                // The computed self-hash is only needed for the report, and so that the message can
+                // give both self-hashes in their multibase form.
+                let mb_hash_function =
+                    named_hash_function.as_mb_hash_function(claimed_self_hash.base());
+                let Ok(computed_self_hash) =
+                    self_hashable.compute_self_hash(mb_hash_function.new_hasher())
+                else {
+                    return verification_report
+                        .with_error(CLIError::new(ErrorKind::HashMismatch, e));
+                };
+                let message = format!(
+                    "computed self-hash {} doesn't match claimed self-hash {}",
+                    computed_self_hash, claimed_self_hash
+                );
+                verification_report.computed_hash_o = Some(computed_self_hash);
+                return verification_report
+                    .with_error(CLIError::new(ErrorKind::HashMismatch, message));
+            }
+            Err(e) => {
+                return verification_report
+                    .with_error(CLIError::new(ErrorKind::of_input_error(&e), e));
+            }
         }
+        verification_report
+    }
+}
+
+/// The result of verifying one input, which is reported as a line of JSON by `verify --output json`.
+#[derive(Default)]
+struct VerificationReport {
+    claimed_hash_o: Option<mbx::MBHash>,
+    computed_hash_o: Option<mbx::MBHash>,
+    named_hash_function_o: Option<selfhash::NamedHashFunction>,
+    base_o: Option<mbx::Base>,
+    /// The resolved self-hash [URL] slots (see SelfHashableDocument::slot_path_v).
+    slot_path_vo: Option<Vec<String>>,
+    /// The error which caused verification to fail, or None if it succeeded.
+    error_o: Option<CLIError>,
+}
+
+impl VerificationReport {
+    fn with_error(mut self, error: CLIError) -> Self {
+        self.error_o = Some(error);
+        self
+    }
+    /// Returns the verified self-hash, or the error which caused verification to fail.
+    fn into_result(self) -> Result<mbx::MBHash, CLIError> {
+        match self.error_o {
+            Some(e) => Err(e),
+            None => Ok(self
+                .claimed_hash_o
+                .expect("programmer error: verified report has no claimed hash")),
+        }
+    }
+    fn to_json_string(&self, path_o: Option<&Path>) -> String {
+        let value = serde_json::json!({
+            "file": path_o.map(|path| path.display().to_string()),
+            "verified": self.error_o.is_none(),
+            "claimedHash": self.claimed_hash_o.as_ref().map(ToString::to_string),
+            "computedHash": self.computed_hash_o.as_ref().map(ToString::to_string),
+            "hashFunction": self.named_hash_function_o.map(|named_hash_function| named_hash_function.to_string()),
+            "base": self.base_o.map(Base::name_of),
+            "slotPaths": self.slot_path_vo,
+            "error": self.error_o.as_ref().map(|e| serde_json::json!({
+                "kind": e.kind.as_str(),
+                "exitCode": e.kind.exit_code(),
+                "message": e.message.as_str(),
+            })),
+        });
+        serde_json_canonicalizer::to_string(&value).expect("programmer error")
+    }
+}
+
+impl From<CLIError> for VerificationReport {
+    fn from(error: CLIError) -> Self {
+        Self::default().with_error(error)
+    }
+}
+
+/// Operate on directory trees (e.g. release bundles) via a self-hashed manifest file (SELFHASH.json) in the
+/// root of the directory, which lists each other file's path, mode, size, and digest, along with the
+/// Merkle root over them.
+#[derive(clap::Subcommand)]
+enum Tree {
+    /// Compute the manifest of the given directory, write it to SELFHASH.json in the directory
+    /// (replacing any existing one), and print its self-hash.
+    Compute(TreeCompute),
+    /// Verify the manifest in the given directory against the files actually present, printing a line for
+    /// each file that was added, removed, or modified, followed by the self-hash of the manifest if the
+    /// directory matches it.  Exits with the hash mismatch status if there are any differences.
+    Verify(TreeVerify),
+}
+
+impl Tree {
+    fn handle(self) -> Result<(), CLIError> {
+        match self {
+            Self::Compute(x) => x.handle(),
+            Self::Verify(x) => x.handle(),
+        }
+    }
+}
+
+#[derive(clap::Args)]
+struct TreeCompute {
+    /// The root of the directory tree.
+    dir: std::path::PathBuf,
+    #[command(flatten)]
+    hash_function_args: HashFunctionArgs,
+}
+
+impl TreeCompute {
+    fn handle(self) -> Result<(), CLIError> {
+        let mb_hash_function = self.hash_function_args.mb_hash_function()?;
+        let tree_manifest = selfhash::tree::write_tree_manifest(&self.dir, &mb_hash_function)
+            .map_err(|e| CLIError::new(ErrorKind::IO, e))?;
+        println!(
+            "{}",
+            tree_manifest
+                .self_hash
+                .expect("programmer error: tree manifest was not self-hashed")
+        );
+        Ok(())
+    }
+}
+
+#[derive(clap::Args)]
+struct TreeVerify {
+    /// The root of the directory tree.
+    dir: std::path::PathBuf,
+}
+
+impl TreeVerify {
+    fn handle(self) -> Result<(), CLIError> {
+        // Read and verify the manifest first, so that its failures are distinguished from failures to read
+        // the directory tree.
+        let manifest_path = self.dir.join(selfhash::tree::MANIFEST_FILE_NAME);
+        if !manifest_path.is_file() {
+            return Err(CLIError::new(
+                ErrorKind::MissingSlot,
+                format!(
+                    "directory {:?} has no tree manifest ({})",
+                    self.dir,
+                    selfhash::tree::MANIFEST_FILE_NAME
+                ),
+            ));
+        }
+        let manifest_json = read_file(&manifest_path).map_err(|e| e.in_file(&manifest_path))?;
+        selfhash::tree::TreeManifest::from_json_str(&manifest_json)
+            .map_err(|e| CLIError::new(ErrorKind::Parse, e))?
+            .verify_self_hash()
+            .map_err(|e| CLIError::new(ErrorKind::HashMismatch, e))?;
+
+        let tree_verification = selfhash::tree::verify_tree_manifest(&self.dir)
+            .map_err(|e| CLIError::new(ErrorKind::IO, e))?;
+        print!("{}", tree_verification.diff);
+        if !tree_verification.is_verified() {
+            return Err(CLIError::new(
+                ErrorKind::HashMismatch,
+                "directory tree does not match its manifest",
+            ));
+        }
+        println!("{}", tree_verification.self_hash);
+        Ok(())
+    }
+}
+
+#[derive(clap::Args)]
+struct ListHashFunctions {
+    #[command(flatten)]
+    base_args: BaseArgs,
+}
+
+impl ListHashFunctions {
+    fn handle(self) -> Result<(), CLIError> {
+        for named_hash_function in selfhash::NamedHashFunction::ALL
+            .iter()
+            .filter(|named_hash_function| named_hash_function.is_enabled())
+        {
+            let placeholder_hash = named_hash_function
+                .as_mb_hash_function(self.base_args.base.into())
+                .placeholder_hash()
+                .into_owned();
+            println!(
+                "{:<8}  0x{:02x}  {}",
+                **named_hash_function,
+                named_hash_function.codec(),
+                placeholder_hash
+            );
+        }
+        Ok(())
+    }
+}
+
+/// The kind of a CLIError, which determines the exit status of the process (see EXIT_STATUS_HELP).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum ErrorKind {
+    /// Reading or writing stdin, stdout, or a file failed.
+    IO,
+    /// The arguments are invalid, e.g. a malformed JSONPath or regex.  Note that clap also exits with
+    /// status 2 for usage errors.
+    Configuration,
+    /// The input couldn't be parsed in the specified format, or a self-hash slot's value is malformed (e.g.
+    /// isn't a valid self-hash).
+    Parse,
+    /// A self-hash slot is missing (and can't be inserted) or unset, or a slot selector selected a number of
+    /// slots violating its cardinality rule.
+    MissingSlot,
+    /// The computed self-hash doesn't match the claimed one, or the self-hash slots don't all match.
+    HashMismatch,
+    /// The hash function is unrecognized or not supported by this build.
+    UnsupportedAlgorithm,
+}
+
+impl ErrorKind {
+    /// Classifies an error from setting up, reading, setting, or verifying the self-hash slots of the input,
+    /// which is reported by the library as selfhash::ErrorKind::MissingSlot if a slot is missing, and as
+    /// selfhash::ErrorKind::HashMismatch if the self-hashes don't match.  Since the arguments are validated
+    /// first, any other such error is due to the input not fitting the self-hash slots, e.g. a slot whose
+    /// value isn't a valid self-hash.
+    fn of_input_error(e: &selfhash::Error) -> Self {
+        match e.kind() {
+            selfhash::ErrorKind::MissingSlot => Self::MissingSlot,
+            selfhash::ErrorKind::HashMismatch => Self::HashMismatch,
+            selfhash::ErrorKind::Other => Self::Parse,
+        }
+    }
+    fn exit_code(self) -> i32 {
+        match self {
+            Self::IO => 1,
+            Self::Configuration => 2,
+            Self::Parse => 3,
+            Self::MissingSlot => 4,
+            Self::HashMismatch => 5,
+            Self::UnsupportedAlgorithm => 6,
+        }
+    }
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::IO => "io",
+            Self::Configuration => "configuration",
+            Self::Parse => "parse",
+            Self::MissingSlot => "missing-slot",
+            Self::HashMismatch => "hash-mismatch",
+            Self::UnsupportedAlgorithm => "unsupported-algorithm",
+        }
+    }
+}
+
+/// An error which is reported to the user, exiting with the status determined by its kind.
+#[derive(Debug)]
+struct CLIError {
+    kind: ErrorKind,
+    message: String,
+}
+
+impl CLIError {
+    fn new(kind: ErrorKind, message: impl std::fmt::Display) -> Self {
+        Self {
+            kind,
+            message: message.to_string(),
+        }
+    }
+    /// Qualifies the message with the file it pertains to.
+    fn in_file(self, path: &Path) -> Self {
+        Self {
+            kind: self.kind,
+            message: format!("{}: {}", path.display(), self.message),
+        }
+    }
+}
+
+impl std::fmt::Display for CLIError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} error: {}", self.kind.as_str(), self.message)
     }
 }
 
 fn main() {
     use clap::Parser;
-    CLI::parse().handle();
+    if let Err(e) = CLI::parse().handle() {
+        eprintln!("{}", e);
+        std::process::exit(e.kind.exit_code());
+    }
 }
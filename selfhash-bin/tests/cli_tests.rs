@@ -0,0 +1,609 @@
+use std::{
+    io::Write,
+    process::{Command, Output, Stdio},
+};
+
+/// Runs the selfhash binary with the given arguments and stdin.
+fn run_selfhash(arg_v: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_selfhash"))
+        .args(arg_v)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("pass");
+    child
+        .stdin
+        .take()
+        .expect("pass")
+        .write_all(stdin.as_bytes())
+        .expect("pass");
+    child.wait_with_output().expect("pass")
+}
+
+fn stdout_of(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).expect("pass")
+}
+
+fn stderr_of(output: &Output) -> String {
+    String::from_utf8(output.stderr.clone()).expect("pass")
+}
+
+/// Self-hashes the given JSON with the default arguments, returning the output and its self-hash.
+fn compute_json(input: &str) -> (String, String) {
+    let output = run_selfhash(&["compute", "--no-newline"], input);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr_of(&output));
+    let self_hashed = stdout_of(&output);
+    let value = serde_json::from_str::<serde_json::Value>(&self_hashed).expect("pass");
+    let self_hash = value["selfHash"].as_str().expect("pass").to_string();
+    (self_hashed, self_hash)
+}
+
+#[test]
+fn test_exit_status_per_error_kind() {
+    let (self_hashed, self_hash) = compute_json(r#"{"thing":3}"#);
+    let tampered = self_hashed.replace(r#""thing":3"#, r#""thing":4"#);
+    let nonexistent_path = std::env::temp_dir().join(format!(
+        "selfhash-bin-test-nonexistent-{}.json",
+        std::process::id()
+    ));
+    let nonexistent_path = nonexistent_path.to_str().expect("pass");
+
+    for (arg_v, stdin, expected_kind, expected_exit_code) in [
+        (vec!["verify", nonexistent_path], "", "io", 1),
+        (
+            vec!["verify", "--self-hash-paths", "$["],
+            self_hashed.as_str(),
+            "configuration",
+            2,
+        ),
+        (vec!["verify"], "not JSON", "parse", 3),
+        // A self-hash slot whose value isn't a valid self-hash is malformed, not missing.
+        (
+            vec!["verify"],
+            r#"{"thing":3,"selfHash":"not-a-hash"}"#,
+            "parse",
+            3,
+        ),
+        (vec!["verify"], r#"{"thing":3}"#, "missing-slot", 4),
+        // A missing self-hash URL slot can't be inserted.
+        (
+            vec!["compute", "--self-hash-url-paths", "$.url"],
+            r#"{"thing":3}"#,
+            "missing-slot",
+            4,
+        ),
+        (vec!["verify"], tampered.as_str(), "hash-mismatch", 5),
+        (
+            vec!["compute", "--hash-function", "NOT-A-HASH-FUNCTION"],
+            r#"{"thing":3}"#,
+            "unsupported-algorithm",
+            6,
+        ),
+    ] {
+        let output = run_selfhash(&arg_v, stdin);
+        assert_eq!(
+            output.status.code(),
+            Some(expected_exit_code),
+            "args: {:?}; stderr: {}",
+            arg_v,
+            stderr_of(&output)
+        );
+        assert!(
+            stderr_of(&output).contains(&format!("{} error: ", expected_kind)),
+            "args: {:?}; stderr: {}",
+            arg_v,
+            stderr_of(&output)
+        );
+    }
+
+    // The verified self-hash is printed on success.
+    let output = run_selfhash(&["verify", "--no-newline"], &self_hashed);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout_of(&output), self_hash);
+}
+
+#[test]
+fn test_verify_json_report() {
+    let (self_hashed, self_hash) = compute_json(r#"{"thing":3}"#);
+
+    let output = run_selfhash(&["verify", "--output", "json"], &self_hashed);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr_of(&output));
+    let report = serde_json::from_str::<serde_json::Value>(&stdout_of(&output)).expect("pass");
+    assert_eq!(report["file"], serde_json::Value::Null);
+    assert_eq!(report["verified"], true);
+    assert_eq!(report["claimedHash"], self_hash.as_str());
+    assert_eq!(report["computedHash"], self_hash.as_str());
+    assert_eq!(report["hashFunction"], "BLAKE3");
+    assert_eq!(report["base"], "base64-url");
+    assert_eq!(report["slotPaths"], serde_json::json!(["$['selfHash']"]));
+    assert_eq!(report["error"], serde_json::Value::Null);
+
+    // A mismatch reports both hashes and the error.
+    let tampered = self_hashed.replace(r#""thing":3"#, r#""thing":4"#);
+    let output = run_selfhash(&["verify", "--output", "json"], &tampered);
+    assert_eq!(output.status.code(), Some(5));
+    let report = serde_json::from_str::<serde_json::Value>(&stdout_of(&output)).expect("pass");
+    assert_eq!(report["verified"], false);
+    assert_eq!(report["claimedHash"], self_hash.as_str());
+    assert!(report["computedHash"].is_string());
+    assert_ne!(report["computedHash"], self_hash.as_str());
+    assert_eq!(report["hashFunction"], "BLAKE3");
+    assert_eq!(report["base"], "base64-url");
+    assert_eq!(report["error"]["kind"], "hash-mismatch");
+    assert_eq!(report["error"]["exitCode"], 5);
+
+    // Unparseable input reports nothing but the error.
+    let output = run_selfhash(&["verify", "--output", "json"], "not JSON");
+    assert_eq!(output.status.code(), Some(3));
+    let report = serde_json::from_str::<serde_json::Value>(&stdout_of(&output)).expect("pass");
+    assert_eq!(report["claimedHash"], serde_json::Value::Null);
+    assert_eq!(report["computedHash"], serde_json::Value::Null);
+    assert_eq!(report["hashFunction"], serde_json::Value::Null);
+    assert_eq!(report["base"], serde_json::Value::Null);
+    assert_eq!(report["slotPaths"], serde_json::Value::Null);
+    assert_eq!(report["error"]["kind"], "parse");
+    assert_eq!(report["error"]["exitCode"], 3);
+}
+
+#[test]
+fn test_verify_json_report_resolved_slot_paths() {
+    // slotPaths lists the slots found by a multi-match query, not the query itself.
+    let output = run_selfhash(
+        &[
+            "compute",
+            "--no-newline",
+            "--hash-function",
+            "SHA-256",
+            "--base",
+            "base58-btc",
+            "--self-hash-paths",
+            "$.items[*].id",
+        ],
+        r#"{"items":[{"id":null},{"id":null}]}"#,
+    );
+    assert_eq!(output.status.code(), Some(0), "{}", stderr_of(&output));
+    let self_hashed = stdout_of(&output);
+
+    let output = run_selfhash(
+        &[
+            "verify",
+            "--output",
+            "json",
+            "--self-hash-paths",
+            "$.items[*].id",
+        ],
+        &self_hashed,
+    );
+    assert_eq!(output.status.code(), Some(0), "{}", stderr_of(&output));
+    let report = serde_json::from_str::<serde_json::Value>(&stdout_of(&output)).expect("pass");
+    assert_eq!(report["hashFunction"], "SHA-256");
+    assert_eq!(report["base"], "base58-btc");
+    assert_eq!(
+        report["slotPaths"],
+        serde_json::json!(["$['items'][0]['id']", "$['items'][1]['id']"])
+    );
+
+    // Commas within brackets and quotes are part of a query, not delimiters, and the option may be
+    // repeated.
+    let output = run_selfhash(
+        &[
+            "compute",
+            "--no-newline",
+            "--self-hash-paths",
+            "$['a,b']",
+            "--self-hash-paths",
+            "$.c,$['d']",
+        ],
+        r#"{"a,b":null,"c":null,"d":null}"#,
+    );
+    assert_eq!(output.status.code(), Some(0), "{}", stderr_of(&output));
+    let output = run_selfhash(
+        &[
+            "verify",
+            "--output",
+            "json",
+            "--self-hash-paths",
+            "$['a,b'],$.c",
+            "--self-hash-paths",
+            "$['d']",
+        ],
+        &stdout_of(&output),
+    );
+    assert_eq!(output.status.code(), Some(0), "{}", stderr_of(&output));
+    let report = serde_json::from_str::<serde_json::Value>(&stdout_of(&output)).expect("pass");
+    assert_eq!(
+        report["slotPaths"],
+        serde_json::json!(["$['a,b']", "$['c']", "$['d']"])
+    );
+}
+
+#[test]
+fn test_hash_function_and_base() {
+    // The hash function and base are encoded in the self-hash, and reported by verify.  Each base may be
+    // given by name or by multibase prefix.
+    for (hash_function, base, base_name, self_hash_prefix) in [
+        ("SHA3-256", "base16-lower", "base16-lower", "f1620"),
+        ("SHA3-256", "f", "base16-lower", "f1620"),
+        ("SHA-256", "F", "base16-upper", "F1220"),
+        ("BLAKE3", "base2", "base2", "0"),
+        ("BLAKE3", "base10", "base10", "9"),
+        ("BLAKE3", "base32-hex-lower", "base32-hex-lower", "v"),
+        ("BLAKE3", "base32-z", "base32-z", "h"),
+        ("BLAKE3", "K", "base36-upper", "K"),
+        ("BLAKE3", "base58-flickr", "base58-flickr", "Z"),
+        ("SHA-512", "base64", "base64", "m"),
+        ("SHA-512", "base64-pad", "base64-pad", "M"),
+        ("SHA-384", "base64-url-pad", "base64-url-pad", "U"),
+    ] {
+        let output = run_selfhash(
+            &[
+                "compute",
+                "--no-newline",
+                "--hash-function",
+                hash_function,
+                "--base",
+                base,
+            ],
+            r#"{"thing":3}"#,
+        );
+        assert_eq!(output.status.code(), Some(0), "{}", stderr_of(&output));
+        let self_hashed = stdout_of(&output);
+        let value = serde_json::from_str::<serde_json::Value>(&self_hashed).expect("pass");
+        let self_hash = value["selfHash"].as_str().expect("pass");
+        assert!(
+            self_hash.starts_with(self_hash_prefix),
+            "base: {}; self-hash: {}",
+            base,
+            self_hash
+        );
+
+        let output = run_selfhash(&["verify", "--output", "json"], &self_hashed);
+        assert_eq!(output.status.code(), Some(0), "{}", stderr_of(&output));
+        let report = serde_json::from_str::<serde_json::Value>(&stdout_of(&output)).expect("pass");
+        assert_eq!(report["claimedHash"], self_hash);
+        assert_eq!(report["hashFunction"], hash_function);
+        assert_eq!(report["base"], base_name);
+    }
+
+    // The identity base isn't supported, since a self-hash is text.
+    let output = run_selfhash(&["compute", "--base", "identity"], r#"{"thing":3}"#);
+    assert_eq!(output.status.code(), Some(2));
+
+    // A base which isn't URL-safe can't be used with a self-hash URL slot.
+    let output = run_selfhash(
+        &[
+            "compute",
+            "--base",
+            "base64",
+            "--self-hash-url-paths",
+            "$.url",
+        ],
+        r#"{"url":"vjson:///"}"#,
+    );
+    assert_ne!(output.status.code(), Some(0));
+    assert!(
+        stderr_of(&output).contains("URL-safe base"),
+        "{}",
+        stderr_of(&output)
+    );
+}
+
+#[test]
+fn test_list_hash_functions() {
+    // Each line is the hash function's name, its multicodec code, and its placeholder hash, which is the
+    // multihash of an all-zero digest (the code as a varint, then the digest length, then the digest).
+    let output = run_selfhash(&["list-hash-functions", "--base", "base16-lower"], "");
+    assert_eq!(output.status.code(), Some(0), "{}", stderr_of(&output));
+    let line_v = stdout_of(&output)
+        .lines()
+        .map(|line| {
+            line.split_whitespace()
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let expected_v = [
+        ("BLAKE3", "0x1e", "1e", 32),
+        ("SHA-224", "0x1013", "9320", 28),
+        ("SHA-256", "0x12", "12", 32),
+        ("SHA-384", "0x20", "20", 48),
+        ("SHA-512", "0x13", "13", 64),
+        ("SHA3-224", "0x17", "17", 28),
+        ("SHA3-256", "0x16", "16", 32),
+        ("SHA3-384", "0x15", "15", 48),
+        ("SHA3-512", "0x14", "14", 64),
+    ];
+    assert_eq!(line_v.len(), expected_v.len());
+    for (line, (name, codec, codec_varint, digest_length)) in line_v.iter().zip(expected_v) {
+        assert_eq!(line.len(), 3, "{:?}", line);
+        assert_eq!(line[0], name);
+        assert_eq!(line[1], codec);
+        assert_eq!(
+            line[2],
+            format!(
+                "f{}{:02x}{}",
+                codec_varint,
+                digest_length,
+                "00".repeat(digest_length)
+            )
+        );
+    }
+
+    // The base defaults to base64-url.
+    let output = run_selfhash(&["list-hash-functions"], "");
+    assert_eq!(output.status.code(), Some(0), "{}", stderr_of(&output));
+    let stdout = stdout_of(&output);
+    let first_line = stdout.lines().next().expect("pass");
+    assert_eq!(
+        first_line.split_whitespace().collect::<Vec<_>>(),
+        vec![
+            "BLAKE3",
+            "0x1e",
+            "uHiAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA"
+        ]
+    );
+}
+
+/// Creates an empty temporary directory for the named test.
+fn temp_dir(name: &str) -> std::path::PathBuf {
+    let dir =
+        std::env::temp_dir().join(format!("selfhash-bin-test-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).expect("pass");
+    dir
+}
+
+/// Asserts that no temporary file from write_file_atomically was left in the given directory.
+fn assert_no_temp_files(dir: &std::path::Path) {
+    for entry_r in std::fs::read_dir(dir).expect("pass") {
+        let file_name = entry_r.expect("pass").file_name();
+        let file_name = file_name.to_string_lossy();
+        assert!(
+            !(file_name.starts_with('.') && file_name.contains(".selfhash-")),
+            "temporary file {:?} was left in {:?}",
+            file_name,
+            dir
+        );
+    }
+}
+
+/// Parses the `<file>: <result>` lines printed for each file.
+fn file_result_v(stdout: &str) -> Vec<(String, String)> {
+    stdout
+        .lines()
+        .map(|line| {
+            let (file, result) = line.rsplit_once(": ").expect("pass");
+            (file.to_string(), result.to_string())
+        })
+        .collect()
+}
+
+#[test]
+fn test_compute_in_place_with_glob() {
+    let dir = temp_dir("in-place");
+    std::fs::write(dir.join("a.json"), r#"{"thing":1}"#).expect("pass");
+    std::fs::write(dir.join("b.json"), r#"{"thing":2}"#).expect("pass");
+    std::fs::write(dir.join("c.txt"), "not matched by the glob").expect("pass");
+    let pattern = dir.join("*.json");
+    let pattern = pattern.to_str().expect("pass");
+
+    let output = run_selfhash(&["compute", "--in-place", pattern], "");
+    assert_eq!(output.status.code(), Some(0), "{}", stderr_of(&output));
+    let computed_v = file_result_v(&stdout_of(&output));
+    assert_eq!(
+        computed_v
+            .iter()
+            .map(|(file, _)| file.as_str())
+            .collect::<Vec<_>>(),
+        vec![
+            dir.join("a.json").to_str().expect("pass"),
+            dir.join("b.json").to_str().expect("pass"),
+        ]
+    );
+    // Each file was overwritten with its self-hashed form.
+    for (file, self_hash) in computed_v.iter() {
+        let value = serde_json::from_str::<serde_json::Value>(
+            &std::fs::read_to_string(file).expect("pass"),
+        )
+        .expect("pass");
+        assert_eq!(value["selfHash"], self_hash.as_str());
+    }
+    assert_eq!(
+        std::fs::read_to_string(dir.join("c.txt")).expect("pass"),
+        "not matched by the glob"
+    );
+    assert_no_temp_files(&dir);
+
+    // Verifying them reports the same self-hashes.
+    let output = run_selfhash(&["verify", pattern], "");
+    assert_eq!(output.status.code(), Some(0), "{}", stderr_of(&output));
+    assert_eq!(file_result_v(&stdout_of(&output)), computed_v);
+
+    // A glob which matches nothing is an error, as is more than one file without --in-place or
+    // --output-dir.
+    let unmatched_pattern = dir.join("*.yaml");
+    let output = run_selfhash(
+        &[
+            "compute",
+            "--in-place",
+            unmatched_pattern.to_str().expect("pass"),
+        ],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(2));
+    let output = run_selfhash(&["compute", pattern], "");
+    assert_eq!(output.status.code(), Some(2));
+
+    std::fs::remove_dir_all(&dir).expect("pass");
+}
+
+#[test]
+fn test_file_args_with_glob_metacharacters() {
+    let dir = temp_dir("glob-metacharacters");
+    std::fs::write(dir.join("[draft].json"), r#"{"thing":1}"#).expect("pass");
+    std::fs::write(dir.join("d.json"), r#"{"thing":2}"#).expect("pass");
+
+    // An existing path is taken literally, even though it's also a glob pattern (which would match
+    // d.json instead).
+    let literal_path = dir.join("[draft].json");
+    let literal_path = literal_path.to_str().expect("pass");
+    let output = run_selfhash(&["compute", "--in-place", literal_path], "");
+    assert_eq!(output.status.code(), Some(0), "{}", stderr_of(&output));
+    assert_eq!(
+        file_result_v(&stdout_of(&output))
+            .iter()
+            .map(|(file, _)| file.as_str())
+            .collect::<Vec<_>>(),
+        vec![literal_path]
+    );
+    assert_eq!(
+        std::fs::read_to_string(dir.join("d.json")).expect("pass"),
+        r#"{"thing":2}"#
+    );
+    let output = run_selfhash(&["verify", literal_path], "");
+    assert_eq!(output.status.code(), Some(0), "{}", stderr_of(&output));
+
+    // A nonexistent path is still a glob pattern.
+    let pattern = dir.join("[d].json");
+    let output = run_selfhash(&["verify", pattern.to_str().expect("pass")], "");
+    assert_eq!(output.status.code(), Some(4), "{}", stderr_of(&output));
+
+    // A directory which can't be read while matching a glob pattern is an I/O error, rather than being
+    // silently skipped.
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let unreadable_dir = dir.join("unreadable");
+        std::fs::create_dir(&unreadable_dir).expect("pass");
+        std::fs::write(unreadable_dir.join("e.json"), r#"{"thing":3}"#).expect("pass");
+        std::fs::set_permissions(&unreadable_dir, std::fs::Permissions::from_mode(0o000))
+            .expect("pass");
+        // Permissions don't apply to a privileged user, in which case there's no error to test.
+        if std::fs::read_dir(&unreadable_dir).is_err() {
+            let pattern = dir.join("*").join("*.json");
+            let output = run_selfhash(&["verify", pattern.to_str().expect("pass")], "");
+            assert_eq!(output.status.code(), Some(1), "{}", stderr_of(&output));
+            assert!(
+                stderr_of(&output).contains("io error: "),
+                "{}",
+                stderr_of(&output)
+            );
+        }
+        std::fs::set_permissions(&unreadable_dir, std::fs::Permissions::from_mode(0o755))
+            .expect("pass");
+    }
+
+    std::fs::remove_dir_all(&dir).expect("pass");
+}
+
+#[test]
+fn test_compute_output_dir() {
+    let dir = temp_dir("output-dir");
+    let output_dir = dir.join("out");
+    std::fs::write(dir.join("a.json"), r#"{"thing":1}"#).expect("pass");
+    std::fs::write(dir.join("b.json"), r#"{"thing":2}"#).expect("pass");
+
+    let output = run_selfhash(
+        &[
+            "compute",
+            "--output-dir",
+            output_dir.to_str().expect("pass"),
+            dir.join("a.json").to_str().expect("pass"),
+            dir.join("b.json").to_str().expect("pass"),
+        ],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(0), "{}", stderr_of(&output));
+    let computed_v = file_result_v(&stdout_of(&output));
+    assert_eq!(computed_v.len(), 2);
+    for ((file, self_hash), file_name) in computed_v.iter().zip(["a.json", "b.json"]) {
+        assert_eq!(file, dir.join(file_name).to_str().expect("pass"));
+        // The input is unchanged, and the output is in the output directory.
+        assert!(!std::fs::read_to_string(file)
+            .expect("pass")
+            .contains("selfHash"));
+        let value = serde_json::from_str::<serde_json::Value>(
+            &std::fs::read_to_string(output_dir.join(file_name)).expect("pass"),
+        )
+        .expect("pass");
+        assert_eq!(value["selfHash"], self_hash.as_str());
+    }
+    assert_no_temp_files(&output_dir);
+
+    std::fs::remove_dir_all(&dir).expect("pass");
+}
+
+#[test]
+fn test_process_files_continues_past_failures() {
+    let dir = temp_dir("failures");
+    std::fs::write(dir.join("a.json"), r#"{"thing":1}"#).expect("pass");
+    std::fs::write(dir.join("b.json"), "not JSON").expect("pass");
+    std::fs::write(dir.join("c.json"), r#"{"thing":3}"#).expect("pass");
+    let pattern = dir.join("*.json");
+    let pattern = pattern.to_str().expect("pass");
+
+    // The other files are still self-hashed, and the exit status is that of the failure.
+    let output = run_selfhash(&["compute", "--in-place", pattern], "");
+    assert_eq!(output.status.code(), Some(3));
+    let computed_v = file_result_v(&stdout_of(&output));
+    assert_eq!(
+        computed_v
+            .iter()
+            .map(|(file, _)| file.as_str())
+            .collect::<Vec<_>>(),
+        vec![
+            dir.join("a.json").to_str().expect("pass"),
+            dir.join("c.json").to_str().expect("pass"),
+        ]
+    );
+    let stderr = stderr_of(&output);
+    assert!(
+        stderr.contains(&format!(
+            "{}: parse error: ",
+            dir.join("b.json").to_str().expect("pass")
+        )),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains("failed to self-hash 1 of 3 files"),
+        "{}",
+        stderr
+    );
+    assert_eq!(
+        std::fs::read_to_string(dir.join("b.json")).expect("pass"),
+        "not JSON"
+    );
+    assert_no_temp_files(&dir);
+
+    // Likewise for verification, where a mismatch is reported with its own exit status.
+    std::fs::write(dir.join("b.json"), r#"{"selfHash":null,"thing":2}"#).expect("pass");
+    let output = run_selfhash(&["compute", "--in-place", pattern], "");
+    assert_eq!(output.status.code(), Some(0), "{}", stderr_of(&output));
+    let c_json = std::fs::read_to_string(dir.join("c.json")).expect("pass");
+    std::fs::write(
+        dir.join("c.json"),
+        c_json.replace(r#""thing":3"#, r#""thing":4"#),
+    )
+    .expect("pass");
+    let output = run_selfhash(&["verify", pattern], "");
+    assert_eq!(output.status.code(), Some(5));
+    assert_eq!(file_result_v(&stdout_of(&output)).len(), 2);
+    let stderr = stderr_of(&output);
+    assert!(
+        stderr.contains(&format!(
+            "{}: hash-mismatch error: ",
+            dir.join("c.json").to_str().expect("pass")
+        )),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains("failed to verify 1 of 3 files"),
+        "{}",
+        stderr
+    );
+
+    std::fs::remove_dir_all(&dir).expect("pass");
+}
@@ -0,0 +1,25 @@
+use crate::{ensure, Result};
+
+/// Normalizes the path of an archive entry to the form used in a TreeManifest, i.e. relative to the root
+/// of the archive with `/` as the separator, stripping any leading `./` and trailing `/`.  Absolute paths
+/// and paths containing empty, `.`, or `..` components are rejected, since they don't name a file within
+/// the archive unambiguously.
+pub(crate) fn normalize_archive_entry_path(raw_path: &str) -> Result<String> {
+    let mut path = raw_path;
+    while let Some(stripped_path) = path.strip_prefix("./") {
+        path = stripped_path;
+    }
+    let path = path.strip_suffix('/').unwrap_or(path);
+    ensure!(
+        !path.is_empty() && !path.starts_with('/'),
+        "archive entry path {:?} is empty or absolute, which is not supported",
+        raw_path
+    );
+    ensure!(
+        path.split('/')
+            .all(|component| !component.is_empty() && component != "." && component != ".."),
+        "archive entry path {:?} contains an empty, \".\", or \"..\" component, which is not supported",
+        raw_path
+    );
+    Ok(path.to_string())
+}
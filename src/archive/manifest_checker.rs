@@ -0,0 +1,71 @@
+use crate::{
+    bail,
+    tree::{TreeManifest, TreeManifestEntry},
+    Result,
+};
+use std::collections::BTreeMap;
+
+/// Checks the entries of an archive against its (already self-hash-verified) TreeManifest as they're
+/// streamed, failing as soon as an entry is unlisted, duplicated, or modified, and failing at the end if
+/// any listed entries were never seen.
+pub(crate) struct ManifestChecker<'a> {
+    /// The listed entries which haven't been seen yet, by path.
+    unseen_entry_m: BTreeMap<&'a str, &'a TreeManifestEntry>,
+    /// The listed entries which have been seen, by path.
+    seen_entry_m: BTreeMap<&'a str, &'a TreeManifestEntry>,
+}
+
+impl<'a> ManifestChecker<'a> {
+    /// Fails if the manifest lists any path more than once, since only one of those entries could be
+    /// checked.
+    pub(crate) fn new(tree_manifest: &'a TreeManifest) -> Result<Self> {
+        let mut unseen_entry_m = BTreeMap::new();
+        for entry in tree_manifest.entries.iter() {
+            if unseen_entry_m.insert(entry.path.as_str(), entry).is_some() {
+                bail!("tree manifest lists path {:?} more than once", entry.path);
+            }
+        }
+        Ok(Self {
+            unseen_entry_m,
+            seen_entry_m: BTreeMap::new(),
+        })
+    }
+    /// Checks the actual entry (computed from the archive) against the one listed in the manifest.
+    pub(crate) fn check(&mut self, actual_entry: &TreeManifestEntry) -> Result<()> {
+        let claimed_entry = match self.unseen_entry_m.remove(actual_entry.path.as_str()) {
+            Some(claimed_entry) => claimed_entry,
+            None => {
+                if self.seen_entry_m.contains_key(actual_entry.path.as_str()) {
+                    bail!(
+                        "archive contains more than one entry with path {:?}",
+                        actual_entry.path
+                    );
+                } else {
+                    bail!(
+                        "archive entry {:?} is not listed in the manifest",
+                        actual_entry.path
+                    );
+                }
+            }
+        };
+        if claimed_entry != actual_entry {
+            bail!(
+                "archive entry {:?} doesn't match the manifest (mode, size, or digest differ)",
+                actual_entry.path
+            );
+        }
+        self.seen_entry_m
+            .insert(claimed_entry.path.as_str(), claimed_entry);
+        Ok(())
+    }
+    /// Ensures that every entry listed in the manifest was seen.
+    pub(crate) fn finish(self) -> Result<()> {
+        if !self.unseen_entry_m.is_empty() {
+            bail!(
+                "archive is missing entries listed in the manifest: {:?}",
+                self.unseen_entry_m.keys().collect::<Vec<_>>()
+            );
+        }
+        Ok(())
+    }
+}
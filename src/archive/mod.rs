@@ -0,0 +1,22 @@
+//! Self-hashing of tar and zip archives, e.g. release bundles shipped as archives.  Such an archive contains
+//! a TreeManifest entry (named MANIFEST_FILE_NAME, in the root of the archive) which lists every other file
+//! entry of the archive along with its mode, size, and digest, exactly as for a directory tree (see the
+//! `tree` module), so that the manifest's self-hash covers the whole archive.  Archives are streamed through
+//! without extracting anything to disk, and verification fails on any entry that the manifest doesn't list.
+//!
+//! Directory entries are only represented by the file entries within them, and entries of any other kind
+//! (e.g. symbolic links) are rejected.  Compressed tar archives (e.g. `.tar.gz`) can be handled by passing
+//! in a decompressing reader.
+
+mod archive_entry_path;
+mod manifest_checker;
+mod tar_archive;
+mod zip_archive;
+
+pub(crate) use {
+    archive_entry_path::normalize_archive_entry_path, manifest_checker::ManifestChecker,
+};
+pub use {
+    tar_archive::{compute_tar_manifest, verify_tar_manifest, write_tar_with_manifest},
+    zip_archive::{compute_zip_manifest, verify_zip_manifest, write_zip_with_manifest},
+};
@@ -0,0 +1,158 @@
+use crate::{
+    archive::{normalize_archive_entry_path, ManifestChecker},
+    bail, error,
+    tree::{TreeManifest, TreeManifestEntry, MANIFEST_FILE_NAME},
+    MBHashFunction, Result,
+};
+use std::io::{Read, Seek, SeekFrom, Write};
+
+/// Computes the self-hashed manifest of the given tar archive, covering all its file entries other than
+/// the manifest entry (see MANIFEST_FILE_NAME) in its root, if present.  The archive is streamed through
+/// once, without extracting anything to disk.
+pub fn compute_tar_manifest<R: Read>(
+    reader: R,
+    mb_hash_function: &MBHashFunction,
+) -> Result<TreeManifest> {
+    let mut archive = tar::Archive::new(reader);
+    let mut entry_v = Vec::new();
+    for entry_r in archive.entries().map_err(map_tar_error)? {
+        let mut entry = entry_r.map_err(map_tar_error)?;
+        let path = match file_entry_path(&entry)? {
+            Some(path) if path != MANIFEST_FILE_NAME => path,
+            _ => continue,
+        };
+        entry_v.push(compute_entry(path, &mut entry, mb_hash_function)?);
+    }
+    let mut tree_manifest = TreeManifest::new(mb_hash_function, entry_v)?;
+    tree_manifest.self_hash()?;
+    Ok(tree_manifest)
+}
+
+/// Computes the self-hashed manifest of the given tar archive (see compute_tar_manifest) and writes a copy
+/// of the archive to the given writer, with the manifest as its first entry, replacing any existing
+/// manifest entry.  The archive is read twice, hence the Seek bound.  Returns the manifest.
+pub fn write_tar_with_manifest<R: Read + Seek, W: Write>(
+    mut reader: R,
+    writer: W,
+    mb_hash_function: &MBHashFunction,
+) -> Result<TreeManifest> {
+    let tree_manifest = compute_tar_manifest(&mut reader, mb_hash_function)?;
+    reader.seek(SeekFrom::Start(0)).map_err(map_tar_error)?;
+
+    let mut builder = tar::Builder::new(writer);
+    let manifest_json = tree_manifest.to_json_string()?;
+    let mut manifest_header = tar::Header::new_gnu();
+    manifest_header.set_entry_type(tar::EntryType::Regular);
+    manifest_header.set_mode(0o644);
+    manifest_header.set_mtime(0);
+    manifest_header.set_size(manifest_json.len() as u64);
+    builder
+        .append_data(
+            &mut manifest_header,
+            MANIFEST_FILE_NAME,
+            manifest_json.as_bytes(),
+        )
+        .map_err(map_tar_error)?;
+
+    let mut archive = tar::Archive::new(reader);
+    for entry_r in archive.entries().map_err(map_tar_error)? {
+        let mut entry = entry_r.map_err(map_tar_error)?;
+        let entry_type = entry.header().entry_type();
+        if !entry_type.is_file() && !entry_type.is_dir() {
+            // Extension headers have already been applied to the entries they describe.
+            continue;
+        }
+        if file_entry_path(&entry)?.as_deref() == Some(MANIFEST_FILE_NAME) {
+            continue;
+        }
+        let mut header = entry.header().clone();
+        let path = entry.path().map_err(map_tar_error)?.into_owned();
+        builder
+            .append_data(&mut header, path, &mut entry)
+            .map_err(map_tar_error)?;
+    }
+    builder.into_inner().map_err(map_tar_error)?;
+    Ok(tree_manifest)
+}
+
+/// Verifies the given tar archive against its manifest, which must be its first file entry so that the
+/// archive can be verified in a single streaming pass (write_tar_with_manifest puts it there).  The
+/// manifest's self-hash is verified, and then every other file entry must match the manifest exactly; an
+/// error is returned as soon as an entry is found that the manifest doesn't list (or that doesn't match),
+/// or at the end if the archive lacks any listed entries.  Returns the verified self-hash.
+pub fn verify_tar_manifest<R: Read>(reader: R) -> Result<mbx::MBHash> {
+    let mut archive = tar::Archive::new(reader);
+    let mut entries = archive.entries().map_err(map_tar_error)?;
+    let tree_manifest = loop {
+        let mut entry = match entries.next() {
+            Some(entry_r) => entry_r.map_err(map_tar_error)?,
+            None => bail!("tar archive has no {} entry", MANIFEST_FILE_NAME),
+        };
+        match file_entry_path(&entry)? {
+            Some(path) if path == MANIFEST_FILE_NAME => {
+                let mut manifest_json = String::new();
+                entry
+                    .read_to_string(&mut manifest_json)
+                    .map_err(map_tar_error)?;
+                break TreeManifest::from_json_str(&manifest_json)?;
+            }
+            Some(path) => bail!(
+                "tar archive entry {:?} precedes the {} entry, which must be the first file entry",
+                path,
+                MANIFEST_FILE_NAME
+            ),
+            None => continue,
+        }
+    };
+    let self_hash = tree_manifest.verify_self_hash()?;
+
+    let mb_hash_function = tree_manifest.mb_hash_function();
+    let mut manifest_checker = ManifestChecker::new(&tree_manifest)?;
+    for entry_r in entries {
+        let mut entry = entry_r.map_err(map_tar_error)?;
+        let path = match file_entry_path(&entry)? {
+            Some(path) => path,
+            None => continue,
+        };
+        manifest_checker.check(&compute_entry(path, &mut entry, &mb_hash_function)?)?;
+    }
+    manifest_checker.finish()?;
+    Ok(self_hash)
+}
+
+/// Returns the normalized path of the given entry if it's a regular file, or None if it's a directory or
+/// an extension header, neither of which is represented in the manifest.  Entries of any other kind (e.g.
+/// symbolic links) are rejected.
+fn file_entry_path<R: Read>(entry: &tar::Entry<R>) -> Result<Option<String>> {
+    let entry_type = entry.header().entry_type();
+    let path_bytes = entry.path_bytes();
+    let raw_path = std::str::from_utf8(&path_bytes)
+        .map_err(|_| error!("tar archive entry path {:?} is not valid UTF-8", path_bytes))?;
+    if entry_type.is_file() {
+        Ok(Some(normalize_archive_entry_path(raw_path)?))
+    } else if entry_type.is_dir()
+        || entry_type.is_pax_global_extensions()
+        || entry_type.is_pax_local_extensions()
+    {
+        Ok(None)
+    } else {
+        bail!(
+            "tar archive entry {:?} is not a regular file or directory (e.g. it's a symbolic link), which is not supported in a manifest",
+            raw_path
+        );
+    }
+}
+
+fn compute_entry<R: Read>(
+    path: String,
+    entry: &mut tar::Entry<R>,
+    mb_hash_function: &MBHashFunction,
+) -> Result<TreeManifestEntry> {
+    let mode = entry.header().mode().map_err(map_tar_error)? & 0o7777;
+    TreeManifestEntry::from_reader(path, format!("{:04o}", mode), entry, mb_hash_function)
+        .map_err(map_tar_error)
+}
+
+fn map_tar_error(e: std::io::Error) -> crate::Error {
+    error!("failed to read or write tar archive; error was {}", e)
+}
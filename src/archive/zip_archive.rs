@@ -0,0 +1,173 @@
+use crate::{
+    archive::{normalize_archive_entry_path, ManifestChecker},
+    bail, error,
+    tree::{TreeManifest, TreeManifestEntry, MANIFEST_FILE_NAME},
+    MBHashFunction, Result,
+};
+use std::io::{Read, Seek, Write};
+
+/// The mask of the file type bits within a Unix mode.
+const UNIX_FILE_TYPE_MASK: u32 = 0o170000;
+/// The file type bits of a regular file within a Unix mode.
+const UNIX_FILE_TYPE_REGULAR: u32 = 0o100000;
+/// The file type bits of a directory within a Unix mode.
+const UNIX_FILE_TYPE_DIRECTORY: u32 = 0o040000;
+
+/// Computes the self-hashed manifest of the given zip archive, covering all its file entries other than
+/// the manifest entry (see MANIFEST_FILE_NAME) in its root, if present.  Each entry is decompressed and
+/// streamed into the hasher, without extracting anything to disk.
+pub fn compute_zip_manifest<R: Read + Seek>(
+    reader: R,
+    mb_hash_function: &MBHashFunction,
+) -> Result<TreeManifest> {
+    let mut archive = zip::ZipArchive::new(reader).map_err(map_zip_error)?;
+    compute_manifest_of_archive(&mut archive, mb_hash_function)
+}
+
+/// Computes the self-hashed manifest of the given zip archive (see compute_zip_manifest) and writes a copy
+/// of the archive to the given writer, with the manifest as its first entry, replacing any existing
+/// manifest entry.  The other entries are copied without being recompressed.  Returns the manifest.
+pub fn write_zip_with_manifest<R: Read + Seek, W: Write + Seek>(
+    reader: R,
+    writer: W,
+    mb_hash_function: &MBHashFunction,
+) -> Result<TreeManifest> {
+    let mut archive = zip::ZipArchive::new(reader).map_err(map_zip_error)?;
+    let tree_manifest = compute_manifest_of_archive(&mut archive, mb_hash_function)?;
+
+    let mut zip_writer = zip::ZipWriter::new(writer);
+    zip_writer
+        .start_file(
+            MANIFEST_FILE_NAME,
+            zip::write::SimpleFileOptions::default().unix_permissions(0o644),
+        )
+        .map_err(map_zip_error)?;
+    zip_writer
+        .write_all(tree_manifest.to_json_string()?.as_bytes())
+        .map_err(|e| error!("failed to write zip archive; error was {}", e))?;
+    for index in 0..archive.len() {
+        let zip_file = archive.by_index_raw(index).map_err(map_zip_error)?;
+        if normalize_archive_entry_path(zip_file.name())? == MANIFEST_FILE_NAME {
+            continue;
+        }
+        zip_writer.raw_copy_file(zip_file).map_err(map_zip_error)?;
+    }
+    zip_writer.finish().map_err(map_zip_error)?;
+    Ok(tree_manifest)
+}
+
+/// Verifies the given zip archive against its manifest entry (see MANIFEST_FILE_NAME).  The manifest's
+/// self-hash is verified, and then every other file entry must match the manifest exactly; an error is
+/// returned as soon as an entry is found that the manifest doesn't list (or that doesn't match), or at the
+/// end if the archive lacks any listed entries.  Returns the verified self-hash.
+pub fn verify_zip_manifest<R: Read + Seek>(reader: R) -> Result<mbx::MBHash> {
+    let mut archive = zip::ZipArchive::new(reader).map_err(map_zip_error)?;
+    let tree_manifest = {
+        let mut zip_file = archive.by_name(MANIFEST_FILE_NAME).map_err(|e| {
+            error!(
+                "zip archive has no {} entry; error was {}",
+                MANIFEST_FILE_NAME, e
+            )
+        })?;
+        let mut manifest_json = String::new();
+        zip_file
+            .read_to_string(&mut manifest_json)
+            .map_err(|e| error!("failed to read zip archive; error was {}", e))?;
+        TreeManifest::from_json_str(&manifest_json)?
+    };
+    let self_hash = tree_manifest.verify_self_hash()?;
+
+    let mb_hash_function = tree_manifest.mb_hash_function();
+    let mut manifest_checker = ManifestChecker::new(&tree_manifest)?;
+    let mut manifest_entry_count = 0usize;
+    for index in 0..archive.len() {
+        let mut zip_file = archive.by_index(index).map_err(map_zip_error)?;
+        let path = match file_entry_path(zip_file.name(), zip_file.is_dir(), zip_file.unix_mode())?
+        {
+            Some(path) => path,
+            None => continue,
+        };
+        if path == MANIFEST_FILE_NAME {
+            manifest_entry_count += 1;
+            if manifest_entry_count > 1 {
+                bail!(
+                    "zip archive contains more than one {} entry",
+                    MANIFEST_FILE_NAME
+                );
+            }
+            continue;
+        }
+        let unix_mode_o = zip_file.unix_mode();
+        manifest_checker.check(&compute_entry(
+            path,
+            unix_mode_o,
+            &mut zip_file,
+            &mb_hash_function,
+        )?)?;
+    }
+    manifest_checker.finish()?;
+    Ok(self_hash)
+}
+
+fn compute_manifest_of_archive<R: Read + Seek>(
+    archive: &mut zip::ZipArchive<R>,
+    mb_hash_function: &MBHashFunction,
+) -> Result<TreeManifest> {
+    let mut entry_v = Vec::new();
+    for index in 0..archive.len() {
+        let mut zip_file = archive.by_index(index).map_err(map_zip_error)?;
+        let path = match file_entry_path(zip_file.name(), zip_file.is_dir(), zip_file.unix_mode())?
+        {
+            Some(path) if path != MANIFEST_FILE_NAME => path,
+            _ => continue,
+        };
+        let unix_mode_o = zip_file.unix_mode();
+        entry_v.push(compute_entry(
+            path,
+            unix_mode_o,
+            &mut zip_file,
+            mb_hash_function,
+        )?);
+    }
+    let mut tree_manifest = TreeManifest::new(mb_hash_function, entry_v)?;
+    tree_manifest.self_hash()?;
+    Ok(tree_manifest)
+}
+
+/// Returns the normalized path of the entry with the given name, directory flag, and Unix mode (if
+/// recorded) if it's a regular file, or None if it's a directory, which is not represented in the
+/// manifest.  Entries of any other kind (e.g. symbolic links) are rejected.
+fn file_entry_path(name: &str, is_dir: bool, unix_mode_o: Option<u32>) -> Result<Option<String>> {
+    let file_type_o = unix_mode_o
+        .map(|unix_mode| unix_mode & UNIX_FILE_TYPE_MASK)
+        .filter(|file_type| *file_type != 0);
+    if is_dir || file_type_o == Some(UNIX_FILE_TYPE_DIRECTORY) {
+        Ok(None)
+    } else if file_type_o.is_none() || file_type_o == Some(UNIX_FILE_TYPE_REGULAR) {
+        Ok(Some(normalize_archive_entry_path(name)?))
+    } else {
+        bail!(
+            "zip archive entry {:?} is not a regular file or directory (e.g. it's a symbolic link), which is not supported in a manifest",
+            name
+        );
+    }
+}
+
+/// Computes the manifest entry for a zip file entry, streaming its (decompressed) content from the given
+/// reader.  The mode defaults to "0644" if the archive doesn't record Unix permissions.
+fn compute_entry(
+    path: String,
+    unix_mode_o: Option<u32>,
+    reader: &mut dyn Read,
+    mb_hash_function: &MBHashFunction,
+) -> Result<TreeManifestEntry> {
+    let mode = unix_mode_o
+        .map(|unix_mode| unix_mode & 0o7777)
+        .unwrap_or(0o644);
+    TreeManifestEntry::from_reader(path, format!("{:04o}", mode), reader, mb_hash_function)
+        .map_err(|e| error!("failed to read zip archive entry; error was {}", e))
+}
+
+fn map_zip_error(e: zip::result::ZipError) -> crate::Error {
+    error!("failed to read or write zip archive; error was {}", e)
+}
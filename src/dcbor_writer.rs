@@ -0,0 +1,153 @@
+use crate::{bail, ensure, error, Result};
+
+/// Writes the given value as deterministically encoded CBOR (see RFC 8949, section 4.2.1) into the given
+/// writer.  In particular:
+/// - Integers, lengths, and tags use the shortest possible encoding of their argument.
+/// - Arrays, maps, byte strings, and text strings use definite-length encoding.
+/// - Map entries are sorted by the bytewise lexicographic order of the deterministic encodings of their
+///   keys, and duplicate keys are an error.
+/// - Floating-point values use the shortest of binary16, binary32, and binary64 which preserves the value,
+///   and all NaNs are encoded as the binary16 quiet NaN 0xf97e00.
+pub(crate) fn write_dcbor<W: std::io::Write>(
+    value: &ciborium::Value,
+    writer: &mut W,
+) -> Result<()> {
+    let dcbor = to_dcbor_vec(value)?;
+    writer
+        .write_all(dcbor.as_slice())
+        .map_err(|e| error!("failed to write deterministic CBOR; error was {}", e))
+}
+
+/// Returns the deterministic CBOR encoding of the given value (see write_dcbor).
+pub(crate) fn to_dcbor_vec(value: &ciborium::Value) -> Result<Vec<u8>> {
+    let mut dcbor = Vec::new();
+    encode(value, &mut dcbor)?;
+    Ok(dcbor)
+}
+
+fn encode(value: &ciborium::Value, out: &mut Vec<u8>) -> Result<()> {
+    match value {
+        ciborium::Value::Integer(integer) => {
+            // ciborium::value::Integer is guaranteed to be in the range [-2^64, 2^64-1].
+            let i = i128::from(*integer);
+            if i >= 0 {
+                encode_head(0, i as u64, out);
+            } else {
+                encode_head(1, (-1 - i) as u64, out);
+            }
+        }
+        ciborium::Value::Bytes(bytes) => {
+            encode_head(2, bytes.len() as u64, out);
+            out.extend_from_slice(bytes);
+        }
+        ciborium::Value::Float(f) => encode_float(*f, out),
+        ciborium::Value::Text(text) => {
+            encode_head(3, text.len() as u64, out);
+            out.extend_from_slice(text.as_bytes());
+        }
+        ciborium::Value::Bool(false) => out.push(0xf4),
+        ciborium::Value::Bool(true) => out.push(0xf5),
+        ciborium::Value::Null => out.push(0xf6),
+        ciborium::Value::Tag(tag, tagged_value) => {
+            encode_head(6, *tag, out);
+            encode(tagged_value, out)?;
+        }
+        ciborium::Value::Array(array) => {
+            encode_head(4, array.len() as u64, out);
+            for element in array.iter() {
+                encode(element, out)?;
+            }
+        }
+        ciborium::Value::Map(map) => {
+            let mut entry_v = map
+                .iter()
+                .map(|(key, value)| Ok((to_dcbor_vec(key)?, value)))
+                .collect::<Result<Vec<_>>>()?;
+            entry_v.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+            ensure!(
+                entry_v.windows(2).all(|pair| pair[0].0 != pair[1].0),
+                "CBOR map has duplicate keys, and therefore has no deterministic encoding"
+            );
+            encode_head(5, entry_v.len() as u64, out);
+            for (key_dcbor, value) in entry_v.into_iter() {
+                out.extend_from_slice(key_dcbor.as_slice());
+                encode(value, out)?;
+            }
+        }
+        _ => {
+            bail!("unsupported CBOR value {:?}", value);
+        }
+    }
+    Ok(())
+}
+
+/// Encodes the initial byte(s) of a data item having the given major type and argument, using the
+/// shortest possible encoding of the argument.
+fn encode_head(major_type: u8, argument: u64, out: &mut Vec<u8>) {
+    let initial_byte = major_type << 5;
+    if argument < 24 {
+        out.push(initial_byte | argument as u8);
+    } else if argument <= u8::MAX as u64 {
+        out.push(initial_byte | 24);
+        out.push(argument as u8);
+    } else if argument <= u16::MAX as u64 {
+        out.push(initial_byte | 25);
+        out.extend_from_slice(&(argument as u16).to_be_bytes());
+    } else if argument <= u32::MAX as u64 {
+        out.push(initial_byte | 26);
+        out.extend_from_slice(&(argument as u32).to_be_bytes());
+    } else {
+        out.push(initial_byte | 27);
+        out.extend_from_slice(&argument.to_be_bytes());
+    }
+}
+
+fn encode_float(f: f64, out: &mut Vec<u8>) {
+    if f.is_nan() {
+        out.extend_from_slice(&[0xf9, 0x7e, 0x00]);
+        return;
+    }
+    let f_32 = f as f32;
+    if f_32 as f64 == f {
+        if let Some(f_16_bits) = f32_to_f16_bits_exact(f_32) {
+            out.push(0xf9);
+            out.extend_from_slice(&f_16_bits.to_be_bytes());
+        } else {
+            out.push(0xfa);
+            out.extend_from_slice(&f_32.to_bits().to_be_bytes());
+        }
+    } else {
+        out.push(0xfb);
+        out.extend_from_slice(&f.to_bits().to_be_bytes());
+    }
+}
+
+/// Returns the bits of the binary16 representation of the given (non-NaN) value, if it can be represented
+/// exactly as a binary16.
+fn f32_to_f16_bits_exact(f: f32) -> Option<u16> {
+    let bits = f.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let biased_exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x007f_ffff;
+    if biased_exponent == 0xff {
+        // Infinity (NaN is handled by the caller).
+        return (mantissa == 0).then_some(sign | 0x7c00);
+    }
+    if biased_exponent == 0 {
+        // Zero is representable, but binary32 subnormals are too small for binary16.
+        return (mantissa == 0).then_some(sign);
+    }
+    let exponent = biased_exponent - 127;
+    if (-14..=15).contains(&exponent) {
+        // Normal binary16, which has 10 bits of mantissa instead of 23.
+        (mantissa & 0x1fff == 0)
+            .then(|| sign | (((exponent + 15) as u16) << 10) | (mantissa >> 13) as u16)
+    } else if (-24..-14).contains(&exponent) {
+        // Subnormal binary16, whose value is m * 2^-24 for a 10-bit m.
+        let significand = mantissa | 0x0080_0000;
+        let shift = -(exponent + 1);
+        (significand & ((1 << shift) - 1) == 0).then(|| sign | (significand >> shift) as u16)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,53 @@
+use crate::{
+    did::did_self_hash_str::DID_SELF_HASH_PREFIX, DIDSelfHashStr, HashT, Result, TryHashT,
+};
+use pneutype::Validate;
+
+/// Represents a DID of the did:selfhash method, which has the form "did:selfhash:<mb-hash>", where the
+/// <mb-hash> is the self-hash of the DID's own DID document (see DIDSelfHashDocument).  The DID
+/// "did:selfhash:" (with an empty self-hash) represents an unset self-hash slot.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, pneutype::PneuString)]
+#[pneu_string(as_pneu_str = "as_did_self_hash_str", borrow = "DIDSelfHashStr")]
+#[derive(serde::Serialize)]
+#[pneu_string(deserialize)]
+pub struct DIDSelfHash(String);
+
+impl DIDSelfHash {
+    /// Produces the DID "did:selfhash:<mb-hash>".  This fails if the MBHash's base isn't allowed in a DID
+    /// (see DIDSelfHashStr).
+    pub fn new(mb_hash: &mbx::MBHashStr) -> Result<Self> {
+        Self::try_from(format!("{}{}", DID_SELF_HASH_PREFIX, mb_hash))
+    }
+    /// Returns the DID whose self-hash slot is unset, i.e. "did:selfhash:".
+    pub fn unset() -> Self {
+        Self::try_from(DID_SELF_HASH_PREFIX.to_string()).unwrap()
+    }
+    /// Sets the self-hash of this DID to the given MBHash, leaving this DID unchanged if that fails, e.g.
+    /// because the MBHash's base isn't allowed in a DID (see DIDSelfHashStr).
+    pub fn set_self_hash_slots_to_mb_hash(&mut self, mb_hash: &mbx::MBHashStr) -> Result<()> {
+        let s = format!("{}{}", DID_SELF_HASH_PREFIX, mb_hash);
+        DIDSelfHashStr::validate(&s)?;
+        self.0 = s;
+        Ok(())
+    }
+}
+
+impl Default for DIDSelfHash {
+    fn default() -> Self {
+        Self::unset()
+    }
+}
+
+/// Note that this panics if the self-hash slot of this DID is unset.  Use TryHashT::try_as_hash_ref
+/// for untrusted input.
+impl HashT<mbx::MBHashStr> for DIDSelfHash {
+    fn as_hash_ref(&self) -> &mbx::MBHashStr {
+        self.as_did_self_hash_str().as_hash_ref()
+    }
+}
+
+impl TryHashT<mbx::MBHashStr> for DIDSelfHash {
+    fn try_as_hash_ref(&self) -> Result<&mbx::MBHashStr> {
+        self.as_did_self_hash_str().try_as_hash_ref()
+    }
+}
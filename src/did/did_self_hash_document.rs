@@ -0,0 +1,90 @@
+use crate::{
+    write_digest_data_using_jcs, DIDSelfHash, DIDSelfHashStr, DIDSelfHashURL, HashFunctionT,
+    HashRefT, Result, SelfHashableT,
+};
+
+/// A verification method within a DIDSelfHashDocument.  Its `id` and its `controller` (if present) are
+/// self-hash slots, e.g. "did:selfhash:<mb-hash>#key-1" and "did:selfhash:<mb-hash>" respectively.  All
+/// other fields (e.g. `type`, `publicKeyMultibase`) are carried along as-is.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct DIDSelfHashVerificationMethod {
+    pub id: DIDSelfHashURL,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub controller: Option<DIDSelfHash>,
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, serde_json::Value>,
+}
+
+/// A DID document for the did:selfhash DID method, in which the DID is the self-hash of the DID
+/// document itself.  The self-hash slots are the document's `id`, its `controller` (if present), and
+/// the `id` and `controller` (if present) of each verification method.  To generate a new DID document, start with unset self-hash
+/// slots (i.e. "did:selfhash:" for `id` and `controller`, and e.g. "did:selfhash:#key-1" for verification
+/// method IDs) and call SelfHashableT::self_hash.  All other fields are carried along as-is and are
+/// covered by the self-hash.
+///
+/// The digest data is the JCS serialization of the document.
+#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DIDSelfHashDocument {
+    #[serde(default)]
+    pub id: DIDSelfHash,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub controller: Option<DIDSelfHash>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub verification_method: Vec<DIDSelfHashVerificationMethod>,
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, serde_json::Value>,
+}
+
+impl DIDSelfHashDocument {
+    /// Returns the DID of this DID document, or None if its self-hash is unset.
+    pub fn did_o(&self) -> Option<&DIDSelfHashStr> {
+        if self.id.is_unset() {
+            None
+        } else {
+            Some(self.id.as_did_self_hash_str())
+        }
+    }
+}
+
+impl SelfHashableT<mbx::MBHashStr> for DIDSelfHashDocument {
+    fn write_digest_data(
+        &self,
+        hasher: &mut <<mbx::MBHashStr as HashRefT>::HashFunction as HashFunctionT<
+            mbx::MBHashStr,
+        >>::Hasher,
+    ) -> Result<()> {
+        write_digest_data_using_jcs(self, hasher)
+    }
+    fn self_hash_oi<'a, 'b: 'a>(
+        &'b self,
+    ) -> Result<Box<dyn std::iter::Iterator<Item = Option<&'b mbx::MBHashStr>> + 'a>> {
+        let mut self_hash_v = Vec::with_capacity(2 + 2 * self.verification_method.len());
+        self_hash_v.push(self.id.mb_hash_o());
+        if let Some(controller) = self.controller.as_ref() {
+            self_hash_v.push(controller.mb_hash_o());
+        }
+        for verification_method in self.verification_method.iter() {
+            self_hash_v.push(verification_method.id.did().mb_hash_o());
+            if let Some(controller) = verification_method.controller.as_ref() {
+                self_hash_v.push(controller.mb_hash_o());
+            }
+        }
+        Ok(Box::new(self_hash_v.into_iter()))
+    }
+    fn set_self_hash_slots_to(&mut self, hash: &mbx::MBHashStr) -> Result<()> {
+        self.id.set_self_hash_slots_to_mb_hash(hash)?;
+        if let Some(controller) = self.controller.as_mut() {
+            controller.set_self_hash_slots_to_mb_hash(hash)?;
+        }
+        for verification_method in self.verification_method.iter_mut() {
+            verification_method
+                .id
+                .set_self_hash_slots_to_mb_hash(hash)?;
+            if let Some(controller) = verification_method.controller.as_mut() {
+                controller.set_self_hash_slots_to_mb_hash(hash)?;
+            }
+        }
+        Ok(())
+    }
+}
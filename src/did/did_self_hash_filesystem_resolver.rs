@@ -0,0 +1,40 @@
+use crate::{
+    DIDSelfHash, DIDSelfHashDocument, DIDSelfHashResolver, DIDSelfHashStr, Result,
+    SelfHashDirectoryResolver, SelfHashResolver, TryHashT,
+};
+use std::path::{Path, PathBuf};
+
+/// A DIDSelfHashResolver which looks up DID documents stored in a directory, where the DID document for
+/// "did:selfhash:<mb-hash>" is stored in the file "<mb-hash>.json".  Each DID document is verified before
+/// being returned.
+#[derive(Clone, Debug)]
+pub struct DIDSelfHashFilesystemResolver {
+    directory_resolver: SelfHashDirectoryResolver<DIDSelfHashDocument>,
+}
+
+impl DIDSelfHashFilesystemResolver {
+    pub fn new(root_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            directory_resolver: SelfHashDirectoryResolver::new(root_dir),
+        }
+    }
+    pub fn root_dir(&self) -> &Path {
+        self.directory_resolver.root_dir()
+    }
+    /// Returns the path of the file in which the DID document for the given self-hash is stored.
+    pub fn document_path(&self, mb_hash: &mbx::MBHashStr) -> Result<PathBuf> {
+        self.directory_resolver.document_path(mb_hash)
+    }
+    /// Verifies the given DID document and stores it (as JCS), returning its DID.
+    pub fn store(&self, did_document: &DIDSelfHashDocument) -> Result<DIDSelfHash> {
+        self.directory_resolver.store(did_document)?;
+        Ok(did_document.id.clone())
+    }
+}
+
+impl DIDSelfHashResolver for DIDSelfHashFilesystemResolver {
+    fn resolve(&self, did: &DIDSelfHashStr) -> Result<DIDSelfHashDocument> {
+        self.directory_resolver
+            .resolve_verified(did.try_as_hash_ref()?)
+    }
+}
@@ -0,0 +1,7 @@
+use crate::{DIDSelfHashDocument, DIDSelfHashStr, Result};
+
+/// Resolves did:selfhash DIDs to their DID documents.  Implementations must only return DID documents
+/// which self-verify and whose DID is exactly the requested DID.
+pub trait DIDSelfHashResolver {
+    fn resolve(&self, did: &DIDSelfHashStr) -> Result<DIDSelfHashDocument>;
+}
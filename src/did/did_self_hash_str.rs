@@ -0,0 +1,78 @@
+use crate::{ensure, error, self_hash_url_str::is_url_safe_base, Error, HashT, Result, TryHashT};
+
+/// The prefix of every did:selfhash DID.
+pub(crate) const DID_SELF_HASH_PREFIX: &str = "did:selfhash:";
+
+/// This is the str-based analog to DIDSelfHash.  Its self-hash, if set, must use a multibase base whose
+/// alphabet only has characters allowed in a DID method-specific-id (e.g. base64url or base58btc).
+#[derive(Debug, Eq, Hash, Ord, PartialEq, PartialOrd, pneutype::PneuStr, serde::Serialize)]
+#[pneu_str(deserialize)]
+#[repr(transparent)]
+pub struct DIDSelfHashStr(str);
+
+impl DIDSelfHashStr {
+    /// Returns the self-hash portion of this DID, i.e. what follows "did:selfhash:".  This will be empty
+    /// if the self-hash slot of this DID is unset, and otherwise is a valid mbx::MBHashStr.
+    pub fn self_hash_str(&self) -> &str {
+        &self.0[DID_SELF_HASH_PREFIX.len()..]
+    }
+    pub fn mb_hash_o(&self) -> Option<&mbx::MBHashStr> {
+        if self.is_unset() {
+            None
+        } else {
+            Some(
+                mbx::MBHashStr::new_ref(self.self_hash_str())
+                    .expect("programmer error: DIDSelfHashStr was already validated"),
+            )
+        }
+    }
+    /// Returns true iff this DID is "did:selfhash:", meaning that its self-hash slot has not been set.
+    pub fn is_unset(&self) -> bool {
+        self.self_hash_str().is_empty()
+    }
+}
+
+/// Note that this panics if the self-hash slot of this DID is unset.  Use TryHashT::try_as_hash_ref
+/// for untrusted input.
+impl HashT<mbx::MBHashStr> for DIDSelfHashStr {
+    fn as_hash_ref(&self) -> &mbx::MBHashStr {
+        self.try_as_hash_ref()
+            .expect("programmer error: did:selfhash DID does not contain a valid MBHash")
+    }
+}
+
+impl TryHashT<mbx::MBHashStr> for DIDSelfHashStr {
+    fn try_as_hash_ref(&self) -> Result<&mbx::MBHashStr> {
+        self.mb_hash_o()
+            .ok_or_else(|| error!("did:selfhash DID {:?} has an unset self-hash", &self.0))
+    }
+}
+
+impl pneutype::Validate for DIDSelfHashStr {
+    type Data = str;
+    type Error = Error;
+    fn validate(s: &Self::Data) -> std::result::Result<(), Self::Error> {
+        let self_hash_str = s.strip_prefix(DID_SELF_HASH_PREFIX).ok_or_else(|| {
+            error!(
+                "did:selfhash DID must start with {:?}; DID was {:?}",
+                DID_SELF_HASH_PREFIX, s
+            )
+        })?;
+        if !self_hash_str.is_empty() {
+            let mb_hash = mbx::MBHashStr::new_ref(self_hash_str).map_err(|e| {
+                error!(
+                    "did:selfhash DID must have an empty or valid self-hash; DID was {:?}; error was {}",
+                    s, e
+                )
+            })?;
+            // DID Core only allows ALPHA / DIGIT / "." / "-" / "_" / pct-encoded in a method-specific-id.
+            ensure!(
+                is_url_safe_base(mb_hash.base()),
+                "did:selfhash DID's self-hash must use a base whose characters are allowed in a DID (e.g. base64url or base58btc, but not base64 or a padded base), but it uses {:?}; DID was {:?}",
+                mb_hash.base(),
+                s
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,30 @@
+use crate::{did::did_self_hash_str::DID_SELF_HASH_PREFIX, DIDSelfHashStr, DIDSelfHashURLStr};
+use pneutype::Validate;
+
+/// Represents a did:selfhash DID URL having a fragment, e.g. "did:selfhash:<mb-hash>#key-1", as used
+/// for the IDs of verification methods in a DIDSelfHashDocument.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, pneutype::PneuString)]
+#[pneu_string(as_pneu_str = "as_did_self_hash_url_str", borrow = "DIDSelfHashURLStr")]
+#[derive(serde::Serialize)]
+#[pneu_string(deserialize)]
+pub struct DIDSelfHashURL(String);
+
+impl DIDSelfHashURL {
+    /// Produces the DID URL having the given DID and fragment (which must not include the leading `#`).
+    pub fn new(did: &DIDSelfHashStr, fragment: &str) -> crate::Result<Self> {
+        Self::try_from(format!("{}#{}", did, fragment))
+    }
+    /// Sets the self-hash of the DID portion of this DID URL to the given MBHash, leaving this DID URL
+    /// unchanged if that fails, e.g. because the MBHash's base isn't allowed in a DID (see DIDSelfHashStr).
+    pub fn set_self_hash_slots_to_mb_hash(
+        &mut self,
+        mb_hash: &mbx::MBHashStr,
+    ) -> crate::Result<()> {
+        let fragment_start = self.0.find('#').expect("programmer error");
+        let mut s = self.0.clone();
+        s.replace_range(DID_SELF_HASH_PREFIX.len()..fragment_start, mb_hash.as_str());
+        DIDSelfHashURLStr::validate(&s)?;
+        self.0 = s;
+        Ok(())
+    }
+}
@@ -0,0 +1,45 @@
+use crate::{did::did_self_hash_str::DID_SELF_HASH_PREFIX, ensure, error, DIDSelfHashStr, Error};
+
+/// This is the str-based analog to DIDSelfHashURL.
+#[derive(Debug, Eq, Hash, Ord, PartialEq, PartialOrd, pneutype::PneuStr, serde::Serialize)]
+#[pneu_str(deserialize)]
+#[repr(transparent)]
+pub struct DIDSelfHashURLStr(str);
+
+impl DIDSelfHashURLStr {
+    /// Returns the DID portion of this DID URL, i.e. everything before the `#`.
+    pub fn did(&self) -> &DIDSelfHashStr {
+        DIDSelfHashStr::new_ref(&self.0[..self.fragment_start() - 1])
+            .expect("programmer error: DIDSelfHashURLStr was already validated")
+    }
+    /// Returns the fragment (without the leading `#`), e.g. "key-1".
+    pub fn fragment(&self) -> &str {
+        &self.0[self.fragment_start()..]
+    }
+    fn fragment_start(&self) -> usize {
+        self.0
+            .find('#')
+            .expect("programmer error: DIDSelfHashURLStr was already validated")
+            + 1
+    }
+}
+
+impl pneutype::Validate for DIDSelfHashURLStr {
+    type Data = str;
+    type Error = Error;
+    fn validate(s: &Self::Data) -> std::result::Result<(), Self::Error> {
+        let (did, fragment) = s.split_once('#').ok_or_else(|| {
+            error!(
+                "did:selfhash DID URL must have the form \"{}<mb-hash>#<fragment>\"; DID URL was {:?}",
+                DID_SELF_HASH_PREFIX, s
+            )
+        })?;
+        DIDSelfHashStr::new_ref(did)?;
+        ensure!(
+            !fragment.is_empty() && !fragment.contains('#'),
+            "did:selfhash DID URL must have a nonempty fragment; DID URL was {:?}",
+            s
+        );
+        Ok(())
+    }
+}
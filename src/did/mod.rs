@@ -0,0 +1,17 @@
+mod did_self_hash;
+mod did_self_hash_document;
+mod did_self_hash_filesystem_resolver;
+mod did_self_hash_resolver;
+mod did_self_hash_str;
+mod did_self_hash_url;
+mod did_self_hash_url_str;
+
+pub use {
+    did_self_hash::DIDSelfHash,
+    did_self_hash_document::{DIDSelfHashDocument, DIDSelfHashVerificationMethod},
+    did_self_hash_filesystem_resolver::DIDSelfHashFilesystemResolver,
+    did_self_hash_resolver::DIDSelfHashResolver,
+    did_self_hash_str::DIDSelfHashStr,
+    did_self_hash_url::DIDSelfHashURL,
+    did_self_hash_url_str::DIDSelfHashURLStr,
+};
@@ -1,7 +1,34 @@
 use std::borrow::Cow;
 
 #[derive(Debug)]
-pub struct Error(Cow<'static, str>);
+pub struct Error(Cow<'static, str>, ErrorKind);
+
+/// Classifies an Error by its cause, for callers which handle some causes differently (e.g. selfhash-bin,
+/// which reports them with different exit statuses).  An Error is ErrorKind::Other unless classified
+/// otherwise via Error::with_kind.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ErrorKind {
+    /// A self-hash [URL] slot is missing and can't be inserted, or the number of self-hash slots selected
+    /// by a selector violates its cardinality rule (see SelfHashSlotCardinality).
+    MissingSlot,
+    /// The self-hash slots don't all hold the same self-hash, or the self-hash computed during verification
+    /// doesn't match the claimed self-hash.
+    HashMismatch,
+    /// Any other cause.
+    Other,
+}
+
+impl Error {
+    /// Returns the classification of the cause of this Error.
+    pub fn kind(&self) -> ErrorKind {
+        self.1
+    }
+    /// Sets the classification of the cause of this Error.
+    pub fn with_kind(mut self, kind: ErrorKind) -> Self {
+        self.1 = kind;
+        self
+    }
+}
 
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
@@ -21,33 +48,41 @@ impl std::error::Error for Error {}
 #[cfg(feature = "mbx")]
 impl From<mbx::Error> for Error {
     fn from(e: mbx::Error) -> Self {
-        Self(Cow::Owned(e.to_string()))
+        Self(Cow::Owned(e.to_string()), ErrorKind::Other)
     }
 }
 
 #[cfg(feature = "self-hashable-json")]
-impl From<jsonpath_lib::JsonPathError> for Error {
-    fn from(e: jsonpath_lib::JsonPathError) -> Self {
-        Self(Cow::Owned(e.to_string()))
+impl From<serde_json_path::ParseError> for Error {
+    fn from(e: serde_json_path::ParseError) -> Self {
+        Self(Cow::Owned(e.to_string()), ErrorKind::Other)
     }
 }
 
 #[cfg(feature = "self-hashable-json")]
 impl From<serde_json::Error> for Error {
     fn from(e: serde_json::Error) -> Self {
-        Self(Cow::Owned(e.to_string()))
+        Self(Cow::Owned(e.to_string()), ErrorKind::Other)
+    }
+}
+
+/// This allows Error to be used as the error type of the crate's serde Serializers.
+#[cfg(feature = "postcard")]
+impl serde::ser::Error for Error {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Self(Cow::Owned(msg.to_string()), ErrorKind::Other)
     }
 }
 
 impl From<&'static str> for Error {
     fn from(s: &'static str) -> Self {
-        Self(Cow::Borrowed(s))
+        Self(Cow::Borrowed(s), ErrorKind::Other)
     }
 }
 
 impl From<String> for Error {
     fn from(s: String) -> Self {
-        Self(Cow::Owned(s))
+        Self(Cow::Owned(s), ErrorKind::Other)
     }
 }
 
@@ -0,0 +1,155 @@
+use crate::{error, json_path::value_at_mut, JSONPathSegment, Result};
+use std::collections::{HashMap, HashSet};
+
+/// Specifies the string values to substitute at particular locations (given as normalized paths; see
+/// JSONPath) in a serde_json::Value.  These can either be applied to the value directly, or substituted
+/// on the fly while writing the value as JCS, which allows the placeholder for each self-hash [URL] slot
+/// to be written without cloning and modifying the value.
+#[derive(Debug, Default)]
+pub(crate) struct JSONSubstitutions {
+    /// Maps the location of each existing value to be replaced to its replacement string.
+    replacement_m: HashMap<Vec<JSONPathSegment>, String>,
+    /// Maps the location of each JSON object to the members to be inserted into it.
+    insertion_m: HashMap<Vec<JSONPathSegment>, Vec<(String, String)>>,
+    /// The locations of all values which contain (or are) a substitution.  Any subtree whose location
+    /// is not in this set can be written directly.
+    touched_location_s: HashSet<Vec<JSONPathSegment>>,
+}
+
+impl JSONSubstitutions {
+    /// Replaces the existing value at the given location with the given string.
+    pub(crate) fn replace(&mut self, location: Vec<JSONPathSegment>, replacement: String) {
+        self.touch(location.as_slice());
+        self.replacement_m.insert(location, replacement);
+    }
+    /// Inserts a member with the given name and string value into the JSON object at the given location.
+    pub(crate) fn insert(
+        &mut self,
+        parent_location: Vec<JSONPathSegment>,
+        name: String,
+        replacement: String,
+    ) {
+        self.touch(parent_location.as_slice());
+        self.insertion_m
+            .entry(parent_location)
+            .or_default()
+            .push((name, replacement));
+    }
+    /// Applies these substitutions to the given value, which must be the value they were determined from.
+    pub(crate) fn apply_to(&self, value: &mut serde_json::Value) {
+        for (location, replacement) in self.replacement_m.iter() {
+            *value_at_mut(value, location).expect("programmer error") =
+                serde_json::Value::String(replacement.clone());
+        }
+        for (parent_location, insertion_v) in self.insertion_m.iter() {
+            let parent = value_at_mut(value, parent_location)
+                .and_then(serde_json::Value::as_object_mut)
+                .expect("programmer error");
+            for (name, replacement) in insertion_v.iter() {
+                parent.insert(name.clone(), serde_json::Value::String(replacement.clone()));
+            }
+        }
+    }
+    fn touch(&mut self, location: &[JSONPathSegment]) {
+        for len in 0..=location.len() {
+            if !self.touched_location_s.contains(&location[..len]) {
+                self.touched_location_s.insert(location[..len].to_vec());
+            }
+        }
+    }
+}
+
+/// Writes the given value as JSON Canonicalization Scheme (JCS; see RFC 8785) into the given writer,
+/// substituting the given string values at the specified locations.  Object members are sorted by
+/// the UTF-16 code units of their names, and scalar values are written by serde_json_canonicalizer.
+pub(crate) fn write_jcs_with_substitutions<W: std::io::Write>(
+    value: &serde_json::Value,
+    substitutions: &JSONSubstitutions,
+    writer: &mut W,
+) -> Result<()> {
+    let mut location = Vec::new();
+    write_jcs_at(value, &mut location, substitutions, writer)
+}
+
+fn write_jcs_at<W: std::io::Write>(
+    value: &serde_json::Value,
+    location: &mut Vec<JSONPathSegment>,
+    substitutions: &JSONSubstitutions,
+    writer: &mut W,
+) -> Result<()> {
+    if !substitutions
+        .touched_location_s
+        .contains(location.as_slice())
+    {
+        // Nothing to substitute within this value, so write it directly.
+        serde_json_canonicalizer::to_writer(value, writer)?;
+        return Ok(());
+    }
+    if let Some(replacement) = substitutions.replacement_m.get(location.as_slice()) {
+        serde_json_canonicalizer::to_writer(replacement.as_str(), writer)?;
+        return Ok(());
+    }
+    match value {
+        serde_json::Value::Object(object) => {
+            let insertion_v = substitutions
+                .insertion_m
+                .get(location.as_slice())
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            let mut member_v = object
+                .iter()
+                .map(|(name, member_value)| (name.as_str(), Some(member_value), None))
+                .chain(
+                    insertion_v
+                        .iter()
+                        .filter(|(name, _)| !object.contains_key(name))
+                        .map(|(name, replacement)| {
+                            (name.as_str(), None, Some(replacement.as_str()))
+                        }),
+                )
+                .collect::<Vec<_>>();
+            member_v
+                .sort_unstable_by(|(a, _, _), (b, _, _)| a.encode_utf16().cmp(b.encode_utf16()));
+            write_bytes(writer, b"{")?;
+            for (i, (name, member_value_o, replacement_o)) in member_v.into_iter().enumerate() {
+                if i > 0 {
+                    write_bytes(writer, b",")?;
+                }
+                serde_json_canonicalizer::to_writer(name, writer)?;
+                write_bytes(writer, b":")?;
+                if let Some(member_value) = member_value_o {
+                    location.push(JSONPathSegment::Name(name.to_string()));
+                    let result = write_jcs_at(member_value, location, substitutions, writer);
+                    location.pop();
+                    result?;
+                } else {
+                    serde_json_canonicalizer::to_writer(replacement_o.unwrap(), writer)?;
+                }
+            }
+            write_bytes(writer, b"}")?;
+        }
+        serde_json::Value::Array(array) => {
+            write_bytes(writer, b"[")?;
+            for (i, element) in array.iter().enumerate() {
+                if i > 0 {
+                    write_bytes(writer, b",")?;
+                }
+                location.push(JSONPathSegment::Index(i as i64));
+                let result = write_jcs_at(element, location, substitutions, writer);
+                location.pop();
+                result?;
+            }
+            write_bytes(writer, b"]")?;
+        }
+        _ => {
+            serde_json_canonicalizer::to_writer(value, writer)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_bytes<W: std::io::Write>(writer: &mut W, bytes: &[u8]) -> Result<()> {
+    writer
+        .write_all(bytes)
+        .map_err(|e| error!("failed to write JCS; error was {}", e))
+}
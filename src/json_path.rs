@@ -0,0 +1,406 @@
+use crate::{bail, ensure, error, Error, Result};
+
+/// A single segment of a normalized JSONPath, i.e. a single member name or a single array index.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum JSONPathSegment {
+    /// Selects the member with the given name of a JSON object.
+    Name(String),
+    /// Selects the element with the given index of a JSON array.  Negative indices count from the end
+    /// of the array, as in RFC 9535.
+    Index(i64),
+}
+
+impl JSONPathSegment {
+    pub fn as_name(&self) -> Option<&str> {
+        match self {
+            Self::Name(name) => Some(name.as_str()),
+            Self::Index(_) => None,
+        }
+    }
+    pub fn as_index(&self) -> Option<i64> {
+        match self {
+            Self::Name(_) => None,
+            Self::Index(index) => Some(*index),
+        }
+    }
+    /// Returns the child of the given value selected by this segment, if it exists.
+    pub fn select<'a>(&self, value: &'a serde_json::Value) -> Option<&'a serde_json::Value> {
+        match (self, value) {
+            (Self::Name(name), serde_json::Value::Object(object)) => object.get(name),
+            (Self::Index(index), serde_json::Value::Array(array)) => {
+                normalized_index(*index, array.len()).map(|i| &array[i])
+            }
+            _ => None,
+        }
+    }
+    /// Returns the child of the given value selected by this segment, if it exists.
+    pub fn select_mut<'a>(
+        &self,
+        value: &'a mut serde_json::Value,
+    ) -> Option<&'a mut serde_json::Value> {
+        match (self, value) {
+            (Self::Name(name), serde_json::Value::Object(object)) => object.get_mut(name),
+            (Self::Index(index), serde_json::Value::Array(array)) => {
+                normalized_index(*index, array.len()).map(move |i| &mut array[i])
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Formats this segment as in an RFC 9535 normalized path (see section 2.7), e.g. `['selfHash']` or `[0]`,
+/// so that `$` followed by the segments of a location is its normalized path, e.g. `$['proof'][0]['id']`.
+impl std::fmt::Display for JSONPathSegment {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        use std::fmt::Write;
+        match self {
+            Self::Name(name) => {
+                f.write_str("['")?;
+                for c in name.chars() {
+                    match c {
+                        '\u{8}' => f.write_str("\\b")?,
+                        '\u{c}' => f.write_str("\\f")?,
+                        '\n' => f.write_str("\\n")?,
+                        '\r' => f.write_str("\\r")?,
+                        '\t' => f.write_str("\\t")?,
+                        '\'' => f.write_str("\\'")?,
+                        '\\' => f.write_str("\\\\")?,
+                        '\u{0}'..='\u{1f}' => write!(f, "\\u{:04x}", c as u32)?,
+                        _ => f.write_char(c)?,
+                    }
+                }
+                f.write_str("']")
+            }
+            Self::Index(index) => write!(f, "[{}]", index),
+        }
+    }
+}
+
+fn normalized_index(index: i64, len: usize) -> Option<usize> {
+    let len = len as i64;
+    let index = if index < 0 { len + index } else { index };
+    if 0 <= index && index < len {
+        Some(index as usize)
+    } else {
+        None
+    }
+}
+
+/// Represents an RFC 9535 JSONPath query.  In addition to the parsed query, this carries enough of the
+/// query's AST to determine the parent query and terminating member name of the query (e.g. for inserting
+/// a missing self-hash field), and, if the query is a "singular query" (see RFC 9535 section 2.3.5.1),
+/// its normalized path AST (a sequence of member names and array indices).
+///
+/// See <https://www.rfc-editor.org/rfc/rfc9535.html>.
+#[derive(Clone, Debug)]
+pub struct JSONPath {
+    path: String,
+    json_path: serde_json_path::JsonPath,
+    /// The normalized path AST, present iff this query is a singular query.
+    singular_segment_vo: Option<Vec<JSONPathSegment>>,
+    /// The query selecting the parent(s) of the nodes selected by this query, present iff this query
+    /// is not the root query `$`.
+    parent_json_path_o: Option<Box<JSONPath>>,
+    /// The member name selected by the last segment of this query, if the last segment is a child
+    /// segment that selects a single member name.
+    terminating_name_o: Option<String>,
+}
+
+impl JSONPath {
+    /// Parses the given JSONPath query, which must be a valid RFC 9535 query.  Member names may be given
+    /// in dot notation (e.g. `$.selfHash`) or bracket notation (e.g. `$['@id']` or `$["a.b"]`), array
+    /// indices in bracket notation (e.g. `$.proof[0]`), and multi-match selectors such as wildcards
+    /// (e.g. `$.verificationMethod[*].controller`), descendant segments (e.g. `$..id`) and filters
+    /// (e.g. `$.service[?@.type == 'X'].id`) are also supported.
+    pub fn parse(path: &str) -> Result<Self> {
+        let json_path = serde_json_path::JsonPath::parse(path)
+            .map_err(|e| error!("invalid JSONPath {:?}; error was {}", path, e))?;
+        let raw_segment_v = split_query_segments(path)?;
+        let singular_segment_vo = raw_segment_v
+            .iter()
+            .map(|raw_segment| parse_singular_segment(raw_segment))
+            .collect::<Option<Vec<_>>>();
+        let (parent_json_path_o, terminating_name_o) = match raw_segment_v.last() {
+            Some(last_raw_segment) => {
+                // Because the raw segments are slices of path, the parent query is everything before
+                // the last segment.
+                let parent_path_len = last_raw_segment.as_ptr() as usize - path.as_ptr() as usize;
+                let parent_json_path = JSONPath::parse(path[..parent_path_len].trim_end())?;
+                let terminating_name_o = parse_singular_segment(last_raw_segment)
+                    .and_then(|segment| segment.as_name().map(str::to_string));
+                (Some(Box::new(parent_json_path)), terminating_name_o)
+            }
+            None => (None, None),
+        };
+        Ok(Self {
+            path: path.to_string(),
+            json_path,
+            singular_segment_vo,
+            parent_json_path_o,
+            terminating_name_o,
+        })
+    }
+    /// Returns the JSONPath query string as it was given to JSONPath::parse.
+    pub fn as_str(&self) -> &str {
+        self.path.as_str()
+    }
+    /// Returns true iff this query is a singular query, meaning that it selects at most one node.
+    pub fn is_singular(&self) -> bool {
+        self.singular_segment_vo.is_some()
+    }
+    /// Returns the normalized path AST for this query if it is a singular query, otherwise None.
+    pub fn singular_segments(&self) -> Option<&[JSONPathSegment]> {
+        self.singular_segment_vo.as_deref()
+    }
+    /// Returns the query selecting the parent(s) of the nodes selected by this query, or None if this
+    /// query is the root query `$`.  Note that for a query with a descendant segment as its last segment,
+    /// this isn't the parent in the strict sense.
+    pub fn parent(&self) -> Option<&JSONPath> {
+        self.parent_json_path_o.as_deref()
+    }
+    /// Returns the terminating member name of this query, if the last segment is a child segment that
+    /// selects a single member name.
+    pub fn terminating_name(&self) -> Option<&str> {
+        self.terminating_name_o.as_deref()
+    }
+    /// Runs this query against the given value, returning all the selected nodes.
+    pub fn query<'a>(&self, value: &'a serde_json::Value) -> Vec<&'a serde_json::Value> {
+        self.json_path.query(value).all()
+    }
+    /// Runs this query against the given value, returning the normalized path of each selected node,
+    /// which can be used with value_at_mut to modify that node.  The segments format as the RFC 9535
+    /// normalized path that serde_json_path computes for the node (see JSONPathSegment's Display).
+    pub fn query_located(&self, value: &serde_json::Value) -> Vec<Vec<JSONPathSegment>> {
+        self.json_path
+            .query_located(value)
+            .iter()
+            .map(|located_node| {
+                located_node
+                    .location()
+                    .iter()
+                    .map(|path_element| match path_element {
+                        serde_json_path::PathElement::Name(name) => {
+                            JSONPathSegment::Name(name.to_string())
+                        }
+                        serde_json_path::PathElement::Index(index) => {
+                            JSONPathSegment::Index(*index as i64)
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+impl std::fmt::Display for JSONPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.path.as_str())
+    }
+}
+
+impl std::str::FromStr for JSONPath {
+    type Err = Error;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+/// Returns the node at the given normalized path, if it exists.
+pub fn value_at<'a>(
+    mut value: &'a serde_json::Value,
+    segment_v: &[JSONPathSegment],
+) -> Option<&'a serde_json::Value> {
+    for segment in segment_v {
+        value = segment.select(value)?;
+    }
+    Some(value)
+}
+
+/// Returns the node at the given normalized path, if it exists.
+pub fn value_at_mut<'a>(
+    mut value: &'a mut serde_json::Value,
+    segment_v: &[JSONPathSegment],
+) -> Option<&'a mut serde_json::Value> {
+    for segment in segment_v {
+        value = segment.select_mut(value)?;
+    }
+    Some(value)
+}
+
+/// Blank space, as defined by RFC 9535.
+fn is_blank(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\n' | '\r')
+}
+
+fn is_name_first(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || !c.is_ascii()
+}
+
+fn is_name_char(c: char) -> bool {
+    is_name_first(c) || c.is_ascii_digit()
+}
+
+/// Splits an RFC 9535 query into its segments (e.g. `.a`, `['b']`, `[*]`, `..c`, `[?@.d == 'e']`),
+/// each of which is a slice of the query.  The query is assumed to have already been validated as a
+/// well-formed JSONPath query.
+fn split_query_segments(path: &str) -> Result<Vec<&str>> {
+    let mut remaining = path
+        .strip_prefix('$')
+        .ok_or_else(|| error!("JSONPath must begin with `$`; path was {:?}", path))?;
+    let mut raw_segment_v = Vec::new();
+    loop {
+        remaining = remaining.trim_start_matches(is_blank);
+        if remaining.is_empty() {
+            break;
+        }
+        let prefix_len = if remaining.starts_with("..") {
+            2
+        } else if remaining.starts_with('.') {
+            1
+        } else {
+            0
+        };
+        let after_prefix = &remaining[prefix_len..];
+        let segment_len = if after_prefix.starts_with('[') {
+            prefix_len + bracketed_selection_len(after_prefix, path)?
+        } else if after_prefix.starts_with('*') && prefix_len > 0 {
+            prefix_len + 1
+        } else if prefix_len > 0 {
+            prefix_len
+                + after_prefix
+                    .find(|c: char| !is_name_char(c))
+                    .unwrap_or(after_prefix.len())
+        } else {
+            bail!("malformed JSONPath segment; path was {:?}", path);
+        };
+        raw_segment_v.push(&remaining[..segment_len]);
+        remaining = &remaining[segment_len..];
+    }
+    Ok(raw_segment_v)
+}
+
+/// Returns the length of the bracketed selection at the start of s, including the enclosing brackets,
+/// taking into account string literals and nested brackets (e.g. within filter expressions).
+fn bracketed_selection_len(s: &str, path: &str) -> Result<usize> {
+    let mut depth = 0usize;
+    let mut quote_o = None;
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        if let Some(quote) = quote_o {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == quote {
+                quote_o = None;
+            }
+            continue;
+        }
+        match c {
+            '\'' | '"' => quote_o = Some(c),
+            '[' => depth += 1,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(i + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    bail!("unterminated bracketed selection in JSONPath {:?}", path);
+}
+
+/// Parses a raw segment (as produced by split_query_segments) into a normalized path segment, if it's a
+/// child segment that selects a single member name or array index.
+fn parse_singular_segment(raw_segment: &str) -> Option<JSONPathSegment> {
+    if raw_segment.starts_with("..") {
+        None
+    } else if let Some(name) = raw_segment.strip_prefix('.') {
+        if name == "*" {
+            None
+        } else {
+            Some(JSONPathSegment::Name(name.to_string()))
+        }
+    } else {
+        let selector = raw_segment
+            .strip_prefix('[')?
+            .strip_suffix(']')?
+            .trim_matches(is_blank);
+        if selector.starts_with(['\'', '"']) {
+            let (name, after_name) = parse_string_literal(selector, raw_segment).ok()?;
+            if after_name.trim_start_matches(is_blank).is_empty() {
+                Some(JSONPathSegment::Name(name))
+            } else {
+                // Multiple selectors, e.g. `['a','b']`.
+                None
+            }
+        } else {
+            selector.parse::<i64>().ok().map(JSONPathSegment::Index)
+        }
+    }
+}
+
+/// Parses an RFC 9535 string literal (single- or double-quoted), returning the unescaped string and the
+/// remaining input.
+fn parse_string_literal<'a>(s: &'a str, path: &str) -> Result<(String, &'a str)> {
+    let mut char_i = s.char_indices();
+    let (_, quote) = char_i.next().expect("programmer error");
+    let mut string = String::new();
+    while let Some((i, c)) = char_i.next() {
+        match c {
+            c if c == quote => {
+                return Ok((string, &s[i + c.len_utf8()..]));
+            }
+            '\\' => {
+                let (_, escaped) = char_i
+                    .next()
+                    .ok_or_else(|| error!("unterminated string literal in JSONPath {:?}", path))?;
+                match escaped {
+                    'b' => string.push('\u{0008}'),
+                    'f' => string.push('\u{000C}'),
+                    'n' => string.push('\n'),
+                    'r' => string.push('\r'),
+                    't' => string.push('\t'),
+                    '/' | '\\' => string.push(escaped),
+                    '\'' | '"' if escaped == quote => string.push(escaped),
+                    'u' => {
+                        let mut code_unit_v = vec![parse_hex4(&mut char_i, path)?];
+                        if (0xD800..0xDC00).contains(&code_unit_v[0]) {
+                            // High surrogate, which must be followed by an escaped low surrogate.
+                            ensure!(
+                                char_i.next().map(|(_, c)| c) == Some('\\')
+                                    && char_i.next().map(|(_, c)| c) == Some('u'),
+                                "invalid unicode surrogate pair in JSONPath {:?}",
+                                path
+                            );
+                            code_unit_v.push(parse_hex4(&mut char_i, path)?);
+                        }
+                        for decoded in char::decode_utf16(code_unit_v) {
+                            string.push(decoded.map_err(|_| {
+                                error!("invalid unicode escape in JSONPath {:?}", path)
+                            })?);
+                        }
+                    }
+                    _ => {
+                        bail!("invalid escape sequence in JSONPath {:?}", path);
+                    }
+                }
+            }
+            _ => string.push(c),
+        }
+    }
+    bail!("unterminated string literal in JSONPath {:?}", path);
+}
+
+/// Parses the 4 hex digits of a `\\uXXXX` escape sequence.
+fn parse_hex4(char_i: &mut std::str::CharIndices, path: &str) -> Result<u16> {
+    let hex = char_i.take(4).map(|(_, c)| c).collect::<String>();
+    ensure!(
+        hex.len() == 4,
+        "invalid unicode escape in JSONPath {:?}",
+        path
+    );
+    u16::from_str_radix(&hex, 16)
+        .map_err(|_| error!("invalid unicode escape in JSONPath {:?}", path))
+}
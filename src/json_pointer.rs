@@ -0,0 +1,149 @@
+use crate::{bail, ensure, Error, JSONPathSegment, Result};
+
+/// Represents an RFC 6901 JSON Pointer, e.g. `/selfHash` or `/proof/0/id`.  Note that the empty string
+/// is the JSON Pointer referring to the whole document.
+///
+/// See <https://www.rfc-editor.org/rfc/rfc6901>.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct JSONPointer {
+    pointer: String,
+    /// The unescaped reference tokens of the pointer.
+    token_v: Vec<String>,
+}
+
+impl JSONPointer {
+    /// Parses the given JSON Pointer, which must either be empty or begin with `/`, and in which each `~`
+    /// must be part of an escape sequence (`~0` for `~` or `~1` for `/`).
+    pub fn parse(pointer: &str) -> Result<Self> {
+        let token_v = if pointer.is_empty() {
+            Vec::new()
+        } else {
+            ensure!(
+                pointer.starts_with('/'),
+                "JSON Pointer must be empty or begin with `/`; pointer was {:?}",
+                pointer
+            );
+            pointer[1..]
+                .split('/')
+                .map(|escaped_token| unescape_token(escaped_token, pointer))
+                .collect::<Result<Vec<_>>>()?
+        };
+        Ok(Self {
+            pointer: pointer.to_string(),
+            token_v,
+        })
+    }
+    /// Returns the JSON Pointer string as it was given to JSONPointer::parse.
+    pub fn as_str(&self) -> &str {
+        self.pointer.as_str()
+    }
+    /// Returns the unescaped reference tokens of this pointer.
+    pub fn tokens(&self) -> &[String] {
+        self.token_v.as_slice()
+    }
+    /// Returns the pointer to the parent of the value referred to by this pointer, or None if this
+    /// pointer refers to the whole document.
+    pub fn parent(&self) -> Option<&str> {
+        self.pointer
+            .rfind('/')
+            .map(|last_slash_index| &self.pointer[..last_slash_index])
+    }
+    /// Returns the unescaped last reference token of this pointer, which, if the parent is a JSON object,
+    /// is the member name of the referred-to value.
+    pub fn terminating_token(&self) -> Option<&str> {
+        self.token_v.last().map(String::as_str)
+    }
+    /// Returns the normalized path (see JSONPath) of the value referred to by this pointer within the
+    /// given value, if it exists.  This resolves the ambiguity between member names and array indices
+    /// (e.g. the token `0`) according to the type of each value along the way.
+    pub fn locate(&self, value: &serde_json::Value) -> Option<Vec<JSONPathSegment>> {
+        locate_tokens(value, self.token_v.as_slice())
+    }
+    /// Returns the normalized path (see JSONPath) of the parent of the value referred to by this pointer
+    /// within the given value, if the parent exists.  Note that the referred-to value itself need not exist.
+    pub fn locate_parent(&self, value: &serde_json::Value) -> Option<Vec<JSONPathSegment>> {
+        let (_, parent_token_v) = self.token_v.split_last()?;
+        locate_tokens(value, parent_token_v)
+    }
+    /// Returns the value referred to by this pointer, if it exists.
+    pub fn query<'a>(&self, value: &'a serde_json::Value) -> Option<&'a serde_json::Value> {
+        value.pointer(self.pointer.as_str())
+    }
+    /// Returns the value referred to by this pointer, if it exists.
+    pub fn query_mut<'a>(
+        &self,
+        value: &'a mut serde_json::Value,
+    ) -> Option<&'a mut serde_json::Value> {
+        value.pointer_mut(self.pointer.as_str())
+    }
+    /// Returns the parent of the value referred to by this pointer, if it exists.  Note that the
+    /// referred-to value itself need not exist.
+    pub fn query_parent_mut<'a>(
+        &self,
+        value: &'a mut serde_json::Value,
+    ) -> Option<&'a mut serde_json::Value> {
+        value.pointer_mut(self.parent()?)
+    }
+}
+
+impl std::fmt::Display for JSONPointer {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.pointer.as_str())
+    }
+}
+
+impl std::str::FromStr for JSONPointer {
+    type Err = Error;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+fn unescape_token(escaped_token: &str, pointer: &str) -> Result<String> {
+    let mut token = String::with_capacity(escaped_token.len());
+    let mut char_i = escaped_token.chars();
+    while let Some(c) = char_i.next() {
+        if c == '~' {
+            match char_i.next() {
+                Some('0') => token.push('~'),
+                Some('1') => token.push('/'),
+                _ => {
+                    bail!(
+                        "JSON Pointer contains `~` that is not part of `~0` or `~1`; pointer was {:?}",
+                        pointer
+                    );
+                }
+            }
+        } else {
+            token.push(c);
+        }
+    }
+    Ok(token)
+}
+
+fn locate_tokens(
+    mut value: &serde_json::Value,
+    token_v: &[String],
+) -> Option<Vec<JSONPathSegment>> {
+    let mut segment_v = Vec::with_capacity(token_v.len());
+    for token in token_v {
+        let segment = match value {
+            serde_json::Value::Object(_) => JSONPathSegment::Name(token.clone()),
+            serde_json::Value::Array(_) => {
+                // RFC 6901 array indices consist only of digits, without leading zeros.
+                if !token.bytes().all(|b| b.is_ascii_digit())
+                    || (token.len() > 1 && token.starts_with('0'))
+                {
+                    return None;
+                }
+                JSONPathSegment::Index(token.parse::<i64>().ok()?)
+            }
+            _ => {
+                return None;
+            }
+        };
+        value = segment.select(value)?;
+        segment_v.push(segment);
+    }
+    Some(segment_v)
+}
@@ -0,0 +1,15 @@
+use crate::{CESRHashStr, HashT};
+
+/// A digest in CESR qb64 text form, i.e. a CESR derivation code followed by the base64url encoding of the
+/// digest (see CESRHashFunction), as used for KERI's Self-Addressing IDentifiers (SAIDs).
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, pneutype::PneuString)]
+#[pneu_string(as_pneu_str = "as_cesr_hash_str", borrow = "CESRHashStr")]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", pneu_string(deserialize))]
+pub struct CESRHash(String);
+
+impl HashT<CESRHashStr> for CESRHash {
+    fn as_hash_ref(&self) -> &CESRHashStr {
+        self.as_cesr_hash_str()
+    }
+}
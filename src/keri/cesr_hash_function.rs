@@ -0,0 +1,228 @@
+use crate::{bail, error, CESRHash, CESRHasher, HashFunctionT, Result};
+use base64::Engine;
+
+/// The character KERI uses to fill a SAID field while computing its SAID.
+const DUMMY_CHAR: char = '#';
+
+/// Represents a hash function by its CESR (Composable Event Streaming Representation) derivation code, as
+/// used by KERI for digests and Self-Addressing IDentifiers (SAIDs), e.g. `E` for BLAKE3-256.  A hash
+/// produced by one of these is a CESRHash, whose "qb64" text form is the derivation code followed by the
+/// base64url encoding of the digest, e.g. "ELOWJI3NYdfkxtJoeH0OIZBaolNMnwfrDfhCazRE1ZCI".
+/// See https://trustoverip.github.io/tswg-cesr-specification/ for details.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct CESRHashFunction {
+    code: &'static str,
+    digest_len: usize,
+}
+
+impl CESRHashFunction {
+    /// BLAKE3 with 256 bit output, which is KERI's default digest.
+    pub const BLAKE3_256: CESRHashFunction = CESRHashFunction {
+        code: "E",
+        digest_len: 32,
+    };
+    /// SHA3-256 (part of the SHA3 family).
+    pub const SHA3_256: CESRHashFunction = CESRHashFunction {
+        code: "H",
+        digest_len: 32,
+    };
+    /// SHA-256 (part of the SHA2 family).
+    pub const SHA2_256: CESRHashFunction = CESRHashFunction {
+        code: "I",
+        digest_len: 32,
+    };
+    /// SHA3-512 (part of the SHA3 family).
+    pub const SHA3_512: CESRHashFunction = CESRHashFunction {
+        code: "0F",
+        digest_len: 64,
+    };
+    /// SHA-512 (part of the SHA2 family).
+    pub const SHA2_512: CESRHashFunction = CESRHashFunction {
+        code: "0G",
+        digest_len: 64,
+    };
+    /// All the supported CESR hash functions.
+    pub const ALL: [CESRHashFunction; 5] = [
+        Self::BLAKE3_256,
+        Self::SHA3_256,
+        Self::SHA2_256,
+        Self::SHA3_512,
+        Self::SHA2_512,
+    ];
+
+    /// Returns the CESR hash function having the given derivation code.
+    pub fn from_code(code: &str) -> Result<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|hash_function| hash_function.code == code)
+            .ok_or_else(|| error!("unsupported CESR digest derivation code {:?}", code))
+    }
+    /// Returns the CESR hash function whose derivation code begins the given qb64 string.  Digest codes
+    /// beginning with `0` are 2 characters long, and the rest are 1 character long.
+    pub(crate) fn from_qb64_prefix(qb64: &str) -> Result<Self> {
+        let code_len = if qb64.starts_with('0') { 2 } else { 1 };
+        match qb64.get(..code_len) {
+            Some(code) => Self::from_code(code),
+            None => bail!("{:?} is too short to be a CESR digest", qb64),
+        }
+    }
+    /// Returns the derivation code, e.g. `E` for BLAKE3-256.
+    pub fn code(&self) -> &'static str {
+        self.code
+    }
+    /// Returns the length (in bytes) of the digests this hash function produces.
+    pub fn digest_len(&self) -> usize {
+        self.digest_len
+    }
+    /// Returns the length of the qb64 text form of the digests this hash function produces.  Note that
+    /// the derivation code takes the place of the pad bytes, so this is a multiple of 4.
+    pub fn qb64_len(&self) -> usize {
+        (self.digest_len + self.pad_len()) * 4 / 3
+    }
+    /// Returns KERI's dummy value for a SAID field of this hash function, which consists of qb64_len
+    /// `#` characters.  This is what SAID fields are set to while computing a SAID.
+    pub fn dummy(&self) -> String {
+        std::iter::repeat(DUMMY_CHAR)
+            .take(self.qb64_len())
+            .collect()
+    }
+    /// Produces the CESRHash having this hash function and the given digest.
+    pub fn encoded(&self, digest: &[u8]) -> Result<CESRHash> {
+        if digest.len() != self.digest_len {
+            bail!(
+                "CESR derivation code {:?} requires a {}-byte digest, but the digest had {} bytes",
+                self.code,
+                self.digest_len,
+                digest.len()
+            );
+        }
+        // The digest is prepadded with zero bytes so that it's a multiple of 3 bytes long, and then the
+        // base64url characters encoding the pad bytes are replaced by the derivation code.
+        let pad_len = self.pad_len();
+        let mut padded = [0u8; 66];
+        padded[pad_len..pad_len + digest.len()].copy_from_slice(digest);
+        let mut qb64 = [0u8; 88];
+        let qb64_len = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .encode_slice(&padded[..pad_len + digest.len()], &mut qb64)
+            .expect("programmer error");
+        qb64[..pad_len].copy_from_slice(self.code.as_bytes());
+        let qb64 = std::str::from_utf8(&qb64[..qb64_len]).expect("programmer error");
+        CESRHash::try_from(qb64)
+    }
+    /// Decodes the digest of the given qb64 string, which must begin with this hash function's derivation
+    /// code and have the correct length.
+    pub(crate) fn decoded_digest(&self, qb64: &str) -> Result<Vec<u8>> {
+        if qb64.len() != self.qb64_len() || !qb64.starts_with(self.code) {
+            bail!(
+                "{:?} is not a valid CESR digest for derivation code {:?}; expected {} characters",
+                qb64,
+                self.code,
+                self.qb64_len()
+            );
+        }
+        // Replace the derivation code with the base64url characters for zero pad bytes.
+        let pad_len = self.pad_len();
+        let mut padded_qb64 = [b'A'; 88];
+        padded_qb64[pad_len..qb64.len()].copy_from_slice(&qb64.as_bytes()[pad_len..]);
+        let mut padded = [0u8; 66 + 3];
+        let padded_len = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .decode_slice(&padded_qb64[..qb64.len()], &mut padded)
+            .map_err(|e| error!("{:?} is not a valid CESR digest; error was {}", qb64, e))?;
+        if padded[..pad_len].iter().any(|b| *b != 0) {
+            bail!(
+                "{:?} is not a valid CESR digest; its pad bits are nonzero",
+                qb64
+            );
+        }
+        Ok(padded[pad_len..padded_len].to_vec())
+    }
+    /// Convenience method for hashing a byte vector with this hash function, producing a CESRHash value.
+    pub fn hash(&self, byte_v: &[u8]) -> CESRHash {
+        let mut hasher = self.new_hasher();
+        use crate::HasherT;
+        hasher.update(byte_v);
+        hasher.finalize()
+    }
+    /// The number of zero bytes the digest is prepadded with, which is also the length of the derivation
+    /// code.
+    fn pad_len(&self) -> usize {
+        (3 - self.digest_len % 3) % 3
+    }
+}
+
+impl std::fmt::Display for CESRHashFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.code)
+    }
+}
+
+impl HashFunctionT<crate::CESRHashStr> for CESRHashFunction {
+    type Hasher = CESRHasher;
+    fn placeholder_hash(&self) -> std::borrow::Cow<'static, crate::CESRHashStr> {
+        // Note that KERI doesn't use this when computing SAIDs; see CESRHashFunction::dummy.
+        std::borrow::Cow::Owned(
+            self.encoded(&[0u8; 64][..self.digest_len])
+                .expect("programmer error"),
+        )
+    }
+    fn new_hasher(&self) -> Self::Hasher {
+        // This allow is necessary if all these features are disabled.
+        #[allow(unused_variables)]
+        let hasher_b = match self.code {
+            "E" => {
+                #[cfg(feature = "blake3")]
+                {
+                    crate::Blake3::new_hasher_dyn()
+                }
+                #[cfg(not(feature = "blake3"))]
+                {
+                    panic!("programmer error: blake3 feature not enabled");
+                }
+            }
+            "H" => {
+                #[cfg(feature = "sha3-256")]
+                {
+                    crate::SHA3_256::new_hasher_dyn()
+                }
+                #[cfg(not(feature = "sha3-256"))]
+                {
+                    panic!("programmer error: sha3-256 feature not enabled");
+                }
+            }
+            "I" => {
+                #[cfg(feature = "sha-256")]
+                {
+                    crate::SHA256::new_hasher_dyn()
+                }
+                #[cfg(not(feature = "sha-256"))]
+                {
+                    panic!("programmer error: sha-256 feature not enabled");
+                }
+            }
+            "0F" => {
+                #[cfg(feature = "sha3-512")]
+                {
+                    crate::SHA3_512::new_hasher_dyn()
+                }
+                #[cfg(not(feature = "sha3-512"))]
+                {
+                    panic!("programmer error: sha3-512 feature not enabled");
+                }
+            }
+            "0G" => {
+                #[cfg(feature = "sha-512")]
+                {
+                    crate::SHA512::new_hasher_dyn()
+                }
+                #[cfg(not(feature = "sha-512"))]
+                {
+                    panic!("programmer error: sha-512 feature not enabled");
+                }
+            }
+            _ => panic!("programmer error: unrecognized CESR code: {:?}", self.code),
+        };
+        // This allow is necessary if all these features are disabled.
+        #[allow(unreachable_code)]
+        CESRHasher::new(*self, hasher_b)
+    }
+}
@@ -0,0 +1,41 @@
+use crate::{CESRHashFunction, Error, HashRefT};
+
+/// This is the str-based analog to CESRHash.
+#[derive(Debug, Eq, Hash, Ord, PartialEq, PartialOrd, pneutype::PneuStr)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", pneu_str(deserialize))]
+#[repr(transparent)]
+pub struct CESRHashStr(str);
+
+impl CESRHashStr {
+    /// Returns the derivation code of this hash, e.g. `E` for BLAKE3-256.
+    pub fn code(&self) -> &str {
+        self.hash_function().code()
+    }
+    /// Returns the raw digest bytes of this hash.
+    pub fn digest(&self) -> Vec<u8> {
+        self.hash_function()
+            .decoded_digest(&self.0)
+            .expect("programmer error: CESRHashStr was already validated")
+    }
+}
+
+impl HashRefT for CESRHashStr {
+    type HashFunction = CESRHashFunction;
+    fn hash_function(&self) -> Self::HashFunction {
+        CESRHashFunction::from_qb64_prefix(&self.0)
+            .expect("programmer error: CESRHashStr was already validated")
+    }
+    fn is_placeholder(&self) -> bool {
+        self.digest().iter().all(|b| *b == 0u8)
+    }
+}
+
+impl pneutype::Validate for CESRHashStr {
+    type Data = str;
+    type Error = Error;
+    fn validate(s: &Self::Data) -> std::result::Result<(), Self::Error> {
+        CESRHashFunction::from_qb64_prefix(s)?.decoded_digest(s)?;
+        Ok(())
+    }
+}
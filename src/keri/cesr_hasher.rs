@@ -0,0 +1,45 @@
+use crate::{CESRHashFunction, HashRefT, HasherDynT, HasherT};
+
+/// A hasher for the CESRHash type.  Note that because the hash function is determined by a runtime
+/// value (the CESR derivation code), the hasher_b Box contains dyn HasherDynT.
+pub struct CESRHasher {
+    hash_function: CESRHashFunction,
+    hasher_b: Box<dyn HasherDynT>,
+}
+
+impl CESRHasher {
+    pub fn new(hash_function: CESRHashFunction, hasher_b: Box<dyn HasherDynT>) -> Self {
+        Self {
+            hash_function,
+            hasher_b,
+        }
+    }
+}
+
+impl HasherT for CESRHasher {
+    type HashRef = crate::CESRHashStr;
+    fn hash_function(&self) -> <Self::HashRef as HashRefT>::HashFunction {
+        self.hash_function
+    }
+    fn update(&mut self, byte_v: &[u8]) {
+        HasherDynT::update(self.hasher_b.as_mut(), byte_v);
+    }
+    fn finalize(self) -> <Self::HashRef as ToOwned>::Owned {
+        let hash_b = self.hasher_b.finalize();
+        self.hash_function
+            .encoded(hash_b.hash_bytes().as_ref())
+            .expect("programmer error")
+    }
+}
+
+impl digest::Update for CESRHasher {
+    fn update(&mut self, byte_v: &[u8]) {
+        HasherDynT::update(self.hasher_b.as_mut(), byte_v);
+    }
+}
+
+impl digest::Update for &mut CESRHasher {
+    fn update(&mut self, byte_v: &[u8]) {
+        HasherDynT::update(self.hasher_b.as_mut(), byte_v);
+    }
+}
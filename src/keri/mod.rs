@@ -0,0 +1,11 @@
+mod cesr_hash;
+mod cesr_hash_function;
+mod cesr_hash_str;
+mod cesr_hasher;
+mod ordered_json;
+mod self_addressing_json;
+
+pub use {
+    cesr_hash::CESRHash, cesr_hash_function::CESRHashFunction, cesr_hash_str::CESRHashStr,
+    cesr_hasher::CESRHasher, self_addressing_json::SelfAddressingJSON,
+};
@@ -0,0 +1,192 @@
+use std::collections::HashSet;
+
+/// A JSON value whose object members are kept in insertion order, which is the order in which KERI
+/// serializes them.  This is used instead of serde_json::Value because the member order of
+/// serde_json::Map depends on serde_json's `preserve_order` feature, and enabling that feature would
+/// change the behavior of serde_json::Map for every crate in the dependency graph.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum OrderedJSON {
+    Null,
+    Bool(bool),
+    Number(serde_json::Number),
+    String(String),
+    Array(Vec<OrderedJSON>),
+    Object(Vec<(String, OrderedJSON)>),
+}
+
+impl OrderedJSON {
+    pub(crate) fn as_object(&self) -> Option<&[(String, OrderedJSON)]> {
+        match self {
+            Self::Object(member_v) => Some(member_v.as_slice()),
+            _ => None,
+        }
+    }
+    pub(crate) fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(s) => Some(s.as_str()),
+            _ => None,
+        }
+    }
+    /// Returns the value of the given member, if this is an object having that member.
+    pub(crate) fn get(&self, name: &str) -> Option<&OrderedJSON> {
+        self.as_object()?
+            .iter()
+            .find(|(member_name, _)| member_name == name)
+            .map(|(_, value)| value)
+    }
+    /// Sets the value of the given member in place, or appends the member if it isn't already present.
+    /// Does nothing if this isn't an object.
+    pub(crate) fn set(&mut self, name: &str, value: OrderedJSON) {
+        if let Self::Object(member_v) = self {
+            match member_v
+                .iter_mut()
+                .find(|(member_name, _)| member_name == name)
+            {
+                Some((_, member_value)) => *member_value = value,
+                None => member_v.push((name.to_string(), value)),
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for OrderedJSON {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let s = serde_json::to_string(self).map_err(|_| std::fmt::Error)?;
+        f.write_str(&s)
+    }
+}
+
+impl From<serde_json::Value> for OrderedJSON {
+    fn from(value: serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Null => Self::Null,
+            serde_json::Value::Bool(b) => Self::Bool(b),
+            serde_json::Value::Number(n) => Self::Number(n),
+            serde_json::Value::String(s) => Self::String(s),
+            serde_json::Value::Array(element_v) => {
+                Self::Array(element_v.into_iter().map(Self::from).collect())
+            }
+            serde_json::Value::Object(map) => Self::Object(
+                map.into_iter()
+                    .map(|(name, value)| (name, Self::from(value)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl From<OrderedJSON> for serde_json::Value {
+    fn from(value: OrderedJSON) -> Self {
+        match value {
+            OrderedJSON::Null => Self::Null,
+            OrderedJSON::Bool(b) => Self::Bool(b),
+            OrderedJSON::Number(n) => Self::Number(n),
+            OrderedJSON::String(s) => Self::String(s),
+            OrderedJSON::Array(element_v) => {
+                Self::Array(element_v.into_iter().map(Self::from).collect())
+            }
+            OrderedJSON::Object(member_v) => Self::Object(
+                member_v
+                    .into_iter()
+                    .map(|(name, value)| (name, Self::from(value)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl serde::Serialize for OrderedJSON {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::{SerializeMap, SerializeSeq};
+        match self {
+            Self::Null => serializer.serialize_unit(),
+            Self::Bool(b) => serializer.serialize_bool(*b),
+            Self::Number(n) => n.serialize(serializer),
+            Self::String(s) => serializer.serialize_str(s),
+            Self::Array(element_v) => {
+                let mut seq = serializer.serialize_seq(Some(element_v.len()))?;
+                for element in element_v.iter() {
+                    seq.serialize_element(element)?;
+                }
+                seq.end()
+            }
+            Self::Object(member_v) => {
+                let mut map = serializer.serialize_map(Some(member_v.len()))?;
+                for (name, value) in member_v.iter() {
+                    map.serialize_entry(name, value)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for OrderedJSON {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(OrderedJSONVisitor)
+    }
+}
+
+struct OrderedJSONVisitor;
+
+impl<'de> serde::de::Visitor<'de> for OrderedJSONVisitor {
+    type Value = OrderedJSON;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("a JSON value")
+    }
+    fn visit_unit<E: serde::de::Error>(self) -> Result<Self::Value, E> {
+        Ok(OrderedJSON::Null)
+    }
+    fn visit_none<E: serde::de::Error>(self) -> Result<Self::Value, E> {
+        Ok(OrderedJSON::Null)
+    }
+    fn visit_some<D: serde::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error> {
+        serde::Deserialize::deserialize(deserializer)
+    }
+    fn visit_bool<E: serde::de::Error>(self, b: bool) -> Result<Self::Value, E> {
+        Ok(OrderedJSON::Bool(b))
+    }
+    fn visit_i64<E: serde::de::Error>(self, n: i64) -> Result<Self::Value, E> {
+        Ok(OrderedJSON::Number(n.into()))
+    }
+    fn visit_u64<E: serde::de::Error>(self, n: u64) -> Result<Self::Value, E> {
+        Ok(OrderedJSON::Number(n.into()))
+    }
+    fn visit_f64<E: serde::de::Error>(self, x: f64) -> Result<Self::Value, E> {
+        serde_json::Number::from_f64(x)
+            .map(OrderedJSON::Number)
+            .ok_or_else(|| E::custom(format!("{} is not a valid JSON number", x)))
+    }
+    fn visit_str<E: serde::de::Error>(self, s: &str) -> Result<Self::Value, E> {
+        Ok(OrderedJSON::String(s.to_string()))
+    }
+    fn visit_string<E: serde::de::Error>(self, s: String) -> Result<Self::Value, E> {
+        Ok(OrderedJSON::String(s))
+    }
+    fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut element_v = Vec::new();
+        while let Some(element) = seq.next_element()? {
+            element_v.push(element);
+        }
+        Ok(OrderedJSON::Array(element_v))
+    }
+    fn visit_map<A: serde::de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        // Duplicate member names are rejected, since which of the values is meant is ambiguous.
+        let mut member_v = Vec::<(String, OrderedJSON)>::new();
+        let mut name_s = HashSet::new();
+        while let Some((name, value)) = map.next_entry::<String, OrderedJSON>()? {
+            if !name_s.insert(name.clone()) {
+                return Err(serde::de::Error::custom(format!(
+                    "duplicate object member name {:?}",
+                    name
+                )));
+            }
+            member_v.push((name, value));
+        }
+        Ok(OrderedJSON::Object(member_v))
+    }
+}
@@ -0,0 +1,222 @@
+use crate::{
+    bail, ensure, error, keri::ordered_json::OrderedJSON, CESRHashStr, HashFunctionT, HashRefT,
+    HasherT, Result, SelfHashableT,
+};
+
+/// The length of a KERI version 1 version string, e.g. "KERI10JSON00011c_".
+const VERSION_STRING_LEN: usize = 17;
+
+/// This data structure provides the context necessary to process a JSON value as KERI Self-Addressed
+/// Data (SAD), computing its Self-Addressing IDentifier (SAID) exactly as KERI does (e.g. keripy's
+/// Saider.saidify), so that the SAIDs it produces and verifies are interoperable with KERI.  In
+/// particular, while computing the SAID:
+/// - Each SAID field (named by a top-level label, `d` by default) is set to KERI's dummy value, which
+///   consists of `#` characters and has the length of the qb64 SAID (see CESRHashFunction::dummy).
+/// - If there is a top-level `v` field, it must be a KERI version 1 version string (e.g.
+///   "KERI10JSON000000_"), and its size field is set to the length of the serialization.
+/// - The value is serialized as compact JSON with its object members in insertion order (rather than as
+///   JCS), and this is hashed and encoded as a CESRHash.
+///
+/// The insertion order is kept by this type itself, independently of serde_json's `preserve_order`
+/// feature.  Use from_json_str to keep the member order of a serialization (e.g. a received KERI event);
+/// a value constructed from a serde_json::Value has the member order of its serde_json::Map.
+///
+/// A SAID field which is an empty string is considered unset.
+#[derive(Clone, Debug)]
+pub struct SelfAddressingJSON {
+    /// This is the JSON value that is being self-addressed, with its object members in insertion order.
+    value: OrderedJSON,
+    /// These are the top-level field names of the SAID fields.
+    label_v: Vec<String>,
+}
+
+impl SelfAddressingJSON {
+    /// Uses the default SAID field label `d`.  Note that the object members are in the order of the
+    /// given serde_json::Map, which is sorted by name unless serde_json's `preserve_order` feature is
+    /// enabled; use from_json_str to keep the order of a serialization.
+    pub fn new(value: serde_json::Value) -> Result<Self> {
+        Self::new_with_labels(value, ["d"])
+    }
+    /// Uses the given SAID field labels, e.g. `["d", "i"]` for a KERI inception event, in which the
+    /// identifier prefix is also the SAID.  Each SAID field must already exist and be a string.  See the
+    /// note on new regarding member order.
+    pub fn new_with_labels<I, S>(value: serde_json::Value, labels: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self::new_with_ordered_value(OrderedJSON::from(value), labels)
+    }
+    /// Parses the given JSON, keeping its object members in the order they appear, and uses the default
+    /// SAID field label `d`.  Duplicate object member names are rejected.
+    pub fn from_json_str(s: &str) -> Result<Self> {
+        Self::from_json_str_with_labels(s, ["d"])
+    }
+    /// Parses the given JSON, keeping its object members in the order they appear, and uses the given
+    /// SAID field labels (see new_with_labels).  Duplicate object member names are rejected.
+    pub fn from_json_str_with_labels<I, S>(s: &str, labels: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self::new_with_ordered_value(serde_json::from_str(s)?, labels)
+    }
+    fn new_with_ordered_value<I, S>(value: OrderedJSON, labels: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let label_v = labels.into_iter().map(Into::into).collect::<Vec<_>>();
+        ensure!(
+            !label_v.is_empty(),
+            "no SAID field labels given, meaning that this JSON value is not self-addressing"
+        );
+        ensure!(
+            value.as_object().is_some(),
+            "self-addressing JSON value is expected to be a JSON object"
+        );
+        for label in label_v.iter() {
+            let said_field = value
+                .get(label)
+                .ok_or_else(|| error!("SAID field {:?} is missing", label))?;
+            ensure!(
+                said_field.as_str().is_some(),
+                "SAID field {:?} is expected to be a string, but was {}",
+                label,
+                said_field
+            );
+        }
+        if let Some(version) = value.get("v") {
+            parse_version_string(version)?;
+        }
+        Ok(Self { value, label_v })
+    }
+    pub fn labels(&self) -> &[String] {
+        self.label_v.as_slice()
+    }
+    /// Returns the value as a serde_json::Value, whose member order is that of serde_json::Map.
+    pub fn to_value(&self) -> serde_json::Value {
+        serde_json::Value::from(self.value.clone())
+    }
+    /// Returns the value as a serde_json::Value, whose member order is that of serde_json::Map.
+    pub fn into_value(self) -> serde_json::Value {
+        serde_json::Value::from(self.value)
+    }
+    /// Returns the compact JSON serialization of the value with its object members in insertion order,
+    /// which is the serialization that the SAID covers (once the SAID fields are set).
+    pub fn to_json_string(&self) -> String {
+        self.value.to_string()
+    }
+    /// Sets each SAID field to the given string, and then updates the size field of the version string
+    /// (if present) to the length of the resulting serialization.
+    fn set_said_fields_to(value: &mut OrderedJSON, label_v: &[String], s: &str) -> Result<()> {
+        for label in label_v.iter() {
+            value.set(label, OrderedJSON::String(s.to_string()));
+        }
+        if let Some(version) = value.get("v") {
+            let version = version.clone();
+            let size = serde_json::to_vec(value)?.len();
+            value.set(
+                "v",
+                OrderedJSON::String(sized_version_string(&version, size)?),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Checks that the given value is a KERI version 1 JSON version string, e.g. "KERI10JSON00011c_", which
+/// consists of a 4-character protocol, a hex major and minor version, a 4-character serialization kind,
+/// a 6-digit hex size, and a `_` terminator, returning it.
+fn parse_version_string(version: &OrderedJSON) -> Result<&str> {
+    let version_string = version.as_str().ok_or_else(|| {
+        error!(
+            "version string field \"v\" is expected to be a string, but was {}",
+            version
+        )
+    })?;
+    let b = version_string.as_bytes();
+    ensure!(
+        b.len() == VERSION_STRING_LEN
+            && b[..4].iter().all(u8::is_ascii_uppercase)
+            && b[4..6]
+                .iter()
+                .all(|c| c.is_ascii_digit() || (b'a'..=b'f').contains(c))
+            && b[10..16]
+                .iter()
+                .all(|c| c.is_ascii_digit() || (b'a'..=b'f').contains(c))
+            && b[16] == b'_',
+        "{:?} is not a valid KERI version 1 version string (e.g. \"KERI10JSON000000_\")",
+        version_string
+    );
+    ensure!(
+        &version_string[6..10] == "JSON",
+        "version string {:?} specifies serialization kind {:?}, but only JSON is supported",
+        version_string,
+        &version_string[6..10]
+    );
+    Ok(version_string)
+}
+
+/// Returns the given version string with its size field set to the given size.
+fn sized_version_string(version: &OrderedJSON, size: usize) -> Result<String> {
+    let version_string = parse_version_string(version)?;
+    if size > 0xffffff {
+        bail!(
+            "serialization size {} exceeds the maximum representable in a version string",
+            size
+        );
+    }
+    Ok(format!("{}{:06x}_", &version_string[..10], size))
+}
+
+impl SelfHashableT<CESRHashStr> for SelfAddressingJSON {
+    fn write_digest_data(
+        &self,
+        hasher: &mut <<CESRHashStr as HashRefT>::HashFunction as HashFunctionT<CESRHashStr>>::Hasher,
+    ) -> Result<()> {
+        let mut value = self.value.clone();
+        Self::set_said_fields_to(
+            &mut value,
+            self.label_v.as_slice(),
+            hasher.hash_function().dummy().as_str(),
+        )?;
+        let mut writer = digest_io::IoWrapper(hasher);
+        serde_json::to_writer(&mut writer, &value)?;
+        Ok(())
+    }
+    fn self_hash_oi<'a, 'b: 'a>(
+        &'b self,
+    ) -> Result<Box<dyn std::iter::Iterator<Item = Option<&'b CESRHashStr>> + 'a>> {
+        let mut said_ov = Vec::with_capacity(self.label_v.len());
+        for label in self.label_v.iter() {
+            let said_str = self
+                .value
+                .get(label)
+                .and_then(OrderedJSON::as_str)
+                .ok_or_else(|| error!("SAID field {:?} is expected to be a string", label))?;
+            if said_str.is_empty() {
+                said_ov.push(None);
+            } else {
+                said_ov.push(Some(CESRHashStr::new_ref(said_str)?));
+            }
+        }
+        // A set SAID must be accompanied by the correctly sized version string (if present), since the
+        // version string is itself covered by the SAID.
+        if let (Some(version), Some(Some(said))) = (self.value.get("v"), said_ov.first()) {
+            let mut value = self.value.clone();
+            Self::set_said_fields_to(&mut value, self.label_v.as_slice(), said.as_str())?;
+            let expected_version = value.get("v").expect("programmer error");
+            ensure!(
+                expected_version == version,
+                "version string {} doesn't match the size of the serialization (expected {})",
+                version,
+                expected_version
+            );
+        }
+        Ok(Box::new(said_ov.into_iter()))
+    }
+    fn set_self_hash_slots_to(&mut self, hash: &CESRHashStr) -> Result<()> {
+        Self::set_said_fields_to(&mut self.value, self.label_v.as_slice(), hash.as_str())
+    }
+}
@@ -1,4 +1,10 @@
+#[cfg(feature = "archive")]
+pub mod archive;
 mod blake3;
+#[cfg(feature = "cbor")]
+mod dcbor_writer;
+#[cfg(feature = "did")]
+mod did;
 mod error;
 mod hash_bytes;
 mod hash_dyn_t;
@@ -7,16 +13,54 @@ mod hash_ref_t;
 mod hash_t;
 mod hasher_dyn_t;
 mod hasher_t;
+#[cfg(feature = "self-hashable-json")]
+mod jcs_writer;
+#[cfg(feature = "self-hashable-json")]
+mod json_path;
+#[cfg(feature = "self-hashable-json")]
+mod json_pointer;
+#[cfg(feature = "keri-said")]
+mod keri;
+#[cfg(feature = "postcard")]
+mod map_order_check;
 #[cfg(feature = "mbx")]
 mod mbx;
 mod named_hash_function;
+#[cfg(feature = "protobuf")]
+pub mod protobuf;
+#[cfg(feature = "resolver")]
+mod self_hash_directory_resolver;
+#[cfg(feature = "resolver")]
+mod self_hash_in_memory_resolver;
+#[cfg(feature = "resolver")]
+mod self_hash_resolver;
+#[cfg(feature = "streaming")]
+mod self_hash_slot;
+#[cfg(feature = "self-hashable-json")]
+mod self_hash_slot_cardinality;
+#[cfg(feature = "streaming")]
+mod self_hash_slot_serializer;
+#[cfg(feature = "self-hashable-json")]
+mod self_hash_template;
 #[cfg(feature = "self-hashable-json")]
 mod self_hash_url;
 #[cfg(feature = "self-hashable-json")]
 mod self_hash_url_str;
+#[cfg(feature = "self-hashable-binary")]
+mod self_hashable_binary;
+#[cfg(feature = "cbor")]
+mod self_hashable_cbor;
 #[cfg(feature = "self-hashable-json")]
 mod self_hashable_json;
 mod self_hashable_t;
+#[cfg(feature = "self-hashable-text")]
+mod self_hashable_text;
+#[cfg(feature = "self-hashable-toml")]
+mod self_hashable_toml;
+#[cfg(feature = "self-hashable-xml")]
+mod self_hashable_xml;
+#[cfg(feature = "self-hashable-yaml")]
+mod self_hashable_yaml;
 mod sha224;
 mod sha256;
 mod sha384;
@@ -25,17 +69,80 @@ mod sha3_256;
 mod sha3_384;
 mod sha3_512;
 mod sha512;
+#[cfg(feature = "self-hashable-text")]
+mod text_slot_marker;
+#[cfg(feature = "tree")]
+pub mod tree;
+mod try_hash_t;
+#[cfg(feature = "self-hashable-xml")]
+mod xml_c14n_writer;
+#[cfg(feature = "self-hashable-xml")]
+mod xml_document;
+#[cfg(feature = "self-hashable-xml")]
+mod xml_path;
 
+#[cfg(feature = "did")]
+pub use crate::did::{
+    DIDSelfHash, DIDSelfHashDocument, DIDSelfHashFilesystemResolver, DIDSelfHashResolver,
+    DIDSelfHashStr, DIDSelfHashURL, DIDSelfHashURLStr, DIDSelfHashVerificationMethod,
+};
+pub use crate::error::ErrorKind;
+#[cfg(feature = "self-hashable-json")]
+pub use crate::json_path::{JSONPath, JSONPathSegment};
+#[cfg(feature = "self-hashable-json")]
+pub use crate::json_pointer::JSONPointer;
+#[cfg(feature = "keri-said")]
+pub use crate::keri::{CESRHash, CESRHashFunction, CESRHashStr, CESRHasher, SelfAddressingJSON};
 #[cfg(feature = "mbx")]
 pub use crate::mbx::{MBHashFunction, MBHasher};
+#[cfg(feature = "resolver")]
+pub use crate::self_hash_directory_resolver::SelfHashDirectoryResolver;
+#[cfg(feature = "resolver")]
+pub use crate::self_hash_in_memory_resolver::SelfHashInMemoryResolver;
+#[cfg(feature = "resolver")]
+pub use crate::self_hash_resolver::SelfHashResolver;
+#[cfg(feature = "async-resolver")]
+pub use crate::self_hash_resolver::SelfHashResolverAsync;
+#[cfg(feature = "streaming")]
+pub use crate::self_hash_slot::SelfHashSlot;
+#[cfg(feature = "self-hashable-json")]
+pub use crate::self_hash_slot_cardinality::SelfHashSlotCardinality;
+#[cfg(feature = "streaming")]
+pub use crate::self_hash_slot_serializer::{SelfHashSlotSerializer, WithSelfHashPlaceholder};
 #[cfg(feature = "self-hashable-json")]
-pub use crate::self_hash_url::SelfHashURL;
+pub use crate::self_hash_template::SelfHashTemplate;
+#[cfg(feature = "self-hashable-json")]
+pub use crate::self_hash_url::{SelfHashURL, SelfHashURLBuilder};
 #[cfg(feature = "self-hashable-json")]
 pub use crate::self_hash_url_str::SelfHashURLStr;
+#[cfg(feature = "self-hashable-binary")]
+pub use crate::self_hashable_binary::{
+    BinarySlot, BinarySlotEncoding, BinarySlotFill, SelfHashableBinary,
+};
+#[cfg(feature = "cbor")]
+pub use crate::self_hashable_cbor::SelfHashableCBOR;
 #[cfg(feature = "self-hashable-json")]
 pub use crate::self_hashable_json::SelfHashableJSON;
+#[cfg(all(feature = "jcs", feature = "streaming"))]
+pub use crate::self_hashable_t::write_digest_data_streaming_jcs;
+#[cfg(all(feature = "postcard", feature = "streaming"))]
+pub use crate::self_hashable_t::write_digest_data_streaming_postcard;
+#[cfg(feature = "cbor")]
+pub use crate::self_hashable_t::write_digest_data_using_dcbor;
 #[cfg(feature = "jcs")]
 pub use crate::self_hashable_t::write_digest_data_using_jcs;
+#[cfg(feature = "postcard")]
+pub use crate::self_hashable_t::write_digest_data_using_postcard;
+#[cfg(feature = "self-hashable-text")]
+pub use crate::self_hashable_text::{LineEndingNormalization, SelfHashableText};
+#[cfg(feature = "self-hashable-toml")]
+pub use crate::self_hashable_toml::SelfHashableTOML;
+#[cfg(feature = "self-hashable-xml")]
+pub use crate::self_hashable_xml::SelfHashableXML;
+#[cfg(feature = "self-hashable-yaml")]
+pub use crate::self_hashable_yaml::{
+    json_value_to_yaml_string, yaml_to_json_value, SelfHashableYAML,
+};
 #[cfg(feature = "sha-224")]
 pub use crate::sha224::{SHA224Hash, SHA224HashInner};
 #[cfg(feature = "sha-256")]
@@ -52,12 +159,16 @@ pub use crate::sha3_384::{SHA3_384_Hash, SHA3_384_HashInner};
 pub use crate::sha3_512::{SHA3_512_Hash, SHA3_512_HashInner};
 #[cfg(feature = "sha-512")]
 pub use crate::sha512::{SHA512Hash, SHA512HashInner};
+#[cfg(feature = "self-hashable-text")]
+pub use crate::text_slot_marker::TextSlotMarker;
+#[cfg(feature = "self-hashable-xml")]
+pub use crate::xml_path::XMLPath;
 pub use crate::{
     blake3::Blake3, error::Error, hash_bytes::HashBytes, hash_dyn_t::HashDynT,
     hash_function_t::HashFunctionT, hash_ref_t::HashRefT, hash_t::HashT, hasher_dyn_t::HasherDynT,
     hasher_t::HasherT, named_hash_function::NamedHashFunction, self_hashable_t::SelfHashableT,
     sha224::SHA224, sha256::SHA256, sha384::SHA384, sha3_224::SHA3_224, sha3_256::SHA3_256,
-    sha3_384::SHA3_384, sha3_512::SHA3_512, sha512::SHA512,
+    sha3_384::SHA3_384, sha3_512::SHA3_512, sha512::SHA512, try_hash_t::TryHashT,
 };
 
 pub type Result<T> = std::result::Result<T, Error>;
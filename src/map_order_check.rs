@@ -0,0 +1,574 @@
+use crate::{ensure, Error, Result};
+use serde::ser::{
+    Serialize, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant, SerializeTuple,
+    SerializeTupleStruct, SerializeTupleVariant, Serializer,
+};
+
+/// Checks that every map within the given value is serialized with its keys in strictly increasing
+/// order, which is the case for e.g. BTreeMap, but generally not for HashMap, whose iteration order
+/// is unspecified.  Keys are compared as Rust's derived Ord would compare them (integers numerically,
+/// strings lexicographically, sequences/tuples/structs lexicographically by element, enums by variant
+/// index and then payload, None before Some).  Note that this can't detect other sources of
+/// nondeterminism, such as a HashSet (which is serialized as a sequence).
+///
+/// Since only the serialized form of each key is available, a map whose key type has a custom Ord that
+/// differs from the derived order of its serialized form (e.g. `BTreeMap<std::cmp::Reverse<u32>, _>`, whose
+/// keys serialize as plain u32s in decreasing order) is rejected, even though its order is deterministic.
+/// Only checking that consecutive keys differ would admit such maps, but would also admit a HashMap, so
+/// this limitation is accepted.
+///
+/// The value is serialized into a serializer which discards everything but map keys, so the data isn't
+/// copied; only the most recent key of each map currently being serialized is held, for comparison.
+pub(crate) fn ensure_maps_are_ordered<T: Serialize + ?Sized>(value: &T) -> Result<()> {
+    value.serialize(Check)
+}
+
+/// A generic capture of a serialized value, whose derived PartialOrd matches Rust's derived Ord for
+/// values of the same type.
+#[derive(Debug, PartialEq, PartialOrd)]
+enum Captured {
+    Unit,
+    Bool(bool),
+    I128(i128),
+    U128(u128),
+    F64(f64),
+    Char(char),
+    Str(String),
+    Bytes(Vec<u8>),
+    None,
+    Some(Box<Captured>),
+    Seq(Vec<Captured>),
+    Variant(u32, Box<Captured>),
+    Map(Vec<(Captured, Captured)>),
+}
+
+impl Captured {
+    fn ensure_maps_are_ordered(&self) -> Result<()> {
+        match self {
+            Captured::Some(captured) | Captured::Variant(_, captured) => {
+                captured.ensure_maps_are_ordered()?;
+            }
+            Captured::Seq(element_v) => {
+                for element in element_v.iter() {
+                    element.ensure_maps_are_ordered()?;
+                }
+            }
+            Captured::Map(entry_v) => {
+                ensure!(
+                    entry_v
+                        .windows(2)
+                        .all(|pair| pair[0].0.partial_cmp(&pair[1].0)
+                            == Some(std::cmp::Ordering::Less)),
+                    "map keys are not serialized in strictly increasing order, so the serialization is not deterministic (use e.g. BTreeMap instead of HashMap)"
+                );
+                for (key, value) in entry_v.iter() {
+                    key.ensure_maps_are_ordered()?;
+                    value.ensure_maps_are_ordered()?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// A Serializer which discards the serialized value, other than checking the order of map keys (see
+/// ensure_maps_are_ordered).
+struct Check;
+
+/// Checks the elements of a sequence, tuple, or struct, or the entries of a map, holding only the most
+/// recent map key.
+#[derive(Default)]
+struct CheckCompound {
+    previous_key_o: Option<Captured>,
+}
+
+impl Serializer for Check {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = CheckCompound;
+    type SerializeTuple = CheckCompound;
+    type SerializeTupleStruct = CheckCompound;
+    type SerializeTupleVariant = CheckCompound;
+    type SerializeMap = CheckCompound;
+    type SerializeStruct = CheckCompound;
+    type SerializeStructVariant = CheckCompound;
+
+    fn serialize_bool(self, _v: bool) -> Result<()> {
+        Ok(())
+    }
+    fn serialize_i8(self, _v: i8) -> Result<()> {
+        Ok(())
+    }
+    fn serialize_i16(self, _v: i16) -> Result<()> {
+        Ok(())
+    }
+    fn serialize_i32(self, _v: i32) -> Result<()> {
+        Ok(())
+    }
+    fn serialize_i64(self, _v: i64) -> Result<()> {
+        Ok(())
+    }
+    fn serialize_i128(self, _v: i128) -> Result<()> {
+        Ok(())
+    }
+    fn serialize_u8(self, _v: u8) -> Result<()> {
+        Ok(())
+    }
+    fn serialize_u16(self, _v: u16) -> Result<()> {
+        Ok(())
+    }
+    fn serialize_u32(self, _v: u32) -> Result<()> {
+        Ok(())
+    }
+    fn serialize_u64(self, _v: u64) -> Result<()> {
+        Ok(())
+    }
+    fn serialize_u128(self, _v: u128) -> Result<()> {
+        Ok(())
+    }
+    fn serialize_f32(self, _v: f32) -> Result<()> {
+        Ok(())
+    }
+    fn serialize_f64(self, _v: f64) -> Result<()> {
+        Ok(())
+    }
+    fn serialize_char(self, _v: char) -> Result<()> {
+        Ok(())
+    }
+    fn serialize_str(self, _v: &str) -> Result<()> {
+        Ok(())
+    }
+    fn collect_str<T: std::fmt::Display + ?Sized>(self, _value: &T) -> Result<()> {
+        Ok(())
+    }
+    fn serialize_bytes(self, _v: &[u8]) -> Result<()> {
+        Ok(())
+    }
+    fn serialize_none(self) -> Result<()> {
+        Ok(())
+    }
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<()> {
+        value.serialize(Check)
+    }
+    fn serialize_unit(self) -> Result<()> {
+        Ok(())
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        Ok(())
+    }
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<()> {
+        Ok(())
+    }
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        value.serialize(Check)
+    }
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        value.serialize(Check)
+    }
+    fn serialize_seq(self, _len: Option<usize>) -> Result<CheckCompound> {
+        Ok(CheckCompound::default())
+    }
+    fn serialize_tuple(self, _len: usize) -> Result<CheckCompound> {
+        Ok(CheckCompound::default())
+    }
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<CheckCompound> {
+        Ok(CheckCompound::default())
+    }
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<CheckCompound> {
+        Ok(CheckCompound::default())
+    }
+    fn serialize_map(self, _len: Option<usize>) -> Result<CheckCompound> {
+        Ok(CheckCompound::default())
+    }
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<CheckCompound> {
+        Ok(CheckCompound::default())
+    }
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<CheckCompound> {
+        Ok(CheckCompound::default())
+    }
+}
+
+impl SerializeSeq for CheckCompound {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(Check)
+    }
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl SerializeTuple for CheckCompound {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(Check)
+    }
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl SerializeTupleStruct for CheckCompound {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(Check)
+    }
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl SerializeTupleVariant for CheckCompound {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(Check)
+    }
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl SerializeStruct for CheckCompound {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        value.serialize(Check)
+    }
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl SerializeStructVariant for CheckCompound {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        value.serialize(Check)
+    }
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl SerializeMap for CheckCompound {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        // Keys are captured so that they can be compared, but they're generally small.
+        let key = key.serialize(Capture)?;
+        key.ensure_maps_are_ordered()?;
+        if let Some(previous_key) = self.previous_key_o.as_ref() {
+            ensure!(
+                previous_key.partial_cmp(&key) == Some(std::cmp::Ordering::Less),
+                "map keys are not serialized in strictly increasing order, so the serialization is not deterministic (use e.g. BTreeMap instead of HashMap)"
+            );
+        }
+        self.previous_key_o = Some(key);
+        Ok(())
+    }
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(Check)
+    }
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// A Serializer which captures the serialized value, for comparing map keys.
+struct Capture;
+
+/// Accumulates the elements of a sequence, tuple, or struct, or the entries of a map.
+#[derive(Default)]
+struct CaptureCompound {
+    variant_index_o: Option<u32>,
+    element_v: Vec<Captured>,
+    entry_v: Vec<(Captured, Captured)>,
+    pending_key_o: Option<Captured>,
+}
+
+impl CaptureCompound {
+    fn variant(variant_index: u32) -> Self {
+        Self {
+            variant_index_o: Some(variant_index),
+            ..Default::default()
+        }
+    }
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.element_v.push(value.serialize(Capture)?);
+        Ok(())
+    }
+    fn end_seq(self) -> Result<Captured> {
+        let seq = Captured::Seq(self.element_v);
+        Ok(match self.variant_index_o {
+            Some(variant_index) => Captured::Variant(variant_index, Box::new(seq)),
+            None => seq,
+        })
+    }
+}
+
+impl Serializer for Capture {
+    type Ok = Captured;
+    type Error = Error;
+    type SerializeSeq = CaptureCompound;
+    type SerializeTuple = CaptureCompound;
+    type SerializeTupleStruct = CaptureCompound;
+    type SerializeTupleVariant = CaptureCompound;
+    type SerializeMap = CaptureCompound;
+    type SerializeStruct = CaptureCompound;
+    type SerializeStructVariant = CaptureCompound;
+
+    fn serialize_bool(self, v: bool) -> Result<Captured> {
+        Ok(Captured::Bool(v))
+    }
+    fn serialize_i8(self, v: i8) -> Result<Captured> {
+        Ok(Captured::I128(v.into()))
+    }
+    fn serialize_i16(self, v: i16) -> Result<Captured> {
+        Ok(Captured::I128(v.into()))
+    }
+    fn serialize_i32(self, v: i32) -> Result<Captured> {
+        Ok(Captured::I128(v.into()))
+    }
+    fn serialize_i64(self, v: i64) -> Result<Captured> {
+        Ok(Captured::I128(v.into()))
+    }
+    fn serialize_i128(self, v: i128) -> Result<Captured> {
+        Ok(Captured::I128(v))
+    }
+    fn serialize_u8(self, v: u8) -> Result<Captured> {
+        Ok(Captured::U128(v.into()))
+    }
+    fn serialize_u16(self, v: u16) -> Result<Captured> {
+        Ok(Captured::U128(v.into()))
+    }
+    fn serialize_u32(self, v: u32) -> Result<Captured> {
+        Ok(Captured::U128(v.into()))
+    }
+    fn serialize_u64(self, v: u64) -> Result<Captured> {
+        Ok(Captured::U128(v.into()))
+    }
+    fn serialize_u128(self, v: u128) -> Result<Captured> {
+        Ok(Captured::U128(v))
+    }
+    fn serialize_f32(self, v: f32) -> Result<Captured> {
+        Ok(Captured::F64(v.into()))
+    }
+    fn serialize_f64(self, v: f64) -> Result<Captured> {
+        Ok(Captured::F64(v))
+    }
+    fn serialize_char(self, v: char) -> Result<Captured> {
+        Ok(Captured::Char(v))
+    }
+    fn serialize_str(self, v: &str) -> Result<Captured> {
+        Ok(Captured::Str(v.to_string()))
+    }
+    fn serialize_bytes(self, v: &[u8]) -> Result<Captured> {
+        Ok(Captured::Bytes(v.to_vec()))
+    }
+    fn serialize_none(self) -> Result<Captured> {
+        Ok(Captured::None)
+    }
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Captured> {
+        Ok(Captured::Some(Box::new(value.serialize(Capture)?)))
+    }
+    fn serialize_unit(self) -> Result<Captured> {
+        Ok(Captured::Unit)
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Captured> {
+        Ok(Captured::Unit)
+    }
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<Captured> {
+        Ok(Captured::Variant(variant_index, Box::new(Captured::Unit)))
+    }
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Captured> {
+        value.serialize(Capture)
+    }
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<Captured> {
+        Ok(Captured::Variant(
+            variant_index,
+            Box::new(value.serialize(Capture)?),
+        ))
+    }
+    fn serialize_seq(self, _len: Option<usize>) -> Result<CaptureCompound> {
+        Ok(CaptureCompound::default())
+    }
+    fn serialize_tuple(self, _len: usize) -> Result<CaptureCompound> {
+        Ok(CaptureCompound::default())
+    }
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<CaptureCompound> {
+        Ok(CaptureCompound::default())
+    }
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<CaptureCompound> {
+        Ok(CaptureCompound::variant(variant_index))
+    }
+    fn serialize_map(self, _len: Option<usize>) -> Result<CaptureCompound> {
+        Ok(CaptureCompound::default())
+    }
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<CaptureCompound> {
+        Ok(CaptureCompound::default())
+    }
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<CaptureCompound> {
+        Ok(CaptureCompound::variant(variant_index))
+    }
+}
+
+impl SerializeSeq for CaptureCompound {
+    type Ok = Captured;
+    type Error = Error;
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+    fn end(self) -> Result<Captured> {
+        self.end_seq()
+    }
+}
+
+impl SerializeTuple for CaptureCompound {
+    type Ok = Captured;
+    type Error = Error;
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+    fn end(self) -> Result<Captured> {
+        self.end_seq()
+    }
+}
+
+impl SerializeTupleStruct for CaptureCompound {
+    type Ok = Captured;
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+    fn end(self) -> Result<Captured> {
+        self.end_seq()
+    }
+}
+
+impl SerializeTupleVariant for CaptureCompound {
+    type Ok = Captured;
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+    fn end(self) -> Result<Captured> {
+        self.end_seq()
+    }
+}
+
+impl SerializeStruct for CaptureCompound {
+    type Ok = Captured;
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.push(value)
+    }
+    fn end(self) -> Result<Captured> {
+        self.end_seq()
+    }
+}
+
+impl SerializeStructVariant for CaptureCompound {
+    type Ok = Captured;
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.push(value)
+    }
+    fn end(self) -> Result<Captured> {
+        self.end_seq()
+    }
+}
+
+impl SerializeMap for CaptureCompound {
+    type Ok = Captured;
+    type Error = Error;
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        self.pending_key_o = Some(key.serialize(Capture)?);
+        Ok(())
+    }
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let key = self
+            .pending_key_o
+            .take()
+            .expect("programmer error: serialize_value called before serialize_key");
+        self.entry_v.push((key, value.serialize(Capture)?));
+        Ok(())
+    }
+    fn end(self) -> Result<Captured> {
+        Ok(Captured::Map(self.entry_v))
+    }
+}
@@ -57,6 +57,37 @@ impl NamedHashFunction {
     /// See https://nvlpubs.nist.gov/nistpubs/fips/nist.fips.202.pdf
     pub const SHA3_512: NamedHashFunction = NamedHashFunction(SHA3_512_STR);
 
+    /// All the named hash functions, whether or not their corresponding features are enabled.
+    pub const ALL: [NamedHashFunction; 9] = [
+        Self::BLAKE3,
+        Self::SHA224,
+        Self::SHA256,
+        Self::SHA384,
+        Self::SHA512,
+        Self::SHA3_224,
+        Self::SHA3_256,
+        Self::SHA3_384,
+        Self::SHA3_512,
+    ];
+
+    /// Returns true iff the feature corresponding to this hash function is enabled, i.e. iff hashers can
+    /// be created for it.
+    pub fn is_enabled(&self) -> bool {
+        match self.0 {
+            BLAKE3_STR => cfg!(feature = "blake3"),
+            SHA_224_STR => cfg!(feature = "sha-224"),
+            SHA_256_STR => cfg!(feature = "sha-256"),
+            SHA_384_STR => cfg!(feature = "sha-384"),
+            SHA_512_STR => cfg!(feature = "sha-512"),
+            SHA3_224_STR => cfg!(feature = "sha3-224"),
+            SHA3_256_STR => cfg!(feature = "sha3-256"),
+            SHA3_384_STR => cfg!(feature = "sha3-384"),
+            SHA3_512_STR => cfg!(feature = "sha3-512"),
+            _ => {
+                panic!("programmer error: unrecognized hash function name");
+            }
+        }
+    }
     #[cfg(feature = "mbx")]
     pub fn as_mb_hash_function(&self, base: mbx::Base) -> crate::MBHashFunction {
         match self.0 {
@@ -90,6 +121,42 @@ impl NamedHashFunction {
             }
         }
     }
+    /// Returns a new hasher for this hash function.  Note that this panics if the corresponding feature
+    /// isn't enabled.
+    pub fn new_hasher_dyn(&self) -> Box<dyn crate::HasherDynT> {
+        match self.0 {
+            BLAKE3_STR => crate::Blake3::new_hasher_dyn(),
+            SHA_224_STR => crate::SHA224::new_hasher_dyn(),
+            SHA_256_STR => crate::SHA256::new_hasher_dyn(),
+            SHA_384_STR => crate::SHA384::new_hasher_dyn(),
+            SHA_512_STR => crate::SHA512::new_hasher_dyn(),
+            SHA3_224_STR => crate::SHA3_224::new_hasher_dyn(),
+            SHA3_256_STR => crate::SHA3_256::new_hasher_dyn(),
+            SHA3_384_STR => crate::SHA3_384::new_hasher_dyn(),
+            SHA3_512_STR => crate::SHA3_512::new_hasher_dyn(),
+            _ => {
+                panic!("programmer error: unrecognized hash function name");
+            }
+        }
+    }
+    /// Returns the multicodec code of this hash function (see https://github.com/multiformats/multicodec).
+    #[cfg(feature = "mbx")]
+    pub fn codec(&self) -> u64 {
+        match self.0 {
+            BLAKE3_STR => ssi_multicodec::BLAKE3,
+            SHA_224_STR => ssi_multicodec::SHA2_224,
+            SHA_256_STR => ssi_multicodec::SHA2_256,
+            SHA_384_STR => ssi_multicodec::SHA2_384,
+            SHA_512_STR => ssi_multicodec::SHA2_512,
+            SHA3_224_STR => ssi_multicodec::SHA3_224,
+            SHA3_256_STR => ssi_multicodec::SHA3_256,
+            SHA3_384_STR => ssi_multicodec::SHA3_384,
+            SHA3_512_STR => ssi_multicodec::SHA3_512,
+            _ => {
+                panic!("programmer error: unrecognized hash function name");
+            }
+        }
+    }
 }
 
 impl std::str::FromStr for NamedHashFunction {
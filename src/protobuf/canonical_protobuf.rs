@@ -0,0 +1,198 @@
+use crate::{bail, ensure, Result};
+use prost_reflect::{
+    prost::{
+        bytes::BufMut,
+        encoding::{encode_key, encode_varint, WireType},
+    },
+    DynamicMessage, Kind, MapKey, ReflectMessage, Value,
+};
+
+/// Returns the canonical protobuf encoding of the given message (see write_canonical_protobuf).
+pub fn to_canonical_protobuf_vec(message: &DynamicMessage) -> Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    write_canonical_protobuf(message, &mut buffer)?;
+    Ok(buffer)
+}
+
+/// Writes the canonical protobuf encoding of the given message, which is its deterministic serialization:
+/// - Fields (including extension fields) are written in increasing order of field number.
+/// - As usual, fields without presence (e.g. proto3 scalars) are omitted when they have their default
+///   value, and fields with presence are written iff they're set.
+/// - Repeated scalar fields are packed iff the field is declared packed (proto3's default).
+/// - Map entries are written in increasing order of key, and each entry has both its key and its value.
+/// - Unknown fields aren't allowed, since they can't be canonically encoded (their types are unknown),
+///   and so this returns an error if there are any.
+///
+/// Thus all encodings of a message (e.g. with fields out of order, or with repeated scalars packed or
+/// not) decode to a DynamicMessage whose canonical encoding is the same.  Note that floating-point values
+/// are encoded bitwise, so e.g. 0.0 and -0.0 are distinct.
+pub fn write_canonical_protobuf(message: &DynamicMessage, buffer: &mut Vec<u8>) -> Result<()> {
+    ensure!(
+        message.unknown_fields().next().is_none(),
+        "protobuf message of type {} has unknown fields, which can't be canonically encoded",
+        message.descriptor().full_name()
+    );
+    let mut field_v = message
+        .fields()
+        .map(|(field, value)| {
+            (
+                field.number(),
+                field.kind(),
+                field.is_packed(),
+                field.is_group(),
+                value,
+            )
+        })
+        .chain(message.extensions().map(|(extension, value)| {
+            (
+                extension.number(),
+                extension.kind(),
+                extension.is_packed(),
+                extension.is_group(),
+                value,
+            )
+        }))
+        .collect::<Vec<_>>();
+    field_v.sort_by_key(|(number, ..)| *number);
+    for (number, kind, is_packed, is_group, value) in field_v {
+        match value {
+            Value::List(element_v) => {
+                if is_packed {
+                    let mut payload = Vec::new();
+                    for element in element_v {
+                        write_scalar_payload(&kind, element, &mut payload)?;
+                    }
+                    encode_key(number, WireType::LengthDelimited, buffer);
+                    encode_varint(payload.len() as u64, buffer);
+                    buffer.put_slice(&payload);
+                } else {
+                    for element in element_v {
+                        write_field(number, &kind, is_group, element, buffer)?;
+                    }
+                }
+            }
+            Value::Map(entry_m) => {
+                let Kind::Message(map_entry_descriptor) = &kind else {
+                    bail!("protobuf map field {} has a non-message kind", number);
+                };
+                let key_kind = map_entry_descriptor.map_entry_key_field().kind();
+                let value_field = map_entry_descriptor.map_entry_value_field();
+                let value_kind = value_field.kind();
+                let mut entry_v = entry_m.iter().collect::<Vec<_>>();
+                entry_v.sort_by(|(lhs, _), (rhs, _)| lhs.cmp(rhs));
+                for (key, value) in entry_v {
+                    let mut payload = Vec::new();
+                    write_field(1, &key_kind, false, &map_key_to_value(key), &mut payload)?;
+                    write_field(2, &value_kind, value_field.is_group(), value, &mut payload)?;
+                    encode_key(number, WireType::LengthDelimited, buffer);
+                    encode_varint(payload.len() as u64, buffer);
+                    buffer.put_slice(&payload);
+                }
+            }
+            _ => {
+                write_field(number, &kind, is_group, value, buffer)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn map_key_to_value(key: &MapKey) -> Value {
+    match key {
+        MapKey::Bool(b) => Value::Bool(*b),
+        MapKey::I32(n) => Value::I32(*n),
+        MapKey::I64(n) => Value::I64(*n),
+        MapKey::U32(n) => Value::U32(*n),
+        MapKey::U64(n) => Value::U64(*n),
+        MapKey::String(s) => Value::String(s.clone()),
+    }
+}
+
+/// Writes a single (i.e. non-list, non-map) field value, including its key.
+fn write_field(
+    number: u32,
+    kind: &Kind,
+    is_group: bool,
+    value: &Value,
+    buffer: &mut Vec<u8>,
+) -> Result<()> {
+    match (kind, value) {
+        (Kind::Message(_), Value::Message(message)) => {
+            if is_group {
+                encode_key(number, WireType::StartGroup, buffer);
+                write_canonical_protobuf(message, buffer)?;
+                encode_key(number, WireType::EndGroup, buffer);
+            } else {
+                let payload = to_canonical_protobuf_vec(message)?;
+                encode_key(number, WireType::LengthDelimited, buffer);
+                encode_varint(payload.len() as u64, buffer);
+                buffer.put_slice(&payload);
+            }
+        }
+        (Kind::String, Value::String(s)) => {
+            encode_key(number, WireType::LengthDelimited, buffer);
+            encode_varint(s.len() as u64, buffer);
+            buffer.put_slice(s.as_bytes());
+        }
+        (Kind::Bytes, Value::Bytes(b)) => {
+            encode_key(number, WireType::LengthDelimited, buffer);
+            encode_varint(b.len() as u64, buffer);
+            buffer.put_slice(b);
+        }
+        _ => {
+            encode_key(number, scalar_wire_type(kind)?, buffer);
+            write_scalar_payload(kind, value, buffer)?;
+        }
+    }
+    Ok(())
+}
+
+fn scalar_wire_type(kind: &Kind) -> Result<WireType> {
+    Ok(match kind {
+        Kind::Int32
+        | Kind::Int64
+        | Kind::Uint32
+        | Kind::Uint64
+        | Kind::Sint32
+        | Kind::Sint64
+        | Kind::Bool
+        | Kind::Enum(_) => WireType::Varint,
+        Kind::Fixed64 | Kind::Sfixed64 | Kind::Double => WireType::SixtyFourBit,
+        Kind::Fixed32 | Kind::Sfixed32 | Kind::Float => WireType::ThirtyTwoBit,
+        Kind::String | Kind::Bytes | Kind::Message(_) => {
+            bail!("protobuf {:?} field is not a scalar", kind);
+        }
+    })
+}
+
+/// Writes the encoding of a scalar value without its key, as it appears in a packed repeated field.
+fn write_scalar_payload(kind: &Kind, value: &Value, buffer: &mut Vec<u8>) -> Result<()> {
+    match (kind, value) {
+        // Note that negative int32 values are sign-extended to 64 bits, per the protobuf encoding.
+        (Kind::Int32, Value::I32(n)) | (Kind::Enum(_), Value::EnumNumber(n)) => {
+            encode_varint(*n as i64 as u64, buffer)
+        }
+        (Kind::Int64, Value::I64(n)) => encode_varint(*n as u64, buffer),
+        (Kind::Uint32, Value::U32(n)) => encode_varint(u64::from(*n), buffer),
+        (Kind::Uint64, Value::U64(n)) => encode_varint(*n, buffer),
+        (Kind::Sint32, Value::I32(n)) => {
+            encode_varint(u64::from(((n << 1) ^ (n >> 31)) as u32), buffer)
+        }
+        (Kind::Sint64, Value::I64(n)) => encode_varint(((n << 1) ^ (n >> 63)) as u64, buffer),
+        (Kind::Bool, Value::Bool(b)) => encode_varint(u64::from(*b), buffer),
+        (Kind::Fixed32, Value::U32(n)) => buffer.put_u32_le(*n),
+        (Kind::Sfixed32, Value::I32(n)) => buffer.put_i32_le(*n),
+        (Kind::Float, Value::F32(x)) => buffer.put_f32_le(*x),
+        (Kind::Fixed64, Value::U64(n)) => buffer.put_u64_le(*n),
+        (Kind::Sfixed64, Value::I64(n)) => buffer.put_i64_le(*n),
+        (Kind::Double, Value::F64(x)) => buffer.put_f64_le(*x),
+        _ => {
+            bail!(
+                "protobuf value {:?} doesn't match its field's kind {:?}",
+                value,
+                kind
+            );
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,9 @@
+mod canonical_protobuf;
+mod self_hashable_protobuf;
+mod write_digest_data_using_protobuf;
+
+pub use {
+    canonical_protobuf::{to_canonical_protobuf_vec, write_canonical_protobuf},
+    self_hashable_protobuf::SelfHashableProtobuf,
+    write_digest_data_using_protobuf::write_digest_data_using_protobuf,
+};
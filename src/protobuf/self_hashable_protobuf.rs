@@ -0,0 +1,274 @@
+use crate::{
+    bail, ensure, error, protobuf::to_canonical_protobuf_vec, HashFunctionT, HashRefT, HasherT,
+    Result, SelfHashURL, SelfHashURLStr, SelfHashableT,
+};
+use prost_reflect::{
+    prost::Message, DynamicMessage, FieldDescriptor, Kind, MessageDescriptor, ReflectMessage, Value,
+};
+use std::borrow::Cow;
+
+/// Resolves a field path (a `.`-delimited sequence of field names, e.g. `header.self_hash`) against the
+/// given message type.  Each field but the last must be a singular message field, and the last must be
+/// a singular string field.
+fn resolve_field_path(
+    message_descriptor: &MessageDescriptor,
+    field_path: &str,
+) -> Result<Vec<FieldDescriptor>> {
+    let mut field_v = Vec::new();
+    let mut message_descriptor = message_descriptor.clone();
+    let field_name_v = field_path.split('.').collect::<Vec<_>>();
+    for (i, field_name) in field_name_v.iter().enumerate() {
+        let field = message_descriptor
+            .get_field_by_name(field_name)
+            .ok_or_else(|| {
+                error!(
+                    "protobuf message type {} has no field named {:?}; field path was {:?}",
+                    message_descriptor.full_name(),
+                    field_name,
+                    field_path
+                )
+            })?;
+        ensure!(
+            !field.is_list() && !field.is_map(),
+            "protobuf field {} is repeated, so it can't be part of a self-hash field path; field path was {:?}",
+            field.full_name(),
+            field_path
+        );
+        if i + 1 < field_name_v.len() {
+            let Kind::Message(child_message_descriptor) = field.kind() else {
+                bail!(
+                    "protobuf field {} is not a message field, so it can't have child fields; field path was {:?}",
+                    field.full_name(),
+                    field_path
+                );
+            };
+            message_descriptor = child_message_descriptor;
+        } else {
+            ensure!(
+                field.kind() == Kind::String,
+                "protobuf field {} is not a string field, so it can't be a self-hash [URL] slot; field path was {:?}",
+                field.full_name(),
+                field_path
+            );
+        }
+        field_v.push(field);
+    }
+    Ok(field_v)
+}
+
+/// Returns the value of the given field, if it's set.  Unlike DynamicMessage::get_field, this borrows
+/// from the message.
+fn field_value<'a>(message: &'a DynamicMessage, field: &FieldDescriptor) -> Option<&'a Value> {
+    message
+        .fields()
+        .find(|(set_field, _)| set_field.number() == field.number())
+        .map(|(_, value)| value)
+}
+
+/// Returns the value of the string field at the given resolved field path, or None if it or one of its
+/// ancestors is unset.
+fn string_at<'a>(message: &'a DynamicMessage, field_v: &[FieldDescriptor]) -> Option<&'a str> {
+    let (field, parent_field_v) = field_v.split_last().expect("programmer error");
+    let mut message = message;
+    for parent_field in parent_field_v {
+        message = field_value(message, parent_field)?.as_message()?;
+    }
+    field_value(message, field)?.as_str()
+}
+
+/// Sets the value of the string field at the given resolved field path, setting its ancestors (to empty
+/// messages) as needed.
+fn set_string_at(message: &mut DynamicMessage, field_v: &[FieldDescriptor], s: &str) {
+    let (field, parent_field_v) = field_v.split_last().expect("programmer error");
+    let mut message = message;
+    for parent_field in parent_field_v {
+        message = message
+            .get_field_mut(parent_field)
+            .as_message_mut()
+            .expect("programmer error: this was checked by resolve_field_path");
+    }
+    message.set_field(field, Value::String(s.to_string()));
+}
+
+/// This provides self-hashable protobuf messages, where the self-hash [URL] slots are string fields
+/// designated by field path (a `.`-delimited sequence of field names, e.g. `self_hash` or
+/// `header.self_hash`), and the digest is computed over the canonical protobuf encoding of the message
+/// (see write_canonical_protobuf).  An unset or empty self-hash field counts as unset.  Messages are held
+/// as prost_reflect::DynamicMessage-s; a statically typed message can be converted to and from one (see
+/// SelfHashableProtobuf::from_message and SelfHashableProtobuf::to_message).
+#[derive(Clone)]
+pub struct SelfHashableProtobuf<'v, 'w: 'v> {
+    /// This is the protobuf message that is being self-hashed.
+    message: DynamicMessage,
+    /// These are all the field paths whose values are considered to define self-hash values.
+    self_hash_field_path_s: Cow<'v, std::collections::HashSet<Cow<'w, str>>>,
+    /// These are all the field paths whose values are considered to define self-hash URL values.
+    self_hash_url_field_path_s: Cow<'v, std::collections::HashSet<Cow<'w, str>>>,
+    /// These are the resolved forms of self_hash_field_path_s.
+    self_hash_field_vv: Vec<Vec<FieldDescriptor>>,
+    /// These are the resolved forms of self_hash_url_field_path_s.
+    self_hash_url_field_vv: Vec<Vec<FieldDescriptor>>,
+}
+
+impl<'v, 'w: 'v> SelfHashableProtobuf<'v, 'w> {
+    /// A self-hash field doesn't have to be set already, but each self-hash URL field must already be set
+    /// to a valid self-hash URL.  The message must not have unknown fields (see write_canonical_protobuf).
+    pub fn new(
+        message: DynamicMessage,
+        self_hash_field_path_s: Cow<'v, std::collections::HashSet<Cow<'w, str>>>,
+        self_hash_url_field_path_s: Cow<'v, std::collections::HashSet<Cow<'w, str>>>,
+    ) -> Result<Self> {
+        ensure!(
+            self_hash_field_path_s.is_disjoint(&self_hash_url_field_path_s),
+            "self-hash field paths and self-hash URL field paths must be disjoint."
+        );
+        let message_descriptor = message.descriptor();
+        let self_hash_field_vv = self_hash_field_path_s
+            .iter()
+            .map(|self_hash_field_path| {
+                resolve_field_path(&message_descriptor, self_hash_field_path)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let self_hash_url_field_vv = self_hash_url_field_path_s
+            .iter()
+            .map(|self_hash_url_field_path| {
+                resolve_field_path(&message_descriptor, self_hash_url_field_path)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        ensure!(
+            self_hash_field_vv.len() + self_hash_url_field_vv.len() > 0,
+            "no self-hash or self-hash URL fields found, meaning that this protobuf message is not self-hashable"
+        );
+
+        let self_hashable_protobuf = SelfHashableProtobuf {
+            message,
+            self_hash_field_path_s,
+            self_hash_url_field_path_s,
+            self_hash_field_vv,
+            self_hash_url_field_vv,
+        };
+        self_hashable_protobuf.validate()?;
+        Ok(self_hashable_protobuf)
+    }
+    /// Converts the given statically typed message into a DynamicMessage and calls
+    /// SelfHashableProtobuf::new.
+    pub fn from_message<M: ReflectMessage>(
+        message: &M,
+        self_hash_field_path_s: Cow<'v, std::collections::HashSet<Cow<'w, str>>>,
+        self_hash_url_field_path_s: Cow<'v, std::collections::HashSet<Cow<'w, str>>>,
+    ) -> Result<Self> {
+        Self::new(
+            message.transcode_to_dynamic(),
+            self_hash_field_path_s,
+            self_hash_url_field_path_s,
+        )
+    }
+    /// Verifies that the message has no unknown fields and that all the self-hash URL slots are set to
+    /// valid self-hash URLs.
+    fn validate(&self) -> Result<()> {
+        ensure!(
+            self.message.unknown_fields().next().is_none(),
+            "protobuf message of type {} has unknown fields, which can't be canonically encoded",
+            self.message.descriptor().full_name()
+        );
+        for self_hash_url_field_v in self.self_hash_url_field_vv.iter() {
+            SelfHashURLStr::new_ref(self.self_hash_url_str(self_hash_url_field_v)?)?;
+        }
+        Ok(())
+    }
+    fn self_hash_url_str(&self, self_hash_url_field_v: &[FieldDescriptor]) -> Result<&str> {
+        string_at(&self.message, self_hash_url_field_v).ok_or_else(|| {
+            error!(
+                "self-hash URL field {} is unset",
+                self_hash_url_field_v.last().unwrap().full_name()
+            )
+        })
+    }
+    /// Returns the canonical protobuf encoding of the message (see write_canonical_protobuf).
+    pub fn to_canonical_protobuf(&self) -> Result<Vec<u8>> {
+        to_canonical_protobuf_vec(&self.message)
+    }
+    /// Converts the message into the given statically typed message.
+    pub fn to_message<M: Message + Default>(&self) -> Result<M> {
+        self.message.transcode_to().map_err(|e| {
+            error!(
+                "failed to convert protobuf message of type {}; error was {}",
+                self.message.descriptor().full_name(),
+                e
+            )
+        })
+    }
+    pub fn self_hash_field_paths(&self) -> &std::collections::HashSet<Cow<'w, str>> {
+        &self.self_hash_field_path_s
+    }
+    pub fn self_hash_url_field_paths(&self) -> &std::collections::HashSet<Cow<'w, str>> {
+        &self.self_hash_url_field_path_s
+    }
+    pub fn message(&self) -> &DynamicMessage {
+        &self.message
+    }
+    /// Note that modifying the message may invalidate the self-hash [URL] slots.
+    pub fn message_mut(&mut self) -> &mut DynamicMessage {
+        &mut self.message
+    }
+    pub fn into_message(self) -> DynamicMessage {
+        self.message
+    }
+}
+
+impl SelfHashableT<mbx::MBHashStr> for SelfHashableProtobuf<'_, '_> {
+    fn write_digest_data(
+        &self,
+        hasher: &mut <<mbx::MBHashStr as HashRefT>::HashFunction as HashFunctionT<
+            mbx::MBHashStr,
+        >>::Hasher,
+    ) -> Result<()> {
+        let mut c = self.clone();
+        c.set_self_hash_slots_to(hasher.hash_function().placeholder_hash().as_ref())?;
+        HasherT::update(hasher, c.to_canonical_protobuf()?.as_slice());
+        Ok(())
+    }
+    fn self_hash_oi<'a, 'b: 'a>(
+        &'b self,
+    ) -> Result<Box<dyn std::iter::Iterator<Item = Option<&'b mbx::MBHashStr>> + 'a>> {
+        let mut self_hash_ov =
+            Vec::with_capacity(self.self_hash_field_vv.len() + self.self_hash_url_field_vv.len());
+        for self_hash_field_v in self.self_hash_field_vv.iter() {
+            let self_hash_o = match string_at(&self.message, self_hash_field_v) {
+                Some(self_hash_str) if !self_hash_str.is_empty() => {
+                    Some(mbx::MBHashStr::new_ref(self_hash_str).map_err(|e| {
+                        error!(
+                            "self-hash field {} value {:?} is not a valid self-hash; error was {}",
+                            self_hash_field_v.last().unwrap().full_name(),
+                            self_hash_str,
+                            e
+                        )
+                    })?)
+                }
+                _ => None,
+            };
+            self_hash_ov.push(self_hash_o);
+        }
+        for self_hash_url_field_v in self.self_hash_url_field_vv.iter() {
+            let self_hash_url_str = self.self_hash_url_str(self_hash_url_field_v)?;
+            self_hash_ov.push(SelfHashURLStr::new_ref(self_hash_url_str)?.mb_hash_o());
+        }
+        Ok(Box::new(self_hash_ov.into_iter()))
+    }
+    fn set_self_hash_slots_to(&mut self, hash: &mbx::MBHashStr) -> Result<()> {
+        for self_hash_field_v in self.self_hash_field_vv.iter() {
+            set_string_at(&mut self.message, self_hash_field_v, hash.as_str());
+        }
+        for self_hash_url_field_v in self.self_hash_url_field_vv.iter() {
+            let mut self_hash_url =
+                SelfHashURL::try_from(self.self_hash_url_str(self_hash_url_field_v)?)?;
+            self_hash_url.set_self_hash_slots_to_mb_hash(hash)?;
+            set_string_at(
+                &mut self.message,
+                self_hash_url_field_v,
+                self_hash_url.to_string().as_str(),
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,26 @@
+use crate::{
+    protobuf::to_canonical_protobuf_vec, HashFunctionT, HashRefT, HasherT, Result, SelfHashableT,
+};
+use prost_reflect::ReflectMessage;
+
+/// This is the canonical implementation of the SelfHashable::write_digest_data method for when the
+/// SelfHashable type implements Clone and is a protobuf message (i.e. implements
+/// prost_reflect::ReflectMessage, which can be derived along with prost::Message).  Simply call this
+/// method from your implementation of SelfHashable::write_digest_data.
+///
+/// The digest data is the canonical protobuf encoding of the message (see write_canonical_protobuf), so
+/// equivalent messages produce the same digest regardless of how they were originally encoded.  Note that
+/// the message must not have unknown fields.
+pub fn write_digest_data_using_protobuf<
+    HashRef: HashRefT + ?Sized + ToOwned,
+    S: Clone + SelfHashableT<HashRef> + ReflectMessage,
+>(
+    self_hashable: &S,
+    hasher: &mut <<HashRef as HashRefT>::HashFunction as HashFunctionT<HashRef>>::Hasher,
+) -> Result<()> {
+    let mut c = self_hashable.clone();
+    c.set_self_hash_slots_to(hasher.hash_function().placeholder_hash().as_ref())?;
+    let canonical_protobuf = to_canonical_protobuf_vec(&c.transcode_to_dynamic())?;
+    HasherT::update(hasher, canonical_protobuf.as_slice());
+    Ok(())
+}
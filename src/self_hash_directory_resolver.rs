@@ -0,0 +1,81 @@
+use crate::{ensure, error, Result, SelfHashResolver, SelfHashableT};
+use std::path::{Path, PathBuf};
+
+/// A SelfHashResolver which looks up documents stored as JSON in a directory, where the document having
+/// self-hash <mb-hash> is stored in the file "<mb-hash>.json".  The document type D determines the
+/// self-hash slots; e.g. for serde_json::Value, the self-hash slot is the top-level "selfHash" field.
+pub struct SelfHashDirectoryResolver<D = serde_json::Value> {
+    root_dir: PathBuf,
+    phantom: std::marker::PhantomData<fn() -> D>,
+}
+
+impl<D> SelfHashDirectoryResolver<D> {
+    pub fn new(root_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            root_dir: root_dir.into(),
+            phantom: std::marker::PhantomData,
+        }
+    }
+    pub fn root_dir(&self) -> &Path {
+        self.root_dir.as_path()
+    }
+    /// Returns the path of the file in which the document having the given self-hash is stored.
+    pub fn document_path(&self, mb_hash: &mbx::MBHashStr) -> Result<PathBuf> {
+        ensure!(
+            mb_hash
+                .as_str()
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'),
+            "self-hash {} can't be used as a filename (use a filesystem-safe base, such as base64url)",
+            mb_hash
+        );
+        Ok(self.root_dir.join(format!("{}.json", mb_hash)))
+    }
+}
+
+impl<D: serde::Serialize + SelfHashableT<mbx::MBHashStr>> SelfHashDirectoryResolver<D> {
+    /// Verifies the given document and stores it (as JCS), returning its self-hash.
+    pub fn store(&self, document: &D) -> Result<mbx::MBHash> {
+        let mb_hash = document.verify_self_hashes()?;
+        let document_path = self.document_path(mb_hash)?;
+        let jcs = serde_json_canonicalizer::to_string(document)?;
+        std::fs::write(&document_path, jcs).map_err(|e| {
+            error!(
+                "failed to write document to {:?}; error was {}",
+                document_path, e
+            )
+        })?;
+        Ok(mb_hash.to_owned())
+    }
+}
+
+impl<D> Clone for SelfHashDirectoryResolver<D> {
+    fn clone(&self) -> Self {
+        Self::new(self.root_dir.clone())
+    }
+}
+
+impl<D> std::fmt::Debug for SelfHashDirectoryResolver<D> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("SelfHashDirectoryResolver")
+            .field("root_dir", &self.root_dir)
+            .finish()
+    }
+}
+
+impl<D> SelfHashResolver for SelfHashDirectoryResolver<D>
+where
+    D: serde::de::DeserializeOwned + SelfHashableT<mbx::MBHashStr>,
+{
+    type Document = D;
+    fn resolve(&self, mb_hash: &mbx::MBHashStr) -> Result<Self::Document> {
+        let document_path = self.document_path(mb_hash)?;
+        let file = std::fs::File::open(&document_path).map_err(|e| {
+            error!(
+                "failed to open document for self-hash {} at {:?}; error was {}",
+                mb_hash, document_path, e
+            )
+        })?;
+        Ok(serde_json::from_reader(std::io::BufReader::new(file))?)
+    }
+}
@@ -0,0 +1,55 @@
+use crate::{error, Result, SelfHashResolver, SelfHashableT};
+use std::collections::HashMap;
+
+/// A SelfHashResolver which holds its documents in memory, keyed by their (verified) self-hashes.
+#[derive(Clone, Debug)]
+pub struct SelfHashInMemoryResolver<D> {
+    document_m: HashMap<mbx::MBHash, D>,
+}
+
+impl<D> SelfHashInMemoryResolver<D> {
+    pub fn new() -> Self {
+        Self {
+            document_m: HashMap::new(),
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.document_m.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.document_m.is_empty()
+    }
+    pub fn contains(&self, mb_hash: &mbx::MBHashStr) -> bool {
+        self.document_m.contains_key(mb_hash)
+    }
+    /// Removes and returns the document having the given self-hash, if present.
+    pub fn remove(&mut self, mb_hash: &mbx::MBHashStr) -> Option<D> {
+        self.document_m.remove(mb_hash)
+    }
+}
+
+impl<D: SelfHashableT<mbx::MBHashStr>> SelfHashInMemoryResolver<D> {
+    /// Verifies the given document and adds it, returning its self-hash.  Any existing document having
+    /// the same self-hash is replaced.
+    pub fn insert(&mut self, document: D) -> Result<mbx::MBHash> {
+        let mb_hash = document.verify_self_hashes()?.to_owned();
+        self.document_m.insert(mb_hash.clone(), document);
+        Ok(mb_hash)
+    }
+}
+
+impl<D> Default for SelfHashInMemoryResolver<D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<D: Clone + SelfHashableT<mbx::MBHashStr>> SelfHashResolver for SelfHashInMemoryResolver<D> {
+    type Document = D;
+    fn resolve(&self, mb_hash: &mbx::MBHashStr) -> Result<Self::Document> {
+        self.document_m
+            .get(mb_hash)
+            .cloned()
+            .ok_or_else(|| error!("no document having self-hash {} was found", mb_hash))
+    }
+}
@@ -0,0 +1,93 @@
+use crate::{ensure, error, Result, SelfHashURLStr, SelfHashableT};
+
+/// Resolves self-hashes to the documents having those self-hashes, e.g. for dereferencing the
+/// "vjson:///<mb-hash>" URLs by which self-hashed documents refer to one another.  Note that
+/// SelfHashResolver::resolve need not verify the document it returns; use
+/// SelfHashResolver::resolve_verified (or SelfHashResolver::resolve_url_verified) to obtain a
+/// document which is guaranteed to self-verify to exactly the requested self-hash.
+pub trait SelfHashResolver {
+    type Document: SelfHashableT<mbx::MBHashStr>;
+    /// Returns the document having the given self-hash, or an error if there is no such document.
+    fn resolve(&self, mb_hash: &mbx::MBHashStr) -> Result<Self::Document>;
+    /// Resolves the given self-hash and checks that the returned document self-verifies to exactly
+    /// that self-hash.
+    fn resolve_verified(&self, mb_hash: &mbx::MBHashStr) -> Result<Self::Document> {
+        let document = self.resolve(mb_hash)?;
+        verify_resolved_document(&document, mb_hash)?;
+        Ok(document)
+    }
+    /// Resolves the self-hash of the given self-hash URL and checks that the returned document
+    /// self-verifies to exactly that self-hash.  Note that any path, query, or fragment in the URL is
+    /// ignored; it's up to the caller to interpret those relative to the returned document.
+    fn resolve_url_verified(&self, self_hash_url: &SelfHashURLStr) -> Result<Self::Document> {
+        let mb_hash = self_hash_url.mb_hash_o().ok_or_else(|| {
+            error!(
+                "self-hash URL {} has an unset self-hash, and therefore can't be resolved",
+                self_hash_url
+            )
+        })?;
+        self.resolve_verified(mb_hash)
+    }
+}
+
+/// The async analog of SelfHashResolver, for resolvers which e.g. fetch documents over the network.
+/// Every SelfHashResolver whose documents can be sent between threads is also a SelfHashResolverAsync.
+#[cfg(feature = "async-resolver")]
+pub trait SelfHashResolverAsync {
+    type Document: SelfHashableT<mbx::MBHashStr> + Send;
+    /// Returns the document having the given self-hash, or an error if there is no such document.
+    fn resolve(
+        &self,
+        mb_hash: &mbx::MBHashStr,
+    ) -> impl std::future::Future<Output = Result<Self::Document>> + Send;
+    /// Resolves the given self-hash and checks that the returned document self-verifies to exactly
+    /// that self-hash.
+    fn resolve_verified(
+        &self,
+        mb_hash: &mbx::MBHashStr,
+    ) -> impl std::future::Future<Output = Result<Self::Document>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            let document = self.resolve(mb_hash).await?;
+            verify_resolved_document(&document, mb_hash)?;
+            Ok(document)
+        }
+    }
+}
+
+#[cfg(feature = "async-resolver")]
+impl<R> SelfHashResolverAsync for R
+where
+    R: SelfHashResolver + Sync,
+    R::Document: Send,
+{
+    type Document = R::Document;
+    fn resolve(
+        &self,
+        mb_hash: &mbx::MBHashStr,
+    ) -> impl std::future::Future<Output = Result<Self::Document>> + Send {
+        std::future::ready(SelfHashResolver::resolve(self, mb_hash))
+    }
+}
+
+/// Checks that the given resolved document self-verifies to exactly the requested self-hash.
+fn verify_resolved_document<D: SelfHashableT<mbx::MBHashStr> + ?Sized>(
+    document: &D,
+    mb_hash: &mbx::MBHashStr,
+) -> Result<()> {
+    let verified_mb_hash = document.verify_self_hashes().map_err(|e| {
+        error!(
+            "resolved document for {} failed to self-verify; {}",
+            mb_hash, e
+        )
+    })?;
+    ensure!(
+        verified_mb_hash == mb_hash,
+        "resolved document self-verified, but its self-hash ({}) does not match the requested self-hash ({})",
+        verified_mb_hash,
+        mb_hash
+    );
+    Ok(())
+}
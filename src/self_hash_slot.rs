@@ -0,0 +1,48 @@
+/// The newtype struct name by which SelfHashSlotSerializer recognizes a SelfHashSlot.  Other serializers
+/// treat newtype structs transparently, so this doesn't appear in any serialization.
+pub(crate) const SELF_HASH_SLOT_NAME: &str = "$selfhash::SelfHashSlot";
+
+/// Marks a field as a self-hash slot, so that its value can be replaced by the placeholder while the
+/// digest data is streamed (see SelfHashSlotSerializer and write_digest_data_streaming_postcard),
+/// without having to clone the data and set its self-hash slots to the placeholder.  The slot's value
+/// should be a hash (which serializes as a string or bytes), or an Option of one.  SelfHashSlot
+/// serializes and deserializes exactly as its value does.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct SelfHashSlot<T>(pub T);
+
+impl<T> SelfHashSlot<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> std::ops::Deref for SelfHashSlot<T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> std::ops::DerefMut for SelfHashSlot<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<T> From<T> for SelfHashSlot<T> {
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl<T: serde::Serialize> serde::Serialize for SelfHashSlot<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(SELF_HASH_SLOT_NAME, &self.0)
+    }
+}
+
+impl<'de, T: serde::Deserialize<'de>> serde::Deserialize<'de> for SelfHashSlot<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(Self)
+    }
+}
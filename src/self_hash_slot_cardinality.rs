@@ -0,0 +1,72 @@
+use crate::{bail, error, Error, ErrorKind, Result};
+
+/// Specifies how many self-hash [URL] slots a slot selector (e.g. a JSONPath query) is required to
+/// select.  This allows multi-match selectors (e.g. wildcards, descendant segments, or filters) to be used
+/// as self-hash slot selectors while still constraining the shape of the self-hashable data.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum SelfHashSlotCardinality {
+    /// The selector must select exactly the given number of slots.
+    Exactly(usize),
+    /// The selector must select at least one slot.
+    AtLeastOne,
+    /// The selector must select at most one slot.  This is the default for singular selectors, such as
+    /// singular JSONPath queries and JSON Pointers.
+    AtMostOne,
+    /// The selector may select any number of slots, including none.  This is the default for multi-match
+    /// selectors.
+    Any,
+}
+
+impl SelfHashSlotCardinality {
+    /// Returns true iff the given slot count satisfies this cardinality rule.
+    pub fn admits(&self, slot_count: usize) -> bool {
+        match self {
+            Self::Exactly(n) => slot_count == *n,
+            Self::AtLeastOne => slot_count >= 1,
+            Self::AtMostOne => slot_count <= 1,
+            Self::Any => true,
+        }
+    }
+    /// Returns an error (of kind ErrorKind::MissingSlot) if the given slot count doesn't satisfy this
+    /// cardinality rule.
+    pub fn ensure_admits(&self, slot_count: usize, selector: &str) -> Result<()> {
+        if !self.admits(slot_count) {
+            return Err(error!(
+                "self-hash slot selector {:?} selected {} slot(s), but its cardinality is {}",
+                selector, slot_count, self
+            )
+            .with_kind(ErrorKind::MissingSlot));
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for SelfHashSlotCardinality {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Exactly(n) => write!(f, "{}", n),
+            Self::AtLeastOne => f.write_str("at-least-one"),
+            Self::AtMostOne => f.write_str("at-most-one"),
+            Self::Any => f.write_str("any"),
+        }
+    }
+}
+
+impl std::str::FromStr for SelfHashSlotCardinality {
+    type Err = Error;
+    /// Parses "any", "at-least-one", "at-most-one", or a non-negative integer N (meaning exactly N).
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "any" => Ok(Self::Any),
+            "at-least-one" => Ok(Self::AtLeastOne),
+            "at-most-one" => Ok(Self::AtMostOne),
+            _ => {
+                if let Ok(n) = s.parse::<usize>() {
+                    Ok(Self::Exactly(n))
+                } else {
+                    bail!("unrecognized self-hash slot cardinality {:?}; expected \"any\", \"at-least-one\", \"at-most-one\", or a non-negative integer", s);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,505 @@
+use crate::self_hash_slot::SELF_HASH_SLOT_NAME;
+use serde::ser::{
+    Error, Impossible, Serialize, SerializeMap, SerializeSeq, SerializeStruct,
+    SerializeStructVariant, SerializeTuple, SerializeTupleStruct, SerializeTupleVariant,
+    Serializer,
+};
+
+/// Serializes the given value with the value of each SelfHashSlot within it replaced by the given
+/// placeholder (see SelfHashSlotSerializer).  This implements serde::Serialize, so it can be passed to
+/// any serde-based serialization function, e.g. `postcard::to_io(&WithSelfHashPlaceholder::new(&value,
+/// placeholder), writer)`.
+pub struct WithSelfHashPlaceholder<'a, T: ?Sized, P: ?Sized> {
+    value: &'a T,
+    placeholder: &'a P,
+}
+
+impl<'a, T: ?Sized, P: ?Sized> WithSelfHashPlaceholder<'a, T, P> {
+    pub fn new(value: &'a T, placeholder: &'a P) -> Self {
+        Self { value, placeholder }
+    }
+}
+
+impl<T: Serialize + ?Sized, P: Serialize + ?Sized> Serialize for WithSelfHashPlaceholder<'_, T, P> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.value
+            .serialize(SelfHashSlotSerializer::new(serializer, self.placeholder))
+    }
+}
+
+/// A serde Serializer which wraps another one, and which serializes the given placeholder in place of
+/// the value of each SelfHashSlot, passing everything else through unchanged.  This makes it possible to
+/// stream the digest data of a value directly into a hasher, without cloning the value to set its
+/// self-hash slots to the placeholder.  The placeholder is serialized exactly as the slot's value would
+/// be if it were set to the placeholder, i.e. as `Some(placeholder)` if the slot's value is an Option,
+/// and otherwise as the placeholder itself.
+///
+/// Note that the serialization order is determined by the Serialize impls, so the output is canonical
+/// only for formats and types whose serialization is deterministic (e.g. structs in postcard), or for
+/// formats such as JCS which canonicalize the order themselves.
+pub struct SelfHashSlotSerializer<'p, S, P: ?Sized> {
+    inner: S,
+    placeholder: &'p P,
+}
+
+impl<'p, S, P: ?Sized> SelfHashSlotSerializer<'p, S, P> {
+    pub fn new(inner: S, placeholder: &'p P) -> Self {
+        Self { inner, placeholder }
+    }
+}
+
+/// Wraps the compound serializers of the inner Serializer, so that SelfHashSlot-s are recognized within
+/// the elements, fields, and entries.
+pub struct SelfHashSlotCompound<'p, C, P: ?Sized> {
+    inner: C,
+    placeholder: &'p P,
+}
+
+impl<'p, S: Serializer, P: Serialize + ?Sized> Serializer for SelfHashSlotSerializer<'p, S, P> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+    type SerializeSeq = SelfHashSlotCompound<'p, S::SerializeSeq, P>;
+    type SerializeTuple = SelfHashSlotCompound<'p, S::SerializeTuple, P>;
+    type SerializeTupleStruct = SelfHashSlotCompound<'p, S::SerializeTupleStruct, P>;
+    type SerializeTupleVariant = SelfHashSlotCompound<'p, S::SerializeTupleVariant, P>;
+    type SerializeMap = SelfHashSlotCompound<'p, S::SerializeMap, P>;
+    type SerializeStruct = SelfHashSlotCompound<'p, S::SerializeStruct, P>;
+    type SerializeStructVariant = SelfHashSlotCompound<'p, S::SerializeStructVariant, P>;
+
+    fn is_human_readable(&self) -> bool {
+        self.inner.is_human_readable()
+    }
+    fn serialize_bool(self, v: bool) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_bool(v)
+    }
+    fn serialize_i8(self, v: i8) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_i8(v)
+    }
+    fn serialize_i16(self, v: i16) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_i16(v)
+    }
+    fn serialize_i32(self, v: i32) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_i32(v)
+    }
+    fn serialize_i64(self, v: i64) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_i64(v)
+    }
+    fn serialize_i128(self, v: i128) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_i128(v)
+    }
+    fn serialize_u8(self, v: u8) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_u8(v)
+    }
+    fn serialize_u16(self, v: u16) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_u16(v)
+    }
+    fn serialize_u32(self, v: u32) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_u32(v)
+    }
+    fn serialize_u64(self, v: u64) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_u64(v)
+    }
+    fn serialize_u128(self, v: u128) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_u128(v)
+    }
+    fn serialize_f32(self, v: f32) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_f32(v)
+    }
+    fn serialize_f64(self, v: f64) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_f64(v)
+    }
+    fn serialize_char(self, v: char) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_char(v)
+    }
+    fn serialize_str(self, v: &str) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_str(v)
+    }
+    fn serialize_bytes(self, v: &[u8]) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_bytes(v)
+    }
+    fn collect_str<T: std::fmt::Display + ?Sized>(self, value: &T) -> Result<S::Ok, S::Error> {
+        self.inner.collect_str(value)
+    }
+    fn serialize_none(self) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_none()
+    }
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<S::Ok, S::Error> {
+        self.inner
+            .serialize_some(&WithSelfHashPlaceholder::new(value, self.placeholder))
+    }
+    fn serialize_unit(self) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_unit()
+    }
+    fn serialize_unit_struct(self, name: &'static str) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_unit_struct(name)
+    }
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+    ) -> Result<S::Ok, S::Error> {
+        self.inner
+            .serialize_unit_variant(name, variant_index, variant)
+    }
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<S::Ok, S::Error> {
+        if name == SELF_HASH_SLOT_NAME {
+            value.serialize(PlaceholderSerializer {
+                inner: self.inner,
+                placeholder: self.placeholder,
+            })
+        } else {
+            self.inner.serialize_newtype_struct(
+                name,
+                &WithSelfHashPlaceholder::new(value, self.placeholder),
+            )
+        }
+    }
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_newtype_variant(
+            name,
+            variant_index,
+            variant,
+            &WithSelfHashPlaceholder::new(value, self.placeholder),
+        )
+    }
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, S::Error> {
+        Ok(SelfHashSlotCompound {
+            inner: self.inner.serialize_seq(len)?,
+            placeholder: self.placeholder,
+        })
+    }
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, S::Error> {
+        Ok(SelfHashSlotCompound {
+            inner: self.inner.serialize_tuple(len)?,
+            placeholder: self.placeholder,
+        })
+    }
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, S::Error> {
+        Ok(SelfHashSlotCompound {
+            inner: self.inner.serialize_tuple_struct(name, len)?,
+            placeholder: self.placeholder,
+        })
+    }
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, S::Error> {
+        Ok(SelfHashSlotCompound {
+            inner: self
+                .inner
+                .serialize_tuple_variant(name, variant_index, variant, len)?,
+            placeholder: self.placeholder,
+        })
+    }
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, S::Error> {
+        Ok(SelfHashSlotCompound {
+            inner: self.inner.serialize_map(len)?,
+            placeholder: self.placeholder,
+        })
+    }
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, S::Error> {
+        Ok(SelfHashSlotCompound {
+            inner: self.inner.serialize_struct(name, len)?,
+            placeholder: self.placeholder,
+        })
+    }
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, S::Error> {
+        Ok(SelfHashSlotCompound {
+            inner: self
+                .inner
+                .serialize_struct_variant(name, variant_index, variant, len)?,
+            placeholder: self.placeholder,
+        })
+    }
+}
+
+impl<C: SerializeSeq, P: Serialize + ?Sized> SerializeSeq for SelfHashSlotCompound<'_, C, P> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), C::Error> {
+        let value = WithSelfHashPlaceholder::new(value, self.placeholder);
+        self.inner.serialize_element(&value)
+    }
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C: SerializeTuple, P: Serialize + ?Sized> SerializeTuple for SelfHashSlotCompound<'_, C, P> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), C::Error> {
+        let value = WithSelfHashPlaceholder::new(value, self.placeholder);
+        self.inner.serialize_element(&value)
+    }
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C: SerializeTupleStruct, P: Serialize + ?Sized> SerializeTupleStruct
+    for SelfHashSlotCompound<'_, C, P>
+{
+    type Ok = C::Ok;
+    type Error = C::Error;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), C::Error> {
+        let value = WithSelfHashPlaceholder::new(value, self.placeholder);
+        self.inner.serialize_field(&value)
+    }
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C: SerializeTupleVariant, P: Serialize + ?Sized> SerializeTupleVariant
+    for SelfHashSlotCompound<'_, C, P>
+{
+    type Ok = C::Ok;
+    type Error = C::Error;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), C::Error> {
+        let value = WithSelfHashPlaceholder::new(value, self.placeholder);
+        self.inner.serialize_field(&value)
+    }
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C: SerializeMap, P: Serialize + ?Sized> SerializeMap for SelfHashSlotCompound<'_, C, P> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), C::Error> {
+        let key = WithSelfHashPlaceholder::new(key, self.placeholder);
+        self.inner.serialize_key(&key)
+    }
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), C::Error> {
+        let value = WithSelfHashPlaceholder::new(value, self.placeholder);
+        self.inner.serialize_value(&value)
+    }
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C: SerializeStruct, P: Serialize + ?Sized> SerializeStruct for SelfHashSlotCompound<'_, C, P> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), C::Error> {
+        let value = WithSelfHashPlaceholder::new(value, self.placeholder);
+        self.inner.serialize_field(key, &value)
+    }
+    fn skip_field(&mut self, key: &'static str) -> Result<(), C::Error> {
+        self.inner.skip_field(key)
+    }
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C: SerializeStructVariant, P: Serialize + ?Sized> SerializeStructVariant
+    for SelfHashSlotCompound<'_, C, P>
+{
+    type Ok = C::Ok;
+    type Error = C::Error;
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), C::Error> {
+        let value = WithSelfHashPlaceholder::new(value, self.placeholder);
+        self.inner.serialize_field(key, &value)
+    }
+    fn skip_field(&mut self, key: &'static str) -> Result<(), C::Error> {
+        self.inner.skip_field(key)
+    }
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+/// Serializes the placeholder in place of the value of a SelfHashSlot, in the shape of that value: an
+/// Option (whether None or Some) becomes `Some(placeholder)`, and a hash (i.e. a string or bytes, or a
+/// newtype struct thereof) becomes the placeholder.
+struct PlaceholderSerializer<'p, S, P: ?Sized> {
+    inner: S,
+    placeholder: &'p P,
+}
+
+impl<S: Serializer, P: Serialize + ?Sized> PlaceholderSerializer<'_, S, P> {
+    fn unsupported(what: &str) -> S::Error {
+        S::Error::custom(format!(
+            "the value of a SelfHashSlot must be a hash or an Option of a hash, but was {}",
+            what
+        ))
+    }
+}
+
+impl<S: Serializer, P: Serialize + ?Sized> Serializer for PlaceholderSerializer<'_, S, P> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+    type SerializeSeq = Impossible<S::Ok, S::Error>;
+    type SerializeTuple = Impossible<S::Ok, S::Error>;
+    type SerializeTupleStruct = Impossible<S::Ok, S::Error>;
+    type SerializeTupleVariant = Impossible<S::Ok, S::Error>;
+    type SerializeMap = Impossible<S::Ok, S::Error>;
+    type SerializeStruct = Impossible<S::Ok, S::Error>;
+    type SerializeStructVariant = Impossible<S::Ok, S::Error>;
+
+    fn is_human_readable(&self) -> bool {
+        self.inner.is_human_readable()
+    }
+    fn serialize_str(self, _v: &str) -> Result<S::Ok, S::Error> {
+        self.placeholder.serialize(self.inner)
+    }
+    fn serialize_bytes(self, _v: &[u8]) -> Result<S::Ok, S::Error> {
+        self.placeholder.serialize(self.inner)
+    }
+    fn collect_str<T: std::fmt::Display + ?Sized>(self, _value: &T) -> Result<S::Ok, S::Error> {
+        self.placeholder.serialize(self.inner)
+    }
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _value: &T,
+    ) -> Result<S::Ok, S::Error> {
+        self.placeholder.serialize(self.inner)
+    }
+    fn serialize_none(self) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_some(self.placeholder)
+    }
+    fn serialize_some<T: Serialize + ?Sized>(self, _value: &T) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_some(self.placeholder)
+    }
+    fn serialize_bool(self, _v: bool) -> Result<S::Ok, S::Error> {
+        Err(Self::unsupported("a bool"))
+    }
+    fn serialize_i8(self, _v: i8) -> Result<S::Ok, S::Error> {
+        Err(Self::unsupported("an integer"))
+    }
+    fn serialize_i16(self, _v: i16) -> Result<S::Ok, S::Error> {
+        Err(Self::unsupported("an integer"))
+    }
+    fn serialize_i32(self, _v: i32) -> Result<S::Ok, S::Error> {
+        Err(Self::unsupported("an integer"))
+    }
+    fn serialize_i64(self, _v: i64) -> Result<S::Ok, S::Error> {
+        Err(Self::unsupported("an integer"))
+    }
+    fn serialize_u8(self, _v: u8) -> Result<S::Ok, S::Error> {
+        Err(Self::unsupported("an integer"))
+    }
+    fn serialize_u16(self, _v: u16) -> Result<S::Ok, S::Error> {
+        Err(Self::unsupported("an integer"))
+    }
+    fn serialize_u32(self, _v: u32) -> Result<S::Ok, S::Error> {
+        Err(Self::unsupported("an integer"))
+    }
+    fn serialize_u64(self, _v: u64) -> Result<S::Ok, S::Error> {
+        Err(Self::unsupported("an integer"))
+    }
+    fn serialize_f32(self, _v: f32) -> Result<S::Ok, S::Error> {
+        Err(Self::unsupported("a float"))
+    }
+    fn serialize_f64(self, _v: f64) -> Result<S::Ok, S::Error> {
+        Err(Self::unsupported("a float"))
+    }
+    fn serialize_char(self, _v: char) -> Result<S::Ok, S::Error> {
+        Err(Self::unsupported("a char"))
+    }
+    fn serialize_unit(self) -> Result<S::Ok, S::Error> {
+        Err(Self::unsupported("a unit"))
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<S::Ok, S::Error> {
+        Err(Self::unsupported("a unit struct"))
+    }
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<S::Ok, S::Error> {
+        Err(Self::unsupported("an enum"))
+    }
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<S::Ok, S::Error> {
+        Err(Self::unsupported("an enum"))
+    }
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, S::Error> {
+        Err(Self::unsupported("a sequence"))
+    }
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, S::Error> {
+        Err(Self::unsupported("a tuple"))
+    }
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, S::Error> {
+        Err(Self::unsupported("a tuple struct"))
+    }
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, S::Error> {
+        Err(Self::unsupported("an enum"))
+    }
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, S::Error> {
+        Err(Self::unsupported("a map"))
+    }
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, S::Error> {
+        Err(Self::unsupported("a struct"))
+    }
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, S::Error> {
+        Err(Self::unsupported("an enum"))
+    }
+}
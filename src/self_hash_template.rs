@@ -0,0 +1,84 @@
+use crate::{ensure, error, Error, Result};
+
+/// The placeholder which marks where the self-hash goes within a SelfHashTemplate.
+const SELF_HASH_PLACEHOLDER: &str = "{selfHash}";
+
+/// Defines the form of a string-valued self-hash slot in which the self-hash is embedded within
+/// surrounding text, e.g. `did:example:{selfHash}#key-1` or `https://example.com/docs/{selfHash}?v=2`.
+/// The template must contain exactly one `{selfHash}` placeholder; all other characters are literal.
+///
+/// A string matches the template if it consists of the template's prefix (the part before the
+/// placeholder), followed by the self-hash, followed by the template's suffix (the part after the
+/// placeholder).  A string in which the self-hash is empty (e.g. `did:example:#key-1`) is considered
+/// to have an unset self-hash slot.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct SelfHashTemplate {
+    template: String,
+    /// The byte index of the placeholder within the template.
+    placeholder_index: usize,
+}
+
+impl SelfHashTemplate {
+    pub fn parse(template: &str) -> Result<Self> {
+        let placeholder_index = template.find(SELF_HASH_PLACEHOLDER).ok_or_else(|| {
+            error!(
+                "self-hash template must contain the placeholder {:?}; template was {:?}",
+                SELF_HASH_PLACEHOLDER, template
+            )
+        })?;
+        ensure!(
+            !template[placeholder_index + SELF_HASH_PLACEHOLDER.len()..]
+                .contains(SELF_HASH_PLACEHOLDER),
+            "self-hash template must contain exactly one placeholder {:?}; template was {:?}",
+            SELF_HASH_PLACEHOLDER,
+            template
+        );
+        Ok(Self {
+            template: template.to_string(),
+            placeholder_index,
+        })
+    }
+    /// Returns the template string as it was given to SelfHashTemplate::parse.
+    pub fn as_str(&self) -> &str {
+        self.template.as_str()
+    }
+    /// Returns the part of the template before the placeholder.
+    pub fn prefix(&self) -> &str {
+        &self.template[..self.placeholder_index]
+    }
+    /// Returns the part of the template after the placeholder.
+    pub fn suffix(&self) -> &str {
+        &self.template[self.placeholder_index + SELF_HASH_PLACEHOLDER.len()..]
+    }
+    /// Returns the self-hash portion of the given string if it matches this template, or None if
+    /// it doesn't match.  Note that the returned self-hash portion is not validated, and will be empty
+    /// if the self-hash slot is unset.
+    pub fn self_hash_str_o<'a>(&self, s: &'a str) -> Option<&'a str> {
+        s.strip_prefix(self.prefix())?.strip_suffix(self.suffix())
+    }
+    /// Returns true iff the given string matches this template.
+    pub fn matches(&self, s: &str) -> bool {
+        self.self_hash_str_o(s).is_some()
+    }
+    /// Produces the string defined by this template with the given self-hash substituted in.
+    pub fn render(&self, self_hash: &str) -> String {
+        let mut s = String::with_capacity(self.template.len() + self_hash.len());
+        s.push_str(self.prefix());
+        s.push_str(self_hash);
+        s.push_str(self.suffix());
+        s
+    }
+}
+
+impl std::fmt::Display for SelfHashTemplate {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.template.as_str())
+    }
+}
+
+impl std::str::FromStr for SelfHashTemplate {
+    type Err = Error;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
@@ -1,8 +1,12 @@
-use crate::{HashT, SelfHashURLStr};
+use crate::{ensure, HashT, Result, SelfHashURLStr, TryHashT};
 use pneutype::Validate;
 
-/// EXPERIMENTAL: Represents a URL that has the form "vjson:///<mb-hash>"
-// TODO: Maybe make this a general URL in which there's a "selfHash=<X>" query parameter.
+/// EXPERIMENTAL: Represents a URL that contains a self-hash, either having the form
+/// "vjson:///<mb-hash>[<path>][?<query>][#<fragment>]" (e.g. "vjson:///<mb-hash>/credentialSubject#name";
+/// see SelfHashURLBuilder), or being an absolute URL having exactly one `selfHash` query parameter, e.g.
+/// "https://api.example.com/records?selfHash=<mb-hash>&v=1".  Setting the self-hash only replaces the
+/// self-hash portion of the URL, preserving the other query parameters, the fragment, and any
+/// percent-encoding.  The self-hash must use a URL-safe base (see SelfHashURLStr).
 #[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, pneutype::PneuString)]
 #[pneu_string(as_pneu_str = "as_self_hash_url_str", borrow = "SelfHashURLStr")]
 #[cfg_attr(feature = "serde", derive(serde::Serialize))]
@@ -10,20 +14,94 @@ use pneutype::Validate;
 pub struct SelfHashURL(String);
 
 impl SelfHashURL {
-    pub fn new(mb_hash: &mbx::MBHashStr) -> Self {
+    /// Returns a builder for a "vjson:///" self-hash URL with the given (optional) components.
+    pub fn builder() -> SelfHashURLBuilder {
+        SelfHashURLBuilder::default()
+    }
+    /// Produces the URL "vjson:///<mb-hash>".  This fails if the MBHash's base isn't URL-safe (see
+    /// SelfHashURLStr).
+    pub fn new(mb_hash: &mbx::MBHashStr) -> Result<Self> {
         let mut s = String::with_capacity("vjson:///".len() + mb_hash.len());
         s.push_str("vjson:///");
         s.push_str(mb_hash.as_str());
-        Self::try_from(s).unwrap()
+        Self::try_from(s)
     }
-    pub fn set_self_hash_slots_to_mb_hash(&mut self, mb_hash: &mbx::MBHashStr) {
-        self.0 = format!("vjson:///{}", mb_hash);
-        assert!(SelfHashURLStr::validate(&self.0).is_ok());
+    /// Replaces the self-hash portion of this URL with the given MBHash, leaving this URL unchanged if
+    /// that fails, e.g. because the MBHash's base isn't URL-safe (see SelfHashURLStr).
+    pub fn set_self_hash_slots_to_mb_hash(&mut self, mb_hash: &mbx::MBHashStr) -> Result<()> {
+        let mut s = self.0.clone();
+        s.replace_range(self.self_hash_range(), mb_hash.as_str());
+        SelfHashURLStr::validate(&s)?;
+        self.0 = s;
+        Ok(())
     }
 }
 
+/// Note that this panics if the self-hash portion of this URL isn't a valid mbx::MBHashStr.  Use
+/// TryHashT::try_as_hash_ref for untrusted input.
 impl HashT<mbx::MBHashStr> for SelfHashURL {
     fn as_hash_ref(&self) -> &mbx::MBHashStr {
-        self.mb_hash_o().unwrap()
+        self.as_self_hash_url_str().as_hash_ref()
+    }
+}
+
+impl TryHashT<mbx::MBHashStr> for SelfHashURL {
+    fn try_as_hash_ref(&self) -> Result<&mbx::MBHashStr> {
+        self.as_self_hash_url_str().try_as_hash_ref()
+    }
+}
+
+/// Builds a self-hash URL of the form "vjson:///<mb-hash>[<path>][?<query>][#<fragment>]".  The self-hash
+/// defaults to being unset (i.e. empty), and the components are validated by SelfHashURLBuilder::build.
+#[derive(Clone, Debug, Default)]
+pub struct SelfHashURLBuilder {
+    mb_hash_o: Option<mbx::MBHash>,
+    path_o: Option<String>,
+    query_o: Option<String>,
+    fragment_o: Option<String>,
+}
+
+impl SelfHashURLBuilder {
+    pub fn with_mb_hash(mut self, mb_hash: &mbx::MBHashStr) -> Self {
+        self.mb_hash_o = Some(mb_hash.to_owned());
+        self
+    }
+    /// The path must be empty or begin with `/`, e.g. "/credentialSubject".
+    pub fn with_path(mut self, path: impl Into<String>) -> Self {
+        self.path_o = Some(path.into());
+        self
+    }
+    /// The query should not include the leading `?`.
+    pub fn with_query(mut self, query: impl Into<String>) -> Self {
+        self.query_o = Some(query.into());
+        self
+    }
+    /// The fragment should not include the leading `#`.
+    pub fn with_fragment(mut self, fragment: impl Into<String>) -> Self {
+        self.fragment_o = Some(fragment.into());
+        self
+    }
+    pub fn build(self) -> Result<SelfHashURL> {
+        let mut s = String::from("vjson:///");
+        if let Some(mb_hash) = self.mb_hash_o.as_ref() {
+            s.push_str(mb_hash.as_str());
+        }
+        if let Some(path) = self.path_o.as_deref() {
+            ensure!(
+                path.is_empty() || path.starts_with('/'),
+                "self-hash URL path must be empty or begin with `/`; path was {:?}",
+                path
+            );
+            s.push_str(path);
+        }
+        if let Some(query) = self.query_o.as_deref() {
+            s.push('?');
+            s.push_str(query);
+        }
+        if let Some(fragment) = self.fragment_o.as_deref() {
+            s.push('#');
+            s.push_str(fragment);
+        }
+        SelfHashURL::try_from(s)
     }
 }
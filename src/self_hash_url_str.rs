@@ -1,6 +1,8 @@
-use crate::{ensure, Error, HashT};
+use crate::{bail, ensure, error, Error, HashT, Result, SelfHashURL, TryHashT};
 
-/// This is the str-based analog to SelfHashURL.
+/// This is the str-based analog to SelfHashURL.  Its self-hash, if set, must use a multibase base whose
+/// alphabet is URL-safe (e.g. base64url or base58btc), so that the self-hash is delimited by the `/` of the
+/// path in a "vjson:///" URL and needs no percent-encoding in a `selfHash` query parameter.
 #[derive(Debug, Eq, Hash, Ord, PartialEq, PartialOrd, pneutype::PneuStr)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize))]
 #[cfg_attr(feature = "serde", pneu_str(deserialize))]
@@ -8,24 +10,133 @@ use crate::{ensure, Error, HashT};
 pub struct SelfHashURLStr(str);
 
 impl SelfHashURLStr {
+    /// Returns the self-hash portion of this URL, i.e. what follows "vjson:///" (up to the path, query,
+    /// or fragment) or the value of the `selfHash` query parameter.  This will be empty if the self-hash
+    /// slot of this URL is unset, and otherwise is a valid mbx::MBHashStr.
+    pub fn self_hash_str(&self) -> &str {
+        &self.0[self.components().self_hash]
+    }
+    /// Returns the byte range of the self-hash portion of this URL (see SelfHashURLStr::self_hash_str).
+    pub fn self_hash_range(&self) -> std::ops::Range<usize> {
+        self.components().self_hash
+    }
     pub fn mb_hash_o(&self) -> Option<&mbx::MBHashStr> {
-        let stripped = self.0.strip_prefix("vjson:///").unwrap();
-        if let Ok(mb_hash) = mbx::MBHashStr::new_ref(stripped) {
-            Some(mb_hash)
-        } else {
-            // If what follows "vjson:///" doesn't parse as a valid mbx::MBHashStr, then consider it "None".
+        if self.is_unset() {
             None
+        } else {
+            Some(
+                mbx::MBHashStr::new_ref(self.self_hash_str())
+                    .expect("programmer error: SelfHashURLStr was already validated"),
+            )
         }
     }
+    /// Returns true iff the self-hash portion of this URL is empty (e.g. "vjson:///" or
+    /// "https://example.com/records?selfHash=&v=1"), meaning that the self-hash slot of this URL has
+    /// not been set.
+    pub fn is_unset(&self) -> bool {
+        self.self_hash_range().is_empty()
+    }
+    /// Returns true iff this URL has the form "vjson:///<mb-hash>[<path>][?<query>][#<fragment>]" (as
+    /// opposed to being an absolute URL having a `selfHash` query parameter).
+    pub fn is_vjson(&self) -> bool {
+        self.0.starts_with(VJSON_PREFIX)
+    }
+    /// Returns the path following the self-hash in a "vjson:///" URL (e.g. "/credentialSubject" in
+    /// "vjson:///<mb-hash>/credentialSubject#name"), if present.  This is always None for a URL having a
+    /// `selfHash` query parameter, since its self-hash isn't part of its path.
+    pub fn path_o(&self) -> Option<&str> {
+        self.components().path_o.map(|path| &self.0[path])
+    }
+    /// Returns the query (without the leading `?`), if present.
+    pub fn query_o(&self) -> Option<&str> {
+        self.components().query_o.map(|query| &self.0[query])
+    }
+    /// Returns the fragment (without the leading `#`), if present.
+    pub fn fragment_o(&self) -> Option<&str> {
+        self.components()
+            .fragment_o
+            .map(|fragment| &self.0[fragment])
+    }
+    /// Resolves the given URI reference (e.g. "#name", "?v=2", or "credentialSubject") against this
+    /// self-hash URL as the base URL, according to RFC 3986 section 5.2, and returns the result, which
+    /// must itself be a valid self-hash URL.  Note that as with any hierarchical URL, a relative path is
+    /// resolved against the base path up to its last `/`, so e.g. "credentialSubject" resolved against
+    /// "vjson:///<mb-hash>/" produces "vjson:///<mb-hash>/credentialSubject", but resolved against
+    /// "vjson:///<mb-hash>" it would replace the self-hash, which is an error.
+    ///
+    /// See <https://www.rfc-editor.org/rfc/rfc3986#section-5.2>.
+    pub fn resolve(&self, reference: &str) -> Result<SelfHashURL> {
+        let base = URIReference::split(&self.0);
+        let reference = URIReference::split(reference);
+        let target = if reference.scheme_o.is_some() {
+            URIReference {
+                path: remove_dot_segments(&reference.path),
+                ..reference
+            }
+        } else if reference.authority_o.is_some() {
+            URIReference {
+                scheme_o: base.scheme_o,
+                path: remove_dot_segments(&reference.path),
+                ..reference
+            }
+        } else if reference.path.is_empty() {
+            URIReference {
+                scheme_o: base.scheme_o,
+                authority_o: base.authority_o,
+                path: base.path,
+                query_o: reference.query_o.or(base.query_o),
+                fragment_o: reference.fragment_o,
+            }
+        } else {
+            let path = if reference.path.starts_with('/') {
+                remove_dot_segments(&reference.path)
+            } else {
+                // Merge the reference path with the base path (see RFC 3986 section 5.2.3).
+                let merged_path = if base.authority_o.is_some() && base.path.is_empty() {
+                    format!("/{}", reference.path)
+                } else {
+                    let base_dir_end = base.path.rfind('/').map(|i| i + 1).unwrap_or(0);
+                    format!("{}{}", &base.path[..base_dir_end], reference.path)
+                };
+                remove_dot_segments(&merged_path)
+            };
+            URIReference {
+                scheme_o: base.scheme_o,
+                authority_o: base.authority_o,
+                path,
+                query_o: reference.query_o,
+                fragment_o: reference.fragment_o,
+            }
+        };
+        SelfHashURL::try_from(target.to_string()).map_err(|e| {
+            error!(
+                "resolving URI reference against self-hash URL {:?} did not produce a valid self-hash URL; error was {}",
+                &self.0, e
+            )
+        })
+    }
+    fn components(&self) -> SelfHashURLComponents {
+        parse_components(&self.0).expect("programmer error: SelfHashURLStr was already validated")
+    }
 }
 
+/// Note that this panics if the self-hash slot of this URL is unset.  Use TryHashT::try_as_hash_ref
+/// for untrusted input.
 impl HashT<mbx::MBHashStr> for SelfHashURLStr {
     fn as_hash_ref(&self) -> &mbx::MBHashStr {
-        if let Some(mb_hash) = self.mb_hash_o() {
-            mb_hash
-        } else {
-            panic!("programmer error: need to handle this case");
-        }
+        self.try_as_hash_ref()
+            .expect("programmer error: self-hash URL does not contain a valid MBHash")
+    }
+}
+
+impl TryHashT<mbx::MBHashStr> for SelfHashURLStr {
+    fn try_as_hash_ref(&self) -> Result<&mbx::MBHashStr> {
+        mbx::MBHashStr::new_ref(self.self_hash_str()).map_err(|e| {
+            error!(
+                "self-hash URL {:?} does not contain a valid MBHash; error was {}",
+                &self.0, e
+            )
+        })
     }
 }
 
@@ -33,10 +144,287 @@ impl pneutype::Validate for SelfHashURLStr {
     type Data = str;
     type Error = Error;
     fn validate(s: &Self::Data) -> std::result::Result<(), Self::Error> {
+        parse_components(s)?;
+        Ok(())
+    }
+}
+
+const VJSON_PREFIX: &str = "vjson:///";
+
+/// The byte ranges of the components of a self-hash URL.
+struct SelfHashURLComponents {
+    self_hash: std::ops::Range<usize>,
+    path_o: Option<std::ops::Range<usize>>,
+    query_o: Option<std::ops::Range<usize>>,
+    fragment_o: Option<std::ops::Range<usize>>,
+}
+
+/// Parses the given self-hash URL, which must either have the form
+/// "vjson:///<mb-hash>[<path>][?<query>][#<fragment>]", or be an absolute URL having exactly one
+/// `selfHash` query parameter, e.g. "https://api.example.com/records?selfHash=<mb-hash>&v=1".  The
+/// self-hash portion must either be empty, meaning that the self-hash slot is unset, or be a valid
+/// mbx::MBHashStr.
+fn parse_components(s: &str) -> Result<SelfHashURLComponents> {
+    // Determine the fragment, which begins at the first `#`, and the query, which is everything between
+    // the first `?` before that and the fragment.
+    let (before_fragment_end, fragment_o) = match s.find('#') {
+        Some(i) => (i, Some(i + 1..s.len())),
+        None => (s.len(), None),
+    };
+    let (hier_part_end, query_o) = match s[..before_fragment_end].find('?') {
+        Some(i) => (i, Some(i + 1..before_fragment_end)),
+        None => (before_fragment_end, None),
+    };
+
+    let (self_hash, path_o) = if s.starts_with(VJSON_PREFIX) {
+        // The self-hash is everything after the prefix up to the path, query, or fragment, and the
+        // rest must conform to the RFC 3986 grammar.
+        let self_hash_start = VJSON_PREFIX.len();
+        let self_hash_end = s[self_hash_start..hier_part_end]
+            .find('/')
+            .map(|i| self_hash_start + i)
+            .unwrap_or(hier_part_end);
+        let path_o = if self_hash_end < hier_part_end {
+            validate_chars(s, &s[self_hash_end..hier_part_end], "path", |c| {
+                is_pchar(c) || c == '/'
+            })?;
+            Some(self_hash_end..hier_part_end)
+        } else {
+            None
+        };
+        if let Some(query) = query_o.as_ref() {
+            validate_chars(s, &s[query.clone()], "query", |c| {
+                is_pchar(c) || c == '/' || c == '?'
+            })?;
+        }
+        if let Some(fragment) = fragment_o.as_ref() {
+            validate_chars(s, &s[fragment.clone()], "fragment", |c| {
+                is_pchar(c) || c == '/' || c == '?'
+            })?;
+        }
+        (self_hash_start..self_hash_end, path_o)
+    } else {
+        // Otherwise it must be an absolute URL, which begins with a scheme (see RFC 3986 section 3.1).
+        let (scheme, _) = s.split_once(':').ok_or_else(|| {
+            error!(
+                "self-hash URL must start with \"vjson:///\" or be an absolute URL; URL was {:?}",
+                s
+            )
+        })?;
+        ensure!(
+            scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.'),
+            "self-hash URL has an invalid scheme; URL was {:?}",
+            s
+        );
         ensure!(
-            s.starts_with("vjson:///"),
-            "self-hash URL must start with \"vjson:///\""
+            !s.chars().any(|c| c.is_whitespace() || c.is_control()),
+            "self-hash URL may not contain whitespace or control characters; URL was {:?}",
+            s
         );
+        validate_percent_encoding(s, s)?;
+        let query = query_o.clone().ok_or_else(|| {
+            error!(
+                "self-hash URL must have a `selfHash` query parameter; URL was {:?}",
+                s
+            )
+        })?;
+        let mut self_hash_o = None;
+        let mut param_start = query.start;
+        for param in s[query].split('&') {
+            if let Some(value) = param.strip_prefix("selfHash=") {
+                if self_hash_o.is_some() {
+                    bail!(
+                        "self-hash URL must have exactly one `selfHash` query parameter; URL was {:?}",
+                        s
+                    );
+                }
+                let value_start = param_start + "selfHash=".len();
+                self_hash_o = Some(value_start..value_start + value.len());
+            }
+            param_start += param.len() + 1;
+        }
+        let self_hash = self_hash_o.ok_or_else(|| {
+            error!(
+                "self-hash URL must have a `selfHash` query parameter; URL was {:?}",
+                s
+            )
+        })?;
+        (self_hash, None)
+    };
+    if !self_hash.is_empty() {
+        let mb_hash = mbx::MBHashStr::new_ref(&s[self_hash.clone()]).map_err(|e| {
+            error!(
+                "self-hash URL must have an empty or valid self-hash; URL was {:?}; error was {}",
+                s, e
+            )
+        })?;
+        ensure!(
+            is_url_safe_base(mb_hash.base()),
+            "self-hash URL's self-hash must use a URL-safe base (e.g. base64url or base58btc, but not base64 or a padded base), but it uses {:?}; URL was {:?}",
+            mb_hash.base(),
+            s
+        );
+    }
+    Ok(SelfHashURLComponents {
+        self_hash,
+        path_o,
+        query_o,
+        fragment_o,
+    })
+}
+
+/// Returns true iff the alphabet of the given multibase base consists only of ALPHA / DIGIT / "." / "-" /
+/// "_", which are allowed unescaped in a URL path, a query parameter value, and a DID method-specific-id
+/// alike.  This is the case for e.g. base64url, base58btc, base32, and base16, but not base64 (which uses
+/// `+` and `/`, so its self-hashes can't be delimited by `/` or survive query parameter decoding), the
+/// padded bases (which use `=`), or the identity base (which is raw bytes).  This is decided by base rather
+/// than by the characters of a particular self-hash, so that whether a self-hash can be used doesn't
+/// depend on its value.
+pub(crate) fn is_url_safe_base(base: mbx::Base) -> bool {
+    !matches!(
+        base,
+        mbx::Base::Identity
+            | mbx::Base::Base32PadLower
+            | mbx::Base::Base32PadUpper
+            | mbx::Base::Base32HexPadLower
+            | mbx::Base::Base32HexPadUpper
+            | mbx::Base::Base64
+            | mbx::Base::Base64Pad
+            | mbx::Base::Base64UrlPad
+    )
+}
+
+/// Returns true iff the given char is an RFC 3986 `pchar` other than the `%` of a percent-encoding
+/// (i.e. an unreserved char, a sub-delim, `:`, or `@`).
+fn is_pchar(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "-._~!$&'()*+,;=:@".contains(c)
+}
+
+fn validate_chars(
+    s: &str,
+    component: &str,
+    component_name: &str,
+    is_allowed: fn(char) -> bool,
+) -> Result<()> {
+    ensure!(
+        component.chars().all(|c| c == '%' || is_allowed(c)),
+        "self-hash URL {} contains an invalid character; URL was {:?}",
+        component_name,
+        s
+    );
+    validate_percent_encoding(s, component)
+}
+
+fn validate_percent_encoding(s: &str, component: &str) -> Result<()> {
+    for (i, b) in component.bytes().enumerate() {
+        if b == b'%' {
+            ensure!(
+                component.len() >= i + 3
+                    && component.as_bytes()[i + 1..i + 3]
+                        .iter()
+                        .all(u8::is_ascii_hexdigit),
+                "self-hash URL contains a malformed percent-encoding; URL was {:?}",
+                s
+            );
+        }
+    }
+    Ok(())
+}
+
+/// The components of a URI reference, as split by the regular expression in RFC 3986 appendix B.
+struct URIReference<'a> {
+    scheme_o: Option<&'a str>,
+    authority_o: Option<&'a str>,
+    path: String,
+    query_o: Option<&'a str>,
+    fragment_o: Option<&'a str>,
+}
+
+impl<'a> URIReference<'a> {
+    fn split(s: &'a str) -> Self {
+        let (s, fragment_o) = match s.split_once('#') {
+            Some((s, fragment)) => (s, Some(fragment)),
+            None => (s, None),
+        };
+        let (s, query_o) = match s.split_once('?') {
+            Some((s, query)) => (s, Some(query)),
+            None => (s, None),
+        };
+        let (scheme_o, s) = match s.find(|c| c == ':' || c == '/') {
+            Some(i) if i > 0 && s.as_bytes()[i] == b':' => (Some(&s[..i]), &s[i + 1..]),
+            _ => (None, s),
+        };
+        let (authority_o, path) = match s.strip_prefix("//") {
+            Some(s) => {
+                let authority_end = s.find('/').unwrap_or(s.len());
+                (Some(&s[..authority_end]), &s[authority_end..])
+            }
+            None => (None, s),
+        };
+        Self {
+            scheme_o,
+            authority_o,
+            path: path.to_string(),
+            query_o,
+            fragment_o,
+        }
+    }
+}
+
+impl std::fmt::Display for URIReference<'_> {
+    /// Recomposes the components (see RFC 3986 section 5.3).
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if let Some(scheme) = self.scheme_o {
+            write!(f, "{}:", scheme)?;
+        }
+        if let Some(authority) = self.authority_o {
+            write!(f, "//{}", authority)?;
+        }
+        f.write_str(&self.path)?;
+        if let Some(query) = self.query_o {
+            write!(f, "?{}", query)?;
+        }
+        if let Some(fragment) = self.fragment_o {
+            write!(f, "#{}", fragment)?;
+        }
         Ok(())
     }
 }
+
+/// See RFC 3986 section 5.2.4.
+fn remove_dot_segments(path: &str) -> String {
+    let mut input = path;
+    let mut output = String::with_capacity(path.len());
+    while !input.is_empty() {
+        if let Some(rest) = input
+            .strip_prefix("../")
+            .or_else(|| input.strip_prefix("./"))
+        {
+            input = rest;
+        } else if input.starts_with("/./") {
+            input = &input[2..];
+        } else if input == "/." {
+            input = "/";
+        } else if input.starts_with("/../") || input == "/.." {
+            input = if input == "/.." { "/" } else { &input[3..] };
+            output.truncate(output.rfind('/').unwrap_or(0));
+        } else if input == "." || input == ".." {
+            input = "";
+        } else {
+            // Move the first path segment (including its initial `/`, if any) to the output.
+            // Skip the first char (rather than byte), since the segment may begin with a multi-byte char.
+            let segment_end = input
+                .char_indices()
+                .skip(1)
+                .find(|&(_, c)| c == '/')
+                .map(|(i, _)| i)
+                .unwrap_or(input.len());
+            output.push_str(&input[..segment_end]);
+            input = &input[segment_end..];
+        }
+    }
+    output
+}
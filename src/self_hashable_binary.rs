@@ -0,0 +1,351 @@
+use crate::{ensure, error, ErrorKind, HashBytes, HashDynT, HasherDynT, NamedHashFunction, Result};
+use std::{
+    borrow::Cow,
+    io::{Read, Seek, SeekFrom, Write},
+};
+
+/// The size of the buffer used to stream data into the hasher.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Appends the unsigned varint encoding of n (see https://github.com/multiformats/unsigned-varint).
+fn push_uvarint(mut n: u64, byte_v: &mut Vec<u8>) {
+    while n >= 0x80 {
+        byte_v.push((n as u8 & 0x7f) | 0x80);
+        n >>= 7;
+    }
+    byte_v.push(n as u8);
+}
+
+/// Specifies how the self-hash is stored in each self-hash slot of a binary image.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum BinarySlotEncoding {
+    /// The slot holds the raw digest bytes, so the hash function is determined by the SelfHashableBinary
+    /// rather than by the image.
+    #[default]
+    RawDigest,
+    /// The slot holds a multihash (see https://github.com/multiformats/multihash), i.e. the varint-encoded
+    /// multicodec code of the hash function and digest length, followed by the digest bytes.
+    Multihash,
+}
+
+/// Specifies what each self-hash slot is replaced with when computing the digest of a binary image.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum BinarySlotFill {
+    /// The slot is replaced with zero bytes.
+    #[default]
+    Zeros,
+    /// The slot is replaced with the placeholder, i.e. the slot encoding of the all-zero digest.  This
+    /// only differs from BinarySlotFill::Zeros for BinarySlotEncoding::Multihash, in which case the
+    /// multihash header is kept.
+    Placeholder,
+}
+
+/// A self-hash slot within a binary image, given by its byte offset and length.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct BinarySlot {
+    offset: u64,
+    length: u64,
+}
+
+impl BinarySlot {
+    pub fn new(offset: u64, length: u64) -> Self {
+        Self { offset, length }
+    }
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+    pub fn length(&self) -> u64 {
+        self.length
+    }
+    /// Returns the offset of the first byte after this slot, saturating at u64::MAX if it would overflow
+    /// (such slots are rejected by SelfHashableBinary::new).
+    pub fn end(&self) -> u64 {
+        self.offset.saturating_add(self.length)
+    }
+}
+
+impl std::fmt::Display for BinarySlot {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.offset.checked_add(self.length) {
+            Some(end) => write!(f, "[{}..{})", self.offset, end),
+            None => write!(f, "[{}..{}+{})", self.offset, self.offset, self.length),
+        }
+    }
+}
+
+/// This provides self-hashing of binary images (e.g. firmware images which reserve a header region for
+/// their own hash), in which the self-hash slots are fixed (offset, length) ranges, each of which holds the
+/// self-hash as raw digest bytes or as a multihash (see BinarySlotEncoding).  The digest is computed over
+/// the bytes of the image, with each slot replaced by zeros or by the placeholder (see BinarySlotFill).  A
+/// slot which holds its fill value (or all zeros) is unset.
+///
+/// Unlike the other self-hashable types, this doesn't hold the data.  Instead, it operates on any
+/// std::io::Read + std::io::Seek (e.g. a std::fs::File), streaming the data into the hasher, so that images
+/// larger than memory can be self-hashed.  Byte buffers (including memory-mapped files) can be operated on
+/// directly (see SelfHashableBinary::self_hash_bytes and SelfHashableBinary::verify_self_hashes_of_bytes).
+#[derive(Clone, Debug)]
+pub struct SelfHashableBinary {
+    named_hash_function: NamedHashFunction,
+    slot_encoding: BinarySlotEncoding,
+    slot_fill: BinarySlotFill,
+    /// The self-hash slots, sorted by offset.
+    slot_v: Vec<BinarySlot>,
+}
+
+impl SelfHashableBinary {
+    /// Creates a SelfHashableBinary with the given hash function, slot encoding, and self-hash slots.  Each
+    /// slot's length must be exactly the length of the encoded self-hash (see
+    /// SelfHashableBinary::slot_length), and the slots must not overlap.  The slot fill defaults to
+    /// BinarySlotFill::Zeros.
+    pub fn new(
+        named_hash_function: NamedHashFunction,
+        slot_encoding: BinarySlotEncoding,
+        mut slot_v: Vec<BinarySlot>,
+    ) -> Result<Self> {
+        ensure!(
+            !slot_v.is_empty(),
+            "no self-hash slots found, meaning that this binary image is not self-hashable"
+        );
+        slot_v.sort();
+        let self_hashable_binary = Self {
+            named_hash_function,
+            slot_encoding,
+            slot_fill: BinarySlotFill::Zeros,
+            slot_v,
+        };
+        let slot_length = self_hashable_binary.slot_length();
+        for slot in self_hashable_binary.slot_v.iter() {
+            // This is checked first, since the end of the slot is only defined if it doesn't overflow.
+            ensure!(
+                slot.offset.checked_add(slot.length).is_some(),
+                "self-hash slot at offset {} with length {} overflows",
+                slot.offset,
+                slot.length
+            );
+            ensure!(
+                slot.length == slot_length,
+                "self-hash slot {} has length {}, but a {:?}-encoded {} self-hash has length {}",
+                slot,
+                slot.length,
+                slot_encoding,
+                named_hash_function,
+                slot_length
+            );
+        }
+        for pair in self_hashable_binary.slot_v.windows(2) {
+            ensure!(
+                pair[0].end() <= pair[1].offset,
+                "self-hash slots {} and {} overlap",
+                pair[0],
+                pair[1]
+            );
+        }
+        Ok(self_hashable_binary)
+    }
+    /// Sets what the self-hash slots are replaced with when computing the digest.
+    pub fn with_slot_fill(mut self, slot_fill: BinarySlotFill) -> Self {
+        self.slot_fill = slot_fill;
+        self
+    }
+    pub fn named_hash_function(&self) -> NamedHashFunction {
+        self.named_hash_function
+    }
+    pub fn slot_encoding(&self) -> BinarySlotEncoding {
+        self.slot_encoding
+    }
+    pub fn slot_fill(&self) -> BinarySlotFill {
+        self.slot_fill
+    }
+    pub fn slots(&self) -> &[BinarySlot] {
+        self.slot_v.as_slice()
+    }
+    /// Returns the length of the encoded self-hash, which is the required length of each slot.
+    pub fn slot_length(&self) -> u64 {
+        self.encode_slot(self.named_hash_function.placeholder_bytes())
+            .len() as u64
+    }
+    /// Encodes the given digest according to the slot encoding.
+    fn encode_slot(&self, digest: &[u8]) -> Vec<u8> {
+        match self.slot_encoding {
+            BinarySlotEncoding::RawDigest => digest.to_vec(),
+            BinarySlotEncoding::Multihash => {
+                let mut byte_v = Vec::with_capacity(digest.len() + 4);
+                push_uvarint(self.named_hash_function.codec(), &mut byte_v);
+                push_uvarint(digest.len() as u64, &mut byte_v);
+                byte_v.extend_from_slice(digest);
+                byte_v
+            }
+        }
+    }
+    /// Returns the bytes that the self-hash slots are replaced with when computing the digest.
+    fn fill_bytes(&self) -> Vec<u8> {
+        match self.slot_fill {
+            BinarySlotFill::Zeros => vec![0u8; self.slot_length() as usize],
+            BinarySlotFill::Placeholder => {
+                self.encode_slot(self.named_hash_function.placeholder_bytes())
+            }
+        }
+    }
+    /// Decodes the self-hash from the given slot bytes, returning None if the slot is unset.
+    fn decode_slot<'a>(
+        &self,
+        slot_byte_v: &'a [u8],
+        slot: &BinarySlot,
+    ) -> Result<Option<&'a [u8]>> {
+        if slot_byte_v.iter().all(|b| *b == 0u8) || slot_byte_v == self.fill_bytes().as_slice() {
+            return Ok(None);
+        }
+        match self.slot_encoding {
+            BinarySlotEncoding::RawDigest => Ok(Some(slot_byte_v)),
+            BinarySlotEncoding::Multihash => {
+                let digest_length = self.named_hash_function.placeholder_bytes().len();
+                let header_length = slot_byte_v.len() - digest_length;
+                let placeholder = self.encode_slot(self.named_hash_function.placeholder_bytes());
+                ensure!(
+                    slot_byte_v[..header_length] == placeholder[..header_length],
+                    "self-hash slot {} doesn't hold a {} multihash",
+                    slot,
+                    self.named_hash_function
+                );
+                Ok(Some(&slot_byte_v[header_length..]))
+            }
+        }
+    }
+    /// Streams the digest data of the image into the given hasher, i.e. the bytes of the image, with each
+    /// self-hash slot replaced by the fill bytes.
+    pub fn write_digest_data<R: Read + Seek>(
+        &self,
+        reader: &mut R,
+        hasher: &mut dyn HasherDynT,
+    ) -> Result<()> {
+        let map_io_error =
+            |e: std::io::Error| error!("failed to read binary image; error was {}", e);
+        let image_length = reader.seek(SeekFrom::End(0)).map_err(map_io_error)?;
+        let last_slot = self.slot_v.last().expect("programmer error");
+        ensure!(
+            last_slot.end() <= image_length,
+            "self-hash slot {} extends past the end of the binary image (whose length is {})",
+            last_slot,
+            image_length
+        );
+        reader.seek(SeekFrom::Start(0)).map_err(map_io_error)?;
+        let fill_byte_v = self.fill_bytes();
+        let mut buffer = vec![0u8; CHUNK_SIZE];
+        let mut offset = 0;
+        for slot in self.slot_v.iter() {
+            hash_range(reader, slot.offset - offset, &mut buffer, hasher).map_err(map_io_error)?;
+            HasherDynT::update(hasher, &fill_byte_v);
+            offset = reader
+                .seek(SeekFrom::Start(slot.end()))
+                .map_err(map_io_error)?;
+        }
+        hash_range(reader, image_length - offset, &mut buffer, hasher).map_err(map_io_error)
+    }
+    /// Computes the self-hash of the image.  Note that this ignores any existing values in the self-hash
+    /// slots, using the fill bytes instead.
+    pub fn compute_self_hash<R: Read + Seek>(&self, reader: &mut R) -> Result<HashBytes<'static>> {
+        let mut hasher_b = self.named_hash_function.new_hasher_dyn();
+        self.write_digest_data(reader, hasher_b.as_mut())?;
+        Ok(HashBytes::new(
+            self.named_hash_function,
+            Cow::Owned(hasher_b.finalize().hash_bytes().into_owned()),
+        ))
+    }
+    /// Reads the self-hash slots of the image, checking that they're either all unset (in which case this
+    /// returns None) or all set to the same self-hash.
+    pub fn get_unverified_self_hash<R: Read + Seek>(
+        &self,
+        reader: &mut R,
+    ) -> Result<Option<HashBytes<'static>>> {
+        let mut self_hash_oo: Option<Option<Vec<u8>>> = None;
+        let mut slot_byte_v = vec![0u8; self.slot_length() as usize];
+        for slot in self.slot_v.iter() {
+            reader
+                .seek(SeekFrom::Start(slot.offset))
+                .and_then(|_| reader.read_exact(&mut slot_byte_v))
+                .map_err(|e| {
+                    error!(
+                        "failed to read self-hash slot {} of binary image; error was {}",
+                        slot, e
+                    )
+                })?;
+            let self_hash_o = self.decode_slot(&slot_byte_v, slot)?;
+            match self_hash_oo.as_ref() {
+                None => {
+                    self_hash_oo = Some(self_hash_o.map(<[u8]>::to_vec));
+                }
+                Some(first_self_hash_o) => {
+                    if first_self_hash_o.is_some() != self_hash_o.is_some() {
+                        return Err(error!("This binary image is malformed as self-hashed because some but not all self-hash slots are populated -- it must be all or nothing.").with_kind(ErrorKind::MissingSlot));
+                    }
+                    if first_self_hash_o.as_deref() != self_hash_o {
+                        return Err(error!("Binary image's self-hash slots do not all match.")
+                            .with_kind(ErrorKind::HashMismatch));
+                    }
+                }
+            }
+        }
+        Ok(self_hash_oo
+            .expect("programmer error")
+            .map(|self_hash| HashBytes::new(self.named_hash_function, Cow::Owned(self_hash))))
+    }
+    /// Computes the self-hash of the image and writes it into all the self-hash slots.
+    pub fn self_hash<F: Read + Write + Seek>(&self, file: &mut F) -> Result<HashBytes<'static>> {
+        let self_hash = self.compute_self_hash(file)?;
+        let slot_byte_v = self.encode_slot(self_hash.bytes());
+        for slot in self.slot_v.iter() {
+            file.seek(SeekFrom::Start(slot.offset))
+                .and_then(|_| file.write_all(&slot_byte_v))
+                .map_err(|e| {
+                    error!(
+                        "failed to write self-hash slot {} of binary image; error was {}",
+                        slot, e
+                    )
+                })?;
+        }
+        file.flush()
+            .map_err(|e| error!("failed to flush binary image; error was {}", e))?;
+        Ok(self_hash)
+    }
+    /// Verifies the self-hashes in the image and returns the verified self-hash.
+    pub fn verify_self_hashes<R: Read + Seek>(&self, reader: &mut R) -> Result<HashBytes<'static>> {
+        let unverified_self_hash = self.get_unverified_self_hash(reader)?.ok_or_else(|| {
+            error!("This binary image's self-hash slots are unset, and therefore it can't be self-verified.")
+                .with_kind(ErrorKind::MissingSlot)
+        })?;
+        let computed_self_hash = self.compute_self_hash(reader)?;
+        if computed_self_hash != unverified_self_hash {
+            return Err(error!(
+                "This binary image's computed self-hash ({:?}) doesn't match its claimed self-hash ({:?}).",
+                computed_self_hash.bytes(),
+                unverified_self_hash.bytes()
+            )
+            .with_kind(ErrorKind::HashMismatch));
+        }
+        Ok(unverified_self_hash)
+    }
+    /// Convenience method for self-hashing a byte buffer (e.g. a memory-mapped file) in place.
+    pub fn self_hash_bytes(&self, byte_v: &mut [u8]) -> Result<HashBytes<'static>> {
+        self.self_hash(&mut std::io::Cursor::new(byte_v))
+    }
+    /// Convenience method for verifying the self-hashes in a byte buffer (e.g. a memory-mapped file).
+    pub fn verify_self_hashes_of_bytes(&self, byte_v: &[u8]) -> Result<HashBytes<'static>> {
+        self.verify_self_hashes(&mut std::io::Cursor::new(byte_v))
+    }
+}
+
+/// Feeds the next length bytes of the reader into the hasher, using the given buffer.
+fn hash_range<R: Read>(
+    reader: &mut R,
+    mut length: u64,
+    buffer: &mut [u8],
+    hasher: &mut dyn HasherDynT,
+) -> std::io::Result<()> {
+    while length > 0 {
+        let chunk_length = length.min(buffer.len() as u64) as usize;
+        reader.read_exact(&mut buffer[..chunk_length])?;
+        HasherDynT::update(hasher, &buffer[..chunk_length]);
+        length -= chunk_length as u64;
+    }
+    Ok(())
+}
@@ -0,0 +1,377 @@
+use crate::{
+    bail,
+    dcbor_writer::{to_dcbor_vec, write_dcbor},
+    ensure, error, ErrorKind, HashFunctionT, HashRefT, HashT, JSONPointer, Result, SelfHashURL,
+    SelfHashURLStr, SelfHashableT, TryHashT,
+};
+use std::borrow::Cow;
+
+/// Interprets a ciborium::Value as a mbx::MBHash (either as a mbx::MBHash directly or through the mbx::MBHashStr
+/// component of a SelfHashURL).  Note that this panics if the value is not a text string containing a valid
+/// mbx::MBHash or SelfHashURL.  Use TryHashT::try_as_hash_ref for untrusted input.
+impl HashT<mbx::MBHashStr> for ciborium::Value {
+    fn as_hash_ref(&self) -> &mbx::MBHashStr {
+        self.try_as_hash_ref()
+            .expect("programmer error: ciborium::Value is not a valid MBHash or SelfHashURL")
+    }
+}
+
+/// Fallibly interprets a ciborium::Value as a mbx::MBHash (either as a mbx::MBHash directly or through the
+/// mbx::MBHashStr component of a SelfHashURL).
+impl TryHashT<mbx::MBHashStr> for ciborium::Value {
+    fn try_as_hash_ref(&self) -> Result<&mbx::MBHashStr> {
+        let s = self.as_text().ok_or_else(|| {
+            error!(
+                "self-hash field is expected to be a text string, but was {:?}",
+                self
+            )
+        })?;
+        if let Ok(self_hash_url) = SelfHashURLStr::new_ref(s) {
+            self_hash_url.try_as_hash_ref()
+        } else {
+            mbx::MBHashStr::new_ref(s).map_err(|e| {
+                error!(
+                    "self-hash field {:?} is not a valid MBHash or SelfHashURL; error was {}",
+                    s, e
+                )
+            })
+        }
+    }
+}
+
+/// Returns the position of the child of the given CBOR value that the given JSON Pointer token refers to,
+/// i.e. the index of the map entry or array element, or None if there is no such child.  Within a map, the
+/// token refers to the entry whose key is the text string equal to the token, or if the token is a decimal
+/// integer, the entry whose key is that integer (it's an error for both to be present).
+fn child_index(value: &ciborium::Value, token: &str) -> Result<Option<usize>> {
+    match value {
+        ciborium::Value::Map(map) => {
+            let integer_key_o = token
+                .parse::<i64>()
+                .ok()
+                .map(|i| ciborium::Value::Integer(i.into()));
+            let mut index_i = map.iter().enumerate().filter_map(|(index, (key, _))| {
+                (key.as_text() == Some(token) || Some(key) == integer_key_o.as_ref())
+                    .then_some(index)
+            });
+            let index_o = index_i.next();
+            ensure!(
+                index_i.next().is_none(),
+                "JSON Pointer token {:?} ambiguously refers to both a text string key and an integer key in a CBOR map",
+                token
+            );
+            Ok(index_o)
+        }
+        ciborium::Value::Array(array) => {
+            if token.is_empty()
+                || !token.bytes().all(|b| b.is_ascii_digit())
+                || (token.len() > 1 && token.starts_with('0'))
+            {
+                return Ok(None);
+            }
+            Ok(token
+                .parse::<usize>()
+                .ok()
+                .filter(|&index| index < array.len()))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Returns the positions (see child_index) of the successive children that the given JSON Pointer tokens
+/// refer to, or None if any of them doesn't exist.
+fn locate(value: &ciborium::Value, token_v: &[String]) -> Result<Option<Vec<usize>>> {
+    let mut location = Vec::with_capacity(token_v.len());
+    let mut current = value;
+    for token in token_v.iter() {
+        let Some(index) = child_index(current, token)? else {
+            return Ok(None);
+        };
+        current = child_at(current, index);
+        location.push(index);
+    }
+    Ok(Some(location))
+}
+
+fn child_at(value: &ciborium::Value, index: usize) -> &ciborium::Value {
+    match value {
+        ciborium::Value::Map(map) => &map[index].1,
+        ciborium::Value::Array(array) => &array[index],
+        _ => panic!("programmer error: CBOR value has no children"),
+    }
+}
+
+fn value_at<'a>(value: &'a ciborium::Value, location: &[usize]) -> &'a ciborium::Value {
+    location
+        .iter()
+        .fold(value, |current, &index| child_at(current, index))
+}
+
+fn value_at_mut<'a>(value: &'a mut ciborium::Value, location: &[usize]) -> &'a mut ciborium::Value {
+    location
+        .iter()
+        .fold(value, |current, &index| match current {
+            ciborium::Value::Map(map) => &mut map[index].1,
+            ciborium::Value::Array(array) => &mut array[index],
+            _ => panic!("programmer error: CBOR value has no children"),
+        })
+}
+
+/// This data structure provides the context necessary to process a ciborium::Value as self-hashable data,
+/// analogously to SelfHashableJSON, where the self-hash slots and self-hash URL slots are specified by
+/// RFC 6901 JSON Pointers (see JSONPointer).  Within a CBOR map, a JSON Pointer token refers to the entry
+/// whose key is the text string equal to the token, or if the token is a decimal integer, the entry whose
+/// key is that integer, which allows e.g. `/-1` to address the entry having integer key -1.  Self-hash
+/// [URL] values are stored as text strings, and the digest data is the deterministic CBOR encoding (see
+/// RFC 8949, section 4.2.1) of the value with its self-hash [URL] slots set to the placeholder.
+#[derive(Clone)]
+pub struct SelfHashableCBOR<'v, 'w: 'v> {
+    /// This is the CBOR value that is being self-hashed.
+    value: ciborium::Value,
+    /// These are all the JSON Pointers whose elements are considered to define self-hash values.
+    self_hash_pointer_s: Cow<'v, std::collections::HashSet<Cow<'w, str>>>,
+    /// These are all the JSON Pointers whose elements are considered to define self-hash URL values.
+    self_hash_url_pointer_s: Cow<'v, std::collections::HashSet<Cow<'w, str>>>,
+    /// These are the parsed forms of self_hash_pointer_s.
+    self_hash_pointer_v: Vec<JSONPointer>,
+    /// These are the parsed forms of self_hash_url_pointer_s.
+    self_hash_url_pointer_v: Vec<JSONPointer>,
+}
+
+impl<'v, 'w: 'v> SelfHashableCBOR<'v, 'w> {
+    /// The value must be a CBOR map.  A self-hash field specified by a self-hash pointer doesn't have to
+    /// exist already (in which case it's inserted with a text string key), but its parent must exist and
+    /// be a CBOR map.  A self-hash URL field must already exist and be a valid self-hash URL.
+    pub fn new(
+        value: ciborium::Value,
+        self_hash_pointer_s: Cow<'v, std::collections::HashSet<Cow<'w, str>>>,
+        self_hash_url_pointer_s: Cow<'v, std::collections::HashSet<Cow<'w, str>>>,
+    ) -> Result<Self> {
+        ensure!(
+            value.is_map(),
+            "self-hashable CBOR value is expected to be a CBOR map"
+        );
+        ensure!(
+            self_hash_pointer_s.is_disjoint(&self_hash_url_pointer_s),
+            "self-hash pointers and self-hash URL pointers must be disjoint."
+        );
+        let self_hash_pointer_v = self_hash_pointer_s
+            .iter()
+            .map(|self_hash_pointer| JSONPointer::parse(self_hash_pointer))
+            .collect::<Result<Vec<_>>>()?;
+        let self_hash_url_pointer_v = self_hash_url_pointer_s
+            .iter()
+            .map(|self_hash_url_pointer| JSONPointer::parse(self_hash_url_pointer))
+            .collect::<Result<Vec<_>>>()?;
+        ensure!(
+            self_hash_pointer_v.len() + self_hash_url_pointer_v.len() > 0,
+            "no self-hash or self-hash URL fields found, meaning that this CBOR value is not self-hashable"
+        );
+        ensure!(
+            self_hash_pointer_v
+                .iter()
+                .chain(self_hash_url_pointer_v.iter())
+                .all(|pointer| !pointer.tokens().is_empty()),
+            "the root of a self-hashable CBOR value can't be a self-hash [URL] slot"
+        );
+
+        let self_hashable_cbor = SelfHashableCBOR {
+            value,
+            self_hash_pointer_s,
+            self_hash_url_pointer_s,
+            self_hash_pointer_v,
+            self_hash_url_pointer_v,
+        };
+        self_hashable_cbor.validate()?;
+        Ok(self_hashable_cbor)
+    }
+    /// Verifies that all the self-hash slots are text strings or nulls, or are missing but can be
+    /// inserted, and that all the self-hash URL slots are present and are valid self-hash URLs.
+    fn validate(&self) -> Result<()> {
+        for self_hash_pointer in self.self_hash_pointer_v.iter() {
+            let token_v = self_hash_pointer.tokens();
+            if let Some(location) = locate(&self.value, token_v)? {
+                let slot_value = value_at(&self.value, location.as_slice());
+                ensure!(
+                    slot_value.is_text() || slot_value.is_null(),
+                    "self-hash pointer {:?} query value expected to be a text string or null, but was {:?}",
+                    self_hash_pointer.as_str(),
+                    slot_value
+                );
+            } else {
+                let parent_location_o = locate(&self.value, &token_v[..token_v.len() - 1])?;
+                if !parent_location_o.is_some_and(|parent_location| {
+                    value_at(&self.value, parent_location.as_slice()).is_map()
+                }) {
+                    return Err(error!(
+                        "self-hash pointer {:?} is missing, and its parent doesn't exist or isn't a CBOR map, so it can't be inserted",
+                        self_hash_pointer.as_str()
+                    )
+                    .with_kind(ErrorKind::MissingSlot));
+                }
+            }
+        }
+        for self_hash_url_pointer in self.self_hash_url_pointer_v.iter() {
+            let location =
+                locate(&self.value, self_hash_url_pointer.tokens())?.ok_or_else(|| {
+                    error!(
+                        "self-hash URL pointer {:?} query value is missing",
+                        self_hash_url_pointer.as_str()
+                    )
+                    .with_kind(ErrorKind::MissingSlot)
+                })?;
+            let slot_value = value_at(&self.value, location.as_slice());
+            let text = slot_value.as_text().ok_or_else(|| {
+                error!(
+                    "self-hash URL pointer {:?} query value expected to be a text string, but was {:?}",
+                    self_hash_url_pointer.as_str(),
+                    slot_value
+                )
+            })?;
+            SelfHashURLStr::new_ref(text).map_err(|e| {
+                error!(
+                    "self-hash URL pointer {:?} query value {:?} is not a valid self-hash URL; error was {}",
+                    self_hash_url_pointer.as_str(),
+                    text,
+                    e
+                )
+            })?;
+        }
+        Ok(())
+    }
+    /// Returns the deterministic CBOR encoding (see RFC 8949, section 4.2.1) of the value.
+    pub fn to_dcbor(&self) -> Result<Vec<u8>> {
+        to_dcbor_vec(&self.value)
+    }
+    pub fn self_hash_pointers(&self) -> &std::collections::HashSet<Cow<'w, str>> {
+        &self.self_hash_pointer_s
+    }
+    pub fn self_hash_url_pointers(&self) -> &std::collections::HashSet<Cow<'w, str>> {
+        &self.self_hash_url_pointer_s
+    }
+    pub fn value(&self) -> &ciborium::Value {
+        &self.value
+    }
+    /// Note that modifying the value may invalidate the self-hash [URL] slots.
+    pub fn value_mut(&mut self) -> &mut ciborium::Value {
+        &mut self.value
+    }
+    pub fn into_value(self) -> ciborium::Value {
+        self.value
+    }
+}
+
+impl SelfHashableT<mbx::MBHashStr> for SelfHashableCBOR<'_, '_> {
+    fn write_digest_data(
+        &self,
+        hasher: &mut <<mbx::MBHashStr as HashRefT>::HashFunction as HashFunctionT<
+            mbx::MBHashStr,
+        >>::Hasher,
+    ) -> Result<()> {
+        use crate::HasherT;
+        let mut c = self.clone();
+        c.set_self_hash_slots_to(hasher.hash_function().placeholder_hash().as_ref())?;
+        let mut writer = digest_io::IoWrapper(hasher);
+        write_dcbor(&c.value, &mut writer)
+    }
+    fn self_hash_oi<'a, 'b: 'a>(
+        &'b self,
+    ) -> Result<Box<dyn std::iter::Iterator<Item = Option<&'b mbx::MBHashStr>> + 'a>> {
+        let mut self_hash_ov =
+            Vec::with_capacity(self.self_hash_pointer_v.len() + self.self_hash_url_pointer_v.len());
+        for self_hash_pointer in self.self_hash_pointer_v.iter() {
+            let self_hash_o = match locate(&self.value, self_hash_pointer.tokens())? {
+                Some(location) => match value_at(&self.value, location.as_slice()) {
+                    ciborium::Value::Null => None,
+                    slot_value => Some(slot_value.try_as_hash_ref().map_err(|e| {
+                        error!(
+                            "malformed self-hash field; pointer was {:?}; error was {}",
+                            self_hash_pointer.as_str(),
+                            e
+                        )
+                        .with_kind(e.kind())
+                    })?),
+                },
+                None => None,
+            };
+            self_hash_ov.push(self_hash_o);
+        }
+        for self_hash_url_pointer in self.self_hash_url_pointer_v.iter() {
+            let Some(location) = locate(&self.value, self_hash_url_pointer.tokens())? else {
+                return Err(error!(
+                    "self-hash URL pointer {:?} query value is missing",
+                    self_hash_url_pointer.as_str()
+                )
+                .with_kind(ErrorKind::MissingSlot));
+            };
+            let text = value_at(&self.value, location.as_slice())
+                .as_text()
+                .ok_or_else(|| {
+                    error!(
+                        "self-hash URL pointer {:?} query value expected to be a text string",
+                        self_hash_url_pointer.as_str()
+                    )
+                })?;
+            let self_hash_url = SelfHashURLStr::new_ref(text).map_err(|e| {
+                error!(
+                    "malformed self-hash URL field; pointer was {:?}; error was {}",
+                    self_hash_url_pointer.as_str(),
+                    e
+                )
+                .with_kind(e.kind())
+            })?;
+            self_hash_ov.push(self_hash_url.mb_hash_o());
+        }
+        Ok(Box::new(self_hash_ov.into_iter()))
+    }
+    fn set_self_hash_slots_to(&mut self, hash: &mbx::MBHashStr) -> Result<()> {
+        for self_hash_pointer in self.self_hash_pointer_v.iter() {
+            let token_v = self_hash_pointer.tokens();
+            if let Some(location) = locate(&self.value, token_v)? {
+                *value_at_mut(&mut self.value, location.as_slice()) =
+                    ciborium::Value::Text(hash.to_string());
+            } else {
+                let parent_location = locate(&self.value, &token_v[..token_v.len() - 1])?
+                    .ok_or_else(|| {
+                        error!(
+                            "parent of self-hash pointer {:?} doesn't exist",
+                            self_hash_pointer.as_str()
+                        )
+                    })?;
+                let ciborium::Value::Map(parent) =
+                    value_at_mut(&mut self.value, parent_location.as_slice())
+                else {
+                    bail!(
+                        "parent of self-hash pointer {:?} is not a CBOR map",
+                        self_hash_pointer.as_str()
+                    );
+                };
+                parent.push((
+                    ciborium::Value::Text(token_v[token_v.len() - 1].clone()),
+                    ciborium::Value::Text(hash.to_string()),
+                ));
+            }
+        }
+        for self_hash_url_pointer in self.self_hash_url_pointer_v.iter() {
+            let location =
+                locate(&self.value, self_hash_url_pointer.tokens())?.ok_or_else(|| {
+                    error!(
+                        "self-hash URL pointer {:?} query value is missing",
+                        self_hash_url_pointer.as_str()
+                    )
+                    .with_kind(ErrorKind::MissingSlot)
+                })?;
+            let slot_value = value_at_mut(&mut self.value, location.as_slice());
+            let mut self_hash_url =
+                SelfHashURL::try_from(slot_value.as_text().ok_or_else(|| {
+                    error!(
+                        "self-hash URL pointer {:?} query value expected to be a text string",
+                        self_hash_url_pointer.as_str()
+                    )
+                })?)?;
+            self_hash_url.set_self_hash_slots_to_mb_hash(hash)?;
+            *slot_value = ciborium::Value::Text(self_hash_url.to_string());
+        }
+        Ok(())
+    }
+}
@@ -1,26 +1,42 @@
 use crate::{
-    bail, ensure, error, write_digest_data_using_jcs, HashFunctionT, HashRefT, HashT, Result,
-    SelfHashURL, SelfHashURLStr, SelfHashableT,
+    bail, ensure, error,
+    jcs_writer::{write_jcs_with_substitutions, JSONSubstitutions},
+    json_path::value_at,
+    ErrorKind, HashFunctionT, HashRefT, HashT, JSONPath, JSONPathSegment, JSONPointer, Result,
+    SelfHashSlotCardinality, SelfHashTemplate, SelfHashURL, SelfHashURLStr, SelfHashableT,
+    TryHashT,
 };
 use std::borrow::Cow;
 
 /// Interprets a serde_json::Value as a mbx::MBHash (either as a mbx::MBHash directly or through the mbx::MBHashStr
-/// component of a SelfHashURL).
+/// component of a SelfHashURL).  Note that this panics if the value is not a string containing a valid
+/// mbx::MBHash or SelfHashURL.  Use TryHashT::try_as_hash_ref for untrusted input.
 impl HashT<mbx::MBHashStr> for serde_json::Value {
     fn as_hash_ref(&self) -> &mbx::MBHashStr {
-        match self {
-            serde_json::Value::String(s) => {
-                if let Ok(self_hash_url) = SelfHashURLStr::new_ref(s) {
-                    self_hash_url.as_hash_ref()
-                } else if let Ok(mb_hash) = mbx::MBHashStr::new_ref(s) {
-                    mb_hash
-                } else {
-                    panic!("selfHash field is not a valid MBHash or SelfHashURL.")
-                }
-            }
-            _ => {
-                panic!("serde_json::Value::as_hash_ref requires a String value.")
-            }
+        self.try_as_hash_ref()
+            .expect("programmer error: serde_json::Value is not a valid MBHash or SelfHashURL")
+    }
+}
+
+/// Fallibly interprets a serde_json::Value as a mbx::MBHash (either as a mbx::MBHash directly or through the
+/// mbx::MBHashStr component of a SelfHashURL).
+impl TryHashT<mbx::MBHashStr> for serde_json::Value {
+    fn try_as_hash_ref(&self) -> Result<&mbx::MBHashStr> {
+        let s = self.as_str().ok_or_else(|| {
+            error!(
+                "self-hash field is expected to be a string, but was {}",
+                self
+            )
+        })?;
+        if let Ok(self_hash_url) = SelfHashURLStr::new_ref(s) {
+            self_hash_url.try_as_hash_ref()
+        } else {
+            mbx::MBHashStr::new_ref(s).map_err(|e| {
+                error!(
+                    "self-hash field {:?} is not a valid MBHash or SelfHashURL; error was {}",
+                    s, e
+                )
+            })
         }
     }
 }
@@ -34,7 +50,28 @@ impl SelfHashableT<mbx::MBHashStr> for serde_json::Value {
             mbx::MBHashStr,
         >>::Hasher,
     ) -> Result<()> {
-        write_digest_data_using_jcs(self, hasher)
+        ensure!(
+            self.is_object(),
+            "self-hashable JSON value is expected to be a JSON object"
+        );
+        use crate::HasherT;
+        let placeholder_hash = hasher.hash_function().placeholder_hash();
+        // Only a top-level "selfHash" field in this JSON object is considered a self-hash slot.
+        let mut substitutions = JSONSubstitutions::default();
+        if self.get("selfHash").is_some() {
+            substitutions.replace(
+                vec![JSONPathSegment::Name("selfHash".to_string())],
+                placeholder_hash.to_string(),
+            );
+        } else {
+            substitutions.insert(
+                Vec::new(),
+                "selfHash".to_string(),
+                placeholder_hash.to_string(),
+            );
+        }
+        let mut writer = digest_io::IoWrapper(hasher);
+        write_jcs_with_substitutions(self, &substitutions, &mut writer)
     }
     fn self_hash_oi<'a, 'b: 'a>(
         &'b self,
@@ -46,9 +83,11 @@ impl SelfHashableT<mbx::MBHashStr> for serde_json::Value {
         let self_hash_o = self.get("selfHash");
         let self_hash_oib = match self_hash_o {
             Some(serde_json::Value::Null) | None => Box::new(std::iter::once(None)),
-            Some(serde_json::Value::String(_)) => Box::new(std::iter::once(
-                self_hash_o.map(|self_hash| self_hash.as_hash_ref()),
-            )),
+            Some(self_hash @ serde_json::Value::String(_)) => {
+                Box::new(std::iter::once(Some(self_hash.try_as_hash_ref().map_err(
+                    |e| error!("malformed selfHash field; error was {}", e),
+                )?)))
+            }
             Some(_) => {
                 bail!("selfHash field must be a string or null.");
             }
@@ -67,9 +106,197 @@ impl SelfHashableT<mbx::MBHashStr> for serde_json::Value {
     }
 }
 
+/// A self-hash [URL] slot selector, given either as a JSONPath query or as a JSON Pointer.
+#[derive(Clone, Debug)]
+enum SlotPath {
+    JSONPath(JSONPath),
+    JSONPointer(JSONPointer),
+}
+
+impl SlotPath {
+    /// Parses the given selector as a JSONPath query if it begins with `$`, and otherwise as a JSON Pointer.
+    fn parse(selector: &str) -> Result<Self> {
+        if selector.starts_with('$') {
+            JSONPath::parse(selector).map(Self::JSONPath)
+        } else {
+            JSONPointer::parse(selector).map(Self::JSONPointer)
+        }
+    }
+    fn as_str(&self) -> &str {
+        match self {
+            Self::JSONPath(json_path) => json_path.as_str(),
+            Self::JSONPointer(json_pointer) => json_pointer.as_str(),
+        }
+    }
+    /// Interprets the given slot value as a mbx::MBHash, producing an error that includes this slot's
+    /// selector if the value is malformed.
+    fn try_as_hash_ref_of<'a>(&self, value: &'a serde_json::Value) -> Result<&'a mbx::MBHashStr> {
+        value.try_as_hash_ref().map_err(|e| {
+            error!(
+                "malformed self-hash [URL] field; slot was {}; error was {}",
+                self, e
+            )
+        })
+    }
+    /// Returns true iff this selector can select at most one node.
+    fn is_singular(&self) -> bool {
+        match self {
+            Self::JSONPath(json_path) => json_path.is_singular(),
+            Self::JSONPointer(_) => true,
+        }
+    }
+    fn default_cardinality(&self) -> SelfHashSlotCardinality {
+        if self.is_singular() {
+            SelfHashSlotCardinality::AtMostOne
+        } else {
+            SelfHashSlotCardinality::Any
+        }
+    }
+    /// Returns the normalized paths of all the nodes selected by this selector.
+    fn query_located(&self, value: &serde_json::Value) -> Vec<Vec<JSONPathSegment>> {
+        match self {
+            Self::JSONPath(json_path) => json_path.query_located(value),
+            Self::JSONPointer(json_pointer) => json_pointer.locate(value).into_iter().collect(),
+        }
+    }
+    /// Returns true iff this selector ends with a member name, so that a missing slot could be inserted.
+    fn has_terminating_name(&self) -> bool {
+        match self {
+            Self::JSONPath(json_path) => json_path.terminating_name().is_some(),
+            Self::JSONPointer(json_pointer) => json_pointer.terminating_token().is_some(),
+        }
+    }
+    /// If this selector ends with a member name and its parent is unambiguous (i.e. the parent selector
+    /// selects exactly one node, which is a JSON object), returns the normalized path of the parent and the
+    /// member name, which is where a missing slot would be inserted.
+    fn insertion_location(
+        &self,
+        value: &serde_json::Value,
+    ) -> Option<(Vec<JSONPathSegment>, String)> {
+        let (parent_location, terminating_name) = match self {
+            Self::JSONPath(json_path) => {
+                let terminating_name = json_path.terminating_name()?;
+                let mut parent_location_v = json_path.parent()?.query_located(value);
+                if parent_location_v.len() != 1 {
+                    return None;
+                }
+                (parent_location_v.pop().unwrap(), terminating_name)
+            }
+            Self::JSONPointer(json_pointer) => (
+                json_pointer.locate_parent(value)?,
+                json_pointer.terminating_token()?,
+            ),
+        };
+        if value_at(value, &parent_location)?.is_object() {
+            Some((parent_location, terminating_name.to_string()))
+        } else {
+            None
+        }
+    }
+}
+
+impl std::fmt::Display for SlotPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A self-hash [URL] slot selector together with the cardinality rule for how many slots it must select.
+#[derive(Clone, Debug)]
+struct SlotSelector {
+    slot_path: SlotPath,
+    cardinality: SelfHashSlotCardinality,
+}
+
+/// The slots selected by a SlotSelector within a particular JSON value.
+struct SlotResolution {
+    /// The normalized paths of the existing slots.
+    location_v: Vec<Vec<JSONPathSegment>>,
+    /// True iff the selector selected nothing, but designates a single missing slot, which will be
+    /// inserted into its parent during set_self_hash_slots_to.
+    has_missing_slot: bool,
+}
+
+impl SlotSelector {
+    fn new(slot_path: SlotPath) -> Self {
+        let cardinality = slot_path.default_cardinality();
+        Self {
+            slot_path,
+            cardinality,
+        }
+    }
+    /// Determines the slots selected within the given value, checking the cardinality rule.  If
+    /// allow_missing_slot is true, then a selector which selects nothing designates a single missing slot
+    /// if it's singular or if its parent is unambiguous (see SlotPath::insertion_location).
+    fn resolve(
+        &self,
+        value: &serde_json::Value,
+        allow_missing_slot: bool,
+    ) -> Result<SlotResolution> {
+        let location_v = self.slot_path.query_located(value);
+        let has_missing_slot = allow_missing_slot
+            && location_v.is_empty()
+            && (self.slot_path.is_singular() || self.slot_path.insertion_location(value).is_some());
+        self.cardinality.ensure_admits(
+            location_v.len() + usize::from(has_missing_slot),
+            self.slot_path.as_str(),
+        )?;
+        Ok(SlotResolution {
+            location_v,
+            has_missing_slot,
+        })
+    }
+}
+
+/// Extracts the self-hash from a self-hash template slot value, which must be null (meaning unset) or a
+/// string matching the template.  An empty self-hash portion also means unset.
+fn template_self_hash_o<'a>(
+    query_value: &'a serde_json::Value,
+    self_hash_template: &SelfHashTemplate,
+    slot_path: &SlotPath,
+) -> Result<Option<&'a mbx::MBHashStr>> {
+    let s = match query_value {
+        serde_json::Value::Null => {
+            return Ok(None);
+        }
+        serde_json::Value::String(s) => s.as_str(),
+        _ => {
+            bail!(
+                "self-hash template slot query result must be a string or null; slot was {}",
+                slot_path
+            );
+        }
+    };
+    let self_hash_str = self_hash_template.self_hash_str_o(s).ok_or_else(|| {
+        error!(
+            "self-hash template field {:?} does not match template {:?}; slot was {}",
+            s,
+            self_hash_template.as_str(),
+            slot_path
+        )
+    })?;
+    if self_hash_str.is_empty() {
+        return Ok(None);
+    }
+    let self_hash = mbx::MBHashStr::new_ref(self_hash_str).map_err(|e| {
+        error!(
+            "malformed self-hash in self-hash template field {:?}; slot was {}; error was {}",
+            s, slot_path, e
+        )
+    })?;
+    Ok(Some(self_hash))
+}
+
+/// Returns the normalized path (e.g. `$['proof'][0]['id']`) of the given location.
+fn normalized_path_of(location: &[JSONPathSegment]) -> String {
+    std::iter::once("$".to_string())
+        .chain(location.iter().map(ToString::to_string))
+        .collect()
+}
+
 /// This data structure provides the context necessary to process a serde_json::Value as
 /// self-hashable data in a configurable way, where the specific field name(s) for self-hash
-/// slots and self-hash URL slots are specifiable.
+/// slots, self-hash URL slots, and self-hash template slots are specifiable.
 #[derive(Clone)]
 pub struct SelfHashableJSON<'v, 'w: 'v> {
     /// This is the JSON value that is being self-hashed.
@@ -78,42 +305,73 @@ pub struct SelfHashableJSON<'v, 'w: 'v> {
     self_hash_path_s: Cow<'v, std::collections::HashSet<Cow<'w, str>>>,
     /// These are all the JSONPath queries whose elements are considered to define self-hash URL values.
     self_hash_url_path_s: Cow<'v, std::collections::HashSet<Cow<'w, str>>>,
-}
-
-/// We restrict admissible paths to ones that end in `.<identifier>`, so that there's a well-defined field
-/// name for each self-hash slot.
-fn jsonpath_terminating_identifier(path: &str) -> Result<&str> {
-    // Not sure if this is really necessary.
-    // ensure!(path.starts_with("$."), "self-hash [URL] path must begin with `$.` (indicating the path starts at the root element)");
-    let (_, after_period) = path.rsplit_once('.').ok_or_else(|| error!("self-hash [URL] path must end with `.<identifier>`, where <identifier> may not contain `[`, `]`, `'`, or `\"`; path was {}", path))?;
-    ensure!(
-        !after_period.contains('['),
-        "self-hash [URL] path terminating identifier may not contain `[`; path was {}",
-        path
-    );
-    ensure!(
-        !after_period.contains(']'),
-        "self-hash [URL] path terminating identifier may not contain `]`; path was {}",
-        path
-    );
-    ensure!(
-        !after_period.contains('\''),
-        "self-hash [URL] path terminating identifier may not contain `'`; path was {}",
-        path
-    );
-    ensure!(
-        !after_period.contains('"'),
-        "self-hash [URL] path terminating identifier may not contain `\"`; path was {}",
-        path
-    );
-    Ok(after_period)
+    /// These are all the JSON Pointers whose elements are considered to define self-hash values.
+    self_hash_pointer_s: Cow<'v, std::collections::HashSet<Cow<'w, str>>>,
+    /// These are all the JSON Pointers whose elements are considered to define self-hash URL values.
+    self_hash_url_pointer_s: Cow<'v, std::collections::HashSet<Cow<'w, str>>>,
+    /// These are the parsed forms of self_hash_path_s and self_hash_pointer_s.
+    self_hash_slot_selector_v: Vec<SlotSelector>,
+    /// These are the parsed forms of self_hash_url_path_s and self_hash_url_pointer_s.
+    self_hash_url_slot_selector_v: Vec<SlotSelector>,
+    /// These map JSONPath queries or JSON Pointers to the templates defining the self-hash template slots
+    /// they select.
+    self_hash_template_m: Cow<'v, std::collections::HashMap<Cow<'w, str>, Cow<'w, str>>>,
+    /// These are the parsed forms of self_hash_template_m.
+    self_hash_template_slot_selector_v: Vec<(SlotSelector, SelfHashTemplate)>,
 }
 
 impl<'v, 'w: 'v> SelfHashableJSON<'v, 'w> {
+    /// Each self-hash path and self-hash URL path must be an RFC 9535 JSONPath query (see JSONPath).
+    /// A singular query (e.g. `$.selfHash` or `$['@id']`) selects at most one slot, and if a self-hash
+    /// field selected by it doesn't exist already, it will be inserted into its parent, which must exist.
+    /// A multi-match query (e.g. `$.verificationMethod[*].controller`) may select any number of slots
+    /// (see SelfHashableJSON::with_self_hash_slot_cardinality to constrain this), and a missing self-hash
+    /// field will be inserted only if its parent is unambiguous.
     pub fn new(
         value: serde_json::Value,
         self_hash_path_s: Cow<'v, std::collections::HashSet<Cow<'w, str>>>,
         self_hash_url_path_s: Cow<'v, std::collections::HashSet<Cow<'w, str>>>,
+    ) -> Result<Self> {
+        Self::new_with_pointers(
+            value,
+            self_hash_path_s,
+            self_hash_url_path_s,
+            Cow::Owned(std::collections::HashSet::new()),
+            Cow::Owned(std::collections::HashSet::new()),
+        )
+    }
+    /// Like SelfHashableJSON::new, but additionally accepts self-hash and self-hash URL slots specified
+    /// as RFC 6901 JSON Pointers (e.g. `/selfHash` or `/proof/0/id`; see JSONPointer).  As with self-hash
+    /// paths, a self-hash field specified by a JSON Pointer doesn't have to exist already, but its parent
+    /// must exist and be a JSON object.
+    pub fn new_with_pointers(
+        value: serde_json::Value,
+        self_hash_path_s: Cow<'v, std::collections::HashSet<Cow<'w, str>>>,
+        self_hash_url_path_s: Cow<'v, std::collections::HashSet<Cow<'w, str>>>,
+        self_hash_pointer_s: Cow<'v, std::collections::HashSet<Cow<'w, str>>>,
+        self_hash_url_pointer_s: Cow<'v, std::collections::HashSet<Cow<'w, str>>>,
+    ) -> Result<Self> {
+        Self::new_with_templates(
+            value,
+            self_hash_path_s,
+            self_hash_url_path_s,
+            self_hash_pointer_s,
+            self_hash_url_pointer_s,
+            Cow::Owned(std::collections::HashMap::new()),
+        )
+    }
+    /// Like SelfHashableJSON::new_with_pointers, but additionally accepts self-hash template slots, given
+    /// as a map from selector (a JSONPath query if it begins with `$`, otherwise a JSON Pointer) to
+    /// template (see SelfHashTemplate), e.g. `$.id` -> `did:example:{selfHash}`.  Each selected field must
+    /// be a string matching its template (where the self-hash portion may be empty, meaning unset), or
+    /// null.  As with self-hash slots, a missing template field will be inserted into its parent.
+    pub fn new_with_templates(
+        value: serde_json::Value,
+        self_hash_path_s: Cow<'v, std::collections::HashSet<Cow<'w, str>>>,
+        self_hash_url_path_s: Cow<'v, std::collections::HashSet<Cow<'w, str>>>,
+        self_hash_pointer_s: Cow<'v, std::collections::HashSet<Cow<'w, str>>>,
+        self_hash_url_pointer_s: Cow<'v, std::collections::HashSet<Cow<'w, str>>>,
+        self_hash_template_m: Cow<'v, std::collections::HashMap<Cow<'w, str>, Cow<'w, str>>>,
     ) -> Result<Self> {
         ensure!(
             value.is_object(),
@@ -123,83 +381,358 @@ impl<'v, 'w: 'v> SelfHashableJSON<'v, 'w> {
             self_hash_path_s.is_disjoint(&self_hash_url_path_s),
             "self-hash paths and self-hash URL paths must be disjoint."
         );
-        // Verify that all the self-hash path query results are present (and are strings or nulls).
-        let mut self_hash_count = 0;
-        for self_hash_path in self_hash_path_s.iter().map(std::ops::Deref::deref) {
-            jsonpath_terminating_identifier(self_hash_path)?;
-            let mut selector = jsonpath_lib::selector(&value);
-            let mut query_result_count = 0;
-            for query_value in selector(self_hash_path)?.into_iter() {
+        ensure!(
+            self_hash_pointer_s.is_disjoint(&self_hash_url_pointer_s),
+            "self-hash pointers and self-hash URL pointers must be disjoint."
+        );
+        let self_hash_slot_selector_v = self_hash_path_s
+            .iter()
+            .map(|self_hash_path| JSONPath::parse(self_hash_path).map(SlotPath::JSONPath))
+            .chain(self_hash_pointer_s.iter().map(|self_hash_pointer| {
+                JSONPointer::parse(self_hash_pointer).map(SlotPath::JSONPointer)
+            }))
+            .map(|slot_path_r| slot_path_r.map(SlotSelector::new))
+            .collect::<Result<Vec<_>>>()?;
+        let self_hash_url_slot_selector_v = self_hash_url_path_s
+            .iter()
+            .map(|self_hash_url_path| JSONPath::parse(self_hash_url_path).map(SlotPath::JSONPath))
+            .chain(self_hash_url_pointer_s.iter().map(|self_hash_url_pointer| {
+                JSONPointer::parse(self_hash_url_pointer).map(SlotPath::JSONPointer)
+            }))
+            .map(|slot_path_r| slot_path_r.map(SlotSelector::new))
+            .collect::<Result<Vec<_>>>()?;
+        let self_hash_template_slot_selector_v = self_hash_template_m
+            .iter()
+            .map(|(selector, template)| {
+                ensure!(
+                    !self_hash_path_s.contains(selector)
+                        && !self_hash_url_path_s.contains(selector)
+                        && !self_hash_pointer_s.contains(selector)
+                        && !self_hash_url_pointer_s.contains(selector),
+                    "self-hash template selectors must be disjoint from self-hash [URL] paths and pointers; selector was {:?}",
+                    selector
+                );
+                Ok((
+                    SlotSelector::new(SlotPath::parse(selector)?),
+                    SelfHashTemplate::parse(template)?,
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        ensure!(self_hash_slot_selector_v.len() + self_hash_url_slot_selector_v.len() + self_hash_template_slot_selector_v.len() > 0, "no self-hash, self-hash URL, or self-hash template fields found, meaning that this JSON value is not self-hashable");
+
+        let self_hashable_json = SelfHashableJSON {
+            value,
+            self_hash_path_s,
+            self_hash_url_path_s,
+            self_hash_pointer_s,
+            self_hash_url_pointer_s,
+            self_hash_slot_selector_v,
+            self_hash_url_slot_selector_v,
+            self_hash_template_m,
+            self_hash_template_slot_selector_v,
+        };
+        self_hashable_json.validate()?;
+        Ok(self_hashable_json)
+    }
+    /// Sets the cardinality rule for the given self-hash [URL] path or pointer, which must be one of the
+    /// ones this SelfHashableJSON was constructed with.  The default is SelfHashSlotCardinality::AtMostOne
+    /// for singular selectors and SelfHashSlotCardinality::Any for multi-match selectors.
+    pub fn with_self_hash_slot_cardinality(
+        mut self,
+        selector: &str,
+        cardinality: SelfHashSlotCardinality,
+    ) -> Result<Self> {
+        let mut found = false;
+        for slot_selector in self
+            .self_hash_slot_selector_v
+            .iter_mut()
+            .chain(self.self_hash_url_slot_selector_v.iter_mut())
+            .chain(
+                self.self_hash_template_slot_selector_v
+                    .iter_mut()
+                    .map(|(slot_selector, _)| slot_selector),
+            )
+            .filter(|slot_selector| slot_selector.slot_path.as_str() == selector)
+        {
+            slot_selector.cardinality = cardinality;
+            found = true;
+        }
+        ensure!(
+            found,
+            "{:?} is not one of the self-hash [URL] paths, pointers, or template selectors of this SelfHashableJSON",
+            selector
+        );
+        self.validate()?;
+        Ok(self)
+    }
+    /// Verifies that all the self-hash slots are strings or nulls, or are missing but can be inserted,
+    /// that all the self-hash URL slots are present and are valid self-hash URLs, that all the self-hash
+    /// template slots match their templates, and that the cardinality rules are satisfied.
+    fn validate(&self) -> Result<()> {
+        for self_hash_slot_selector in self.self_hash_slot_selector_v.iter() {
+            let slot_path = &self_hash_slot_selector.slot_path;
+            let slot_resolution = self_hash_slot_selector.resolve(&self.value, true)?;
+            for location in slot_resolution.location_v.iter() {
+                let query_value = value_at(&self.value, location).expect("programmer error");
                 ensure!(
                     query_value.is_string() || query_value.is_null(),
                     "self-hash field (query was {:?}) is expected to be a string or null",
-                    self_hash_path
+                    slot_path.as_str()
                 );
                 if let Some(query_value_str) = query_value.as_str() {
                     ensure!(
                         mbx::MBHashStr::new_ref(query_value_str).is_ok(),
                         "self-hash field {:?} (query was {:?}) is expected to be a valid self-hash",
                         query_value,
-                        self_hash_path
+                        slot_path.as_str()
                     );
                 }
-                query_result_count += 1;
             }
-            match query_result_count {
-                0 => {
-                    // This is fine, this self-hash field will be added in the self-hashing operation.
-                }
-                1 => {
-                    // This is fine, this self-hash field was present.
-                }
-                _ => {
-                    bail!("self-hash path query returned more than 1 result (it returned {} results), which is not a valid self-hash path query by definition; path was {}", query_result_count, self_hash_path);
+            if slot_resolution.has_missing_slot {
+                // This is fine, this self-hash field will be added in the self-hashing operation,
+                // but only if there's a well-defined member name to add.
+                if !slot_path.has_terminating_name() {
+                    return Err(error!(
+                        "self-hash slot query returned 0 results, and the slot doesn't end with a member name, so the self-hash field can't be added; slot was {}",
+                        slot_path
+                    )
+                    .with_kind(ErrorKind::MissingSlot));
                 }
             }
-            self_hash_count += 1;
         }
-        // Verify that all the self-hash URL path query results are present (and are strings).
-        let mut self_hash_url_count = 0;
-        for self_hash_url_path in self_hash_url_path_s.iter().map(std::ops::Deref::deref) {
-            jsonpath_terminating_identifier(self_hash_url_path)?;
-            let mut selector = jsonpath_lib::selector(&value);
-            let mut query_result_count = 0;
-            for query_value in selector(self_hash_url_path)?.into_iter() {
+        for self_hash_url_slot_selector in self.self_hash_url_slot_selector_v.iter() {
+            let slot_path = &self_hash_url_slot_selector.slot_path;
+            let slot_resolution = self_hash_url_slot_selector.resolve(&self.value, false)?;
+            // A missing self-hash URL field is an error, because the form of the URL is specified by the
+            // value itself.
+            if slot_resolution.location_v.is_empty() && slot_path.is_singular() {
+                return Err(error!(
+                    "self-hash URL slot query returned 0 results, which is not a valid self-hash URL slot query by definition; slot was {}",
+                    slot_path
+                )
+                .with_kind(ErrorKind::MissingSlot));
+            }
+            for location in slot_resolution.location_v.iter() {
+                let query_value = value_at(&self.value, location).expect("programmer error");
                 ensure!(
                     query_value.is_string(),
                     "self-hash URL field (query was {:?}) is expected to be a string",
-                    self_hash_url_path
+                    slot_path.as_str()
                 );
-                ensure!(
-                    SelfHashURLStr::new_ref(query_value.as_str().unwrap()).is_ok(),
-                    "self-hash URL field {:?} (query was {:?}) is expected to be a valid self-hash URL",
-                    query_value,
-                    self_hash_url_path
-                );
-                query_result_count += 1;
+                let self_hash_url = SelfHashURLStr::new_ref(query_value.as_str().unwrap())
+                    .map_err(|e| {
+                        error!(
+                            "self-hash URL field {:?} (query was {:?}) is expected to be a valid self-hash URL; error was {}",
+                            query_value,
+                            slot_path.as_str(),
+                            e
+                        )
+                    })?;
+                // If the self-hash URL's self-hash slot is set, then it must be a valid self-hash.
+                if !self_hash_url.is_unset() {
+                    slot_path.try_as_hash_ref_of(query_value)?;
+                }
+            }
+        }
+        for (self_hash_template_slot_selector, self_hash_template) in
+            self.self_hash_template_slot_selector_v.iter()
+        {
+            let slot_path = &self_hash_template_slot_selector.slot_path;
+            let slot_resolution = self_hash_template_slot_selector.resolve(&self.value, true)?;
+            for location in slot_resolution.location_v.iter() {
+                let query_value = value_at(&self.value, location).expect("programmer error");
+                template_self_hash_o(query_value, self_hash_template, slot_path)?;
             }
-            match query_result_count {
-                0 => {
-                    // There were no results, so the field is missing, which is an error.
-                    bail!("self-hash URL path query returned 0 results, which is not a valid self-hash URL path query by definition; path was {}", self_hash_url_path);
+            if slot_resolution.has_missing_slot {
+                if !slot_path.has_terminating_name() {
+                    return Err(error!(
+                        "self-hash template slot query returned 0 results, and the slot doesn't end with a member name, so the self-hash template field can't be added; slot was {}",
+                        slot_path
+                    )
+                    .with_kind(ErrorKind::MissingSlot));
                 }
-                1 => {
-                    // The slot was already counted in the loop above.
+            }
+        }
+        // The selectors are disjoint as strings (see SelfHashableJSON::new_with_templates), but different
+        // selectors (e.g. a JSONPath query and a JSON Pointer) can still resolve to the same slot, which
+        // can only be one kind of slot.
+        let mut slot_kind_m = std::collections::HashMap::new();
+        for (slot_kind, location_v) in [
+            (
+                "self-hash",
+                self.resolved_slot_location_v(self.self_hash_slot_selector_v.iter(), true)?,
+            ),
+            (
+                "self-hash URL",
+                self.resolved_slot_location_v(self.self_hash_url_slot_selector_v.iter(), false)?,
+            ),
+            (
+                "self-hash template",
+                self.resolved_slot_location_v(
+                    self.self_hash_template_slot_selector_v
+                        .iter()
+                        .map(|(slot_selector, _)| slot_selector),
+                    true,
+                )?,
+            ),
+        ] {
+            for location in location_v.into_iter() {
+                let normalized_path = normalized_path_of(&location);
+                if let Some(other_slot_kind) = slot_kind_m.insert(location, slot_kind) {
+                    ensure!(
+                        other_slot_kind == slot_kind,
+                        "slot {} is selected as both a {} slot and a {} slot, but self-hash, self-hash URL, and self-hash template slots must be disjoint",
+                        normalized_path,
+                        other_slot_kind,
+                        slot_kind
+                    );
                 }
-                _ => {
-                    bail!("self-hash URL path query returned more than 1 result (it returned {} results), which is not a valid self-hash URL path query by definition; path was {}", query_result_count, self_hash_url_path);
+            }
+        }
+        Ok(())
+    }
+    /// Determines the substitutions necessary to set all the self-hash [URL] slots to the given hash,
+    /// including the insertion of any missing self-hash fields.  These are used both to write the digest
+    /// data (with the placeholder hash) without cloning the value, and to set the self-hash slots.
+    fn self_hash_slot_substitutions(&self, hash: &mbx::MBHashStr) -> Result<JSONSubstitutions> {
+        let mut substitutions = JSONSubstitutions::default();
+        for self_hash_slot_selector in self.self_hash_slot_selector_v.iter() {
+            let slot_path = &self_hash_slot_selector.slot_path;
+            let slot_resolution = self_hash_slot_selector.resolve(&self.value, true)?;
+            for location in slot_resolution.location_v.into_iter() {
+                substitutions.replace(location, hash.to_string());
+            }
+            // Because the self-hash fields aren't required to exist beforehand, if the field doesn't
+            // exist, then we have to insert it into its parent, which is determined by the slot's AST.
+            if slot_resolution.has_missing_slot {
+                let (parent_location, terminating_name) = slot_path
+                    .insertion_location(&self.value)
+                    .ok_or_else(|| {
+                        error!(
+                            "self-hash slot query parent must exist, be unique, and be a JSON object; slot was {}",
+                            slot_path
+                        )
+                        .with_kind(ErrorKind::MissingSlot)
+                    })?;
+                substitutions.insert(parent_location, terminating_name, hash.to_string());
+            }
+        }
+        for self_hash_url_slot_selector in self.self_hash_url_slot_selector_v.iter() {
+            let slot_path = &self_hash_url_slot_selector.slot_path;
+            let slot_resolution = self_hash_url_slot_selector.resolve(&self.value, false)?;
+            if slot_resolution.location_v.is_empty() && slot_path.is_singular() {
+                return Err(error!(
+                    "self-hash URL slot query returned 0 results; slot was {}",
+                    slot_path
+                )
+                .with_kind(ErrorKind::MissingSlot));
+            }
+            for location in slot_resolution.location_v.into_iter() {
+                let query_value = value_at(&self.value, &location).expect("programmer error");
+                let mut self_hash_url =
+                    SelfHashURL::try_from(query_value.as_str().ok_or_else(|| {
+                        error!(
+                            "self-hash URL slot query result must be a string; slot was {}",
+                            slot_path
+                        )
+                    })?)?;
+                self_hash_url.set_self_hash_slots_to_mb_hash(hash)?;
+                substitutions.replace(location, self_hash_url.to_string());
+            }
+        }
+        for (self_hash_template_slot_selector, self_hash_template) in
+            self.self_hash_template_slot_selector_v.iter()
+        {
+            let slot_path = &self_hash_template_slot_selector.slot_path;
+            let slot_resolution = self_hash_template_slot_selector.resolve(&self.value, true)?;
+            for location in slot_resolution.location_v.into_iter() {
+                substitutions.replace(location, self_hash_template.render(hash.as_str()));
+            }
+            if slot_resolution.has_missing_slot {
+                let (parent_location, terminating_name) = slot_path
+                    .insertion_location(&self.value)
+                    .ok_or_else(|| {
+                        error!(
+                            "self-hash template slot query parent must exist, be unique, and be a JSON object; slot was {}",
+                            slot_path
+                        )
+                        .with_kind(ErrorKind::MissingSlot)
+                    })?;
+                substitutions.insert(
+                    parent_location,
+                    terminating_name,
+                    self_hash_template.render(hash.as_str()),
+                );
+            }
+        }
+        Ok(substitutions)
+    }
+    /// Returns the normalized paths of the slots selected by the given selectors, including that of each
+    /// missing slot which would be inserted into its parent (see SlotPath::insertion_location).
+    fn resolved_slot_location_v<'a>(
+        &self,
+        slot_selector_i: impl Iterator<Item = &'a SlotSelector>,
+        allow_missing_slot: bool,
+    ) -> Result<Vec<Vec<JSONPathSegment>>> {
+        let mut location_v = Vec::new();
+        for slot_selector in slot_selector_i {
+            let slot_resolution = slot_selector.resolve(&self.value, allow_missing_slot)?;
+            location_v.extend(slot_resolution.location_v);
+            if slot_resolution.has_missing_slot {
+                if let Some((mut location, terminating_name)) =
+                    slot_selector.slot_path.insertion_location(&self.value)
+                {
+                    location.push(JSONPathSegment::Name(terminating_name));
+                    location_v.push(location);
                 }
             }
-            self_hash_url_count += 1;
         }
-
-        ensure!(self_hash_count + self_hash_url_count > 0, "no self-hash or self-hash URL fields found, meaning that this JSON value is not self-hashable");
-
-        Ok(SelfHashableJSON {
-            value,
-            self_hash_path_s,
-            self_hash_url_path_s,
-        })
+        Ok(location_v)
+    }
+    /// Returns the normalized paths (e.g. `$['selfHash']` or `$['proof'][0]['id']`) of all the self-hash,
+    /// self-hash URL, and self-hash template slots in the value, including any missing self-hash [template]
+    /// fields which would be inserted, sorted.  Unlike the selectors, these identify the individual slots.
+    pub fn self_hash_slot_normalized_paths(&self) -> Result<Vec<String>> {
+        let mut location_v =
+            self.resolved_slot_location_v(self.self_hash_slot_selector_v.iter(), true)?;
+        location_v.extend(
+            self.resolved_slot_location_v(self.self_hash_url_slot_selector_v.iter(), false)?,
+        );
+        location_v.extend(
+            self.resolved_slot_location_v(
+                self.self_hash_template_slot_selector_v
+                    .iter()
+                    .map(|(slot_selector, _)| slot_selector),
+                true,
+            )?,
+        );
+        let mut normalized_path_v = location_v
+            .iter()
+            .map(|location| normalized_path_of(location))
+            .collect::<Vec<_>>();
+        normalized_path_v.sort();
+        normalized_path_v.dedup();
+        Ok(normalized_path_v)
+    }
+    /// Returns the JSONPath queries whose elements are considered to define self-hash values.
+    pub fn self_hash_paths(&self) -> &std::collections::HashSet<Cow<'w, str>> {
+        self.self_hash_path_s.as_ref()
+    }
+    /// Returns the JSONPath queries whose elements are considered to define self-hash URL values.
+    pub fn self_hash_url_paths(&self) -> &std::collections::HashSet<Cow<'w, str>> {
+        self.self_hash_url_path_s.as_ref()
+    }
+    /// Returns the JSON Pointers whose elements are considered to define self-hash values.
+    pub fn self_hash_pointers(&self) -> &std::collections::HashSet<Cow<'w, str>> {
+        self.self_hash_pointer_s.as_ref()
+    }
+    /// Returns the JSON Pointers whose elements are considered to define self-hash URL values.
+    pub fn self_hash_url_pointers(&self) -> &std::collections::HashSet<Cow<'w, str>> {
+        self.self_hash_url_pointer_s.as_ref()
+    }
+    /// Returns the map from selector to template defining the self-hash template slots.
+    pub fn self_hash_templates(&self) -> &std::collections::HashMap<Cow<'w, str>, Cow<'w, str>> {
+        self.self_hash_template_m.as_ref()
     }
     pub fn value(&self) -> &serde_json::Value {
         &self.value
@@ -220,237 +753,111 @@ impl SelfHashableT<mbx::MBHashStr> for SelfHashableJSON<'_, '_> {
             mbx::MBHashStr,
         >>::Hasher,
     ) -> Result<()> {
-        let mut c = SelfHashableJSON {
-            value: self.value.clone(),
-            self_hash_path_s: self.self_hash_path_s.clone(),
-            self_hash_url_path_s: self.self_hash_url_path_s.clone(),
-        };
         use crate::HasherT;
         let placeholder_hash = hasher.hash_function().placeholder_hash();
-        c.set_self_hash_slots_to(placeholder_hash.as_ref())?;
-        // Use JCS to produce canonical output.
+        let substitutions = self.self_hash_slot_substitutions(placeholder_hash.as_ref())?;
+        // Use JCS to produce canonical output, writing the placeholder into the self-hash [URL] slots as
+        // it goes, so that the value doesn't have to be cloned.
         let mut writer = digest_io::IoWrapper(hasher);
-        serde_json_canonicalizer::to_writer(&c.value, &mut writer)?;
+        write_jcs_with_substitutions(&self.value, &substitutions, &mut writer)?;
         Ok(())
     }
     fn self_hash_oi<'a, 'b: 'a>(
         &'b self,
     ) -> Result<Box<dyn std::iter::Iterator<Item = Option<&'b mbx::MBHashStr>> + 'a>> {
-        // println!(
-        //     "SelfHashableJSON::self_hash_oi; self.value: {:?}",
-        //     self.value
-        // );
         // This will provide storage for the returned iterator.
         let mut self_hash_v = Vec::new();
-        // Iterate through all self-hash path query results.  For self-hash fields, a missing field
-        // is fine, it just counts as a None.
-        // TODO: Not sure how to detect missing fields, unless there's a known number of expected results.
-        for self_hash_path in self.self_hash_path_s.iter().map(std::ops::Deref::deref) {
-            // println!("    self_hash_path: {:?}", self_hash_path);
-            let mut selector = jsonpath_lib::selector(&self.value);
-            let mut query_result_count = 0;
-            for query_value in selector(self_hash_path)
-                .map_err(|e| error!("invalid self-hash path {}; error was {}", self_hash_path, e))?
-                .into_iter()
-            {
-                // println!("        query_value: {:?}", query_value);
-                match query_value {
+        // Iterate through all self-hash slot query results.  For self-hash fields, a missing field
+        // is fine, it just counts as a None (it will be added during set_self_hash_slots_to).
+        for self_hash_slot_selector in self.self_hash_slot_selector_v.iter() {
+            let slot_resolution = self_hash_slot_selector.resolve(&self.value, true)?;
+            for location in slot_resolution.location_v.iter() {
+                match value_at(&self.value, location).expect("programmer error") {
                     serde_json::Value::Null => {
                         self_hash_v.push(None);
                     }
-                    serde_json::Value::String(_) => {
-                        self_hash_v.push(Some(query_value.as_hash_ref()));
+                    query_value @ serde_json::Value::String(_) => {
+                        self_hash_v.push(Some(
+                            self_hash_slot_selector
+                                .slot_path
+                                .try_as_hash_ref_of(query_value)?,
+                        ));
                     }
                     _ => {
                         bail!(
-                            "self-hash path query result must be a string or null; path was {}",
-                            self_hash_path
+                            "self-hash slot query result must be a string or null; slot was {}",
+                            self_hash_slot_selector.slot_path
                         );
                     }
                 }
-                query_result_count += 1;
             }
-            match query_result_count {
-                0 => {
-                    // There were no results, so the field is missing, which counts as a None (it will be
-                    // added during set_self_hash_slots_to).
-                    self_hash_v.push(None);
-                }
-                1 => {
-                    // The slot was already counted in the loop above.
-                }
-                _ => {
-                    bail!("self-hash path query returned more than 1 result (it returned {} results), which is not a valid self-hash path query by definition; path was {}", query_result_count, self_hash_path);
-                }
+            if slot_resolution.has_missing_slot {
+                self_hash_v.push(None);
             }
         }
-        // Iterate through all self-hash URL path query results.  For self-hash URL fields, a missing
+        // Iterate through all self-hash URL slot query results.  For self-hash URL fields, a missing
         // field is an error, because the form of the URL is specified by the value itself.
-        // TODO: Not sure how to detect missing fields, unless there's a known number of expected results.
-        for self_hash_url_path in self.self_hash_url_path_s.iter().map(std::ops::Deref::deref) {
-            // println!("    self_hash_url_path: {:?}", self_hash_url_path);
-            let mut selector = jsonpath_lib::selector(&self.value);
-            let mut query_result_count = 0;
-            for query_value in selector(self_hash_url_path)
-                .map_err(|e| {
-                    error!(
-                        "invalid self-hash URL path {}; error was {}",
-                        self_hash_url_path, e
-                    )
-                })?
-                .into_iter()
+        for self_hash_url_slot_selector in self.self_hash_url_slot_selector_v.iter() {
+            let slot_resolution = self_hash_url_slot_selector.resolve(&self.value, false)?;
+            if slot_resolution.location_v.is_empty()
+                && self_hash_url_slot_selector.slot_path.is_singular()
             {
-                // println!("        query_value: {:?}", query_value);
-                match query_value {
-                    serde_json::Value::Null => {
-                        bail!("a self-hash URL path query result can not be missing for self-hashing or self-hash verification; path was {}", self_hash_url_path);
-                    }
-                    serde_json::Value::String(_) => {
-                        self_hash_v.push(Some(query_value.as_hash_ref()));
+                return Err(error!(
+                    "a self-hash URL slot query result can not be missing for self-hashing or self-hash verification; slot was {}",
+                    self_hash_url_slot_selector.slot_path
+                )
+                .with_kind(ErrorKind::MissingSlot));
+            }
+            for location in slot_resolution.location_v.iter() {
+                match value_at(&self.value, location).expect("programmer error") {
+                    query_value @ serde_json::Value::String(_) => {
+                        // A self-hash URL whose self-hash slot is unset (e.g. "vjson:///") counts as a None.
+                        if SelfHashURLStr::new_ref(query_value.as_str().unwrap())
+                            .map(SelfHashURLStr::is_unset)
+                            .unwrap_or(false)
+                        {
+                            self_hash_v.push(None);
+                        } else {
+                            self_hash_v.push(Some(
+                                self_hash_url_slot_selector
+                                    .slot_path
+                                    .try_as_hash_ref_of(query_value)?,
+                            ));
+                        }
                     }
                     _ => {
                         bail!(
-                            "self-hash URL path query result must be a string or null; path was {}",
-                            self_hash_url_path
+                            "self-hash URL slot query result must be a string; slot was {}",
+                            self_hash_url_slot_selector.slot_path
                         );
                     }
                 }
-                query_result_count += 1;
             }
-            match query_result_count {
-                0 => {
-                    // There were no results, so the field is missing, which is an error.
-                    bail!("self-hash URL path query returned 0 results, which is not a valid self-hash URL path query by definition; path was {}", self_hash_url_path);
-                }
-                1 => {
-                    // The slot was already counted in the loop above.
-                }
-                _ => {
-                    bail!("self-hash URL path query returned more than 1 result (it returned {} results), which is not a valid self-hash URL path query by definition; path was {}", query_result_count, self_hash_url_path);
-                }
+        }
+        // Iterate through all self-hash template slot query results.  As with self-hash fields, a missing
+        // field counts as a None.
+        for (self_hash_template_slot_selector, self_hash_template) in
+            self.self_hash_template_slot_selector_v.iter()
+        {
+            let slot_path = &self_hash_template_slot_selector.slot_path;
+            let slot_resolution = self_hash_template_slot_selector.resolve(&self.value, true)?;
+            for location in slot_resolution.location_v.iter() {
+                let query_value = value_at(&self.value, location).expect("programmer error");
+                self_hash_v.push(template_self_hash_o(
+                    query_value,
+                    self_hash_template,
+                    slot_path,
+                )?);
+            }
+            if slot_resolution.has_missing_slot {
+                self_hash_v.push(None);
             }
         }
         Ok(Box::new(self_hash_v.into_iter()))
     }
     fn set_self_hash_slots_to(&mut self, hash: &mbx::MBHashStr) -> Result<()> {
-        // println!("SelfHashableJSON::set_self_hash_slots_to");
-        let hash_string = hash.to_string();
-        // Because of the signature of jsonpath_lib::replace_with, we have to actually hand the ownership
-        // of Value over, and then take it back.  DUMB, but whateva.
-        let mut value = self.value.take();
-        for self_hash_path in self.self_hash_path_s.iter().map(std::ops::Deref::deref) {
-            // Because the self-hash fields aren't required to exist beforehand, we have to do some fanciness
-            // to set them.  If they exist already, then we can use jsonpath_lib::replace_with.  But if they
-            // don't exist, then we have to query the parent Value and operate on it.
-
-            let mut query_result_count = 0;
-            {
-                let query_result_count = &mut query_result_count;
-                value = jsonpath_lib::replace_with(
-                    value,
-                    self_hash_path,
-                    &mut |_query_value| -> Option<serde_json::Value> {
-                        // println!(
-                        //     "    self-hash path query result: {:?}; path was {}",
-                        //     _query_value, self_hash_path
-                        // );
-                        *query_result_count += 1;
-                        Some(serde_json::Value::String(hash_string.clone()))
-                    },
-                )
-                .map_err(|e| {
-                    error!("invalid self-hash path {}; error was {}", self_hash_path, e)
-                })?;
-            }
-
-            match query_result_count {
-                0 => {
-                    // We have to query the parent and operate on it manually.
-                    let terminating_identifier = jsonpath_terminating_identifier(self_hash_path).expect("programmer error: this should be impossible due to validation in SelfHashableJSON::new");
-                    let parent_path = self_hash_path
-                        .strip_suffix(terminating_identifier)
-                        .unwrap()
-                        .strip_suffix('.')
-                        .unwrap();
-                    // println!("    self-hash path query produced 0 results, so have to query the parent and modify directly.\n        path was: {}\n        parent_path: {}\n        terminating_identifier: {}", self_hash_path, parent_path, terminating_identifier);
-                    if parent_path == "$" {
-                        // println!("    modifying parent Value: {:?}", value);
-                        // It seems that jsonpath_lib::replace_with doesn't work if you specify path "$",
-                        // so we have to handle this case directly to work around it.
-                        let parent = value.as_object_mut().ok_or_else(|| {
-                            error!(
-                                "self-hash path query parent (parent path was {}) must be a JSON object; path was {}",
-                                parent_path,
-                                self_hash_path
-                            )
-                        })?;
-                        debug_assert!(!parent.contains_key(terminating_identifier));
-                        parent.insert(
-                            terminating_identifier.to_string(),
-                            serde_json::Value::String(hash_string.clone()),
-                        );
-                    } else {
-                        // First, validate that the parent_query will produce a JSON object, so we can return
-                        // an appropriate error if it's not instead of panicking.
-                        {
-                            let mut selector = jsonpath_lib::selector(&value);
-                            let mut query_result_count = 0;
-                            for query_value in selector(parent_path)?.into_iter() {
-                                ensure!(query_value.is_object(), "self-hash path query parent (parent path was {}) must be a JSON object; path was {}", parent_path, self_hash_path);
-                                query_result_count += 1;
-                            }
-                            ensure!(query_result_count == 1, "self-hash path query parent (parent path was {}) must produce exactly 1 result (it produced {} results); path was {}", parent_path, query_result_count, self_hash_path);
-                        }
-                        value = jsonpath_lib::replace_with(
-                            value,
-                            parent_path,
-                            &mut |mut query_value| -> Option<serde_json::Value> {
-                                // println!("    modifying parent Value: {:?}", query_value);
-                                {
-                                    let parent = query_value.as_object_mut().unwrap();
-                                    debug_assert!(!parent.contains_key(terminating_identifier));
-                                    parent.insert(
-                                        terminating_identifier.to_string(),
-                                        serde_json::Value::String(hash_string.clone()),
-                                    );
-                                }
-                                Some(query_value)
-                            },
-                        )
-                        .map_err(|e| {
-                            error!("invalid self-hash path {}; error was {}", self_hash_path, e)
-                        })?;
-                    }
-                }
-                1 => {
-                    // The query was already handled.  Nothing more to do.
-                }
-                _ => {
-                    panic!("programmer error (or the JSON value was modified in an unexpected way before self-hashing, which is still a programmer error)");
-                }
-            }
-        }
-        for self_hash_url_path in self.self_hash_url_path_s.iter().map(std::ops::Deref::deref) {
-            value = jsonpath_lib::replace_with(
-                value,
-                self_hash_url_path,
-                &mut |query_value| -> Option<serde_json::Value> {
-                    let mut self_hash_url =
-                        SelfHashURL::try_from(query_value.as_str().expect("programmer error"))
-                            .unwrap();
-                    self_hash_url.set_self_hash_slots_to_mb_hash(hash);
-                    Some(serde_json::Value::String(self_hash_url.to_string()))
-                },
-            )
-            .map_err(|e| {
-                error!(
-                    "invalid self-hash URL path {}; error was {}",
-                    self_hash_url_path, e
-                )
-            })?;
-        }
-        // Give the Value back.
-        self.value = value;
+        let substitutions = self.self_hash_slot_substitutions(hash)?;
+        substitutions.apply_to(&mut self.value);
         Ok(())
     }
 }
@@ -1,4 +1,4 @@
-use crate::{bail, ensure, error, Error, HashFunctionT, HashRefT, HasherT, Result};
+use crate::{bail, ensure, error, Error, ErrorKind, HashFunctionT, HashRefT, HasherT, Result};
 
 /// This is the canonical implementation of the SelfHashable::write_digest_data
 /// method for when the SelfHashable type implements Clone and the desired desired serialization
@@ -26,6 +26,140 @@ where
     Ok(())
 }
 
+/// This is like write_digest_data_using_jcs, except that instead of cloning the SelfHashable and setting its
+/// self-hash slots to the placeholder, it serializes the SelfHashable directly, substituting the placeholder
+/// for the value of each SelfHashSlot field as it goes (see SelfHashSlot and SelfHashSlotSerializer).  Thus
+/// it doesn't require Clone.  Note that JCS requires object members to be sorted by key, so the JCS
+/// canonicalizer buffers the members of each object as it goes, but the SelfHashable itself isn't cloned.
+/// Each self-hash slot of the SelfHashable must be a SelfHashSlot field, since other fields are written as-is.
+/// For a SelfHashable whose self-hash slots are all SelfHashSlot fields, this produces the same digest data
+/// as write_digest_data_using_jcs.
+#[cfg(all(feature = "jcs", feature = "streaming"))]
+pub fn write_digest_data_streaming_jcs<
+    HashRef: HashRefT + ?Sized + ToOwned,
+    S: SelfHashableT<HashRef> + serde::Serialize,
+>(
+    self_hashable: &S,
+    hasher: &mut <<HashRef as HashRefT>::HashFunction as crate::HashFunctionT<HashRef>>::Hasher,
+) -> Result<()>
+where
+    HashRef::Owned: serde::Serialize,
+    for<'a> &'a mut <<HashRef as HashRefT>::HashFunction as HashFunctionT<HashRef>>::Hasher:
+        digest::Update,
+{
+    use crate::HashFunctionT;
+    let placeholder = hasher.hash_function().placeholder_hash().into_owned();
+    let with_placeholder = crate::WithSelfHashPlaceholder::new(self_hashable, &placeholder);
+    // Use JCS to produce canonical output.
+    let mut writer = digest_io::IoWrapper(hasher);
+    serde_json_canonicalizer::to_writer(&with_placeholder, &mut writer)
+        .map_err(|e| error!("Failed to write digest data using JCS; error was {}", e))?;
+    Ok(())
+}
+
+/// This is the canonical implementation of the SelfHashable::write_digest_data method for when the
+/// SelfHashable type implements Clone and the desired serialization format is deterministically encoded
+/// CBOR (see RFC 8949, section 4.2.1).  Simply call this method from your implementation of
+/// SelfHashable::write_digest_data.
+#[cfg(feature = "cbor")]
+pub fn write_digest_data_using_dcbor<
+    HashRef: HashRefT + ?Sized + ToOwned,
+    S: Clone + SelfHashableT<HashRef> + serde::Serialize,
+>(
+    self_hashable: &S,
+    hasher: &mut <<HashRef as HashRefT>::HashFunction as crate::HashFunctionT<HashRef>>::Hasher,
+) -> Result<()>
+where
+    for<'a> &'a mut <<HashRef as HashRefT>::HashFunction as HashFunctionT<HashRef>>::Hasher:
+        digest::Update,
+{
+    let mut c = self_hashable.clone();
+    use crate::HashFunctionT;
+    c.set_self_hash_slots_to(hasher.hash_function().placeholder_hash().as_ref())?;
+    // Use deterministically encoded CBOR to produce canonical output.
+    let value = ciborium::Value::serialized(&c)
+        .map_err(|e| error!("Failed to write digest data using dCBOR; error was {}", e))?;
+    let mut writer = digest_io::IoWrapper(hasher);
+    crate::dcbor_writer::write_dcbor(&value, &mut writer)
+}
+
+/// This is the canonical implementation of the SelfHashable::write_digest_data method for when the
+/// SelfHashable type implements Clone and the desired serialization format is postcard (see
+/// https://postcard.jamesmunch.com/wire-format).  Simply call this method from your implementation of
+/// SelfHashable::write_digest_data.
+///
+/// The postcard encoding of a value is determined by its serde data model representation, so it's
+/// canonical for types whose serialization is deterministic, which includes primitives, String, Vec,
+/// arrays, tuples, Option, BTreeMap, BTreeSet, and structs and enums composed of these.  It's not canonical
+/// for types whose iteration order is unspecified, such as HashMap and HashSet.  To catch the most common
+/// such mistake, this returns an error if any map is serialized with its keys out of order (as a HashMap
+/// generally is); note that a HashSet can't be detected this way.  Keys are compared by the derived order of
+/// their serialized form, so a map whose key type has a custom Ord which disagrees with that (e.g.
+/// `BTreeMap<std::cmp::Reverse<u32>, _>`) is also rejected, even though it's deterministic.  Also note that
+/// floating-point values are encoded bitwise, so e.g. 0.0 and -0.0 (or NaNs with different payloads)
+/// produce different digests.
+#[cfg(feature = "postcard")]
+pub fn write_digest_data_using_postcard<
+    HashRef: HashRefT + ?Sized + ToOwned,
+    S: Clone + SelfHashableT<HashRef> + serde::Serialize,
+>(
+    self_hashable: &S,
+    hasher: &mut <<HashRef as HashRefT>::HashFunction as crate::HashFunctionT<HashRef>>::Hasher,
+) -> Result<()>
+where
+    for<'a> &'a mut <<HashRef as HashRefT>::HashFunction as HashFunctionT<HashRef>>::Hasher:
+        digest::Update,
+{
+    let mut c = self_hashable.clone();
+    use crate::HashFunctionT;
+    c.set_self_hash_slots_to(hasher.hash_function().placeholder_hash().as_ref())?;
+    crate::map_order_check::ensure_maps_are_ordered(&c)?;
+    // Use postcard to produce canonical output.
+    postcard::to_io(&c, digest_io::IoWrapper(hasher)).map_err(|e| {
+        error!(
+            "Failed to write digest data using postcard; error was {}",
+            e
+        )
+    })?;
+    Ok(())
+}
+
+/// This is like write_digest_data_using_postcard, except that instead of cloning the SelfHashable and
+/// setting its self-hash slots to the placeholder, it streams the postcard encoding directly into the hasher,
+/// substituting the placeholder for the value of each SelfHashSlot field as it goes (see SelfHashSlot and
+/// SelfHashSlotSerializer).  Thus it doesn't require Clone and doesn't copy the data; the check for out-of-order
+/// map keys is a separate pass over the value which holds only the most recent key of each map being checked.
+/// As with write_digest_data_using_postcard, maps whose key type has a custom Ord that disagrees with the
+/// derived order of the keys' serialized form (e.g. `BTreeMap<std::cmp::Reverse<u32>, _>`) are rejected.
+/// Each self-hash slot of the SelfHashable must be a SelfHashSlot field, since other fields are written as-is.
+/// For a SelfHashable whose self-hash slots are all SelfHashSlot fields, this produces the same digest data
+/// as write_digest_data_using_postcard.
+#[cfg(all(feature = "postcard", feature = "streaming"))]
+pub fn write_digest_data_streaming_postcard<
+    HashRef: HashRefT + ?Sized + ToOwned,
+    S: SelfHashableT<HashRef> + serde::Serialize,
+>(
+    self_hashable: &S,
+    hasher: &mut <<HashRef as HashRefT>::HashFunction as crate::HashFunctionT<HashRef>>::Hasher,
+) -> Result<()>
+where
+    HashRef::Owned: serde::Serialize,
+    for<'a> &'a mut <<HashRef as HashRefT>::HashFunction as HashFunctionT<HashRef>>::Hasher:
+        digest::Update,
+{
+    use crate::HashFunctionT;
+    let placeholder = hasher.hash_function().placeholder_hash().into_owned();
+    let with_placeholder = crate::WithSelfHashPlaceholder::new(self_hashable, &placeholder);
+    crate::map_order_check::ensure_maps_are_ordered(&with_placeholder)?;
+    postcard::to_io(&with_placeholder, digest_io::IoWrapper(hasher)).map_err(|e| {
+        error!(
+            "Failed to write digest data using postcard; error was {}",
+            e
+        )
+    })?;
+    Ok(())
+}
+
 /// This trait allows a self-hashing procedure to be defined for a data type.  The data type must implement
 /// the following required methods:
 /// - self_hash_oi: defines the self-hash slots.
@@ -80,21 +214,21 @@ pub trait SelfHashableT<HashRef: HashRefT + ?Sized + ToOwned> {
                 // All self-hash slots are populated, so we have to check them.
             }
             Some(_) => {
-                bail!("This object is a malformed as SelfHashing because some but not all self-hash slots are populated -- it must be all or nothing.");
+                return Err(error!("This object is a malformed as SelfHashing because some but not all self-hash slots are populated -- it must be all or nothing.").with_kind(ErrorKind::MissingSlot));
             }
             None => {
-                bail!("This object has no self-hash slots, and therefore can't be self-hashed or self-verified.");
+                return Err(error!("This object has no self-hash slots, and therefore can't be self-hashed or self-verified.").with_kind(ErrorKind::MissingSlot));
             }
         }
 
         let first_self_hash = self.self_hash_oi()?.nth(0).unwrap().unwrap();
         // Now ensure all self-hash slots are equal.
         for self_hash in self.self_hash_oi()?.map(|self_hash_o| self_hash_o.unwrap()) {
-            ensure!(
-                // self_hash.equals(first_self_hash),
-                self_hash == first_self_hash,
-                "Object's self-hash slots do not all match."
-            );
+            // if !self_hash.equals(first_self_hash) {
+            if self_hash != first_self_hash {
+                return Err(error!("Object's self-hash slots do not all match.")
+                    .with_kind(ErrorKind::HashMismatch));
+            }
         }
         // If it got this far, it's valid.
         Ok(Some(first_self_hash))
@@ -131,6 +265,7 @@ pub trait SelfHashableT<HashRef: HashRefT + ?Sized + ToOwned> {
             .nth(0)
             .ok_or_else(|| {
                 error!("This object has no self-hash slots, and therefore can't be self-hashed.")
+                    .with_kind(ErrorKind::MissingSlot)
             })?
             .unwrap();
         Ok(first_self_hash)
@@ -138,7 +273,10 @@ pub trait SelfHashableT<HashRef: HashRefT + ?Sized + ToOwned> {
     /// Verifies the self-hashes in this object and returns a reference to the verified self-hash.
     fn verify_self_hashes<'a, 'b: 'a>(&'b self) -> Result<&'a HashRef> {
         let unverified_self_hash = self.get_unverified_self_hash()?.ok_or_else(|| {
-            "This object has no self-hash slots, and therefore can't be self-verified."
+            error!(
+                "This object's self-hash slots are unset, and therefore it can't be self-verified."
+            )
+            .with_kind(ErrorKind::MissingSlot)
         })?;
         // Now compute the digest which will be used either as the direct hash value, or as the input
         // to the signature algorithm.
@@ -151,7 +289,7 @@ pub trait SelfHashableT<HashRef: HashRefT + ?Sized + ToOwned> {
         if computed_self_hash.borrow() != unverified_self_hash {
             return Err(
                 Error::from(format!("This object's computed self-hash ({:?}) doesn't match the object's claimed self-hash ({:?}).",
-                computed_self_hash.borrow(), unverified_self_hash))
+                computed_self_hash.borrow(), unverified_self_hash)).with_kind(ErrorKind::HashMismatch)
             );
         }
         // If it got this far, it's valid.
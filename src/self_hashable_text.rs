@@ -0,0 +1,324 @@
+use crate::{
+    ensure, error, HashFunctionT, HashRefT, Result, SelfHashSlotCardinality, SelfHashableT,
+    TextSlotMarker,
+};
+use std::{borrow::Cow, io::Write};
+
+/// Specifies how line endings in a text file are normalized before its digest is computed (see
+/// SelfHashableText).  Normalization only applies to the digest data; the text itself is never modified
+/// other than to set its self-hash slots.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum LineEndingNormalization {
+    /// Line endings are hashed as they are.
+    #[default]
+    None,
+    /// CRLF and lone CR line endings are hashed as LF, so that e.g. a checkout which converts line endings
+    /// still verifies.
+    LF,
+    /// LF and lone CR line endings are hashed as CRLF.
+    CRLF,
+}
+
+impl LineEndingNormalization {
+    /// Writes the given bytes to the writer with this normalization applied.  Note that a CR at the end of
+    /// the given bytes is treated as a lone CR, which is correct as long as no LF immediately follows it in
+    /// the text, which holds for digest data, since slots (which contain only multibase characters) are the
+    /// only breaks between the chunks it's written in.
+    fn write_normalized(self, bytes: &[u8], writer: &mut impl Write) -> std::io::Result<()> {
+        let line_ending: &[u8] = match self {
+            Self::None => {
+                return writer.write_all(bytes);
+            }
+            Self::LF => b"\n",
+            Self::CRLF => b"\r\n",
+        };
+        let mut rest = bytes;
+        while let Some(i) = rest.iter().position(|&b| b == b'\r' || b == b'\n') {
+            writer.write_all(&rest[..i])?;
+            writer.write_all(line_ending)?;
+            let line_ending_len = if rest[i..].starts_with(b"\r\n") { 2 } else { 1 };
+            rest = &rest[i + line_ending_len..];
+        }
+        writer.write_all(rest)
+    }
+}
+
+impl std::fmt::Display for LineEndingNormalization {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::None => f.write_str("none"),
+            Self::LF => f.write_str("lf"),
+            Self::CRLF => f.write_str("crlf"),
+        }
+    }
+}
+
+impl std::str::FromStr for LineEndingNormalization {
+    type Err = crate::Error;
+    /// Parses "none", "lf", or "crlf".
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "lf" => Ok(Self::LF),
+            "crlf" => Ok(Self::CRLF),
+            _ => Err(error!(
+                "unrecognized line ending normalization {:?}; expected \"none\", \"lf\", or \"crlf\"",
+                s
+            )),
+        }
+    }
+}
+
+/// A TextSlotMarker together with the cardinality rule for how many slots it must match.
+#[derive(Clone, Debug)]
+struct SlotSelector {
+    text_slot_marker: TextSlotMarker,
+    cardinality: SelfHashSlotCardinality,
+}
+
+/// Interprets the given self-hash slot bytes, where the empty slot means unset.
+fn self_hash_o<'a>(
+    self_hash_bytes: &'a [u8],
+    text_slot_marker: &TextSlotMarker,
+) -> Result<Option<&'a mbx::MBHashStr>> {
+    if self_hash_bytes.is_empty() {
+        return Ok(None);
+    }
+    let self_hash_str = std::str::from_utf8(self_hash_bytes).map_err(|_| {
+        error!(
+            "self-hash slot is expected to be a valid self-hash, but is not valid UTF-8; marker was {:?}",
+            text_slot_marker.as_str()
+        )
+    })?;
+    let self_hash = mbx::MBHashStr::new_ref(self_hash_str).map_err(|e| {
+        error!(
+            "self-hash slot {:?} is expected to be a valid self-hash; marker was {:?}; error was {}",
+            self_hash_str,
+            text_slot_marker.as_str(),
+            e
+        )
+    })?;
+    Ok(Some(self_hash))
+}
+
+/// This provides self-hashable text files (e.g. source files, Markdown, or plain-text policies) which carry
+/// their own self-hash, e.g. in a header line `selfhash: <self-hash>`.  The self-hash slots are found by
+/// TextSlotMarker-s, given as regexes or templates, and an empty slot means unset.  There is no notion of
+/// a missing slot: each marker must match at least one slot by default (see
+/// SelfHashableText::with_self_hash_slot_cardinality).
+///
+/// The digest is computed over the raw bytes of the text, with each self-hash slot replaced byte-for-byte
+/// by the placeholder, and with no canonicalization other than the optional normalization of line endings
+/// (see LineEndingNormalization).  Since the self-hash has the same length as the placeholder, setting the
+/// self-hash slots changes no other bytes of the text.
+#[derive(Clone)]
+pub struct SelfHashableText<'v, 'w: 'v> {
+    /// This is the text that is being self-hashed.
+    text: Vec<u8>,
+    /// These are all the regexes whose matches are considered to be self-hash slots.
+    self_hash_regex_s: Cow<'v, std::collections::HashSet<Cow<'w, str>>>,
+    /// These are all the templates whose matches are considered to be self-hash slots.
+    self_hash_template_s: Cow<'v, std::collections::HashSet<Cow<'w, str>>>,
+    /// These are the parsed forms of self_hash_regex_s and self_hash_template_s.
+    self_hash_slot_selector_v: Vec<SlotSelector>,
+    line_ending_normalization: LineEndingNormalization,
+}
+
+impl<'v, 'w: 'v> SelfHashableText<'v, 'w> {
+    /// Sets up the given text for self-hashing, with self-hash slots found by the given regexes and
+    /// templates (see TextSlotMarker).  Each slot must be empty or a valid self-hash, and the slots must
+    /// not overlap.
+    pub fn new(
+        text: impl Into<Vec<u8>>,
+        self_hash_regex_s: Cow<'v, std::collections::HashSet<Cow<'w, str>>>,
+        self_hash_template_s: Cow<'v, std::collections::HashSet<Cow<'w, str>>>,
+    ) -> Result<Self> {
+        let self_hash_slot_selector_v = self_hash_regex_s
+            .iter()
+            .map(|self_hash_regex| TextSlotMarker::from_regex(self_hash_regex))
+            .chain(
+                self_hash_template_s
+                    .iter()
+                    .map(|self_hash_template| TextSlotMarker::from_template(self_hash_template)),
+            )
+            .map(|text_slot_marker_r| {
+                text_slot_marker_r.map(|text_slot_marker| SlotSelector {
+                    text_slot_marker,
+                    cardinality: SelfHashSlotCardinality::AtLeastOne,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        ensure!(
+            !self_hash_slot_selector_v.is_empty(),
+            "no self-hash regexes or templates found, meaning that this text is not self-hashable"
+        );
+
+        let self_hashable_text = Self {
+            text: text.into(),
+            self_hash_regex_s,
+            self_hash_template_s,
+            self_hash_slot_selector_v,
+            line_ending_normalization: LineEndingNormalization::None,
+        };
+        self_hashable_text.validate()?;
+        Ok(self_hashable_text)
+    }
+    /// Sets the line ending normalization applied to the digest data.  The default is
+    /// LineEndingNormalization::None.
+    pub fn with_line_ending_normalization(
+        mut self,
+        line_ending_normalization: LineEndingNormalization,
+    ) -> Self {
+        self.line_ending_normalization = line_ending_normalization;
+        self
+    }
+    /// Sets the cardinality rule for the given self-hash regex or template, which must be one of the ones
+    /// this SelfHashableText was constructed with.  The default is SelfHashSlotCardinality::AtLeastOne.
+    pub fn with_self_hash_slot_cardinality(
+        mut self,
+        marker: &str,
+        cardinality: SelfHashSlotCardinality,
+    ) -> Result<Self> {
+        let mut found = false;
+        for slot_selector in self
+            .self_hash_slot_selector_v
+            .iter_mut()
+            .filter(|slot_selector| slot_selector.text_slot_marker.as_str() == marker)
+        {
+            slot_selector.cardinality = cardinality;
+            found = true;
+        }
+        ensure!(
+            found,
+            "{:?} is not one of the self-hash regexes or templates of this SelfHashableText",
+            marker
+        );
+        self.validate()?;
+        Ok(self)
+    }
+    /// Verifies that all the self-hash slots are empty or valid self-hashes, and that the cardinality rules
+    /// are satisfied.
+    fn validate(&self) -> Result<()> {
+        for (self_hash_range, text_slot_marker) in self.self_hash_slot_v()? {
+            self_hash_o(&self.text[self_hash_range], text_slot_marker)?;
+        }
+        Ok(())
+    }
+    /// Returns the byte ranges of the self-hashes of all the self-hash slots (in order), along with the
+    /// markers that matched them, checking the cardinality rules and that the slots don't overlap.
+    fn self_hash_slot_v(&self) -> Result<Vec<(std::ops::Range<usize>, &TextSlotMarker)>> {
+        let mut self_hash_slot_v = Vec::new();
+        for slot_selector in self.self_hash_slot_selector_v.iter() {
+            let self_hash_range_v = slot_selector
+                .text_slot_marker
+                .self_hash_range_v(&self.text)?;
+            slot_selector.cardinality.ensure_admits(
+                self_hash_range_v.len(),
+                slot_selector.text_slot_marker.as_str(),
+            )?;
+            self_hash_slot_v.extend(
+                self_hash_range_v
+                    .into_iter()
+                    .map(|self_hash_range| (self_hash_range, &slot_selector.text_slot_marker)),
+            );
+        }
+        self_hash_slot_v
+            .sort_by_key(|(self_hash_range, _)| (self_hash_range.start, self_hash_range.end));
+        for pair in self_hash_slot_v.windows(2) {
+            ensure!(
+                pair[0].0.end <= pair[1].0.start && pair[0].0 != pair[1].0,
+                "self-hash slots matched by markers {:?} and {:?} overlap at byte offset {}",
+                pair[0].1.as_str(),
+                pair[1].1.as_str(),
+                pair[1].0.start
+            );
+        }
+        Ok(self_hash_slot_v)
+    }
+    /// Returns the byte ranges of the self-hashes of all the self-hash slots in the text, in order, where an
+    /// unset self-hash is an empty range.
+    pub fn self_hash_slot_ranges(&self) -> Result<Vec<std::ops::Range<usize>>> {
+        Ok(self
+            .self_hash_slot_v()?
+            .into_iter()
+            .map(|(self_hash_range, _)| self_hash_range)
+            .collect())
+    }
+    /// Returns the regexes whose matches are considered to be self-hash slots.
+    pub fn self_hash_regexes(&self) -> &std::collections::HashSet<Cow<'w, str>> {
+        self.self_hash_regex_s.as_ref()
+    }
+    /// Returns the templates whose matches are considered to be self-hash slots.
+    pub fn self_hash_templates(&self) -> &std::collections::HashSet<Cow<'w, str>> {
+        self.self_hash_template_s.as_ref()
+    }
+    pub fn line_ending_normalization(&self) -> LineEndingNormalization {
+        self.line_ending_normalization
+    }
+    /// Returns the text, including its self-hash slots.
+    pub fn as_bytes(&self) -> &[u8] {
+        self.text.as_slice()
+    }
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.text
+    }
+}
+
+impl SelfHashableT<mbx::MBHashStr> for SelfHashableText<'_, '_> {
+    fn write_digest_data(
+        &self,
+        hasher: &mut <<mbx::MBHashStr as HashRefT>::HashFunction as HashFunctionT<
+            mbx::MBHashStr,
+        >>::Hasher,
+    ) -> Result<()> {
+        use crate::HasherT;
+        let placeholder_hash_string = hasher.hash_function().placeholder_hash().to_string();
+        let self_hash_slot_v = self.self_hash_slot_v()?;
+        // Stream the text into the hasher, substituting the placeholder for each self-hash slot, so that
+        // the text doesn't have to be copied.
+        let mut writer = digest_io::IoWrapper(hasher);
+        let mut write_digest_data = || -> std::io::Result<()> {
+            let mut offset = 0;
+            for (self_hash_range, _) in self_hash_slot_v.iter() {
+                self.line_ending_normalization
+                    .write_normalized(&self.text[offset..self_hash_range.start], &mut writer)?;
+                writer.write_all(placeholder_hash_string.as_bytes())?;
+                offset = self_hash_range.end;
+            }
+            self.line_ending_normalization
+                .write_normalized(&self.text[offset..], &mut writer)
+        };
+        write_digest_data().map_err(|e| error!("failed to write digest data; error was {}", e))
+    }
+    fn self_hash_oi<'a, 'b: 'a>(
+        &'b self,
+    ) -> Result<Box<dyn std::iter::Iterator<Item = Option<&'b mbx::MBHashStr>> + 'a>> {
+        let self_hash_v = self
+            .self_hash_slot_v()?
+            .into_iter()
+            .map(|(self_hash_range, text_slot_marker)| {
+                self_hash_o(&self.text[self_hash_range], text_slot_marker)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Box::new(self_hash_v.into_iter()))
+    }
+    fn set_self_hash_slots_to(&mut self, hash: &mbx::MBHashStr) -> Result<()> {
+        let self_hash_range_v = self
+            .self_hash_slot_v()?
+            .into_iter()
+            .map(|(self_hash_range, _)| self_hash_range)
+            .collect::<Vec<_>>();
+        let hash_str = hash.to_string();
+        let mut text =
+            Vec::with_capacity(self.text.len() + self_hash_range_v.len() * hash_str.len());
+        let mut offset = 0;
+        for self_hash_range in self_hash_range_v {
+            text.extend_from_slice(&self.text[offset..self_hash_range.start]);
+            text.extend_from_slice(hash_str.as_bytes());
+            offset = self_hash_range.end;
+        }
+        text.extend_from_slice(&self.text[offset..]);
+        self.text = text;
+        Ok(())
+    }
+}
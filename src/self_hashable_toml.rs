@@ -0,0 +1,332 @@
+use crate::{
+    bail, dcbor_writer::write_dcbor, ensure, error, ErrorKind, HashFunctionT, HashRefT, Result,
+    SelfHashURL, SelfHashURLStr, SelfHashableT,
+};
+use std::borrow::Cow;
+
+/// Converts a TOML item into the canonical digest form of TOML data (see SelfHashableTOML).
+fn toml_item_to_cbor(item: &toml_edit::Item) -> Result<ciborium::Value> {
+    match item {
+        toml_edit::Item::None => {
+            bail!("programmer error: TOML item is unexpectedly empty");
+        }
+        toml_edit::Item::Value(value) => toml_value_to_cbor(value),
+        toml_edit::Item::Table(table) => toml_table_like_to_cbor(table),
+        toml_edit::Item::ArrayOfTables(array_of_tables) => Ok(ciborium::Value::Array(
+            array_of_tables
+                .iter()
+                .map(|table| toml_table_like_to_cbor(table))
+                .collect::<Result<Vec<_>>>()?,
+        )),
+    }
+}
+
+fn toml_table_like_to_cbor(table_like: &dyn toml_edit::TableLike) -> Result<ciborium::Value> {
+    Ok(ciborium::Value::Map(
+        table_like
+            .iter()
+            .map(|(key, item)| {
+                Ok((
+                    ciborium::Value::Text(key.to_string()),
+                    toml_item_to_cbor(item)?,
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?,
+    ))
+}
+
+fn toml_value_to_cbor(value: &toml_edit::Value) -> Result<ciborium::Value> {
+    Ok(match value {
+        toml_edit::Value::String(s) => ciborium::Value::Text(s.value().clone()),
+        toml_edit::Value::Integer(n) => ciborium::Value::Integer((*n.value()).into()),
+        toml_edit::Value::Float(x) => ciborium::Value::Float(*x.value()),
+        toml_edit::Value::Boolean(b) => ciborium::Value::Bool(*b.value()),
+        toml_edit::Value::Datetime(datetime) => {
+            let mut datetime = *datetime.value();
+            if datetime.offset == Some(toml_edit::Offset::Custom { minutes: 0 }) {
+                datetime.offset = Some(toml_edit::Offset::Z);
+            }
+            ciborium::Value::Tag(0, Box::new(ciborium::Value::Text(datetime.to_string())))
+        }
+        toml_edit::Value::Array(array) => ciborium::Value::Array(
+            array
+                .iter()
+                .map(toml_value_to_cbor)
+                .collect::<Result<Vec<_>>>()?,
+        ),
+        toml_edit::Value::InlineTable(inline_table) => toml_table_like_to_cbor(inline_table)?,
+    })
+}
+
+/// Parses a TOML dotted key (e.g. `metadata.selfHash` or `package."self-hash"`) into its component keys.
+fn parse_dotted_key(dotted_key: &str) -> Result<Vec<String>> {
+    let key_v = toml_edit::Key::parse(dotted_key).map_err(|e| {
+        error!(
+            "malformed TOML dotted key {:?}; error was {}",
+            dotted_key, e
+        )
+    })?;
+    Ok(key_v.iter().map(|key| key.get().to_string()).collect())
+}
+
+/// Returns the table-like item at the given key path, if it exists and is a table.
+fn table_like_at<'a>(
+    document: &'a toml_edit::DocumentMut,
+    key_v: &[String],
+) -> Option<&'a dyn toml_edit::TableLike> {
+    let mut table_like: &dyn toml_edit::TableLike = document.as_table();
+    for key in key_v {
+        table_like = table_like.get(key)?.as_table_like()?;
+    }
+    Some(table_like)
+}
+
+fn table_like_at_mut<'a>(
+    document: &'a mut toml_edit::DocumentMut,
+    key_v: &[String],
+) -> Option<&'a mut dyn toml_edit::TableLike> {
+    let mut table_like: &mut dyn toml_edit::TableLike = document.as_table_mut();
+    for key in key_v {
+        table_like = table_like.get_mut(key)?.as_table_like_mut()?;
+    }
+    Some(table_like)
+}
+
+/// Sets the string value of the given item, preserving its formatting (i.e. whitespace and comments
+/// around it).
+fn set_string_value_preserving_decor(value: &mut toml_edit::Value, s: &str) {
+    let decor = value.decor().clone();
+    *value = toml_edit::Value::from(s);
+    *value.decor_mut() = decor;
+}
+
+/// This provides self-hashable TOML documents, e.g. configuration files which carry their own self-hash.
+/// The document is held as a toml_edit::DocumentMut, so that setting the self-hash slots preserves the
+/// layout, comments, and formatting of the rest of the document (see SelfHashableTOML::to_toml_string).
+/// Self-hash [URL] slots are specified by TOML dotted keys (e.g. `selfHash` or `metadata."self-hash"`).
+///
+/// The digest is computed over the canonical digest form of the document, which is the following mapping
+/// into the CBOR data model, encoded deterministically (see RFC 8949, section 4.2.1).
+/// - Tables (whether standard, inline, or defined via dotted keys) become maps with text string keys.
+/// - Arrays (including arrays of tables) become arrays.
+/// - Strings (of any quoting style) become text strings, and booleans become booleans.
+/// - Integers become integers and floats become floats, so e.g. `1` and `1.0` are distinct.  Note
+///   that `nan`, `+nan`, and `-nan` are all encoded as the canonical NaN.
+/// - Datetimes become text strings of their normalized RFC 3339 rendering (e.g. with `T` as the date/time
+///   separator, trailing zeros of fractional seconds removed, and `Z` for a zero offset), with tag 0.
+///   The four kinds of TOML datetime (offset datetime, local datetime, local date, and local time) are
+///   distinguished by their renderings.  Note that an offset datetime is digested as written, i.e. it's
+///   not converted to UTC.
+///
+/// Thus the digest depends only on the data, not on the layout, comments, or formatting of the document.
+#[derive(Clone)]
+pub struct SelfHashableTOML<'v, 'w: 'v> {
+    /// This is the TOML document that is being self-hashed.
+    document: toml_edit::DocumentMut,
+    /// These are all the dotted keys whose values are considered to define self-hash values.
+    self_hash_dotted_key_s: Cow<'v, std::collections::HashSet<Cow<'w, str>>>,
+    /// These are all the dotted keys whose values are considered to define self-hash URL values.
+    self_hash_url_dotted_key_s: Cow<'v, std::collections::HashSet<Cow<'w, str>>>,
+    /// These are the parsed forms of self_hash_dotted_key_s.
+    self_hash_key_vv: Vec<Vec<String>>,
+    /// These are the parsed forms of self_hash_url_dotted_key_s.
+    self_hash_url_key_vv: Vec<Vec<String>>,
+}
+
+impl<'v, 'w: 'v> SelfHashableTOML<'v, 'w> {
+    /// Parses the given TOML document.  A self-hash field doesn't have to exist already (in which case
+    /// it's inserted at the end of its table), but its parent table must exist.  A self-hash field which
+    /// exists must be a string, where the empty string means unset.  A self-hash URL field must already
+    /// exist and be a valid self-hash URL.
+    pub fn new(
+        toml: &str,
+        self_hash_dotted_key_s: Cow<'v, std::collections::HashSet<Cow<'w, str>>>,
+        self_hash_url_dotted_key_s: Cow<'v, std::collections::HashSet<Cow<'w, str>>>,
+    ) -> Result<Self> {
+        let document = toml
+            .parse::<toml_edit::DocumentMut>()
+            .map_err(|e| error!("malformed TOML; error was {}", e))?;
+        ensure!(
+            self_hash_dotted_key_s.is_disjoint(&self_hash_url_dotted_key_s),
+            "self-hash dotted keys and self-hash URL dotted keys must be disjoint."
+        );
+        let self_hash_key_vv = self_hash_dotted_key_s
+            .iter()
+            .map(|self_hash_dotted_key| parse_dotted_key(self_hash_dotted_key))
+            .collect::<Result<Vec<_>>>()?;
+        let self_hash_url_key_vv = self_hash_url_dotted_key_s
+            .iter()
+            .map(|self_hash_url_dotted_key| parse_dotted_key(self_hash_url_dotted_key))
+            .collect::<Result<Vec<_>>>()?;
+        ensure!(
+            self_hash_key_vv.len() + self_hash_url_key_vv.len() > 0,
+            "no self-hash or self-hash URL fields found, meaning that this TOML document is not self-hashable"
+        );
+
+        let self_hashable_toml = SelfHashableTOML {
+            document,
+            self_hash_dotted_key_s,
+            self_hash_url_dotted_key_s,
+            self_hash_key_vv,
+            self_hash_url_key_vv,
+        };
+        self_hashable_toml.validate()?;
+        Ok(self_hashable_toml)
+    }
+    /// Verifies that all the self-hash slots are strings, or are missing but can be inserted, and that
+    /// all the self-hash URL slots are present and are valid self-hash URLs.
+    fn validate(&self) -> Result<()> {
+        for self_hash_key_v in self.self_hash_key_vv.iter() {
+            self.self_hash_str_o(self_hash_key_v)?;
+        }
+        for self_hash_url_key_v in self.self_hash_url_key_vv.iter() {
+            SelfHashURLStr::new_ref(self.self_hash_url_str(self_hash_url_key_v)?)?;
+        }
+        Ok(())
+    }
+    /// Returns the string value of the given self-hash slot, or None if it's missing.
+    fn self_hash_str_o(&self, self_hash_key_v: &[String]) -> Result<Option<&str>> {
+        let (key, parent_key_v) = self_hash_key_v.split_last().expect("programmer error");
+        let parent = table_like_at(&self.document, parent_key_v).ok_or_else(|| {
+            error!(
+                "parent of self-hash dotted key {:?} doesn't exist or isn't a TOML table",
+                self_hash_key_v.join(".")
+            )
+        })?;
+        match parent.get(key) {
+            Some(item) => Ok(Some(item.as_str().ok_or_else(|| {
+                error!(
+                    "self-hash dotted key {:?} value expected to be a string",
+                    self_hash_key_v.join(".")
+                )
+            })?)),
+            None => Ok(None),
+        }
+    }
+    /// Returns the string value of the given self-hash URL slot.
+    fn self_hash_url_str(&self, self_hash_url_key_v: &[String]) -> Result<&str> {
+        let (key, parent_key_v) = self_hash_url_key_v.split_last().expect("programmer error");
+        table_like_at(&self.document, parent_key_v)
+            .and_then(|parent| parent.get(key))
+            .ok_or_else(|| {
+                error!(
+                    "self-hash URL dotted key {:?} value is missing",
+                    self_hash_url_key_v.join(".")
+                )
+                .with_kind(ErrorKind::MissingSlot)
+            })?
+            .as_str()
+            .ok_or_else(|| {
+                error!(
+                    "self-hash URL dotted key {:?} value expected to be a string",
+                    self_hash_url_key_v.join(".")
+                )
+            })
+    }
+    /// Returns the canonical digest form of the document (see SelfHashableTOML), with the self-hash
+    /// [URL] slots as they currently are.
+    pub fn to_canonical_dcbor(&self) -> Result<Vec<u8>> {
+        let mut buffer = Vec::new();
+        write_dcbor(&toml_item_to_cbor(self.document.as_item())?, &mut buffer)?;
+        Ok(buffer)
+    }
+    /// Renders the document, including its layout, comments, and formatting.
+    pub fn to_toml_string(&self) -> String {
+        self.document.to_string()
+    }
+    pub fn self_hash_dotted_keys(&self) -> &std::collections::HashSet<Cow<'w, str>> {
+        &self.self_hash_dotted_key_s
+    }
+    pub fn self_hash_url_dotted_keys(&self) -> &std::collections::HashSet<Cow<'w, str>> {
+        &self.self_hash_url_dotted_key_s
+    }
+    pub fn document(&self) -> &toml_edit::DocumentMut {
+        &self.document
+    }
+    /// Note that modifying the document may invalidate the self-hash [URL] slots.
+    pub fn document_mut(&mut self) -> &mut toml_edit::DocumentMut {
+        &mut self.document
+    }
+    pub fn into_document(self) -> toml_edit::DocumentMut {
+        self.document
+    }
+}
+
+impl SelfHashableT<mbx::MBHashStr> for SelfHashableTOML<'_, '_> {
+    fn write_digest_data(
+        &self,
+        hasher: &mut <<mbx::MBHashStr as HashRefT>::HashFunction as HashFunctionT<
+            mbx::MBHashStr,
+        >>::Hasher,
+    ) -> Result<()> {
+        use crate::HasherT;
+        let mut c = self.clone();
+        c.set_self_hash_slots_to(hasher.hash_function().placeholder_hash().as_ref())?;
+        let mut writer = digest_io::IoWrapper(hasher);
+        write_dcbor(&toml_item_to_cbor(c.document.as_item())?, &mut writer)
+    }
+    fn self_hash_oi<'a, 'b: 'a>(
+        &'b self,
+    ) -> Result<Box<dyn std::iter::Iterator<Item = Option<&'b mbx::MBHashStr>> + 'a>> {
+        let mut self_hash_ov =
+            Vec::with_capacity(self.self_hash_key_vv.len() + self.self_hash_url_key_vv.len());
+        for self_hash_key_v in self.self_hash_key_vv.iter() {
+            let self_hash_o = match self.self_hash_str_o(self_hash_key_v)? {
+                Some(self_hash_str) if !self_hash_str.is_empty() => {
+                    Some(mbx::MBHashStr::new_ref(self_hash_str).map_err(|e| {
+                        error!(
+                            "self-hash dotted key {:?} value {:?} is not a valid self-hash; error was {}",
+                            self_hash_key_v.join("."),
+                            self_hash_str,
+                            e
+                        )
+                    })?)
+                }
+                _ => None,
+            };
+            self_hash_ov.push(self_hash_o);
+        }
+        for self_hash_url_key_v in self.self_hash_url_key_vv.iter() {
+            let self_hash_url_str = self.self_hash_url_str(self_hash_url_key_v)?;
+            self_hash_ov.push(SelfHashURLStr::new_ref(self_hash_url_str)?.mb_hash_o());
+        }
+        Ok(Box::new(self_hash_ov.into_iter()))
+    }
+    fn set_self_hash_slots_to(&mut self, hash: &mbx::MBHashStr) -> Result<()> {
+        for self_hash_key_v in self.self_hash_key_vv.iter() {
+            let (key, parent_key_v) = self_hash_key_v.split_last().expect("programmer error");
+            let parent = table_like_at_mut(&mut self.document, parent_key_v).ok_or_else(|| {
+                error!(
+                    "parent of self-hash dotted key {:?} doesn't exist or isn't a TOML table",
+                    self_hash_key_v.join(".")
+                )
+            })?;
+            match parent.get_mut(key) {
+                Some(item) => {
+                    let value = item.as_value_mut().ok_or_else(|| {
+                        error!(
+                            "self-hash dotted key {:?} value expected to be a string",
+                            self_hash_key_v.join(".")
+                        )
+                    })?;
+                    set_string_value_preserving_decor(value, hash.as_str());
+                }
+                None => {
+                    parent.insert(key, toml_edit::value(hash.as_str()));
+                }
+            }
+        }
+        for self_hash_url_key_v in self.self_hash_url_key_vv.iter() {
+            let mut self_hash_url =
+                SelfHashURL::try_from(self.self_hash_url_str(self_hash_url_key_v)?)?;
+            self_hash_url.set_self_hash_slots_to_mb_hash(hash)?;
+            let (key, parent_key_v) = self_hash_url_key_v.split_last().expect("programmer error");
+            let value = table_like_at_mut(&mut self.document, parent_key_v)
+                .and_then(|parent| parent.get_mut(key))
+                .and_then(toml_edit::Item::as_value_mut)
+                .expect("programmer error: this was checked by self_hash_url_str");
+            set_string_value_preserving_decor(value, self_hash_url.to_string().as_str());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,409 @@
+use crate::{
+    ensure, error,
+    xml_c14n_writer::{to_exclusive_c14n_string, write_exclusive_c14n},
+    xml_document::{split_qname, XMLDocument},
+    xml_path::XMLSlotLocation,
+    ErrorKind, HashFunctionT, HashRefT, Result, SelfHashSlotCardinality, SelfHashURL,
+    SelfHashURLStr, SelfHashableT, XMLPath,
+};
+use std::borrow::Cow;
+
+/// An XMLPath together with the cardinality rule for how many slots it must select.
+#[derive(Clone, Debug)]
+struct SlotSelector {
+    xml_path: XMLPath,
+    cardinality: SelfHashSlotCardinality,
+}
+
+/// The slots selected by a SlotSelector within a particular XML document.
+struct SlotResolution {
+    /// The locations of the existing slots.
+    location_v: Vec<XMLSlotLocation>,
+    /// If the selector selected nothing, but designates a single missing attribute, this is its location.
+    /// It will be inserted during set_self_hash_slots_to.
+    missing_location_o: Option<XMLSlotLocation>,
+}
+
+impl SlotSelector {
+    fn new(xml_path: XMLPath) -> Self {
+        let cardinality = if xml_path.is_singular() {
+            SelfHashSlotCardinality::AtMostOne
+        } else {
+            SelfHashSlotCardinality::Any
+        };
+        Self {
+            xml_path,
+            cardinality,
+        }
+    }
+    /// Determines the slots selected within the given document, checking the cardinality rule.  If
+    /// allow_missing_slot is true, then a selector which selects nothing designates a single missing slot
+    /// if it selects an attribute of exactly one element (see XMLPath::insertion_location).
+    fn resolve(&self, document: &XMLDocument, allow_missing_slot: bool) -> Result<SlotResolution> {
+        let location_v = self.xml_path.query_located(document);
+        let missing_location_o = if allow_missing_slot && location_v.is_empty() {
+            self.xml_path.insertion_location(document)
+        } else {
+            None
+        };
+        self.cardinality.ensure_admits(
+            location_v.len() + usize::from(missing_location_o.is_some()),
+            self.xml_path.as_str(),
+        )?;
+        Ok(SlotResolution {
+            location_v,
+            missing_location_o,
+        })
+    }
+}
+
+/// Returns the value of the slot at the given location, which is either an attribute value or the text
+/// content of an element (in which case the element must have no children other than text).
+fn slot_value<'a>(
+    document: &'a XMLDocument,
+    location: &XMLSlotLocation,
+    xml_path: &XMLPath,
+) -> Result<&'a str> {
+    let element = document
+        .element_at(&location.element_location)
+        .expect("programmer error");
+    match location.attribute_qname_o.as_deref() {
+        Some(attribute_qname) => Ok(element
+            .attribute(attribute_qname)
+            .expect("programmer error")),
+        None => element.text_content_o().ok_or_else(|| {
+            error!(
+                "self-hash [URL] slot element must contain only text; slot was {}",
+                xml_path
+            )
+        }),
+    }
+}
+
+fn set_slot_value(document: &mut XMLDocument, location: &XMLSlotLocation, value: String) {
+    let element = document
+        .element_at_mut(&location.element_location)
+        .expect("programmer error");
+    match location.attribute_qname_o.as_deref() {
+        Some(attribute_qname) => element.set_attribute(attribute_qname, value),
+        None => element.set_text_content(value),
+    }
+}
+
+/// Interprets the given self-hash slot value, where the empty string means unset.
+fn self_hash_o<'a>(value: &'a str, xml_path: &XMLPath) -> Result<Option<&'a mbx::MBHashStr>> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+    let self_hash = mbx::MBHashStr::new_ref(value).map_err(|e| {
+        error!(
+            "self-hash field {:?} is expected to be a valid self-hash; slot was {}; error was {}",
+            value, xml_path, e
+        )
+    })?;
+    Ok(Some(self_hash))
+}
+
+/// Interprets the given self-hash URL slot value, where a self-hash URL whose self-hash slot is unset
+/// (e.g. "vjson:///") means unset.
+fn self_hash_url_self_hash_o<'a>(
+    value: &'a str,
+    xml_path: &XMLPath,
+) -> Result<Option<&'a mbx::MBHashStr>> {
+    let self_hash_url = SelfHashURLStr::new_ref(value).map_err(|e| {
+        error!(
+            "self-hash URL field {:?} is expected to be a valid self-hash URL; slot was {}; error was {}",
+            value, xml_path, e
+        )
+    })?;
+    if self_hash_url.is_unset() {
+        return Ok(None);
+    }
+    let self_hash = self_hash_url.mb_hash_o().ok_or_else(|| {
+        error!(
+            "malformed self-hash in self-hash URL field {:?}; slot was {}",
+            value, xml_path
+        )
+    })?;
+    Ok(Some(self_hash))
+}
+
+/// This provides self-hashable XML documents, where the self-hash [URL] slots are attributes or the text
+/// content of elements, selected by XMLPath-s (a subset of XPath, e.g. `/record/@selfHash`), and the digest
+/// is computed over the Exclusive XML Canonicalization (without comments) of the document.  The semantics
+/// are otherwise those of SelfHashableJSON, where an empty self-hash field (or a self-hash URL whose
+/// self-hash is empty) counts as unset, and a missing self-hash attribute is added if its element is
+/// unambiguous.  Note that a self-hash element must already exist.
+///
+/// Since the canonical form doesn't depend on the layout of the document (e.g. attribute order, quoting,
+/// or namespace declarations that aren't used), the self-hashed document is output in canonical form.
+#[derive(Clone)]
+pub struct SelfHashableXML<'v, 'w: 'v> {
+    /// This is the XML document that is being self-hashed.
+    document: XMLDocument,
+    /// These are all the XMLPath-s whose results are considered to define self-hash values.
+    self_hash_path_s: Cow<'v, std::collections::HashSet<Cow<'w, str>>>,
+    /// These are all the XMLPath-s whose results are considered to define self-hash URL values.
+    self_hash_url_path_s: Cow<'v, std::collections::HashSet<Cow<'w, str>>>,
+    /// These are the parsed forms of self_hash_path_s.
+    self_hash_slot_selector_v: Vec<SlotSelector>,
+    /// These are the parsed forms of self_hash_url_path_s.
+    self_hash_url_slot_selector_v: Vec<SlotSelector>,
+}
+
+impl<'v, 'w: 'v> SelfHashableXML<'v, 'w> {
+    /// Parses the given XML document and sets up its self-hash [URL] slots.  A self-hash field doesn't
+    /// have to exist already, as long as it's an attribute of an unambiguous element, but each self-hash
+    /// URL field must already exist and be a valid self-hash URL.  Documents with a DTD are rejected.
+    pub fn new(
+        xml: &str,
+        self_hash_path_s: Cow<'v, std::collections::HashSet<Cow<'w, str>>>,
+        self_hash_url_path_s: Cow<'v, std::collections::HashSet<Cow<'w, str>>>,
+    ) -> Result<Self> {
+        ensure!(
+            self_hash_path_s.is_disjoint(&self_hash_url_path_s),
+            "self-hash paths and self-hash URL paths must be disjoint."
+        );
+        let document = XMLDocument::parse(xml)?;
+        let self_hash_slot_selector_v = self_hash_path_s
+            .iter()
+            .map(|self_hash_path| XMLPath::parse(self_hash_path).map(SlotSelector::new))
+            .collect::<Result<Vec<_>>>()?;
+        let self_hash_url_slot_selector_v = self_hash_url_path_s
+            .iter()
+            .map(|self_hash_url_path| XMLPath::parse(self_hash_url_path).map(SlotSelector::new))
+            .collect::<Result<Vec<_>>>()?;
+        ensure!(
+            self_hash_slot_selector_v.len() + self_hash_url_slot_selector_v.len() > 0,
+            "no self-hash or self-hash URL paths found, meaning that this XML document is not self-hashable"
+        );
+
+        let self_hashable_xml = SelfHashableXML {
+            document,
+            self_hash_path_s,
+            self_hash_url_path_s,
+            self_hash_slot_selector_v,
+            self_hash_url_slot_selector_v,
+        };
+        self_hashable_xml.validate()?;
+        Ok(self_hashable_xml)
+    }
+    /// Sets the cardinality rule for the given self-hash [URL] path, which must be one of the ones this
+    /// SelfHashableXML was constructed with.  The default is SelfHashSlotCardinality::AtMostOne for
+    /// singular XMLPath-s and SelfHashSlotCardinality::Any otherwise (see XMLPath::is_singular).
+    pub fn with_self_hash_slot_cardinality(
+        mut self,
+        selector: &str,
+        cardinality: SelfHashSlotCardinality,
+    ) -> Result<Self> {
+        let mut found = false;
+        for slot_selector in self
+            .self_hash_slot_selector_v
+            .iter_mut()
+            .chain(self.self_hash_url_slot_selector_v.iter_mut())
+            .filter(|slot_selector| slot_selector.xml_path.as_str() == selector)
+        {
+            slot_selector.cardinality = cardinality;
+            found = true;
+        }
+        ensure!(
+            found,
+            "{:?} is not one of the self-hash [URL] paths of this SelfHashableXML",
+            selector
+        );
+        self.validate()?;
+        Ok(self)
+    }
+    /// Verifies that all the self-hash slots are empty or valid self-hashes, or are missing but can be
+    /// inserted, that all the self-hash URL slots are present and are valid self-hash URLs, and that the
+    /// cardinality rules are satisfied.
+    fn validate(&self) -> Result<()> {
+        for self_hash_slot_selector in self.self_hash_slot_selector_v.iter() {
+            let xml_path = &self_hash_slot_selector.xml_path;
+            let slot_resolution = self_hash_slot_selector.resolve(&self.document, true)?;
+            for location in slot_resolution.location_v.iter() {
+                self_hash_o(slot_value(&self.document, location, xml_path)?, xml_path)?;
+            }
+            if slot_resolution.location_v.is_empty() && xml_path.is_singular() {
+                // A singular self-hash path which selects nothing has to designate an attribute which
+                // can be added, since a self-hash element can't be.
+                let missing_location = slot_resolution.missing_location_o.as_ref().ok_or_else(|| {
+                    error!(
+                        "self-hash slot selected nothing, and doesn't designate an attribute of a single existing element, so the self-hash field can't be added; slot was {}",
+                        xml_path
+                    )
+                    .with_kind(ErrorKind::MissingSlot)
+                })?;
+                let (prefix, _) =
+                    split_qname(xml_path.attribute_qname().expect("programmer error"));
+                ensure!(
+                    self.document
+                        .element_at(&missing_location.element_location)
+                        .expect("programmer error")
+                        .is_prefix_bound(prefix),
+                    "self-hash attribute prefix {:?} is not bound to a namespace, so the self-hash field can't be added; slot was {}",
+                    prefix,
+                    xml_path
+                );
+            }
+        }
+        for self_hash_url_slot_selector in self.self_hash_url_slot_selector_v.iter() {
+            let xml_path = &self_hash_url_slot_selector.xml_path;
+            let slot_resolution = self_hash_url_slot_selector.resolve(&self.document, false)?;
+            // A missing self-hash URL field is an error, because the form of the URL is specified by the
+            // document itself.
+            if slot_resolution.location_v.is_empty() && xml_path.is_singular() {
+                return Err(error!(
+                    "self-hash URL slot selected nothing, which is not a valid self-hash URL slot by definition; slot was {}",
+                    xml_path
+                )
+                .with_kind(ErrorKind::MissingSlot));
+            }
+            for location in slot_resolution.location_v.iter() {
+                self_hash_url_self_hash_o(
+                    slot_value(&self.document, location, xml_path)?,
+                    xml_path,
+                )?;
+            }
+        }
+        Ok(())
+    }
+    /// Returns the Exclusive XML Canonicalization of the document (which is also what the digest is
+    /// computed over, with the self-hash [URL] slots set to the placeholder).
+    pub fn to_c14n_string(&self) -> String {
+        to_exclusive_c14n_string(&self.document)
+    }
+    /// Returns the absolute XMLPath-s (e.g. `/record/@selfHash` or `/record/header[1]/selfHash[1]`) of all
+    /// the self-hash and self-hash URL slots in the document, including any missing self-hash attributes
+    /// which would be inserted, sorted.  Unlike the self-hash [URL] paths, these identify the individual
+    /// slots.
+    pub fn self_hash_slot_xml_paths(&self) -> Result<Vec<String>> {
+        let mut location_v = Vec::new();
+        for self_hash_slot_selector in self.self_hash_slot_selector_v.iter() {
+            let slot_resolution = self_hash_slot_selector.resolve(&self.document, true)?;
+            location_v.extend(slot_resolution.location_v);
+            location_v.extend(slot_resolution.missing_location_o);
+        }
+        for self_hash_url_slot_selector in self.self_hash_url_slot_selector_v.iter() {
+            location_v.extend(
+                self_hash_url_slot_selector
+                    .resolve(&self.document, false)?
+                    .location_v,
+            );
+        }
+        let mut xml_path_v = location_v
+            .iter()
+            .map(|location| location.to_xml_path_string(&self.document))
+            .collect::<Vec<_>>();
+        xml_path_v.sort();
+        xml_path_v.dedup();
+        Ok(xml_path_v)
+    }
+    /// Returns the XMLPath-s whose results are considered to define self-hash values.
+    pub fn self_hash_paths(&self) -> &std::collections::HashSet<Cow<'w, str>> {
+        self.self_hash_path_s.as_ref()
+    }
+    /// Returns the XMLPath-s whose results are considered to define self-hash URL values.
+    pub fn self_hash_url_paths(&self) -> &std::collections::HashSet<Cow<'w, str>> {
+        self.self_hash_url_path_s.as_ref()
+    }
+}
+
+impl SelfHashableT<mbx::MBHashStr> for SelfHashableXML<'_, '_> {
+    fn write_digest_data(
+        &self,
+        hasher: &mut <<mbx::MBHashStr as HashRefT>::HashFunction as HashFunctionT<
+            mbx::MBHashStr,
+        >>::Hasher,
+    ) -> Result<()> {
+        use crate::HasherT;
+        let mut c = self.clone();
+        c.set_self_hash_slots_to(hasher.hash_function().placeholder_hash().as_ref())?;
+        let mut writer = digest_io::IoWrapper(hasher);
+        write_exclusive_c14n(&c.document, &mut writer)
+    }
+    fn self_hash_oi<'a, 'b: 'a>(
+        &'b self,
+    ) -> Result<Box<dyn std::iter::Iterator<Item = Option<&'b mbx::MBHashStr>> + 'a>> {
+        let mut self_hash_v = Vec::new();
+        // For self-hash fields, a missing field is fine, it just counts as a None (it will be added
+        // during set_self_hash_slots_to).
+        for self_hash_slot_selector in self.self_hash_slot_selector_v.iter() {
+            let xml_path = &self_hash_slot_selector.xml_path;
+            let slot_resolution = self_hash_slot_selector.resolve(&self.document, true)?;
+            for location in slot_resolution.location_v.iter() {
+                self_hash_v.push(self_hash_o(
+                    slot_value(&self.document, location, xml_path)?,
+                    xml_path,
+                )?);
+            }
+            if slot_resolution.missing_location_o.is_some() {
+                self_hash_v.push(None);
+            }
+        }
+        // For self-hash URL fields, a missing field is an error, because the form of the URL is specified
+        // by the document itself.
+        for self_hash_url_slot_selector in self.self_hash_url_slot_selector_v.iter() {
+            let xml_path = &self_hash_url_slot_selector.xml_path;
+            let slot_resolution = self_hash_url_slot_selector.resolve(&self.document, false)?;
+            if slot_resolution.location_v.is_empty() && xml_path.is_singular() {
+                return Err(error!(
+                    "a self-hash URL slot can not be missing for self-hashing or self-hash verification; slot was {}",
+                    xml_path
+                )
+                .with_kind(ErrorKind::MissingSlot));
+            }
+            for location in slot_resolution.location_v.iter() {
+                self_hash_v.push(self_hash_url_self_hash_o(
+                    slot_value(&self.document, location, xml_path)?,
+                    xml_path,
+                )?);
+            }
+        }
+        Ok(Box::new(self_hash_v.into_iter()))
+    }
+    fn set_self_hash_slots_to(&mut self, hash: &mbx::MBHashStr) -> Result<()> {
+        for self_hash_slot_selector in self.self_hash_slot_selector_v.iter() {
+            let xml_path = &self_hash_slot_selector.xml_path;
+            let slot_resolution = self_hash_slot_selector.resolve(&self.document, true)?;
+            if slot_resolution.location_v.is_empty()
+                && slot_resolution.missing_location_o.is_none()
+                && xml_path.is_singular()
+            {
+                return Err(error!(
+                    "self-hash slot selected nothing, and doesn't designate an attribute of a single existing element; slot was {}",
+                    xml_path
+                )
+                .with_kind(ErrorKind::MissingSlot));
+            }
+            for location in slot_resolution
+                .location_v
+                .iter()
+                .chain(slot_resolution.missing_location_o.iter())
+            {
+                // Check that an element slot contains only text, so that no content is discarded.
+                if location.attribute_qname_o.is_none() {
+                    slot_value(&self.document, location, xml_path)?;
+                }
+                set_slot_value(&mut self.document, location, hash.to_string());
+            }
+        }
+        for self_hash_url_slot_selector in self.self_hash_url_slot_selector_v.iter() {
+            let xml_path = &self_hash_url_slot_selector.xml_path;
+            let slot_resolution = self_hash_url_slot_selector.resolve(&self.document, false)?;
+            if slot_resolution.location_v.is_empty() && xml_path.is_singular() {
+                return Err(
+                    error!("self-hash URL slot selected nothing; slot was {}", xml_path)
+                        .with_kind(ErrorKind::MissingSlot),
+                );
+            }
+            for location in slot_resolution.location_v.iter() {
+                let mut self_hash_url =
+                    SelfHashURL::try_from(slot_value(&self.document, location, xml_path)?)?;
+                self_hash_url.set_self_hash_slots_to_mb_hash(hash)?;
+                set_slot_value(&mut self.document, location, self_hash_url.to_string());
+            }
+        }
+        Ok(())
+    }
+}
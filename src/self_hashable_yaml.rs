@@ -0,0 +1,386 @@
+use crate::{
+    bail, ensure, error, HashFunctionT, HashRefT, Result, SelfHashableJSON, SelfHashableT,
+};
+use std::borrow::Cow;
+use yaml_rust2::{
+    parser::{Event, Parser},
+    scanner::{Marker, TScalarStyle},
+    Yaml, YamlEmitter,
+};
+
+/// Parses a single YAML document into the JSON data model, using the YAML 1.2 core schema to resolve
+/// plain (i.e. unquoted) scalars: `null`, `~`, and the empty scalar are null, `true` and `false` (in the
+/// core schema's capitalizations) are booleans, decimal, `0o` octal, and `0x` hexadecimal integers are
+/// integers, and decimal floats are floats.  All other scalars, including all quoted and block scalars,
+/// are strings.  Because the result must be representable as JSON, the following are rejected:
+/// - tags (e.g. `!!binary` or `!custom`), since they have no JSON counterpart,
+/// - anchors and aliases, since they would let one node appear in several places,
+/// - mapping keys which aren't strings (e.g. `1: x`, `true: x`, or `[a, b]: x`), and duplicate keys,
+/// - `.inf`, `.nan`, and integers which don't fit in 64 bits, and
+/// - input containing zero or more than one document.
+pub fn yaml_to_json_value(yaml: &str) -> Result<serde_json::Value> {
+    let mut parser = Parser::new_from_str(yaml);
+    let (event, marker) = next_event(&mut parser)?;
+    ensure!(
+        matches!(event, Event::StreamStart),
+        "malformed YAML; expected start of stream at {}",
+        location(&marker)
+    );
+    let (event, _) = next_event(&mut parser)?;
+    ensure!(
+        matches!(event, Event::DocumentStart),
+        "YAML input must contain exactly one document, but it contained none"
+    );
+    let (event, marker) = next_event(&mut parser)?;
+    let value = parse_node(&mut parser, event, &marker)?;
+    let (event, marker) = next_event(&mut parser)?;
+    ensure!(
+        matches!(event, Event::DocumentEnd),
+        "malformed YAML; expected end of document at {}",
+        location(&marker)
+    );
+    let (event, marker) = next_event(&mut parser)?;
+    ensure!(
+        matches!(event, Event::StreamEnd),
+        "YAML input must contain exactly one document, but another one began at {}",
+        location(&marker)
+    );
+    Ok(value)
+}
+
+/// Renders the given JSON value as a YAML document.  Since some strings can't be distinguished from
+/// other scalars when emitted as plain YAML scalars, the rendered document is checked to parse back
+/// (via yaml_to_json_value) to the given value, and an error is returned if it doesn't.
+pub fn json_value_to_yaml_string(value: &serde_json::Value) -> Result<String> {
+    let mut yaml_string = String::new();
+    YamlEmitter::new(&mut yaml_string)
+        .dump(&json_value_to_yaml(value))
+        .map_err(|e| error!("failed to emit YAML; error was {}", e))?;
+    yaml_string.push('\n');
+    ensure!(
+        yaml_to_json_value(&yaml_string)? == *value,
+        "value can't be faithfully represented as YAML (some string would be read back as a non-string)"
+    );
+    Ok(yaml_string)
+}
+
+fn next_event(parser: &mut Parser<std::str::Chars<'_>>) -> Result<(Event, Marker)> {
+    parser
+        .next_token()
+        .map_err(|e| error!("malformed YAML; error was {}", e))
+}
+
+fn location(marker: &Marker) -> String {
+    format!("line {}, column {}", marker.line(), marker.col() + 1)
+}
+
+fn ensure_no_anchor_or_tag(
+    anchor_id: usize,
+    tag_o: Option<&yaml_rust2::parser::Tag>,
+    marker: &Marker,
+) -> Result<()> {
+    ensure!(
+        anchor_id == 0,
+        "YAML anchors are not supported, since they have no JSON counterpart; anchor was at {}",
+        location(marker)
+    );
+    if let Some(tag) = tag_o {
+        bail!(
+            "YAML tags are not supported, since they have no JSON counterpart; tag {}{} was at {}",
+            tag.handle,
+            tag.suffix,
+            location(marker)
+        );
+    }
+    Ok(())
+}
+
+fn parse_node(
+    parser: &mut Parser<std::str::Chars<'_>>,
+    event: Event,
+    marker: &Marker,
+) -> Result<serde_json::Value> {
+    match event {
+        Event::Alias(_) => {
+            bail!(
+                "YAML aliases are not supported, since they have no JSON counterpart; alias was at {}",
+                location(marker)
+            );
+        }
+        Event::Scalar(s, style, anchor_id, tag_o) => {
+            ensure_no_anchor_or_tag(anchor_id, tag_o.as_ref(), marker)?;
+            resolve_scalar(s, style, marker)
+        }
+        Event::SequenceStart(anchor_id, tag_o) => {
+            ensure_no_anchor_or_tag(anchor_id, tag_o.as_ref(), marker)?;
+            let mut element_v = Vec::new();
+            loop {
+                let (event, marker) = next_event(parser)?;
+                if matches!(event, Event::SequenceEnd) {
+                    break;
+                }
+                element_v.push(parse_node(parser, event, &marker)?);
+            }
+            Ok(serde_json::Value::Array(element_v))
+        }
+        Event::MappingStart(anchor_id, tag_o) => {
+            ensure_no_anchor_or_tag(anchor_id, tag_o.as_ref(), marker)?;
+            let mut map = serde_json::Map::new();
+            loop {
+                let (event, key_marker) = next_event(parser)?;
+                let key = match event {
+                    Event::MappingEnd => {
+                        break;
+                    }
+                    Event::Scalar(s, style, anchor_id, tag_o) => {
+                        ensure_no_anchor_or_tag(anchor_id, tag_o.as_ref(), &key_marker)?;
+                        match resolve_scalar(s, style, &key_marker)? {
+                            serde_json::Value::String(key) => key,
+                            key => {
+                                bail!(
+                                    "YAML mapping keys must be strings, but key {} at {} isn't (quote it to make it a string)",
+                                    key,
+                                    location(&key_marker)
+                                );
+                            }
+                        }
+                    }
+                    Event::Alias(_) => {
+                        bail!(
+                            "YAML aliases are not supported, since they have no JSON counterpart; alias was at {}",
+                            location(&key_marker)
+                        );
+                    }
+                    _ => {
+                        bail!(
+                            "YAML mapping keys must be strings, but the key at {} is a collection",
+                            location(&key_marker)
+                        );
+                    }
+                };
+                let (event, marker) = next_event(parser)?;
+                let value = parse_node(parser, event, &marker)?;
+                ensure!(
+                    !map.contains_key(&key),
+                    "duplicate YAML mapping key {:?} at {}",
+                    key,
+                    location(&key_marker)
+                );
+                map.insert(key, value);
+            }
+            Ok(serde_json::Value::Object(map))
+        }
+        _ => {
+            bail!("malformed YAML; unexpected event at {}", location(marker));
+        }
+    }
+}
+
+/// Resolves a scalar according to the YAML 1.2 core schema (see yaml_to_json_value).
+fn resolve_scalar(s: String, style: TScalarStyle, marker: &Marker) -> Result<serde_json::Value> {
+    if style != TScalarStyle::Plain {
+        return Ok(serde_json::Value::String(s));
+    }
+    match s.as_str() {
+        "" | "~" | "null" | "Null" | "NULL" => {
+            return Ok(serde_json::Value::Null);
+        }
+        "true" | "True" | "TRUE" => {
+            return Ok(serde_json::Value::Bool(true));
+        }
+        "false" | "False" | "FALSE" => {
+            return Ok(serde_json::Value::Bool(false));
+        }
+        _ => {}
+    }
+    let unsigned = s.strip_prefix(['-', '+']).unwrap_or(&s);
+    if is_digits(unsigned, 10) {
+        // Parsing as i128 handles both the full u64 range and the full i64 range, and the sign.
+        let n = s.parse::<i128>().ok();
+        if let Some(n) = n.and_then(|n| i64::try_from(n).ok()) {
+            return Ok(serde_json::Value::from(n));
+        }
+        if let Some(n) = n.and_then(|n| u64::try_from(n).ok()) {
+            return Ok(serde_json::Value::from(n));
+        }
+        bail!(
+            "YAML integer {} at {} is out of the supported (64-bit) range",
+            s,
+            location(marker)
+        );
+    }
+    for (prefix, radix) in [("0o", 8), ("0x", 16)] {
+        if let Some(digits) = s.strip_prefix(prefix).filter(|d| is_digits(d, radix)) {
+            let n = u64::from_str_radix(digits, radix).map_err(|_| {
+                error!(
+                    "YAML integer {} at {} is out of the supported (64-bit) range",
+                    s,
+                    location(marker)
+                )
+            })?;
+            return Ok(serde_json::Value::from(n));
+        }
+    }
+    if is_float(unsigned) {
+        let x = s.parse::<f64>().map_err(|e| {
+            error!(
+                "malformed YAML float {} at {}; error was {}",
+                s,
+                location(marker),
+                e
+            )
+        })?;
+        return serde_json::Number::from_f64(x)
+            .map(serde_json::Value::Number)
+            .ok_or_else(|| {
+                error!(
+                    "YAML float {} at {} can't be represented in JSON",
+                    s,
+                    location(marker)
+                )
+            });
+    }
+    if matches!(
+        unsigned,
+        ".inf" | ".Inf" | ".INF" | ".nan" | ".NaN" | ".NAN"
+    ) {
+        bail!(
+            "YAML float {} at {} can't be represented in JSON",
+            s,
+            location(marker)
+        );
+    }
+    Ok(serde_json::Value::String(s))
+}
+
+fn is_digits(s: &str, radix: u32) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_digit(radix))
+}
+
+/// Returns true iff s matches `(\.[0-9]+|[0-9]+(\.[0-9]*)?)([eE][-+]?[0-9]+)?` (the unsigned part of the
+/// YAML 1.2 core schema's float pattern).
+fn is_float(s: &str) -> bool {
+    let (mantissa, exponent_o) = match s.find(['e', 'E']) {
+        Some(i) => (&s[..i], Some(&s[i + 1..])),
+        None => (s, None),
+    };
+    let mantissa_is_valid = match mantissa.split_once('.') {
+        Some((integer_part, fraction_part)) => {
+            (integer_part.is_empty() || is_digits(integer_part, 10))
+                && (fraction_part.is_empty() || is_digits(fraction_part, 10))
+                && !(integer_part.is_empty() && fraction_part.is_empty())
+        }
+        None => is_digits(mantissa, 10),
+    };
+    let exponent_is_valid = match exponent_o {
+        Some(exponent) => is_digits(exponent.strip_prefix(['-', '+']).unwrap_or(exponent), 10),
+        None => true,
+    };
+    mantissa_is_valid && exponent_is_valid
+}
+
+fn json_value_to_yaml(value: &serde_json::Value) -> Yaml {
+    match value {
+        serde_json::Value::Null => Yaml::Null,
+        serde_json::Value::Bool(b) => Yaml::Boolean(*b),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(n) => Yaml::Integer(n),
+            // This is a u64 beyond the range of i64 or a float, and either way, its JSON rendering
+            // is a valid YAML plain scalar that resolves to the same number.
+            None => Yaml::Real(n.to_string()),
+        },
+        serde_json::Value::String(s) => Yaml::String(s.clone()),
+        serde_json::Value::Array(element_v) => {
+            Yaml::Array(element_v.iter().map(json_value_to_yaml).collect())
+        }
+        serde_json::Value::Object(map) => Yaml::Hash(
+            map.iter()
+                .map(|(key, value)| (Yaml::String(key.clone()), json_value_to_yaml(value)))
+                .collect(),
+        ),
+    }
+}
+
+/// This provides self-hashable YAML documents.  The YAML is parsed into the JSON data model (see
+/// yaml_to_json_value), and is then self-hashed exactly as SelfHashableJSON would self-hash the
+/// corresponding JSON, i.e. the slots are selected by JSONPath queries and JSON Pointers, and the digest
+/// is computed over the JCS serialization.  Thus a YAML document and its JSON counterpart have the same
+/// self-hash.  Note that comments and formatting in the YAML aren't preserved by to_yaml_string.
+#[derive(Clone)]
+pub struct SelfHashableYAML<'v, 'w: 'v> {
+    self_hashable_json: SelfHashableJSON<'v, 'w>,
+}
+
+impl<'v, 'w: 'v> SelfHashableYAML<'v, 'w> {
+    /// Parses the given YAML document; see SelfHashableJSON::new for the meaning of the other arguments.
+    pub fn new(
+        yaml: &str,
+        self_hash_path_s: Cow<'v, std::collections::HashSet<Cow<'w, str>>>,
+        self_hash_url_path_s: Cow<'v, std::collections::HashSet<Cow<'w, str>>>,
+    ) -> Result<Self> {
+        Ok(Self::from_self_hashable_json(SelfHashableJSON::new(
+            yaml_to_json_value(yaml)?,
+            self_hash_path_s,
+            self_hash_url_path_s,
+        )?))
+    }
+    /// Parses the given YAML document; see SelfHashableJSON::new_with_templates for the meaning of the
+    /// other arguments.
+    pub fn new_with_templates(
+        yaml: &str,
+        self_hash_path_s: Cow<'v, std::collections::HashSet<Cow<'w, str>>>,
+        self_hash_url_path_s: Cow<'v, std::collections::HashSet<Cow<'w, str>>>,
+        self_hash_pointer_s: Cow<'v, std::collections::HashSet<Cow<'w, str>>>,
+        self_hash_url_pointer_s: Cow<'v, std::collections::HashSet<Cow<'w, str>>>,
+        self_hash_template_m: Cow<'v, std::collections::HashMap<Cow<'w, str>, Cow<'w, str>>>,
+    ) -> Result<Self> {
+        Ok(Self::from_self_hashable_json(
+            SelfHashableJSON::new_with_templates(
+                yaml_to_json_value(yaml)?,
+                self_hash_path_s,
+                self_hash_url_path_s,
+                self_hash_pointer_s,
+                self_hash_url_pointer_s,
+                self_hash_template_m,
+            )?,
+        ))
+    }
+    /// Wraps a SelfHashableJSON, e.g. one whose value was produced by yaml_to_json_value and which was
+    /// configured further (e.g. via SelfHashableJSON::with_self_hash_slot_cardinality).
+    pub fn from_self_hashable_json(self_hashable_json: SelfHashableJSON<'v, 'w>) -> Self {
+        Self { self_hashable_json }
+    }
+    pub fn self_hashable_json(&self) -> &SelfHashableJSON<'v, 'w> {
+        &self.self_hashable_json
+    }
+    pub fn into_self_hashable_json(self) -> SelfHashableJSON<'v, 'w> {
+        self.self_hashable_json
+    }
+    /// Returns the JSON data model value of the YAML document.
+    pub fn value(&self) -> &serde_json::Value {
+        self.self_hashable_json.value()
+    }
+    /// Renders the YAML document (with whatever its self-hash slots are currently set to) as YAML.
+    pub fn to_yaml_string(&self) -> Result<String> {
+        json_value_to_yaml_string(self.value())
+    }
+}
+
+impl SelfHashableT<mbx::MBHashStr> for SelfHashableYAML<'_, '_> {
+    fn write_digest_data(
+        &self,
+        hasher: &mut <<mbx::MBHashStr as HashRefT>::HashFunction as HashFunctionT<
+            mbx::MBHashStr,
+        >>::Hasher,
+    ) -> Result<()> {
+        self.self_hashable_json.write_digest_data(hasher)
+    }
+    fn self_hash_oi<'a, 'b: 'a>(
+        &'b self,
+    ) -> Result<Box<dyn std::iter::Iterator<Item = Option<&'b mbx::MBHashStr>> + 'a>> {
+        self.self_hashable_json.self_hash_oi()
+    }
+    fn set_self_hash_slots_to(&mut self, hash: &mbx::MBHashStr) -> Result<()> {
+        self.self_hashable_json.set_self_hash_slots_to(hash)
+    }
+}
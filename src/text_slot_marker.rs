@@ -0,0 +1,103 @@
+use crate::{ensure, error, Result, SelfHashTemplate};
+
+/// The name of the capture group which delimits the self-hash within a TextSlotMarker's regex.
+const SELF_HASH_CAPTURE_GROUP_NAME: &str = "selfHash";
+
+/// Matches the characters that can appear in a multibase-encoded hash (in any of the bases supported by
+/// mbx), so that a template slot extends exactly over its self-hash, which may be empty.
+const SELF_HASH_CHARS_PATTERN: &str = "[0-9A-Za-z_+/=-]*";
+
+/// Specifies how the self-hash slots are found within a text file (see SelfHashableText).  A marker is
+/// either a regex containing a capture group named `selfHash` (or, if there is no such group, exactly one
+/// capture group), e.g. `(?m)^selfhash: (?P<selfHash>\S*)$`, or a SelfHashTemplate, e.g.
+/// `selfhash: {selfHash}`, in which all characters other than the placeholder are matched literally and
+/// the placeholder matches a (possibly empty) run of multibase characters.  Each match of the marker is a
+/// self-hash slot, and the capture group delimits the self-hash, where an empty capture means unset.
+#[derive(Clone, Debug)]
+pub struct TextSlotMarker {
+    /// The regex or template that this marker was parsed from.
+    marker: String,
+    regex: regex::bytes::Regex,
+    /// The index of the capture group which delimits the self-hash.
+    capture_group_index: usize,
+}
+
+impl TextSlotMarker {
+    /// Parses a regex marker.  Note that the regex is matched against the raw bytes of the text, so
+    /// Unicode classes such as `\w` only match valid UTF-8.
+    pub fn from_regex(regex: &str) -> Result<Self> {
+        let compiled_regex = regex::bytes::Regex::new(regex).map_err(|e| {
+            error!(
+                "malformed self-hash marker regex {:?}; error was {}",
+                regex, e
+            )
+        })?;
+        let capture_group_index = match compiled_regex
+            .capture_names()
+            .position(|name_o| name_o == Some(SELF_HASH_CAPTURE_GROUP_NAME))
+        {
+            Some(capture_group_index) => capture_group_index,
+            None => {
+                // Capture group 0 is the whole match, so there must be exactly one other group.
+                ensure!(
+                    compiled_regex.captures_len() == 2,
+                    "self-hash marker regex must contain a capture group named {:?} or exactly one capture group; regex was {:?}",
+                    SELF_HASH_CAPTURE_GROUP_NAME,
+                    regex
+                );
+                1
+            }
+        };
+        Ok(Self {
+            marker: regex.to_string(),
+            regex: compiled_regex,
+            capture_group_index,
+        })
+    }
+    /// Parses a template marker (see SelfHashTemplate), e.g. `selfhash: {selfHash}`.
+    pub fn from_template(template: &str) -> Result<Self> {
+        let self_hash_template = SelfHashTemplate::parse(template)?;
+        let regex = format!(
+            "{}(?P<{}>{}){}",
+            regex::escape(self_hash_template.prefix()),
+            SELF_HASH_CAPTURE_GROUP_NAME,
+            SELF_HASH_CHARS_PATTERN,
+            regex::escape(self_hash_template.suffix())
+        );
+        Ok(Self {
+            marker: template.to_string(),
+            regex: regex::bytes::Regex::new(&regex).expect("programmer error"),
+            capture_group_index: 1,
+        })
+    }
+    /// Returns the regex or template that this marker was parsed from.
+    pub fn as_str(&self) -> &str {
+        self.marker.as_str()
+    }
+    /// Returns the byte ranges of the self-hashes of the slots that this marker matches in the given text,
+    /// in order.  Matches in which the capture group doesn't participate are an error, since they would
+    /// have no self-hash.
+    pub(crate) fn self_hash_range_v(&self, text: &[u8]) -> Result<Vec<std::ops::Range<usize>>> {
+        self.regex
+            .captures_iter(text)
+            .map(|captures| {
+                captures
+                    .get(self.capture_group_index)
+                    .map(|self_hash_match| self_hash_match.range())
+                    .ok_or_else(|| {
+                        error!(
+                            "self-hash marker {:?} matched at byte offset {} without matching its self-hash capture group",
+                            self.marker,
+                            captures.get(0).expect("programmer error").start()
+                        )
+                    })
+            })
+            .collect()
+    }
+}
+
+impl std::fmt::Display for TextSlotMarker {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
@@ -0,0 +1,67 @@
+use crate::{error, tree::TreeManifestEntry, HashFunctionT, HasherT, MBHashFunction, Result};
+
+/// Domain separation prefix for the hash of a leaf (see RFC 6962, section 2.1).
+const LEAF_PREFIX: u8 = 0x00;
+/// Domain separation prefix for the hash of an interior node (see RFC 6962, section 2.1).
+const NODE_PREFIX: u8 = 0x01;
+
+/// Hashes the concatenation of the given parts, returning the raw digest.
+fn digest(mb_hash_function: &MBHashFunction, part_v: &[&[u8]]) -> Result<Vec<u8>> {
+    let mut hasher = mb_hash_function.new_hasher();
+    for part in part_v {
+        HasherT::update(&mut hasher, part);
+    }
+    // NOTE: Use 64 bytes as max supported digest size.
+    let multihash = hasher
+        .finalize()
+        .decoded::<64>()
+        .map_err(|e| error!("failed to decode Merkle tree hash; error was {}", e))?;
+    Ok(multihash.digest().to_vec())
+}
+
+/// Computes the Merkle tree hash (see RFC 6962, section 2.1) of the given leaf digests.
+fn merkle_tree_hash(
+    mb_hash_function: &MBHashFunction,
+    leaf_digest_v: &[Vec<u8>],
+) -> Result<Vec<u8>> {
+    match leaf_digest_v {
+        [] => digest(mb_hash_function, &[]),
+        [leaf_digest] => Ok(leaf_digest.clone()),
+        _ => {
+            // Split at the largest power of two less than the number of leaves.
+            let k = 1usize << (usize::BITS - 1 - (leaf_digest_v.len() - 1).leading_zeros());
+            let left = merkle_tree_hash(mb_hash_function, &leaf_digest_v[..k])?;
+            let right = merkle_tree_hash(mb_hash_function, &leaf_digest_v[k..])?;
+            digest(mb_hash_function, &[&[NODE_PREFIX], &left, &right])
+        }
+    }
+}
+
+/// Computes the Merkle root of the given manifest entries, as in RFC 6962 (section 2.1), where the data of
+/// each leaf is the JCS serialization of the corresponding entry.  The entries should be sorted by path
+/// (as they are in a TreeManifest).  The root is encoded as a multibase multihash using the given hash
+/// function.
+pub fn merkle_root(
+    mb_hash_function: &MBHashFunction,
+    entry_v: &[TreeManifestEntry],
+) -> Result<mbx::MBHash> {
+    let leaf_digest_v = entry_v
+        .iter()
+        .map(|entry| {
+            let leaf_data = serde_json_canonicalizer::to_vec(entry).map_err(|e| {
+                error!(
+                    "failed to serialize tree manifest entry {:?}; error was {}",
+                    entry.path, e
+                )
+            })?;
+            digest(mb_hash_function, &[&[LEAF_PREFIX], &leaf_data])
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let root_digest = merkle_tree_hash(mb_hash_function, &leaf_digest_v)?;
+    mbx::MBHash::encoded::<64>(
+        mb_hash_function.base(),
+        mb_hash_function.codec(),
+        root_digest.as_slice(),
+    )
+    .map_err(|e| error!("failed to encode Merkle root; error was {}", e))
+}
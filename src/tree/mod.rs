@@ -0,0 +1,20 @@
+//! Self-hashing of directory trees, e.g. release bundles shipped as directories.  A directory tree is
+//! described by a TreeManifest, which lists every file (by sorted relative path) along with its mode, size,
+//! and multihash digest, and the Merkle root over those entries.  The manifest is stored in the directory
+//! itself (as MANIFEST_FILE_NAME) and is self-hashed via SelfHashableJSON, so that its self-hash covers
+//! every other file in the directory.
+
+mod merkle_root;
+mod tree_diff;
+mod tree_manifest;
+mod tree_manifest_entry;
+
+pub use {
+    merkle_root::merkle_root,
+    tree_diff::TreeDiff,
+    tree_manifest::{
+        compute_tree_manifest, verify_tree_manifest, write_tree_manifest, TreeManifest,
+        TreeVerification, MANIFEST_FILE_NAME,
+    },
+    tree_manifest_entry::TreeManifestEntry,
+};
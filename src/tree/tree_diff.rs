@@ -0,0 +1,79 @@
+use crate::tree::TreeManifestEntry;
+
+/// The differences between the files listed in a TreeManifest and the files actually present in the
+/// directory tree, by relative path.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TreeDiff {
+    /// The files present in the directory tree but not listed in the manifest.
+    pub added_v: Vec<String>,
+    /// The files listed in the manifest but not present in the directory tree.
+    pub removed_v: Vec<String>,
+    /// The files whose mode, size, or digest differ from those listed in the manifest.
+    pub modified_v: Vec<String>,
+}
+
+impl TreeDiff {
+    /// Computes the differences between the claimed entries (e.g. from a manifest) and the actual entries
+    /// (e.g. computed from the directory tree), both of which must be sorted by path.
+    pub fn new(
+        claimed_entry_v: &[TreeManifestEntry],
+        actual_entry_v: &[TreeManifestEntry],
+    ) -> Self {
+        let mut tree_diff = Self::default();
+        let mut claimed_entry_i = claimed_entry_v.iter().peekable();
+        let mut actual_entry_i = actual_entry_v.iter().peekable();
+        loop {
+            match (claimed_entry_i.peek(), actual_entry_i.peek()) {
+                (None, None) => break,
+                (Some(claimed_entry), None) => {
+                    tree_diff.removed_v.push(claimed_entry.path.clone());
+                    claimed_entry_i.next();
+                }
+                (None, Some(actual_entry)) => {
+                    tree_diff.added_v.push(actual_entry.path.clone());
+                    actual_entry_i.next();
+                }
+                (Some(claimed_entry), Some(actual_entry)) => {
+                    match claimed_entry.path.cmp(&actual_entry.path) {
+                        std::cmp::Ordering::Less => {
+                            tree_diff.removed_v.push(claimed_entry.path.clone());
+                            claimed_entry_i.next();
+                        }
+                        std::cmp::Ordering::Greater => {
+                            tree_diff.added_v.push(actual_entry.path.clone());
+                            actual_entry_i.next();
+                        }
+                        std::cmp::Ordering::Equal => {
+                            if claimed_entry != actual_entry {
+                                tree_diff.modified_v.push(actual_entry.path.clone());
+                            }
+                            claimed_entry_i.next();
+                            actual_entry_i.next();
+                        }
+                    }
+                }
+            }
+        }
+        tree_diff
+    }
+    /// Returns true iff there are no differences.
+    pub fn is_empty(&self) -> bool {
+        self.added_v.is_empty() && self.removed_v.is_empty() && self.modified_v.is_empty()
+    }
+}
+
+impl std::fmt::Display for TreeDiff {
+    /// Writes one line per difference, e.g. "added: docs/new.md", in the order added, removed, modified.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for path in self.added_v.iter() {
+            writeln!(f, "added: {}", path)?;
+        }
+        for path in self.removed_v.iter() {
+            writeln!(f, "removed: {}", path)?;
+        }
+        for path in self.modified_v.iter() {
+            writeln!(f, "modified: {}", path)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,271 @@
+use crate::{
+    bail, ensure, error,
+    tree::{merkle_root, TreeDiff, TreeManifestEntry},
+    HashFunctionT, HashRefT, MBHashFunction, Result, SelfHashableJSON, SelfHashableT,
+};
+use std::{
+    borrow::Cow,
+    path::{Path, PathBuf},
+};
+
+/// The name of the file, in the root of a directory tree, in which its TreeManifest is stored.
+pub const MANIFEST_FILE_NAME: &str = "SELFHASH.json";
+
+/// A deterministic description of a directory tree, listing each file (other than the manifest itself)
+/// along with its mode, size, and digest, sorted by path, and the Merkle root over those entries (see
+/// merkle_root).  The manifest is self-hashed via SelfHashableJSON, with `$.selfHash` as the self-hash slot,
+/// so its self-hash covers the whole directory tree.  Directories are only represented by the files within
+/// them, so empty directories aren't covered.
+#[derive(Clone, Debug, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TreeManifest {
+    /// The self-hash of the manifest, or None if it hasn't been self-hashed yet.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub self_hash: Option<mbx::MBHash>,
+    /// The Merkle root over the entries, which also determines the hash function used for the file digests.
+    pub merkle_root: mbx::MBHash,
+    /// The entries for the files in the directory tree, sorted by path.
+    pub entries: Vec<TreeManifestEntry>,
+}
+
+impl TreeManifest {
+    /// Creates an un-self-hashed manifest from the given entries, which are sorted by path, and whose
+    /// digests must use the given hash function.
+    pub fn new(
+        mb_hash_function: &MBHashFunction,
+        mut entry_v: Vec<TreeManifestEntry>,
+    ) -> Result<Self> {
+        entry_v.sort_by(|a, b| a.path.cmp(&b.path));
+        ensure_entries_are_valid(mb_hash_function, &entry_v)?;
+        Ok(Self {
+            self_hash: None,
+            merkle_root: merkle_root(mb_hash_function, &entry_v)?,
+            entries: entry_v,
+        })
+    }
+    /// Parses a manifest from JSON, ensuring that its entries are sorted by path, with no path listed more
+    /// than once, and that their digests use the manifest's hash function, just as new does.
+    pub fn from_json_str(json: &str) -> Result<Self> {
+        let tree_manifest = serde_json::from_str::<Self>(json)
+            .map_err(|e| error!("malformed tree manifest; error was {}", e))?;
+        ensure_entries_are_valid(&tree_manifest.mb_hash_function(), &tree_manifest.entries)?;
+        Ok(tree_manifest)
+    }
+    /// Returns the manifest serialized as canonical JSON (JCS).
+    pub fn to_json_string(&self) -> Result<String> {
+        Ok(serde_json_canonicalizer::to_string(self)?)
+    }
+    /// Returns the hash function used for the file digests and the Merkle root.
+    pub fn mb_hash_function(&self) -> MBHashFunction {
+        self.merkle_root.hash_function()
+    }
+    /// Returns the manifest as self-hashable JSON, with `$.selfHash` as the self-hash slot.
+    fn to_self_hashable_json(&self) -> Result<SelfHashableJSON<'static, 'static>> {
+        let value = serde_json::to_value(self)
+            .map_err(|e| error!("failed to serialize tree manifest; error was {}", e))?;
+        SelfHashableJSON::new(
+            value,
+            Cow::Owned(std::collections::HashSet::from([Cow::Borrowed(
+                "$.selfHash",
+            )])),
+            Cow::Owned(std::collections::HashSet::new()),
+        )
+    }
+    /// Computes the self-hash of the manifest using its hash function and sets it.
+    pub fn self_hash(&mut self) -> Result<mbx::MBHash> {
+        let mut self_hashable_json = self.to_self_hashable_json()?;
+        let self_hash = self_hashable_json
+            .self_hash(self.mb_hash_function().new_hasher())?
+            .to_owned();
+        self.self_hash = Some(self_hash.clone());
+        Ok(self_hash)
+    }
+    /// Verifies the self-hash of the manifest, that its entries are valid (see from_json_str), and that its
+    /// Merkle root matches its entries, returning the verified self-hash.
+    pub fn verify_self_hash(&self) -> Result<mbx::MBHash> {
+        ensure_entries_are_valid(&self.mb_hash_function(), &self.entries)?;
+        let self_hash = self
+            .to_self_hashable_json()?
+            .verify_self_hashes()?
+            .to_owned();
+        ensure!(
+            merkle_root(&self.mb_hash_function(), &self.entries)? == self.merkle_root,
+            "tree manifest's Merkle root doesn't match its entries"
+        );
+        Ok(self_hash)
+    }
+}
+
+/// Ensures that the given manifest entries are strictly sorted by path (i.e. sorted, with no path listed
+/// more than once), which TreeDiff::new depends on, and that their digests use the given hash function.
+fn ensure_entries_are_valid(
+    mb_hash_function: &MBHashFunction,
+    entry_v: &[TreeManifestEntry],
+) -> Result<()> {
+    for pair in entry_v.windows(2) {
+        ensure!(
+            pair[0].path != pair[1].path,
+            "tree manifest lists path {:?} more than once",
+            pair[0].path
+        );
+        ensure!(
+            pair[0].path < pair[1].path,
+            "tree manifest entries are not sorted by path ({:?} precedes {:?})",
+            pair[0].path,
+            pair[1].path
+        );
+    }
+    for entry in entry_v.iter() {
+        ensure!(
+            entry.digest.hash_function() == *mb_hash_function,
+            "digest of tree manifest entry {:?} doesn't use the manifest's hash function",
+            entry.path
+        );
+    }
+    Ok(())
+}
+
+/// The result of verifying a directory tree against its manifest.
+#[derive(Clone, Debug)]
+pub struct TreeVerification {
+    /// The verified self-hash of the manifest.
+    pub self_hash: mbx::MBHash,
+    /// The differences between the manifest and the directory tree.
+    pub diff: TreeDiff,
+}
+
+impl TreeVerification {
+    /// Returns true iff the directory tree matches its manifest exactly.
+    pub fn is_verified(&self) -> bool {
+        self.diff.is_empty()
+    }
+}
+
+/// Computes the self-hashed manifest of the given directory tree, excluding the manifest file (see
+/// MANIFEST_FILE_NAME) in its root.  Symbolic links and non-UTF-8 file names are rejected.
+pub fn compute_tree_manifest(
+    root_dir: &Path,
+    mb_hash_function: &MBHashFunction,
+) -> Result<TreeManifest> {
+    let mut tree_manifest = TreeManifest::new(
+        mb_hash_function,
+        compute_entries(root_dir, mb_hash_function)?,
+    )?;
+    tree_manifest.self_hash()?;
+    Ok(tree_manifest)
+}
+
+/// Computes the self-hashed manifest of the given directory tree (see compute_tree_manifest) and writes it
+/// (as JCS) to the manifest file in its root, replacing any existing one.
+pub fn write_tree_manifest(
+    root_dir: &Path,
+    mb_hash_function: &MBHashFunction,
+) -> Result<TreeManifest> {
+    let tree_manifest = compute_tree_manifest(root_dir, mb_hash_function)?;
+    let manifest_path = root_dir.join(MANIFEST_FILE_NAME);
+    std::fs::write(&manifest_path, tree_manifest.to_json_string()?).map_err(|e| {
+        error!(
+            "failed to write tree manifest to {:?}; error was {}",
+            manifest_path, e
+        )
+    })?;
+    Ok(tree_manifest)
+}
+
+/// Reads the manifest file in the root of the given directory tree, verifies its self-hash, and compares
+/// it against the files actually present, reporting which were added, removed, or modified.  An error is
+/// returned if the manifest is missing, malformed, or fails self-hash verification.
+pub fn verify_tree_manifest(root_dir: &Path) -> Result<TreeVerification> {
+    let manifest_path = root_dir.join(MANIFEST_FILE_NAME);
+    let manifest_json = std::fs::read_to_string(&manifest_path).map_err(|e| {
+        error!(
+            "failed to read tree manifest from {:?}; error was {}",
+            manifest_path, e
+        )
+    })?;
+    let tree_manifest = TreeManifest::from_json_str(&manifest_json)?;
+    let self_hash = tree_manifest.verify_self_hash()?;
+    let actual_entry_v = compute_entries(root_dir, &tree_manifest.mb_hash_function())?;
+    Ok(TreeVerification {
+        self_hash,
+        diff: TreeDiff::new(&tree_manifest.entries, &actual_entry_v),
+    })
+}
+
+/// Computes the entries for all the files in the given directory tree (other than the manifest file in its
+/// root), sorted by path.
+fn compute_entries(
+    root_dir: &Path,
+    mb_hash_function: &MBHashFunction,
+) -> Result<Vec<TreeManifestEntry>> {
+    let mut file_v = Vec::new();
+    collect_files(root_dir, "", &mut file_v)?;
+    let mut entry_v = file_v
+        .into_iter()
+        .filter(|(path, _)| path != MANIFEST_FILE_NAME)
+        .map(|(path, file_path)| compute_entry(path, &file_path, mb_hash_function))
+        .collect::<Result<Vec<_>>>()?;
+    entry_v.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(entry_v)
+}
+
+/// Collects the (relative path, filesystem path) pairs of the files within the given directory.
+fn collect_files(dir: &Path, path_prefix: &str, file_v: &mut Vec<(String, PathBuf)>) -> Result<()> {
+    let read_dir = std::fs::read_dir(dir)
+        .map_err(|e| error!("failed to read directory {:?}; error was {}", dir, e))?;
+    for dir_entry_r in read_dir {
+        let dir_entry = dir_entry_r
+            .map_err(|e| error!("failed to read directory {:?}; error was {}", dir, e))?;
+        let file_path = dir_entry.path();
+        let file_name = dir_entry.file_name();
+        let file_name = file_name
+            .to_str()
+            .ok_or_else(|| error!("file name of {:?} is not valid UTF-8", file_path))?;
+        let path = format!("{}{}", path_prefix, file_name);
+        let file_type = dir_entry.file_type().map_err(|e| {
+            error!(
+                "failed to read file type of {:?}; error was {}",
+                file_path, e
+            )
+        })?;
+        if file_type.is_dir() {
+            collect_files(&file_path, &format!("{}/", path), file_v)?;
+        } else if file_type.is_file() {
+            file_v.push((path, file_path));
+        } else {
+            bail!(
+                "{:?} is not a regular file or directory (e.g. it's a symbolic link), which is not supported in a tree manifest",
+                file_path
+            );
+        }
+    }
+    Ok(())
+}
+
+fn compute_entry(
+    path: String,
+    file_path: &Path,
+    mb_hash_function: &MBHashFunction,
+) -> Result<TreeManifestEntry> {
+    let map_io_error =
+        |e: std::io::Error| error!("failed to read {:?}; error was {}", file_path, e);
+    let mut file = std::fs::File::open(file_path).map_err(map_io_error)?;
+    let metadata = file.metadata().map_err(map_io_error)?;
+    TreeManifestEntry::from_reader(path, file_mode(&metadata), &mut file, mb_hash_function)
+        .map_err(map_io_error)
+}
+
+#[cfg(unix)]
+fn file_mode(metadata: &std::fs::Metadata) -> String {
+    use std::os::unix::fs::PermissionsExt;
+    format!("{:04o}", metadata.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+fn file_mode(metadata: &std::fs::Metadata) -> String {
+    if metadata.permissions().readonly() {
+        "0444".to_string()
+    } else {
+        "0644".to_string()
+    }
+}
@@ -0,0 +1,49 @@
+use crate::{HashFunctionT, HasherT, MBHashFunction};
+use std::io::Read;
+
+/// The size of the buffer used to stream file content into the hasher.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// An entry in a TreeManifest, describing one file of the directory tree.
+#[derive(Clone, Debug, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TreeManifestEntry {
+    /// The path of the file relative to the root of the directory tree, with `/` as the separator.
+    pub path: String,
+    /// The permission bits of the file, in octal (e.g. "0644" or "0755").  On platforms without Unix
+    /// permissions, this is "0444" for read-only files and "0644" otherwise.
+    pub mode: String,
+    /// The size of the file in bytes.
+    pub size: u64,
+    /// The multibase-encoded multihash of the content of the file.
+    pub digest: mbx::MBHash,
+}
+
+impl TreeManifestEntry {
+    /// Creates the entry for a file with the given path and mode, streaming its content from the given
+    /// reader to compute its size and digest.
+    pub fn from_reader(
+        path: String,
+        mode: String,
+        reader: &mut dyn Read,
+        mb_hash_function: &MBHashFunction,
+    ) -> std::io::Result<Self> {
+        let mut hasher = mb_hash_function.new_hasher();
+        let mut buffer = vec![0u8; CHUNK_SIZE];
+        let mut size = 0u64;
+        loop {
+            let read_length = reader.read(&mut buffer)?;
+            if read_length == 0 {
+                break;
+            }
+            HasherT::update(&mut hasher, &buffer[..read_length]);
+            size += read_length as u64;
+        }
+        Ok(Self {
+            path,
+            mode,
+            size,
+            digest: hasher.finalize(),
+        })
+    }
+}
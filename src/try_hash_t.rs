@@ -0,0 +1,8 @@
+use crate::{HashRefT, Result};
+
+/// Represents a value that can possibly be interpreted as a hash reference, e.g. a string field of
+/// untrusted input which is meant to contain a hash.  This is the fallible analog to HashT, and should be
+/// used instead of HashT wherever the value hasn't already been validated.
+pub trait TryHashT<HashRef: HashRefT + ?Sized + ToOwned> {
+    fn try_as_hash_ref(&self) -> Result<&HashRef>;
+}
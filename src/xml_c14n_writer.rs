@@ -0,0 +1,158 @@
+use crate::{
+    error,
+    xml_document::{split_qname, XMLDocument, XMLElement, XMLNode},
+    Result,
+};
+use std::collections::BTreeMap;
+
+/// Writes the given document in Exclusive XML Canonicalization form, without comments (see
+/// https://www.w3.org/TR/xml-exc-c14n/), with an empty InclusiveNamespaces PrefixList.  In particular:
+/// - Empty elements are written as start-end tag pairs, and the whitespace within tags is normalized.
+/// - Namespace declarations are written only where a prefix is visibly utilized (i.e. by the name of the
+///   element or one of its attributes) and isn't already declared with the same URI by an ancestor, and
+///   they're sorted by prefix, with the default namespace first.
+/// - Attributes are sorted by namespace URI (with no namespace first), then by local name.
+/// - Attribute values and text are written with a fixed set of characters escaped.
+/// - Processing instructions outside the document element are separated from it by a line feed.
+pub(crate) fn write_exclusive_c14n<W: std::io::Write>(
+    document: &XMLDocument,
+    writer: &mut W,
+) -> Result<()> {
+    let mut output = String::new();
+    for (target, data) in document.prolog_pi_v.iter() {
+        write_pi(target, data, &mut output);
+        output.push('\n');
+    }
+    write_element(&document.root, &BTreeMap::new(), &mut output);
+    for (target, data) in document.epilog_pi_v.iter() {
+        output.push('\n');
+        write_pi(target, data, &mut output);
+    }
+    writer
+        .write_all(output.as_bytes())
+        .map_err(|e| error!("failed to write canonical XML; error was {}", e))
+}
+
+/// Returns the given document in Exclusive XML Canonicalization form (see write_exclusive_c14n).
+pub(crate) fn to_exclusive_c14n_string(document: &XMLDocument) -> String {
+    let mut buffer = Vec::new();
+    write_exclusive_c14n(document, &mut buffer).expect("programmer error");
+    String::from_utf8(buffer).expect("programmer error")
+}
+
+/// The rendered_namespace_m argument maps each prefix to the namespace URI that the nearest output
+/// ancestor declared it as.
+fn write_element(
+    element: &XMLElement,
+    rendered_namespace_m: &BTreeMap<&str, &str>,
+    output: &mut String,
+) {
+    // Determine which namespace declarations have to be written.  The default namespace is visibly
+    // utilized by an unprefixed element name (but not by unprefixed attribute names, which are in no
+    // namespace), and an undeclared default namespace is equivalent to the empty namespace URI.
+    let mut rendered_namespace_m = rendered_namespace_m.clone();
+    let mut namespace_declaration_m = BTreeMap::new();
+    let (element_prefix, _) = split_qname(element.qname.as_str());
+    let utilized_prefix_i = std::iter::once(element_prefix).chain(
+        element
+            .attribute_v
+            .iter()
+            .map(|(qname, _)| split_qname(qname.as_str()).0)
+            .filter(|prefix| !prefix.is_empty()),
+    );
+    for prefix in utilized_prefix_i {
+        if prefix == "xml" {
+            continue;
+        }
+        let namespace_uri = element.namespace_uri(prefix);
+        if rendered_namespace_m.get(prefix).copied().unwrap_or("") != namespace_uri {
+            rendered_namespace_m.insert(prefix, namespace_uri);
+            namespace_declaration_m.insert(prefix, namespace_uri);
+        }
+    }
+
+    output.push('<');
+    output.push_str(element.qname.as_str());
+    // BTreeMap iterates in prefix order, and the empty prefix (the default namespace) sorts first.
+    for (prefix, namespace_uri) in namespace_declaration_m {
+        if prefix.is_empty() {
+            output.push_str(" xmlns=\"");
+        } else {
+            output.push_str(" xmlns:");
+            output.push_str(prefix);
+            output.push_str("=\"");
+        }
+        push_escaped_attribute_value(namespace_uri, output);
+        output.push('"');
+    }
+    let mut attribute_v = element
+        .attribute_v
+        .iter()
+        .map(|(qname, value)| {
+            let (prefix, local_name) = split_qname(qname.as_str());
+            let namespace_uri = if prefix.is_empty() {
+                ""
+            } else {
+                element.namespace_uri(prefix)
+            };
+            ((namespace_uri, local_name), qname.as_str(), value.as_str())
+        })
+        .collect::<Vec<_>>();
+    attribute_v.sort_by(|a, b| a.0.cmp(&b.0));
+    for (_, qname, value) in attribute_v {
+        output.push(' ');
+        output.push_str(qname);
+        output.push_str("=\"");
+        push_escaped_attribute_value(value, output);
+        output.push('"');
+    }
+    output.push('>');
+    for child in element.child_v.iter() {
+        match child {
+            XMLNode::Element(child_element) => {
+                write_element(child_element, &rendered_namespace_m, output)
+            }
+            XMLNode::Text(text) => push_escaped_text(text, output),
+            XMLNode::ProcessingInstruction { target, data } => write_pi(target, data, output),
+        }
+    }
+    output.push_str("</");
+    output.push_str(element.qname.as_str());
+    output.push('>');
+}
+
+fn write_pi(target: &str, data: &str, output: &mut String) {
+    output.push_str("<?");
+    output.push_str(target);
+    if !data.is_empty() {
+        output.push(' ');
+        output.push_str(data);
+    }
+    output.push_str("?>");
+}
+
+fn push_escaped_text(text: &str, output: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '\r' => output.push_str("&#xD;"),
+            _ => output.push(c),
+        }
+    }
+}
+
+fn push_escaped_attribute_value(value: &str, output: &mut String) {
+    for c in value.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '"' => output.push_str("&quot;"),
+            '\t' => output.push_str("&#x9;"),
+            '\n' => output.push_str("&#xA;"),
+            '\r' => output.push_str("&#xD;"),
+            _ => output.push(c),
+        }
+    }
+}
@@ -0,0 +1,240 @@
+use crate::{error, Result};
+use std::{collections::BTreeMap, rc::Rc};
+
+/// The namespace URI that the `xml` prefix is bound to by definition.
+pub(crate) const XML_NAMESPACE_URI: &str = "http://www.w3.org/XML/1998/namespace";
+
+/// Splits a qualified name into its prefix (empty if there is none) and its local name.
+pub(crate) fn split_qname(qname: &str) -> (&str, &str) {
+    match qname.split_once(':') {
+        Some((prefix, local_name)) => (prefix, local_name),
+        None => ("", qname),
+    }
+}
+
+/// A node within an XML element, in the form relevant to canonicalization.  Comments are dropped, CDATA
+/// sections and character and entity references are merged into text, and line endings and attribute
+/// values are normalized, all during parsing.
+#[derive(Clone, Debug)]
+pub(crate) enum XMLNode {
+    Element(XMLElement),
+    Text(String),
+    ProcessingInstruction { target: String, data: String },
+}
+
+/// An XML element, whose names are kept in the form they were written (i.e. with their prefixes), along
+/// with the namespaces in scope, so that the namespace of each name can be determined.
+#[derive(Clone, Debug)]
+pub(crate) struct XMLElement {
+    /// The qualified name of the element, e.g. `record` or `ds:Signature`.
+    pub(crate) qname: String,
+    /// Maps each prefix in scope (the empty string for the default namespace) to its namespace URI.
+    pub(crate) namespace_m: Rc<BTreeMap<String, String>>,
+    /// The qualified names and (normalized) values of the attributes, not including namespace declarations.
+    pub(crate) attribute_v: Vec<(String, String)>,
+    pub(crate) child_v: Vec<XMLNode>,
+}
+
+impl XMLElement {
+    /// Returns the namespace URI bound to the given prefix (the empty string for the default namespace),
+    /// or the empty string if it's unbound.
+    pub(crate) fn namespace_uri(&self, prefix: &str) -> &str {
+        if prefix == "xml" {
+            XML_NAMESPACE_URI
+        } else {
+            self.namespace_m
+                .get(prefix)
+                .map(String::as_str)
+                .unwrap_or("")
+        }
+    }
+    /// Returns true iff the given prefix is bound in this element (the empty prefix always is).
+    pub(crate) fn is_prefix_bound(&self, prefix: &str) -> bool {
+        prefix.is_empty() || prefix == "xml" || self.namespace_m.contains_key(prefix)
+    }
+    pub(crate) fn attribute(&self, qname: &str) -> Option<&str> {
+        self.attribute_v
+            .iter()
+            .find(|(attribute_qname, _)| attribute_qname == qname)
+            .map(|(_, value)| value.as_str())
+    }
+    /// Sets the value of the given attribute, adding it if it doesn't exist.
+    pub(crate) fn set_attribute(&mut self, qname: &str, value: String) {
+        if let Some((_, attribute_value)) = self
+            .attribute_v
+            .iter_mut()
+            .find(|(attribute_qname, _)| attribute_qname == qname)
+        {
+            *attribute_value = value;
+        } else {
+            self.attribute_v.push((qname.to_string(), value));
+        }
+    }
+    /// Returns the indices (within child_v) of the child elements.
+    pub(crate) fn child_element_index_i(&self) -> impl Iterator<Item = usize> + '_ {
+        self.child_v
+            .iter()
+            .enumerate()
+            .filter(|(_, child)| matches!(child, XMLNode::Element(_)))
+            .map(|(i, _)| i)
+    }
+    pub(crate) fn child_element(&self, index: usize) -> Option<&XMLElement> {
+        match self.child_v.get(index) {
+            Some(XMLNode::Element(element)) => Some(element),
+            _ => None,
+        }
+    }
+    pub(crate) fn child_element_mut(&mut self, index: usize) -> Option<&mut XMLElement> {
+        match self.child_v.get_mut(index) {
+            Some(XMLNode::Element(element)) => Some(element),
+            _ => None,
+        }
+    }
+    /// Returns the text content of this element, or None if it has any children other than text.
+    pub(crate) fn text_content_o(&self) -> Option<&str> {
+        match self.child_v.as_slice() {
+            [] => Some(""),
+            // Adjacent text is always merged, so there's at most one text child.
+            [XMLNode::Text(text)] => Some(text.as_str()),
+            _ => None,
+        }
+    }
+    /// Replaces the children of this element with the given text.
+    pub(crate) fn set_text_content(&mut self, text: String) {
+        self.child_v = vec![XMLNode::Text(text)];
+    }
+}
+
+/// An XML document, in the form relevant to canonicalization, i.e. its document element along with the
+/// processing instructions before and after it.  The XML declaration, comments, and whitespace outside the
+/// document element are dropped.  Documents with a DTD are rejected, since the DTD could affect the
+/// canonical form (e.g. via default attribute values).
+#[derive(Clone, Debug)]
+pub(crate) struct XMLDocument {
+    /// The processing instructions (target, data) before the document element.
+    pub(crate) prolog_pi_v: Vec<(String, String)>,
+    pub(crate) root: XMLElement,
+    /// The processing instructions (target, data) after the document element.
+    pub(crate) epilog_pi_v: Vec<(String, String)>,
+}
+
+impl XMLDocument {
+    pub(crate) fn parse(xml: &str) -> Result<Self> {
+        let document = roxmltree::Document::parse(xml)
+            .map_err(|e| error!("malformed XML; error was {}", e))?;
+        let mut prolog_pi_v = Vec::new();
+        let mut root_o = None;
+        let mut epilog_pi_v = Vec::new();
+        let root_namespace_m = Rc::new(BTreeMap::new());
+        for node in document.root().children() {
+            match node.node_type() {
+                roxmltree::NodeType::Element => {
+                    root_o = Some(convert_element(xml, node, &root_namespace_m));
+                }
+                roxmltree::NodeType::PI => {
+                    let pi = node.pi().expect("programmer error");
+                    let pi = (
+                        pi.target.to_string(),
+                        pi.value.unwrap_or_default().to_string(),
+                    );
+                    if root_o.is_none() {
+                        prolog_pi_v.push(pi);
+                    } else {
+                        epilog_pi_v.push(pi);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(Self {
+            prolog_pi_v,
+            root: root_o.ok_or_else(|| error!("XML document has no document element"))?,
+            epilog_pi_v,
+        })
+    }
+    /// Returns the element at the given location, which is a sequence of child indices starting from the
+    /// document element (see XMLPath).
+    pub(crate) fn element_at(&self, element_location: &[usize]) -> Option<&XMLElement> {
+        let mut element = &self.root;
+        for &index in element_location {
+            element = element.child_element(index)?;
+        }
+        Some(element)
+    }
+    pub(crate) fn element_at_mut(&mut self, element_location: &[usize]) -> Option<&mut XMLElement> {
+        let mut element = &mut self.root;
+        for &index in element_location {
+            element = element.child_element_mut(index)?;
+        }
+        Some(element)
+    }
+}
+
+fn convert_element(
+    xml: &str,
+    node: roxmltree::Node,
+    parent_namespace_m: &Rc<BTreeMap<String, String>>,
+) -> XMLElement {
+    // roxmltree resolves names to namespace URIs, so the qualified names are recovered from the input.
+    let qname = xml[node.range().start + 1..]
+        .split(|c: char| c.is_ascii_whitespace() || c == '/' || c == '>')
+        .next()
+        .expect("programmer error")
+        .to_string();
+    let namespace_m = node
+        .namespaces()
+        .filter(|namespace| namespace.name() != Some("xml"))
+        .map(|namespace| {
+            (
+                namespace.name().unwrap_or_default().to_string(),
+                namespace.uri().to_string(),
+            )
+        })
+        .collect::<BTreeMap<_, _>>();
+    // Share the namespace map with the parent when it's the same, which is the usual case.
+    let namespace_m = if namespace_m == **parent_namespace_m {
+        parent_namespace_m.clone()
+    } else {
+        Rc::new(namespace_m)
+    };
+    let attribute_v = node
+        .attributes()
+        .map(|attribute| {
+            (
+                xml[attribute.range_qname()].to_string(),
+                attribute.value().to_string(),
+            )
+        })
+        .collect();
+    let mut child_v = Vec::new();
+    for child in node.children() {
+        match child.node_type() {
+            roxmltree::NodeType::Element => {
+                child_v.push(XMLNode::Element(convert_element(xml, child, &namespace_m)));
+            }
+            roxmltree::NodeType::Text => {
+                // Text separated only by a (dropped) comment is merged.
+                let text = child.text().unwrap_or_default();
+                if let Some(XMLNode::Text(previous_text)) = child_v.last_mut() {
+                    previous_text.push_str(text);
+                } else {
+                    child_v.push(XMLNode::Text(text.to_string()));
+                }
+            }
+            roxmltree::NodeType::PI => {
+                let pi = child.pi().expect("programmer error");
+                child_v.push(XMLNode::ProcessingInstruction {
+                    target: pi.target.to_string(),
+                    data: pi.value.unwrap_or_default().to_string(),
+                });
+            }
+            roxmltree::NodeType::Comment | roxmltree::NodeType::Root => {}
+        }
+    }
+    XMLElement {
+        qname,
+        namespace_m,
+        attribute_v,
+        child_v,
+    }
+}
@@ -0,0 +1,390 @@
+use crate::{
+    bail, ensure, error,
+    xml_document::{XMLDocument, XMLElement},
+    Result,
+};
+use std::collections::BTreeSet;
+
+/// A predicate in an XMLPath step.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum XMLPathPredicate {
+    /// `[n]` selects the nth (1-based) of the elements selected so far by the step (per parent).
+    Position(usize),
+    /// `[@name]` selects the elements having the named attribute, and `[@name='value']` (or with double
+    /// quotes) those for which the attribute has the given value.
+    Attribute {
+        qname: String,
+        value_o: Option<String>,
+    },
+}
+
+/// A step in an XMLPath, which selects child elements (or descendants, if preceded by `//`).
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct XMLPathStep {
+    /// True iff the step was preceded by `//` instead of `/`.
+    is_descendant: bool,
+    /// The qualified name of the selected elements, or None for `*`.
+    qname_o: Option<String>,
+    predicate_v: Vec<XMLPathPredicate>,
+}
+
+/// The location of a self-hash [URL] slot within an XML document: either the text content of an element,
+/// or an attribute of an element.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub(crate) struct XMLSlotLocation {
+    /// The sequence of child indices leading from the document element to the element (see
+    /// XMLDocument::element_at).
+    pub(crate) element_location: Vec<usize>,
+    /// The qualified name of the attribute, or None for the element's text content.
+    pub(crate) attribute_qname_o: Option<String>,
+}
+
+impl XMLSlotLocation {
+    /// Returns the absolute XMLPath which selects exactly this slot, in which each step below the document
+    /// element has a position predicate, e.g. `/record/header[1]/@selfHash`.
+    pub(crate) fn to_xml_path_string(&self, document: &XMLDocument) -> String {
+        let mut element = &document.root;
+        let mut xml_path_string = format!("/{}", element.qname);
+        for &index in self.element_location.iter() {
+            let child = element.child_element(index).expect("programmer error");
+            let position = 1 + element
+                .child_element_index_i()
+                .take_while(|&sibling_index| sibling_index < index)
+                .filter(|&sibling_index| {
+                    element
+                        .child_element(sibling_index)
+                        .expect("programmer error")
+                        .qname
+                        == child.qname
+                })
+                .count();
+            xml_path_string.push_str(&format!("/{}[{}]", child.qname, position));
+            element = child;
+        }
+        if let Some(attribute_qname) = self.attribute_qname_o.as_deref() {
+            xml_path_string.push_str(&format!("/@{}", attribute_qname));
+        }
+        xml_path_string
+    }
+}
+
+/// A selector for elements or attributes in an XML document, in a subset of XPath 1.0's abbreviated syntax.
+/// It consists of one or more steps, each preceded by `/` (selecting children) or `//` (selecting
+/// descendants), where each step is an element name (or `*`) followed by any number of predicates, which
+/// are either positions (e.g. `[2]`, 1-based) or attribute tests (e.g. `[@rel]` or `[@rel='self']`).  The
+/// last step may instead be an attribute name preceded by `@`.  For example:
+/// - `/record/@selfHash` selects the `selfHash` attribute of the `record` document element.
+/// - `/record/header/selfHash` selects the `selfHash` child element of the `header` child element.
+/// - `//link[@rel='self']/@href` selects the `href` attribute of every `link` element whose `rel`
+///   attribute is `self`.
+///
+/// Names are matched as written in the document, including their namespace prefixes (e.g. `ds:Reference`),
+/// rather than by namespace URI.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct XMLPath {
+    selector: String,
+    step_v: Vec<XMLPathStep>,
+    /// The qualified name of the selected attribute, if the last step is an attribute.
+    attribute_qname_o: Option<String>,
+}
+
+fn is_name_char(c: char) -> bool {
+    !c.is_whitespace() && !matches!(c, '/' | '[' | ']' | '@' | '=' | '\'' | '"' | '*')
+}
+
+/// Parses a (possibly prefixed) name at the start of the given string, returning it and the rest.
+fn parse_qname<'a>(s: &'a str, selector: &str) -> Result<(&'a str, &'a str)> {
+    let len = s.find(|c| !is_name_char(c)).unwrap_or(s.len());
+    let qname = &s[..len];
+    ensure!(
+        !qname.is_empty()
+            && !qname.starts_with(':')
+            && !qname.ends_with(':')
+            && qname.matches(':').count() <= 1,
+        "expected a name in XMLPath {:?} at {:?}",
+        selector,
+        s
+    );
+    Ok((qname, &s[len..]))
+}
+
+impl XMLPath {
+    pub fn parse(selector: &str) -> Result<Self> {
+        let mut step_v = Vec::new();
+        let mut attribute_qname_o = None;
+        let mut s = selector;
+        ensure!(
+            s.starts_with('/'),
+            "XMLPath {:?} must begin with `/` or `//`",
+            selector
+        );
+        while !s.is_empty() {
+            ensure!(
+                attribute_qname_o.is_none(),
+                "an attribute can only be the last step of XMLPath {:?}",
+                selector
+            );
+            let is_descendant = if let Some(rest) = s.strip_prefix("//") {
+                s = rest;
+                true
+            } else if let Some(rest) = s.strip_prefix('/') {
+                s = rest;
+                false
+            } else {
+                bail!("expected `/` or `//` in XMLPath {:?} at {:?}", selector, s);
+            };
+            if let Some(rest) = s.strip_prefix('@') {
+                ensure!(
+                    !is_descendant && !step_v.is_empty(),
+                    "an attribute step must be preceded by an element step and `/` in XMLPath {:?}",
+                    selector
+                );
+                let (qname, rest) = parse_qname(rest, selector)?;
+                attribute_qname_o = Some(qname.to_string());
+                s = rest;
+                continue;
+            }
+            let qname_o = if let Some(rest) = s.strip_prefix('*') {
+                s = rest;
+                None
+            } else {
+                let (qname, rest) = parse_qname(s, selector)?;
+                s = rest;
+                Some(qname.to_string())
+            };
+            let mut predicate_v = Vec::new();
+            while let Some(rest) = s.strip_prefix('[') {
+                let (predicate_str, rest) = rest
+                    .split_once(']')
+                    .ok_or_else(|| error!("unterminated predicate in XMLPath {:?}", selector))?;
+                s = rest;
+                predicate_v.push(Self::parse_predicate(predicate_str, selector)?);
+            }
+            step_v.push(XMLPathStep {
+                is_descendant,
+                qname_o,
+                predicate_v,
+            });
+        }
+        Ok(Self {
+            selector: selector.to_string(),
+            step_v,
+            attribute_qname_o,
+        })
+    }
+    fn parse_predicate(predicate_str: &str, selector: &str) -> Result<XMLPathPredicate> {
+        if let Some(rest) = predicate_str.strip_prefix('@') {
+            let (qname, rest) = parse_qname(rest, selector)?;
+            let value_o = if rest.is_empty() {
+                None
+            } else {
+                let quoted_value = rest.strip_prefix('=').ok_or_else(|| {
+                    error!(
+                        "malformed predicate [{}] in XMLPath {:?}",
+                        predicate_str, selector
+                    )
+                })?;
+                let value = quoted_value
+                    .strip_prefix('\'')
+                    .and_then(|v| v.strip_suffix('\''))
+                    .or_else(|| {
+                        quoted_value
+                            .strip_prefix('"')
+                            .and_then(|v| v.strip_suffix('"'))
+                    })
+                    .filter(|value| !value.contains(['\'', '"']))
+                    .ok_or_else(|| {
+                        error!(
+                            "malformed attribute value in predicate [{}] in XMLPath {:?}",
+                            predicate_str, selector
+                        )
+                    })?;
+                Some(value.to_string())
+            };
+            Ok(XMLPathPredicate::Attribute {
+                qname: qname.to_string(),
+                value_o,
+            })
+        } else {
+            let position = predicate_str
+                .parse::<usize>()
+                .ok()
+                .filter(|position| *position >= 1)
+                .ok_or_else(|| {
+                    error!(
+                        "malformed predicate [{}] in XMLPath {:?}; expected a positive integer or an attribute test",
+                        predicate_str, selector
+                    )
+                })?;
+            Ok(XMLPathPredicate::Position(position))
+        }
+    }
+    pub fn as_str(&self) -> &str {
+        self.selector.as_str()
+    }
+    /// Returns true iff this XMLPath selects an attribute (as opposed to an element).
+    pub fn selects_attribute(&self) -> bool {
+        self.attribute_qname_o.is_some()
+    }
+    /// Returns the qualified name of the attribute selected by this XMLPath, if any.
+    pub fn attribute_qname(&self) -> Option<&str> {
+        self.attribute_qname_o.as_deref()
+    }
+    /// Returns true iff this XMLPath has no `//` or `*`.  Note that it may still select more than one
+    /// element, if there are sibling elements with the same name.
+    pub fn is_singular(&self) -> bool {
+        self.step_v
+            .iter()
+            .all(|step| !step.is_descendant && step.qname_o.is_some())
+    }
+    /// Returns the locations (in document order) of the elements selected by the element steps of this
+    /// XMLPath.
+    fn query_elements(&self, document: &XMLDocument) -> Vec<Vec<usize>> {
+        // None represents the document node, whose only child is the document element.
+        let mut context_v: Vec<Option<Vec<usize>>> = vec![None];
+        for step in self.step_v.iter() {
+            if step.is_descendant {
+                // Expand the context to the descendant-or-self nodes of each context node.
+                let mut expanded_context_s = BTreeSet::new();
+                for context in context_v.into_iter() {
+                    match context {
+                        None => {
+                            expanded_context_s.insert(None);
+                            expanded_context_s.insert(Some(vec![]));
+                            collect_descendants(&document.root, &mut vec![], &mut |location| {
+                                expanded_context_s.insert(Some(location.to_vec()));
+                            });
+                        }
+                        Some(mut location) => {
+                            expanded_context_s.insert(Some(location.clone()));
+                            let element = document.element_at(&location).expect("programmer error");
+                            collect_descendants(element, &mut location, &mut |location| {
+                                expanded_context_s.insert(Some(location.to_vec()));
+                            });
+                        }
+                    }
+                }
+                context_v = expanded_context_s.into_iter().collect();
+            }
+            let mut next_context_v = Vec::new();
+            for context in context_v.iter() {
+                // Determine the children of the context node matching the step's name.
+                let mut candidate_v = match context {
+                    None => {
+                        if step.matches_name(&document.root) {
+                            vec![(vec![], &document.root)]
+                        } else {
+                            vec![]
+                        }
+                    }
+                    Some(location) => {
+                        let element = document.element_at(location).expect("programmer error");
+                        element
+                            .child_element_index_i()
+                            .map(|i| {
+                                let mut child_location = location.clone();
+                                child_location.push(i);
+                                (child_location, element.child_element(i).unwrap())
+                            })
+                            .filter(|(_, child)| step.matches_name(child))
+                            .collect()
+                    }
+                };
+                for predicate in step.predicate_v.iter() {
+                    candidate_v = match predicate {
+                        XMLPathPredicate::Position(position) => candidate_v
+                            .into_iter()
+                            .nth(position - 1)
+                            .into_iter()
+                            .collect(),
+                        XMLPathPredicate::Attribute { qname, value_o } => candidate_v
+                            .into_iter()
+                            .filter(|(_, element)| match element.attribute(qname) {
+                                Some(value) => value_o.as_deref().is_none_or(|v| v == value),
+                                None => false,
+                            })
+                            .collect(),
+                    };
+                }
+                next_context_v.extend(candidate_v.into_iter().map(|(location, _)| Some(location)));
+            }
+            // Sort and deduplicate, since the context nodes of a descendant step can overlap.
+            let next_context_s = next_context_v.into_iter().collect::<BTreeSet<_>>();
+            context_v = next_context_s.into_iter().collect();
+        }
+        context_v
+            .into_iter()
+            .map(|context| context.expect("programmer error"))
+            .collect()
+    }
+    /// Returns the locations (in document order) of the slots selected by this XMLPath, i.e. the selected
+    /// attributes which exist, or the selected elements.
+    pub(crate) fn query_located(&self, document: &XMLDocument) -> Vec<XMLSlotLocation> {
+        let element_location_v = self.query_elements(document);
+        match self.attribute_qname_o.as_deref() {
+            Some(attribute_qname) => element_location_v
+                .into_iter()
+                .filter(|element_location| {
+                    document
+                        .element_at(element_location)
+                        .expect("programmer error")
+                        .attribute(attribute_qname)
+                        .is_some()
+                })
+                .map(|element_location| XMLSlotLocation {
+                    element_location,
+                    attribute_qname_o: Some(attribute_qname.to_string()),
+                })
+                .collect(),
+            None => element_location_v
+                .into_iter()
+                .map(|element_location| XMLSlotLocation {
+                    element_location,
+                    attribute_qname_o: None,
+                })
+                .collect(),
+        }
+    }
+    /// If this XMLPath selects an attribute and its element steps select exactly one element, returns
+    /// the location of that attribute, which is where a missing slot would be inserted.
+    pub(crate) fn insertion_location(&self, document: &XMLDocument) -> Option<XMLSlotLocation> {
+        let attribute_qname = self.attribute_qname_o.as_ref()?;
+        let mut element_location_v = self.query_elements(document);
+        if element_location_v.len() != 1 {
+            return None;
+        }
+        Some(XMLSlotLocation {
+            element_location: element_location_v.pop().unwrap(),
+            attribute_qname_o: Some(attribute_qname.clone()),
+        })
+    }
+}
+
+impl XMLPathStep {
+    fn matches_name(&self, element: &XMLElement) -> bool {
+        self.qname_o
+            .as_deref()
+            .is_none_or(|qname| qname == element.qname)
+    }
+}
+
+/// Calls f with the location of each descendant element of the given element (at the given location),
+/// in document order.
+fn collect_descendants(
+    element: &XMLElement,
+    location: &mut Vec<usize>,
+    f: &mut impl FnMut(&[usize]),
+) {
+    for i in element.child_element_index_i() {
+        location.push(i);
+        f(location);
+        collect_descendants(element.child_element(i).unwrap(), location, f);
+        location.pop();
+    }
+}
+
+impl std::fmt::Display for XMLPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
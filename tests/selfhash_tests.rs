@@ -365,6 +365,43 @@ fn test_mb_hash_placeholder() {
     }
 }
 
+#[cfg(feature = "mbx")]
+#[test]
+fn test_named_hash_function_codec() {
+    use selfhash::NamedHashFunction;
+    // These are the codes in the multicodec table (see https://github.com/multiformats/multicodec).
+    for (named_hash_function, name, codec) in [
+        (NamedHashFunction::BLAKE3, "BLAKE3", 0x1e),
+        (NamedHashFunction::SHA224, "SHA-224", 0x1013),
+        (NamedHashFunction::SHA256, "SHA-256", 0x12),
+        (NamedHashFunction::SHA384, "SHA-384", 0x20),
+        (NamedHashFunction::SHA512, "SHA-512", 0x13),
+        (NamedHashFunction::SHA3_224, "SHA3-224", 0x17),
+        (NamedHashFunction::SHA3_256, "SHA3-256", 0x16),
+        (NamedHashFunction::SHA3_384, "SHA3-384", 0x15),
+        (NamedHashFunction::SHA3_512, "SHA3-512", 0x14),
+    ] {
+        assert_eq!(named_hash_function.codec(), codec);
+        assert_eq!(named_hash_function.to_string(), name);
+        assert_eq!(
+            name.parse::<NamedHashFunction>().expect("pass"),
+            named_hash_function
+        );
+        assert_eq!(
+            named_hash_function
+                .as_mb_hash_function(mbx::Base::Base64Url)
+                .codec(),
+            codec
+        );
+    }
+    // The codecs are distinct, so a hash function can be identified by its codec.
+    let codec_s = NamedHashFunction::ALL
+        .iter()
+        .map(NamedHashFunction::codec)
+        .collect::<std::collections::HashSet<_>>();
+    assert_eq!(codec_s.len(), NamedHashFunction::ALL.len());
+}
+
 #[cfg(feature = "mbx")]
 #[test]
 #[serial_test::serial]
@@ -434,7 +471,13 @@ fn test_self_hashable_mb_hash() {
             // Let's make sure that altering the data causes the verification to fail.
             let mut altered_simple_data_0 = simple_data_0.clone();
             altered_simple_data_0.name = "maaaaaaaaaa".to_string();
-            assert!(altered_simple_data_0.verify_self_hashes().is_err());
+            assert_eq!(
+                altered_simple_data_0
+                    .verify_self_hashes()
+                    .err()
+                    .map(|e| e.kind()),
+                Some(selfhash::ErrorKind::HashMismatch)
+            );
 
             let mut simple_data_1 = SimpleDataMBHash {
                 previous_o: simple_data_0.self_hash_o.clone(),
@@ -735,7 +778,46 @@ fn test_self_hashable_json_1d() {
             .expect("pass");
         println!("json before self-hashing: {}", value.to_string());
         let self_hash_path_s = HashSet::new();
-        let self_hash_url_path_s = maplit::hashset! { Cow::Borrowed("$.$id") };
+        // Note that `$` is not a valid first character of a dot-notation member name in RFC 9535 JSONPath,
+        // so bracket notation must be used.
+        let self_hash_url_path_s = maplit::hashset! { Cow::Borrowed("$['$id']") };
+        let mut self_hashable_json = SelfHashableJSON::new(
+            value,
+            Cow::Owned(self_hash_path_s),
+            Cow::Owned(self_hash_url_path_s),
+        )
+        .expect("pass");
+        let mb_hash_function =
+            selfhash::MBHashFunction::new(mbx::Base::Base64Url, ssi_multicodec::BLAKE3)
+                .expect("programmer error");
+        self_hashable_json
+            .self_hash(mb_hash_function.new_hasher())
+            .expect("pass");
+        self_hashable_json.verify_self_hashes().expect("pass");
+        println!(
+            "json after self-hashing: {}",
+            self_hashable_json.value().to_string()
+        );
+    }
+}
+
+#[cfg(feature = "self-hashable-json")]
+#[test]
+fn test_self_hashable_json_1e() {
+    use selfhash::{HashFunctionT, SelfHashableJSON, SelfHashableT};
+    use std::{borrow::Cow, collections::HashSet};
+    {
+        println!("with bracket-notation self-hash paths:");
+        // Here, the "@id" field is missing, and the "a.b" field's name contains a period.
+        let value =
+            serde_json::from_str::<serde_json::Value>(r#"{"thing":3, "nested": {"a.b": null}}"#)
+                .expect("pass");
+        println!("json before self-hashing: {}", value.to_string());
+        let self_hash_path_s = maplit::hashset! {
+            Cow::Borrowed("$['@id']"),
+            Cow::Borrowed(r#"$.nested["a.b"]"#),
+        };
+        let self_hash_url_path_s = HashSet::new();
         let mut self_hashable_json = SelfHashableJSON::new(
             value,
             Cow::Owned(self_hash_path_s),
@@ -753,5 +835,2868 @@ fn test_self_hashable_json_1d() {
             "json after self-hashing: {}",
             self_hashable_json.value().to_string()
         );
+        assert!(self_hashable_json.value()["@id"].is_string());
+        assert_eq!(
+            self_hashable_json.value()["@id"],
+            self_hashable_json.value()["nested"]["a.b"]
+        );
+    }
+    {
+        // Every slot selected by a multi-match path must be a valid self-hash slot.
+        let value = serde_json::from_str::<serde_json::Value>(r#"{"thing":3}"#).expect("pass");
+        let self_hash_path_s = maplit::hashset! { Cow::Borrowed("$.*") };
+        assert!(SelfHashableJSON::new(
+            value,
+            Cow::Owned(self_hash_path_s),
+            Cow::Owned(HashSet::new()),
+        )
+        .is_err());
+    }
+}
+
+#[cfg(feature = "self-hashable-json")]
+#[test]
+fn test_self_hashable_json_wildcard() {
+    use selfhash::{HashFunctionT, SelfHashSlotCardinality, SelfHashableJSON, SelfHashableT};
+    use std::{borrow::Cow, collections::HashSet};
+    let value = serde_json::from_str::<serde_json::Value>(
+        r#"{"verificationMethod": [{"id": "a", "controller": null}, {"id": "b"}], "service": []}"#,
+    )
+    .expect("pass");
+    let self_hash_path_s = maplit::hashset! {
+        Cow::Borrowed("$.selfHash"),
+        Cow::Borrowed("$.verificationMethod[*].controller"),
+    };
+    {
+        // The wildcard path selects only one slot (the second element has no "controller" field, and
+        // its parent is ambiguous, so it isn't inserted), so a cardinality of exactly 2 is violated.
+        let self_hashable_json = SelfHashableJSON::new(
+            value.clone(),
+            Cow::Borrowed(&self_hash_path_s),
+            Cow::Owned(HashSet::new()),
+        )
+        .expect("pass");
+        assert_eq!(
+            self_hashable_json
+                .self_hash_slot_normalized_paths()
+                .expect("pass"),
+            vec![
+                "$['selfHash']".to_string(),
+                "$['verificationMethod'][0]['controller']".to_string(),
+            ]
+        );
+        let error = match self_hashable_json.with_self_hash_slot_cardinality(
+            "$.verificationMethod[*].controller",
+            SelfHashSlotCardinality::Exactly(2),
+        ) {
+            Ok(_) => panic!("expected a cardinality violation"),
+            Err(error) => error,
+        };
+        assert_eq!(error.kind(), selfhash::ErrorKind::MissingSlot);
+    }
+    {
+        // Unknown selectors are rejected.
+        assert!(SelfHashableJSON::new(
+            value.clone(),
+            Cow::Borrowed(&self_hash_path_s),
+            Cow::Owned(HashSet::new()),
+        )
+        .expect("pass")
+        .with_self_hash_slot_cardinality("$.service[*].id", SelfHashSlotCardinality::AtLeastOne)
+        .is_err());
+    }
+    {
+        let mut value = value.clone();
+        value["verificationMethod"][1]["controller"] = serde_json::Value::Null;
+        println!("json before self-hashing: {}", value.to_string());
+        let mut self_hashable_json = SelfHashableJSON::new(
+            value,
+            Cow::Borrowed(&self_hash_path_s),
+            Cow::Owned(HashSet::new()),
+        )
+        .expect("pass")
+        .with_self_hash_slot_cardinality(
+            "$.verificationMethod[*].controller",
+            SelfHashSlotCardinality::Exactly(2),
+        )
+        .expect("pass");
+        let mb_hash_function =
+            selfhash::MBHashFunction::new(mbx::Base::Base64Url, ssi_multicodec::BLAKE3)
+                .expect("programmer error");
+        let self_hash = self_hashable_json
+            .self_hash(mb_hash_function.new_hasher())
+            .expect("pass")
+            .to_owned();
+        self_hashable_json.verify_self_hashes().expect("pass");
+        println!(
+            "json after self-hashing: {}",
+            self_hashable_json.value().to_string()
+        );
+        for verification_method in self_hashable_json.value()["verificationMethod"]
+            .as_array()
+            .unwrap()
+        {
+            assert_eq!(
+                verification_method["controller"].as_str(),
+                Some(self_hash.as_str())
+            );
+        }
+    }
+}
+
+#[cfg(feature = "self-hashable-json")]
+#[test]
+fn test_self_hashable_json_streaming_digest() {
+    use selfhash::{HashFunctionT, HasherT, SelfHashableT};
+    // The digest data of serde_json::Value is written without cloning the value, substituting the
+    // placeholder on the fly.  It must match the clone-based write_digest_data_using_jcs exactly,
+    // including the UTF-16 ordering of member names.
+    let mb_hash_function =
+        selfhash::MBHashFunction::new(mbx::Base::Base64Url, ssi_multicodec::BLAKE3)
+            .expect("programmer error");
+    for value in [
+        serde_json::json!({"thing": 3}),
+        serde_json::json!({"selfHash": null, "b": [1.5, {"z": "\u{e9}", "a": [true, null]}], "\u{1F600}": 2, "\u{ff61}": 3}),
+    ] {
+        let streamed_self_hash = value
+            .compute_self_hash(mb_hash_function.new_hasher())
+            .expect("pass");
+        let mut hasher = mb_hash_function.new_hasher();
+        selfhash::write_digest_data_using_jcs(&value, &mut hasher).expect("pass");
+        assert_eq!(streamed_self_hash, hasher.finalize());
+    }
+}
+
+#[cfg(feature = "self-hashable-json")]
+#[test]
+fn test_self_hashable_json_malformed() {
+    use selfhash::{SelfHashableJSON, SelfHashableT, TryHashT};
+    use std::{borrow::Cow, collections::HashSet};
+    // Malformed self-hash [URL] fields produce errors instead of panicking.
+    assert!(serde_json::json!("not a hash").try_as_hash_ref().is_err());
+    assert!(serde_json::json!("vjson:///not a hash")
+        .try_as_hash_ref()
+        .is_err());
+    assert!(serde_json::json!(3).try_as_hash_ref().is_err());
+    assert!(serde_json::json!({"selfHash": "vjson:///not a hash"})
+        .verify_self_hashes()
+        .is_err());
+    {
+        let value = serde_json::from_str::<serde_json::Value>(
+            r#"{"thing":3, "$id": "vjson:///not a hash"}"#,
+        )
+        .expect("pass");
+        let e = SelfHashableJSON::new(
+            value,
+            Cow::Owned(HashSet::new()),
+            Cow::Owned(maplit::hashset! { Cow::Borrowed("$['$id']") }),
+        )
+        .err()
+        .expect("pass");
+        println!("error was: {}", e);
+        assert!(e.contains("$['$id']"));
+    }
+    {
+        // An unset self-hash URL is not an error, it just doesn't have a self-hash yet.
+        let value = serde_json::from_str::<serde_json::Value>(r#"{"thing":3, "$id": "vjson:///"}"#)
+            .expect("pass");
+        let self_hashable_json = SelfHashableJSON::new(
+            value,
+            Cow::Owned(HashSet::new()),
+            Cow::Owned(maplit::hashset! { Cow::Borrowed("$['$id']") }),
+        )
+        .expect("pass");
+        assert!(self_hashable_json
+            .get_unverified_self_hash()
+            .expect("pass")
+            .is_none());
+    }
+}
+
+#[cfg(feature = "self-hashable-json")]
+#[test]
+fn test_json_path_segment_normalized_path() {
+    use selfhash::JSONPathSegment;
+
+    // Segments format as in an RFC 9535 normalized path (section 2.7): single-quoted member names,
+    // with only `'`, `\`, and control characters escaped.
+    let location = [
+        JSONPathSegment::Name("proof".to_string()),
+        JSONPathSegment::Index(0),
+        JSONPathSegment::Name("it's \"é\"\\\n\u{1}".to_string()),
+    ];
+    assert_eq!(
+        std::iter::once("$".to_string())
+            .chain(location.iter().map(ToString::to_string))
+            .collect::<String>(),
+        r#"$['proof'][0]['it\'s "é"\\\n\u0001']"#
+    );
+}
+
+#[cfg(feature = "self-hashable-json")]
+#[test]
+fn test_self_hash_url_query_parameter() {
+    use selfhash::{HashFunctionT, SelfHashURL, SelfHashURLStr, SelfHashableJSON, SelfHashableT};
+    use std::{borrow::Cow, collections::HashSet};
+
+    let self_hash_url =
+        SelfHashURLStr::new_ref("https://api.example.com/records?selfHash=&v=1#top").expect("pass");
+    assert!(self_hash_url.is_unset());
+    assert!(SelfHashURLStr::new_ref("https://api.example.com/records?v=1").is_err());
+    assert!(SelfHashURLStr::new_ref("https://api.example.com/records#?selfHash=").is_err());
+    assert!(SelfHashURLStr::new_ref("https://api.example.com/r?selfHash=&selfHash=").is_err());
+    assert!(SelfHashURLStr::new_ref("https://api.example.com/r?q=%zz&selfHash=").is_err());
+
+    let mb_hash_function =
+        selfhash::MBHashFunction::new(mbx::Base::Base64Url, ssi_multicodec::BLAKE3)
+            .expect("programmer error");
+    let value = serde_json::from_str::<serde_json::Value>(
+        r#"{"thing":3, "link": "https://api.example.com/records?q=a%20b&selfHash=&v=1#top"}"#,
+    )
+    .expect("pass");
+    let mut self_hashable_json = SelfHashableJSON::new(
+        value,
+        Cow::Owned(HashSet::new()),
+        Cow::Owned(maplit::hashset! { Cow::Borrowed("$.link") }),
+    )
+    .expect("pass");
+    let self_hash = self_hashable_json
+        .self_hash(mb_hash_function.new_hasher())
+        .expect("pass")
+        .to_owned();
+    self_hashable_json.verify_self_hashes().expect("pass");
+    println!(
+        "json after self-hashing: {}",
+        self_hashable_json.value().to_string()
+    );
+    // The other query parameters, the percent-encoding, and the fragment are preserved.
+    assert_eq!(
+        self_hashable_json.value()["link"].as_str(),
+        Some(
+            format!(
+                "https://api.example.com/records?q=a%20b&selfHash={}&v=1#top",
+                self_hash
+            )
+            .as_str()
+        )
+    );
+    let self_hash_url = SelfHashURL::try_from(
+        self_hashable_json.value()["link"]
+            .as_str()
+            .unwrap()
+            .to_string(),
+    )
+    .expect("pass");
+    assert_eq!(self_hash_url.self_hash_str(), self_hash.as_str());
+}
+
+#[cfg(feature = "self-hashable-json")]
+#[test]
+fn test_self_hash_url_components() {
+    use selfhash::{HashFunctionT, SelfHashURL, SelfHashURLStr, SelfHashableJSON, SelfHashableT};
+    use std::{borrow::Cow, collections::HashSet};
+
+    let mb_hash_function =
+        selfhash::MBHashFunction::new(mbx::Base::Base64Url, ssi_multicodec::BLAKE3)
+            .expect("programmer error");
+    let placeholder_hash = mb_hash_function.placeholder_hash();
+
+    // Build a self-hash URL and check its components.
+    let self_hash_url = SelfHashURL::builder()
+        .with_mb_hash(&placeholder_hash)
+        .with_path("/credentialSubject")
+        .with_query("v=2")
+        .with_fragment("name")
+        .build()
+        .expect("pass");
+    println!("self_hash_url: {}", self_hash_url);
+    assert!(self_hash_url.is_vjson());
+    assert_eq!(self_hash_url.self_hash_str(), placeholder_hash.as_str());
+    assert_eq!(self_hash_url.path_o(), Some("/credentialSubject"));
+    assert_eq!(self_hash_url.query_o(), Some("v=2"));
+    assert_eq!(self_hash_url.fragment_o(), Some("name"));
+    assert!(SelfHashURL::builder()
+        .with_path("no-slash")
+        .build()
+        .is_err());
+
+    // Strict validation rejects garbage after the prefix.
+    assert!(SelfHashURLStr::new_ref("vjson:///").is_ok());
+    assert!(SelfHashURLStr::new_ref("vjson:///not a hash").is_err());
+    assert!(SelfHashURLStr::new_ref("vjson:///not-a-hash/path").is_err());
+    assert!(SelfHashURLStr::new_ref("vjson:////path with space").is_err());
+    assert!(SelfHashURLStr::new_ref("vjson:////path#%zz").is_err());
+
+    // Resolve relative references.
+    let base = SelfHashURL::builder()
+        .with_mb_hash(&placeholder_hash)
+        .with_path("/")
+        .build()
+        .expect("pass");
+    assert_eq!(
+        base.resolve("credentialSubject#name")
+            .expect("pass")
+            .as_str(),
+        format!("vjson:///{}/credentialSubject#name", placeholder_hash)
+    );
+    assert_eq!(
+        base.resolve("a/b/../c?v=2").expect("pass").as_str(),
+        format!("vjson:///{}/a/c?v=2", placeholder_hash)
+    );
+    assert_eq!(
+        self_hash_url.resolve("#other").expect("pass").as_str(),
+        format!("vjson:///{}/credentialSubject?v=2#other", placeholder_hash)
+    );
+    // This would replace the self-hash with garbage.
+    assert!(base.resolve("/not-a-hash").is_err());
+    // Non-ASCII paths are invalid in a self-hash URL, but must be rejected gracefully.
+    assert!(base.resolve("x:é").is_err());
+    assert!(base.resolve("vjson:é/ü").is_err());
+    assert!(base.resolve("é").is_err());
+
+    // Self-hashing preserves the path, query, and fragment.
+    let value = serde_json::from_str::<serde_json::Value>(
+        r#"{"thing":3, "$id": "vjson:////credentialSubject?v=2#name"}"#,
+    )
+    .expect("pass");
+    let mut self_hashable_json = SelfHashableJSON::new(
+        value,
+        Cow::Owned(HashSet::new()),
+        Cow::Owned(maplit::hashset! { Cow::Borrowed("$['$id']") }),
+    )
+    .expect("pass");
+    let self_hash = self_hashable_json
+        .self_hash(mb_hash_function.new_hasher())
+        .expect("pass")
+        .to_owned();
+    self_hashable_json.verify_self_hashes().expect("pass");
+    assert_eq!(
+        self_hashable_json.value()["$id"].as_str(),
+        Some(format!("vjson:///{}/credentialSubject?v=2#name", self_hash).as_str())
+    );
+
+    // A self-hash whose base isn't URL-safe (e.g. base64, which uses `+` and `/`) is rejected rather
+    // than producing an ambiguous URL.
+    let base64_mb_hash_function =
+        selfhash::MBHashFunction::new(mbx::Base::Base64, ssi_multicodec::BLAKE3)
+            .expect("programmer error");
+    let base64_hash = (0u32..)
+        .map(|i| base64_mb_hash_function.hash(i.to_string().as_bytes()))
+        .find(|mb_hash| mb_hash.as_str().contains('/') && mb_hash.as_str().contains('+'))
+        .expect("pass");
+    assert!(SelfHashURL::new(&base64_hash).is_err());
+    assert!(SelfHashURLStr::new_ref(&format!("vjson:///{}", base64_hash)).is_err());
+    assert!(SelfHashURLStr::new_ref(&format!(
+        "https://api.example.com/records?selfHash={}",
+        base64_hash
+    ))
+    .is_err());
+    let mut self_hash_url = self_hash_url.clone();
+    assert!(self_hash_url
+        .set_self_hash_slots_to_mb_hash(&base64_hash)
+        .is_err());
+    assert_eq!(self_hash_url.self_hash_str(), placeholder_hash.as_str());
+    let value = serde_json::from_str::<serde_json::Value>(r#"{"thing":3, "$id": "vjson:///"}"#)
+        .expect("pass");
+    let mut self_hashable_json = SelfHashableJSON::new(
+        value,
+        Cow::Owned(HashSet::new()),
+        Cow::Owned(maplit::hashset! { Cow::Borrowed("$['$id']") }),
+    )
+    .expect("pass");
+    assert!(self_hashable_json
+        .self_hash(base64_mb_hash_function.new_hasher())
+        .is_err());
+}
+
+#[cfg(feature = "self-hashable-json")]
+#[test]
+fn test_self_hashable_json_templates() {
+    use selfhash::{HashFunctionT, SelfHashTemplate, SelfHashableJSON, SelfHashableT};
+    use std::{borrow::Cow, collections::HashSet};
+
+    let template = SelfHashTemplate::parse("did:example:{selfHash}#key-1").expect("pass");
+    assert_eq!(template.prefix(), "did:example:");
+    assert_eq!(template.suffix(), "#key-1");
+    assert_eq!(template.self_hash_str_o("did:example:#key-1"), Some(""));
+    assert_eq!(template.self_hash_str_o("did:other:#key-1"), None);
+    assert!(SelfHashTemplate::parse("did:example:").is_err());
+    assert!(SelfHashTemplate::parse("{selfHash}/{selfHash}").is_err());
+
+    // Here, "id" is missing (and will be inserted), "/verificationMethod/0/id" has an unset self-hash,
+    // and "$.service[*].serviceEndpoint" puts the self-hash inside an https URL.
+    let value = serde_json::from_str::<serde_json::Value>(
+        r#"{
+            "verificationMethod": [{"id": "did:example:#key-1"}],
+            "service": [{"serviceEndpoint": null}]
+        }"#,
+    )
+    .expect("pass");
+    let self_hash_template_m = maplit::hashmap! {
+        Cow::Borrowed("$.id") => Cow::Borrowed("did:example:{selfHash}"),
+        Cow::Borrowed("/verificationMethod/0/id") => Cow::Borrowed("did:example:{selfHash}#key-1"),
+        Cow::Borrowed("$.service[*].serviceEndpoint") => Cow::Borrowed("https://example.com/docs/{selfHash}?v=2"),
+    };
+    let mut self_hashable_json = SelfHashableJSON::new_with_templates(
+        value,
+        Cow::Owned(HashSet::new()),
+        Cow::Owned(HashSet::new()),
+        Cow::Owned(HashSet::new()),
+        Cow::Owned(HashSet::new()),
+        Cow::Borrowed(&self_hash_template_m),
+    )
+    .expect("pass");
+    let mb_hash_function =
+        selfhash::MBHashFunction::new(mbx::Base::Base64Url, ssi_multicodec::BLAKE3)
+            .expect("programmer error");
+    let self_hash = self_hashable_json
+        .self_hash(mb_hash_function.new_hasher())
+        .expect("pass")
+        .to_owned();
+    self_hashable_json.verify_self_hashes().expect("pass");
+    println!(
+        "json after self-hashing: {}",
+        self_hashable_json.value().to_string()
+    );
+    assert_eq!(
+        self_hashable_json.value()["id"].as_str(),
+        Some(format!("did:example:{}", self_hash).as_str())
+    );
+    assert_eq!(
+        self_hashable_json.value()["verificationMethod"][0]["id"].as_str(),
+        Some(format!("did:example:{}#key-1", self_hash).as_str())
+    );
+    assert_eq!(
+        self_hashable_json.value()["service"][0]["serviceEndpoint"].as_str(),
+        Some(format!("https://example.com/docs/{}?v=2", self_hash).as_str())
+    );
+
+    // Tampering with the value causes verification to fail, and a field which doesn't match its
+    // template is an error.
+    let mut value = self_hashable_json.into_value();
+    value["verificationMethod"][0]["type"] = serde_json::Value::from("Multikey");
+    assert!(SelfHashableJSON::new_with_templates(
+        value.clone(),
+        Cow::Owned(HashSet::new()),
+        Cow::Owned(HashSet::new()),
+        Cow::Owned(HashSet::new()),
+        Cow::Owned(HashSet::new()),
+        Cow::Borrowed(&self_hash_template_m),
+    )
+    .expect("pass")
+    .verify_self_hashes()
+    .is_err());
+    value["id"] = serde_json::Value::from("did:other:xyz");
+    assert!(SelfHashableJSON::new_with_templates(
+        value,
+        Cow::Owned(HashSet::new()),
+        Cow::Owned(HashSet::new()),
+        Cow::Owned(HashSet::new()),
+        Cow::Owned(HashSet::new()),
+        Cow::Borrowed(&self_hash_template_m),
+    )
+    .is_err());
+}
+
+#[cfg(feature = "self-hashable-json")]
+#[test]
+fn test_self_hashable_json_pointers() {
+    use selfhash::{HashFunctionT, SelfHashableJSON, SelfHashableT};
+    use std::{borrow::Cow, collections::HashSet};
+    {
+        // Here, "/selfHash" is missing (and will be inserted), "/proof/0/id" is present, and "/a~1b"
+        // refers to the field named "a/b".
+        let value = serde_json::from_str::<serde_json::Value>(
+            r#"{"thing":3, "proof": [{"id": null}], "a/b": "vjson:///"}"#,
+        )
+        .expect("pass");
+        println!("json before self-hashing: {}", value.to_string());
+        let self_hash_pointer_s = maplit::hashset! {
+            Cow::Borrowed("/selfHash"),
+            Cow::Borrowed("/proof/0/id"),
+        };
+        let self_hash_url_pointer_s = maplit::hashset! { Cow::Borrowed("/a~1b") };
+        let mut self_hashable_json = SelfHashableJSON::new_with_pointers(
+            value,
+            Cow::Owned(HashSet::new()),
+            Cow::Owned(HashSet::new()),
+            Cow::Owned(self_hash_pointer_s),
+            Cow::Owned(self_hash_url_pointer_s),
+        )
+        .expect("pass");
+        let mb_hash_function =
+            selfhash::MBHashFunction::new(mbx::Base::Base64Url, ssi_multicodec::BLAKE3)
+                .expect("programmer error");
+        let self_hash = self_hashable_json
+            .self_hash(mb_hash_function.new_hasher())
+            .expect("pass")
+            .to_owned();
+        self_hashable_json.verify_self_hashes().expect("pass");
+        println!(
+            "json after self-hashing: {}",
+            self_hashable_json.value().to_string()
+        );
+        assert_eq!(
+            self_hashable_json.value()["selfHash"].as_str(),
+            Some(self_hash.as_str())
+        );
+        assert_eq!(
+            self_hashable_json.value()["proof"][0]["id"].as_str(),
+            Some(self_hash.as_str())
+        );
+        assert_eq!(
+            self_hashable_json.value()["a/b"].as_str(),
+            Some(format!("vjson:///{}", self_hash).as_str())
+        );
+    }
+    {
+        // Invalid JSON Pointers are rejected.
+        let value = serde_json::from_str::<serde_json::Value>(r#"{"thing":3}"#).expect("pass");
+        assert!(SelfHashableJSON::new_with_pointers(
+            value,
+            Cow::Owned(HashSet::new()),
+            Cow::Owned(HashSet::new()),
+            Cow::Owned(maplit::hashset! { Cow::Borrowed("selfHash") }),
+            Cow::Owned(HashSet::new()),
+        )
+        .is_err());
+    }
+    {
+        // A JSONPath query and a JSON Pointer which resolve to the same slot can't select different
+        // kinds of slots, including when the slot is missing and would be inserted.
+        let value = serde_json::from_str::<serde_json::Value>(r#"{"thing":3, "a": "vjson:///"}"#)
+            .expect("pass");
+        assert!(SelfHashableJSON::new_with_pointers(
+            value.clone(),
+            Cow::Owned(maplit::hashset! { Cow::Borrowed("$.a") }),
+            Cow::Owned(HashSet::new()),
+            Cow::Owned(HashSet::new()),
+            Cow::Owned(maplit::hashset! { Cow::Borrowed("/a") }),
+        )
+        .is_err());
+        assert!(SelfHashableJSON::new_with_templates(
+            value.clone(),
+            Cow::Owned(maplit::hashset! { Cow::Borrowed("$.selfHash") }),
+            Cow::Owned(HashSet::new()),
+            Cow::Owned(HashSet::new()),
+            Cow::Owned(HashSet::new()),
+            Cow::Owned(maplit::hashmap! {
+                Cow::Borrowed("/selfHash") => Cow::Borrowed("urn:{selfHash}"),
+            }),
+        )
+        .is_err());
+        // Selecting the same slot more than once as the same kind of slot is fine.
+        let self_hashable_json = SelfHashableJSON::new_with_pointers(
+            value,
+            Cow::Owned(maplit::hashset! { Cow::Borrowed("$.selfHash") }),
+            Cow::Owned(maplit::hashset! { Cow::Borrowed("$.a") }),
+            Cow::Owned(maplit::hashset! { Cow::Borrowed("/selfHash") }),
+            Cow::Owned(HashSet::new()),
+        )
+        .expect("pass");
+        assert_eq!(
+            self_hashable_json
+                .self_hash_slot_normalized_paths()
+                .expect("pass"),
+            vec!["$['a']", "$['selfHash']"]
+        );
+    }
+}
+
+#[cfg(feature = "did")]
+#[test]
+fn test_did_self_hash() {
+    use selfhash::{
+        DIDSelfHashDocument, DIDSelfHashFilesystemResolver, DIDSelfHashResolver, DIDSelfHashStr,
+        HashFunctionT, SelfHashableT,
+    };
+
+    assert!(DIDSelfHashStr::new_ref("did:selfhash:")
+        .expect("pass")
+        .is_unset());
+    assert!(DIDSelfHashStr::new_ref("did:example:abc").is_err());
+    assert!(DIDSelfHashStr::new_ref("did:selfhash:not-a-hash").is_err());
+    // A self-hash whose base has characters not allowed in a DID method-specific-id (base64 uses `+`
+    // and `/`, and the padded bases use `=`) is rejected, whatever its particular characters are.
+    for base in [
+        mbx::Base::Base64,
+        mbx::Base::Base64Pad,
+        mbx::Base::Base64UrlPad,
+    ] {
+        let mb_hash = selfhash::MBHashFunction::new(base, ssi_multicodec::BLAKE3)
+            .expect("programmer error")
+            .hash(b"not allowed in a DID");
+        assert!(DIDSelfHashStr::new_ref(&format!("did:selfhash:{}", mb_hash)).is_err());
+        assert!(selfhash::DIDSelfHash::new(&mb_hash).is_err());
+        let mut did = selfhash::DIDSelfHash::unset();
+        assert!(did.set_self_hash_slots_to_mb_hash(&mb_hash).is_err());
+        assert!(did.is_unset());
+    }
+
+    // Generate a DID document from one with unset self-hash slots.
+    let mut did_document = serde_json::from_str::<DIDSelfHashDocument>(
+        r#"{
+            "controller": "did:selfhash:",
+            "verificationMethod": [
+                {"id": "did:selfhash:#key-1", "type": "Multikey", "controller": "did:selfhash:", "publicKeyMultibase": "z6MkExample"}
+            ],
+            "service": []
+        }"#,
+    )
+    .expect("pass");
+    assert!(did_document.did_o().is_none());
+    let mb_hash_function =
+        selfhash::MBHashFunction::new(mbx::Base::Base64Url, ssi_multicodec::BLAKE3)
+            .expect("programmer error");
+    let self_hash = did_document
+        .self_hash(mb_hash_function.new_hasher())
+        .expect("pass")
+        .to_owned();
+    did_document.verify_self_hashes().expect("pass");
+    println!(
+        "did_document: {}",
+        serde_json::to_string(&did_document).expect("pass")
+    );
+    let did = did_document.did_o().expect("pass").to_owned();
+    assert_eq!(did.as_str(), format!("did:selfhash:{}", self_hash));
+    assert_eq!(did_document.controller.as_ref(), Some(&did));
+    assert_eq!(
+        did_document.verification_method[0].id.as_str(),
+        format!("did:selfhash:{}#key-1", self_hash)
+    );
+    assert_eq!(did_document.verification_method[0].id.fragment(), "key-1");
+    assert_eq!(
+        did_document.verification_method[0].controller.as_ref(),
+        Some(&did)
+    );
+    // Self-hashing with a base that isn't allowed in a DID is an error rather than a panic.
+    assert!(did_document
+        .clone()
+        .self_hash(
+            selfhash::MBHashFunction::new(mbx::Base::Base64, ssi_multicodec::BLAKE3)
+                .expect("programmer error")
+                .new_hasher()
+        )
+        .is_err());
+
+    // Store it and resolve it.
+    let root_dir = std::env::temp_dir().join(format!("selfhash-did-test-{}", std::process::id()));
+    std::fs::create_dir_all(&root_dir).expect("pass");
+    let resolver = DIDSelfHashFilesystemResolver::new(&root_dir);
+    assert_eq!(resolver.store(&did_document).expect("pass"), did);
+    let resolved_did_document = resolver.resolve(&did).expect("pass");
+    assert_eq!(
+        resolved_did_document
+            .verify_self_hashes()
+            .expect("pass")
+            .to_owned(),
+        self_hash
+    );
+
+    // A tampered DID document doesn't resolve.
+    let document_path = resolver.document_path(&self_hash).expect("pass");
+    let tampered = std::fs::read_to_string(&document_path)
+        .expect("pass")
+        .replace("z6MkExample", "z6MkTampered");
+    std::fs::write(&document_path, tampered).expect("pass");
+    assert!(resolver.resolve(&did).is_err());
+    // Neither does a DID whose document doesn't exist.
+    std::fs::remove_file(&document_path).expect("pass");
+    assert!(resolver.resolve(&did).is_err());
+    std::fs::remove_dir_all(&root_dir).expect("pass");
+}
+
+#[cfg(feature = "resolver")]
+#[test]
+fn test_self_hash_resolver() {
+    use selfhash::{
+        HashFunctionT, SelfHashDirectoryResolver, SelfHashInMemoryResolver, SelfHashResolver,
+        SelfHashURL, SelfHashableT,
+    };
+
+    let mb_hash_function =
+        selfhash::MBHashFunction::new(mbx::Base::Base64Url, ssi_multicodec::BLAKE3)
+            .expect("programmer error");
+    let mut document = serde_json::json!({"name": "alice", "friends": []});
+    let self_hash = document
+        .self_hash(mb_hash_function.new_hasher())
+        .expect("pass")
+        .to_owned();
+    // A second document which refers to the first via a self-hash URL.
+    let mut referring_document = serde_json::json!({
+        "name": "bob",
+        "friends": [SelfHashURL::new(&self_hash).expect("pass").as_str()],
+    });
+    referring_document
+        .self_hash(mb_hash_function.new_hasher())
+        .expect("pass");
+    let mut tampered_document = document.clone();
+    tampered_document["name"] = serde_json::Value::String("mallory".to_string());
+
+    // In-memory
+    {
+        let mut resolver = SelfHashInMemoryResolver::new();
+        assert_eq!(resolver.insert(document.clone()).expect("pass"), self_hash);
+        resolver.insert(referring_document.clone()).expect("pass");
+        assert_eq!(resolver.len(), 2);
+        // Documents which don't self-verify can't be inserted.
+        assert!(resolver.insert(tampered_document.clone()).is_err());
+
+        // Dereference bob's friend.
+        let friend_url = selfhash::SelfHashURLStr::new_ref(
+            referring_document["friends"][0].as_str().expect("pass"),
+        )
+        .expect("pass");
+        let friend = resolver.resolve_url_verified(friend_url).expect("pass");
+        assert_eq!(friend, document);
+        assert!(resolver
+            .resolve_url_verified(selfhash::SelfHashURLStr::new_ref("vjson:///").expect("pass"))
+            .is_err());
+    }
+
+    // Directory-backed
+    {
+        let root_dir =
+            std::env::temp_dir().join(format!("selfhash-resolver-test-{}", std::process::id()));
+        std::fs::create_dir_all(&root_dir).expect("pass");
+        let resolver = SelfHashDirectoryResolver::<serde_json::Value>::new(&root_dir);
+        assert_eq!(resolver.store(&document).expect("pass"), self_hash);
+        assert_eq!(
+            resolver.resolve_verified(&self_hash).expect("pass"),
+            document
+        );
+
+        // A tampered document still resolves, but doesn't resolve verified.
+        let document_path = resolver.document_path(&self_hash).expect("pass");
+        std::fs::write(
+            &document_path,
+            serde_json::to_string(&tampered_document).expect("pass"),
+        )
+        .expect("pass");
+        assert_eq!(
+            resolver.resolve(&self_hash).expect("pass"),
+            tampered_document
+        );
+        assert!(resolver.resolve_verified(&self_hash).is_err());
+
+        // A self-verifying document stored under another self-hash doesn't resolve verified either.
+        std::fs::write(
+            &document_path,
+            serde_json::to_string(&referring_document).expect("pass"),
+        )
+        .expect("pass");
+        assert!(resolver.resolve_verified(&self_hash).is_err());
+
+        std::fs::remove_file(&document_path).expect("pass");
+        assert!(resolver.resolve(&self_hash).is_err());
+        std::fs::remove_dir_all(&root_dir).expect("pass");
+    }
+
+    // Async, via the blanket impl for sync resolvers.
+    #[cfg(feature = "async-resolver")]
+    {
+        use selfhash::SelfHashResolverAsync;
+        use std::future::Future;
+
+        let mut resolver = SelfHashInMemoryResolver::new();
+        resolver.insert(document.clone()).expect("pass");
+        let future = SelfHashResolverAsync::resolve_verified(&resolver, &self_hash);
+        let mut future = std::pin::pin!(future);
+        let mut context = std::task::Context::from_waker(std::task::Waker::noop());
+        match future.as_mut().poll(&mut context) {
+            std::task::Poll::Ready(result) => assert_eq!(result.expect("pass"), document),
+            std::task::Poll::Pending => panic!("expected resolution to complete immediately"),
+        }
+    }
+}
+
+/// MBHash-using data whose self-hash is computed over deterministically encoded CBOR.
+#[cfg(feature = "cbor")]
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct SensorReadingMBHash {
+    pub sensor: String,
+    pub temperature: f64,
+    pub sequence: u64,
+    #[serde(rename = "selfHash")]
+    pub self_hash_o: Option<mbx::MBHash>,
+}
+
+#[cfg(feature = "cbor")]
+impl selfhash::SelfHashableT<mbx::MBHashStr> for SensorReadingMBHash {
+    fn write_digest_data(
+        &self,
+        hasher: &mut <<mbx::MBHashStr as selfhash::HashRefT>::HashFunction as selfhash::HashFunctionT<mbx::MBHashStr>>::Hasher,
+    ) -> selfhash::Result<()> {
+        selfhash::write_digest_data_using_dcbor(self, hasher)
+    }
+    fn self_hash_oi<'a, 'b: 'a>(
+        &'b self,
+    ) -> selfhash::Result<Box<dyn std::iter::Iterator<Item = Option<&'b mbx::MBHashStr>> + 'a>>
+    {
+        Ok(Box::new(std::iter::once(
+            self.self_hash_o
+                .as_ref()
+                .map(|s| -> &'b mbx::MBHashStr { s }),
+        )))
+    }
+    fn set_self_hash_slots_to(&mut self, hash: &mbx::MBHashStr) -> selfhash::Result<()> {
+        self.self_hash_o = Some(hash.to_owned());
+        Ok(())
+    }
+}
+
+#[cfg(feature = "cbor")]
+#[test]
+fn test_self_hashable_cbor() {
+    use selfhash::{HashFunctionT, SelfHashableCBOR, SelfHashableT};
+    use std::borrow::Cow;
+
+    let mb_hash_function =
+        selfhash::MBHashFunction::new(mbx::Base::Base64Url, ssi_multicodec::BLAKE3)
+            .expect("programmer error");
+
+    // Deterministic encoding sorts map keys by their encodings and uses the shortest encodings.
+    {
+        let value = ciborium::Value::Map(vec![
+            (ciborium::Value::Text("b".to_string()), 1.into()),
+            (ciborium::Value::Text("a".to_string()), 1.5.into()),
+            (10.into(), 100000.0.into()),
+            ((-1).into(), 1000.into()),
+            (
+                ciborium::Value::Text("selfHash".to_string()),
+                ciborium::Value::Null,
+            ),
+        ]);
+        let self_hashable_cbor = SelfHashableCBOR::new(
+            value,
+            Cow::Owned(maplit::hashset! { Cow::Borrowed("/selfHash") }),
+            Cow::Owned(maplit::hashset! {}),
+        )
+        .expect("pass");
+        let mut expected_dcbor = vec![
+            0xa5, 0x0a, 0xfa, 0x47, 0xc3, 0x50, 0x00, 0x20, 0x19, 0x03, 0xe8, 0x61, 0x61, 0xf9,
+            0x3e, 0x00, 0x61, 0x62, 0x01, 0x68,
+        ];
+        expected_dcbor.extend_from_slice(b"selfHash");
+        expected_dcbor.push(0xf6);
+        assert_eq!(self_hashable_cbor.to_dcbor().expect("pass"), expected_dcbor);
+    }
+
+    // SelfHashableCBOR with a missing self-hash slot, a self-hash slot with an integer key, and a
+    // self-hash URL slot.
+    {
+        let value = ciborium::Value::Map(vec![
+            (
+                ciborium::Value::Text("sensor".to_string()),
+                ciborium::Value::Text("thermometer-7".to_string()),
+            ),
+            (1.into(), ciborium::Value::Null),
+            (
+                ciborium::Value::Text("links".to_string()),
+                ciborium::Value::Array(vec![ciborium::Value::Text("vjson:///".to_string())]),
+            ),
+        ]);
+        let self_hash_pointer_s =
+            maplit::hashset! { Cow::Borrowed("/selfHash"), Cow::Borrowed("/1") };
+        let self_hash_url_pointer_s = maplit::hashset! { Cow::Borrowed("/links/0") };
+        let mut self_hashable_cbor = SelfHashableCBOR::new(
+            value,
+            Cow::Borrowed(&self_hash_pointer_s),
+            Cow::Borrowed(&self_hash_url_pointer_s),
+        )
+        .expect("pass");
+        let self_hash = self_hashable_cbor
+            .self_hash(mb_hash_function.new_hasher())
+            .expect("pass")
+            .to_owned();
+        self_hashable_cbor.verify_self_hashes().expect("pass");
+        println!("self-hashed CBOR value: {:?}", self_hashable_cbor.value());
+        assert_eq!(self_hashable_cbor.self_hash_oi().expect("pass").count(), 3);
+        assert!(self_hashable_cbor
+            .self_hash_oi()
+            .expect("pass")
+            .all(|self_hash_o| self_hash_o == Some(&*self_hash)));
+
+        // Round-trip through the deterministic encoding.
+        let dcbor = self_hashable_cbor.to_dcbor().expect("pass");
+        let value: ciborium::Value = ciborium::from_reader(dcbor.as_slice()).expect("pass");
+        let self_hashable_cbor = SelfHashableCBOR::new(
+            value,
+            Cow::Borrowed(&self_hash_pointer_s),
+            Cow::Borrowed(&self_hash_url_pointer_s),
+        )
+        .expect("pass");
+        assert_eq!(
+            self_hashable_cbor
+                .verify_self_hashes()
+                .expect("pass")
+                .to_owned(),
+            self_hash
+        );
+        assert_eq!(self_hashable_cbor.to_dcbor().expect("pass"), dcbor);
+
+        // Tampering is detected.
+        let mut value = self_hashable_cbor.into_value();
+        value.as_map_mut().expect("pass")[0].1 = ciborium::Value::Text("thermometer-8".to_string());
+        let self_hashable_cbor = SelfHashableCBOR::new(
+            value,
+            Cow::Borrowed(&self_hash_pointer_s),
+            Cow::Borrowed(&self_hash_url_pointer_s),
+        )
+        .expect("pass");
+        assert!(self_hashable_cbor.verify_self_hashes().is_err());
+    }
+
+    // Malformed self-hash [URL] slots.
+    {
+        let value = ciborium::Value::Map(vec![(
+            ciborium::Value::Text("selfHash".to_string()),
+            ciborium::Value::Bytes(vec![0x00]),
+        )]);
+        assert!(SelfHashableCBOR::new(
+            value,
+            Cow::Owned(maplit::hashset! { Cow::Borrowed("/selfHash") }),
+            Cow::Owned(maplit::hashset! {}),
+        )
+        .is_err());
+        let value = ciborium::Value::Map(vec![]);
+        assert!(SelfHashableCBOR::new(
+            value.clone(),
+            Cow::Owned(maplit::hashset! {}),
+            Cow::Owned(maplit::hashset! { Cow::Borrowed("/selfHashURL") }),
+        )
+        .is_err());
+        assert!(SelfHashableCBOR::new(
+            value,
+            Cow::Owned(maplit::hashset! { Cow::Borrowed("/missing/selfHash") }),
+            Cow::Owned(maplit::hashset! {}),
+        )
+        .is_err());
+        // A text slot which isn't a valid self-hash is reported with its pointer.
+        let value = ciborium::Value::Map(vec![(
+            ciborium::Value::Text("proof".to_string()),
+            ciborium::Value::Array(vec![ciborium::Value::Map(vec![(
+                ciborium::Value::Text("id".to_string()),
+                ciborium::Value::Text("not-a-hash".to_string()),
+            )])]),
+        )]);
+        let self_hashable_cbor = SelfHashableCBOR::new(
+            value,
+            Cow::Owned(maplit::hashset! { Cow::Borrowed("/proof/0/id") }),
+            Cow::Owned(maplit::hashset! {}),
+        )
+        .expect("pass");
+        let error = self_hashable_cbor.self_hash_oi().err().expect("pass");
+        assert!(error.contains("\"/proof/0/id\""), "{}", error);
+        assert_eq!(error.kind(), selfhash::ErrorKind::Other);
+    }
+
+    // A serde type using write_digest_data_using_dcbor.
+    {
+        let mut sensor_reading = SensorReadingMBHash {
+            sensor: "thermometer-7".to_string(),
+            temperature: 21.5,
+            sequence: 42,
+            self_hash_o: None,
+        };
+        let self_hash = sensor_reading
+            .self_hash(mb_hash_function.new_hasher())
+            .expect("pass")
+            .to_owned();
+        sensor_reading.verify_self_hashes().expect("pass");
+        println!("sensor_reading: {:?}", sensor_reading);
+
+        // The same data as SelfHashableCBOR has the same self-hash, since both use deterministic CBOR.
+        let self_hashable_cbor = SelfHashableCBOR::new(
+            ciborium::Value::serialized(&sensor_reading).expect("pass"),
+            Cow::Owned(maplit::hashset! { Cow::Borrowed("/selfHash") }),
+            Cow::Owned(maplit::hashset! {}),
+        )
+        .expect("pass");
+        assert_eq!(
+            self_hashable_cbor
+                .verify_self_hashes()
+                .expect("pass")
+                .to_owned(),
+            self_hash
+        );
+
+        let mut altered_sensor_reading = sensor_reading.clone();
+        altered_sensor_reading.temperature = 22.0;
+        assert!(altered_sensor_reading.verify_self_hashes().is_err());
+    }
+}
+
+#[cfg(feature = "keri-said")]
+#[test]
+fn test_cesr_hash() {
+    use selfhash::{CESRHashFunction, CESRHashStr, HashFunctionT, HashRefT};
+
+    let cesr_hash = CESRHashFunction::BLAKE3_256.hash(b"");
+    assert_eq!(
+        cesr_hash.as_str(),
+        "EK8TSbn1-aGmoEBN6jbcyUmbyyXJrcESt8yak8rkHzJi"
+    );
+    assert_eq!(cesr_hash.code(), "E");
+    assert_eq!(cesr_hash.hash_function(), CESRHashFunction::BLAKE3_256);
+    assert_eq!(cesr_hash.digest().len(), 32);
+    assert!(!cesr_hash.is_placeholder());
+    let cesr_hash = CESRHashFunction::SHA2_512.hash(b"abc");
+    assert_eq!(
+        cesr_hash.as_str(),
+        "0GDdrzWhk2F6usxBc0muIEExEub6TompfqIKnu7mS1XTmiGSmSonT8GoNro8I6P-671FTUQjZDzoDiqayU-lTKSf"
+    );
+    assert_eq!(cesr_hash.hash_function(), CESRHashFunction::SHA2_512);
+
+    let placeholder_hash = CESRHashFunction::BLAKE3_256.placeholder_hash();
+    assert_eq!(
+        placeholder_hash.as_str(),
+        "EAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA"
+    );
+    assert!(placeholder_hash.is_placeholder());
+    assert_eq!(CESRHashFunction::BLAKE3_256.dummy(), "#".repeat(44));
+    assert_eq!(CESRHashFunction::SHA3_512.dummy(), "#".repeat(88));
+
+    // Unknown derivation code
+    assert!(CESRHashStr::new_ref("XK8TSbn1-aGmoEBN6jbcyUmbyyXJrcESt8yak8rkHzJi").is_err());
+    // Wrong length
+    assert!(CESRHashStr::new_ref("EK8TSbn1-aGmoEBN6jbcyUmbyyXJrcESt8yak8rkHzJ").is_err());
+    // Invalid base64url
+    assert!(CESRHashStr::new_ref("EK8TSbn1+aGmoEBN6jbcyUmbyyXJrcESt8yak8rkHzJi").is_err());
+    // Nonzero pad bits (the 2nd character of a 1-character-code digest must be in `A`..=`P`)
+    assert!(CESRHashStr::new_ref("EZ8TSbn1-aGmoEBN6jbcyUmbyyXJrcESt8yak8rkHzJi").is_err());
+    // KERI's dummy isn't a valid CESR hash.
+    assert!(CESRHashStr::new_ref(&"#".repeat(44)).is_err());
+}
+
+#[cfg(feature = "keri-said")]
+#[test]
+fn test_keri_said() {
+    use selfhash::{CESRHashFunction, HashFunctionT, SelfAddressingJSON, SelfHashableT};
+
+    // These vectors follow keripy's Saider.saidify: the SAID fields are filled with `#` characters,
+    // the version string (if any) is sized, and the compact insertion-ordered JSON is hashed.
+    for (hash_function, expected_said) in [
+        (
+            CESRHashFunction::BLAKE3_256,
+            "EPjC9oI1JVaeGTvqZbqq9gIuDnAM2ATUhbT4x3g88zll",
+        ),
+        (
+            CESRHashFunction::SHA3_256,
+            "HGQJ4vetZJ_DfufKM0YcTyBXHlR3LxHRu-tOckDHTDM3",
+        ),
+        (
+            CESRHashFunction::SHA2_256,
+            "IFvJUGAb-3CR_i-34QIg0qJ12-Dnq27pDdgEo3icRdM1",
+        ),
+        (
+            CESRHashFunction::SHA3_512,
+            "0FBdJNOmNZvo_KizcETRgciKVk_CVt6vQS2t9iKTw67-eeYVBsZ8rbkpesbhtTuQPSZh4_toTNNocNGR-Fg5GsL0",
+        ),
+        (
+            CESRHashFunction::SHA2_512,
+            "0GDYEHjdM1-i2bj4QIuogL1Tk763voMS2qYRvLpAozED666WghunvHLryCnn6cOujkCLfWvVJ9mqVFgLNq7CjL1M",
+        ),
+    ] {
+        let mut sad = SelfAddressingJSON::from_json_str(
+            r#"{"d":"","first":"Sue","last":"Smith","role":"Founder"}"#,
+        )
+        .expect("pass");
+        let said = sad
+            .self_hash(hash_function.new_hasher())
+            .expect("pass")
+            .to_owned();
+        assert_eq!(said.as_str(), expected_said);
+        assert_eq!(sad.verify_self_hashes().expect("pass").to_owned(), said);
+        assert_eq!(sad.to_value()["d"], expected_said);
+    }
+
+    // Non-ASCII characters are serialized as-is (not escaped).
+    {
+        let mut sad = SelfAddressingJSON::from_json_str(
+            r#"{"d":"","name":"José Müller","note":"tab\there"}"#,
+        )
+        .expect("pass");
+        let said = sad
+            .self_hash(CESRHashFunction::BLAKE3_256.new_hasher())
+            .expect("pass");
+        assert_eq!(
+            said.as_str(),
+            "EL46YfAsduZAdubkIJA7RMe-YZ0pIxBBnqU05WdjZwJ_"
+        );
+    }
+
+    // A KERI inception event, in which both the `d` and `i` fields are the SAID, and which has a
+    // version string.  Note that its members are not in sorted order.
+    {
+        let mut sad = SelfAddressingJSON::from_json_str_with_labels(
+            r#"{
+                "v": "KERI10JSON000000_",
+                "t": "icp",
+                "d": "",
+                "i": "",
+                "s": "0",
+                "kt": "1",
+                "k": ["DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA"],
+                "nt": "0",
+                "n": [],
+                "bt": "0",
+                "b": [],
+                "c": [],
+                "a": []
+            }"#,
+            ["d", "i"],
+        )
+        .expect("pass");
+        let said = sad
+            .self_hash(CESRHashFunction::BLAKE3_256.new_hasher())
+            .expect("pass")
+            .to_owned();
+        let serialized = sad.to_json_string();
+        println!("inception event: {}", serialized);
+        assert_eq!(
+            said.as_str(),
+            "EGq_0kRg--n5UWn2vc7UOwLPNAvERZFatG41Vnv6W8D3"
+        );
+        assert_eq!(
+            serialized,
+            r#"{"v":"KERI10JSON0000fd_","t":"icp","d":"EGq_0kRg--n5UWn2vc7UOwLPNAvERZFatG41Vnv6W8D3","i":"EGq_0kRg--n5UWn2vc7UOwLPNAvERZFatG41Vnv6W8D3","s":"0","kt":"1","k":["DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA"],"nt":"0","n":[],"bt":"0","b":[],"c":[],"a":[]}"#
+        );
+        assert_eq!(serialized.len(), 0xfd);
+        sad.verify_self_hashes().expect("pass");
+
+        // Parsing the serialization preserves the member order, so it verifies.
+        let sad =
+            SelfAddressingJSON::from_json_str_with_labels(&serialized, ["d", "i"]).expect("pass");
+        sad.verify_self_hashes().expect("pass");
+        assert_eq!(sad.to_json_string(), serialized);
+
+        // A wrongly sized version string doesn't verify.
+        let sad = SelfAddressingJSON::from_json_str_with_labels(
+            &serialized.replace("KERI10JSON0000fd_", "KERI10JSON000000_"),
+            ["d", "i"],
+        )
+        .expect("pass");
+        assert!(sad.verify_self_hashes().is_err());
+
+        // Neither does an altered event.
+        let sad = SelfAddressingJSON::from_json_str_with_labels(
+            &serialized.replace(r#""kt":"1""#, r#""kt":"2""#),
+            ["d", "i"],
+        )
+        .expect("pass");
+        assert!(sad.verify_self_hashes().is_err());
+    }
+
+    // Malformed self-addressing data.
+    assert!(SelfAddressingJSON::from_json_str(r#"{"d":"","d":""}"#).is_err());
+    assert!(SelfAddressingJSON::from_json_str("not JSON").is_err());
+    assert!(SelfAddressingJSON::new(serde_json::json!({"first": "Sue"})).is_err());
+    assert!(SelfAddressingJSON::new(serde_json::json!({"d": 3})).is_err());
+    assert!(
+        SelfAddressingJSON::new(serde_json::json!({"v": "KERI10CBOR000000_", "d": ""})).is_err()
+    );
+    assert!(
+        SelfAddressingJSON::new(serde_json::json!({"d": "not-a-said"}))
+            .expect("pass")
+            .verify_self_hashes()
+            .is_err()
+    );
+}
+
+/// MBHash-using data whose self-hash is computed over its postcard encoding.
+#[cfg(feature = "postcard")]
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct ServiceMessageMBHash<Settings: Clone + serde::Serialize> {
+    pub sender: String,
+    pub sequence: u64,
+    pub settings: Settings,
+    pub self_hash_o: Option<mbx::MBHash>,
+}
+
+#[cfg(feature = "postcard")]
+impl<Settings: Clone + serde::Serialize> selfhash::SelfHashableT<mbx::MBHashStr>
+    for ServiceMessageMBHash<Settings>
+{
+    fn write_digest_data(
+        &self,
+        hasher: &mut <<mbx::MBHashStr as selfhash::HashRefT>::HashFunction as selfhash::HashFunctionT<mbx::MBHashStr>>::Hasher,
+    ) -> selfhash::Result<()> {
+        selfhash::write_digest_data_using_postcard(self, hasher)
+    }
+    fn self_hash_oi<'a, 'b: 'a>(
+        &'b self,
+    ) -> selfhash::Result<Box<dyn std::iter::Iterator<Item = Option<&'b mbx::MBHashStr>> + 'a>>
+    {
+        Ok(Box::new(std::iter::once(
+            self.self_hash_o
+                .as_ref()
+                .map(|s| -> &'b mbx::MBHashStr { s }),
+        )))
+    }
+    fn set_self_hash_slots_to(&mut self, hash: &mbx::MBHashStr) -> selfhash::Result<()> {
+        self.self_hash_o = Some(hash.to_owned());
+        Ok(())
+    }
+}
+
+#[cfg(feature = "postcard")]
+#[test]
+fn test_self_hashable_postcard() {
+    use selfhash::{HashFunctionT, SelfHashableT};
+
+    let mb_hash_function =
+        selfhash::MBHashFunction::new(mbx::Base::Base64Url, ssi_multicodec::BLAKE3)
+            .expect("programmer error");
+
+    let mut service_message = ServiceMessageMBHash {
+        sender: "billing".to_string(),
+        sequence: 7,
+        settings: (0..20)
+            .map(|i| (format!("setting-{}", i), i))
+            .collect::<std::collections::BTreeMap<_, _>>(),
+        self_hash_o: None,
+    };
+    let self_hash = service_message
+        .self_hash(mb_hash_function.new_hasher())
+        .expect("pass")
+        .to_owned();
+    service_message.verify_self_hashes().expect("pass");
+    println!(
+        "service_message (self-hash {}): {:?}",
+        self_hash, service_message
+    );
+
+    let mut altered_service_message = service_message.clone();
+    altered_service_message.sequence = 8;
+    assert!(altered_service_message.verify_self_hashes().is_err());
+
+    // A HashMap doesn't serialize deterministically, so it's rejected.  The chance of this HashMap
+    // iterating in sorted order by accident is 1 in 20!.
+    let mut service_message = ServiceMessageMBHash {
+        sender: "billing".to_string(),
+        sequence: 7,
+        settings: (0..20)
+            .map(|i| (format!("setting-{}", i), i))
+            .collect::<std::collections::HashMap<_, _>>(),
+        self_hash_o: None,
+    };
+    assert!(service_message
+        .self_hash(mb_hash_function.new_hasher())
+        .is_err());
+
+    // Keys are compared by the derived order of their serialized form, so a map whose key type has a
+    // custom Ord is rejected too, even though it's deterministic (this is a known limitation).
+    let mut service_message = ServiceMessageMBHash {
+        sender: "billing".to_string(),
+        sequence: 7,
+        settings: (0..20)
+            .map(|i| (std::cmp::Reverse(i), i))
+            .collect::<std::collections::BTreeMap<_, _>>(),
+        self_hash_o: None,
+    };
+    assert!(service_message
+        .self_hash(mb_hash_function.new_hasher())
+        .is_err());
+}
+
+/// Like ServiceMessageMBHash, but not Clone; its digest data is streamed into the hasher, with the
+/// self-hash slot marked by SelfHashSlot.
+#[cfg(all(feature = "postcard", feature = "streaming"))]
+#[derive(Debug, serde::Serialize)]
+pub struct StreamedServiceMessageMBHash<Settings: serde::Serialize> {
+    pub sender: String,
+    pub sequence: u64,
+    pub settings: Settings,
+    pub self_hash_o: selfhash::SelfHashSlot<Option<mbx::MBHash>>,
+}
+
+#[cfg(all(feature = "postcard", feature = "streaming"))]
+impl<Settings: serde::Serialize> selfhash::SelfHashableT<mbx::MBHashStr>
+    for StreamedServiceMessageMBHash<Settings>
+{
+    fn write_digest_data(
+        &self,
+        hasher: &mut <<mbx::MBHashStr as selfhash::HashRefT>::HashFunction as selfhash::HashFunctionT<mbx::MBHashStr>>::Hasher,
+    ) -> selfhash::Result<()> {
+        selfhash::write_digest_data_streaming_postcard(self, hasher)
+    }
+    fn self_hash_oi<'a, 'b: 'a>(
+        &'b self,
+    ) -> selfhash::Result<Box<dyn std::iter::Iterator<Item = Option<&'b mbx::MBHashStr>> + 'a>>
+    {
+        Ok(Box::new(std::iter::once(
+            self.self_hash_o
+                .as_ref()
+                .map(|s| -> &'b mbx::MBHashStr { s }),
+        )))
+    }
+    fn set_self_hash_slots_to(&mut self, hash: &mbx::MBHashStr) -> selfhash::Result<()> {
+        *self.self_hash_o = Some(hash.to_owned());
+        Ok(())
+    }
+}
+
+#[cfg(all(feature = "postcard", feature = "streaming"))]
+#[test]
+fn test_self_hashable_streaming_postcard() {
+    use selfhash::{HashFunctionT, SelfHashableT};
+
+    let mb_hash_function =
+        selfhash::MBHashFunction::new(mbx::Base::Base64Url, ssi_multicodec::BLAKE3)
+            .expect("programmer error");
+
+    let settings = (0..20)
+        .map(|i| (format!("setting-{}", i), i))
+        .collect::<std::collections::BTreeMap<_, _>>();
+    let mut streamed_service_message = StreamedServiceMessageMBHash {
+        sender: "billing".to_string(),
+        sequence: 7,
+        settings: settings.clone(),
+        self_hash_o: selfhash::SelfHashSlot(None),
+    };
+    let self_hash = streamed_service_message
+        .self_hash(mb_hash_function.new_hasher())
+        .expect("pass")
+        .to_owned();
+    streamed_service_message.verify_self_hashes().expect("pass");
+    println!(
+        "streamed_service_message (self-hash {}): {:?}",
+        self_hash, streamed_service_message
+    );
+
+    // Streaming produces the same digest data as cloning and setting the self-hash slot.
+    let mut service_message = ServiceMessageMBHash {
+        sender: "billing".to_string(),
+        sequence: 7,
+        settings,
+        self_hash_o: None,
+    };
+    service_message
+        .self_hash(mb_hash_function.new_hasher())
+        .expect("pass");
+    assert_eq!(service_message.self_hash_o, Some(self_hash.clone()));
+
+    // SelfHashSlot serializes exactly as its value does.
+    assert_eq!(
+        serde_json::to_value(&streamed_service_message).expect("pass"),
+        serde_json::to_value(&service_message).expect("pass")
+    );
+
+    streamed_service_message.sequence = 8;
+    assert!(streamed_service_message.verify_self_hashes().is_err());
+}
+
+/// Like SimpleDataMBHash, but not Clone; its JCS digest data is serialized directly into the hasher,
+/// with the self-hash slot marked by SelfHashSlot.
+#[cfg(all(feature = "jcs", feature = "mbx", feature = "streaming"))]
+#[derive(Debug, serde::Serialize)]
+pub struct StreamedSimpleDataMBHash {
+    #[serde(rename = "previous")]
+    pub previous_o: Option<mbx::MBHash>,
+    pub name: String,
+    pub stuff_count: u32,
+    pub data_byte_v: Vec<u8>,
+    #[serde(rename = "self_hash")]
+    pub self_hash_o: selfhash::SelfHashSlot<Option<mbx::MBHash>>,
+}
+
+#[cfg(all(feature = "jcs", feature = "mbx", feature = "streaming"))]
+impl selfhash::SelfHashableT<mbx::MBHashStr> for StreamedSimpleDataMBHash {
+    fn write_digest_data(
+        &self,
+        hasher: &mut <<mbx::MBHashStr as selfhash::HashRefT>::HashFunction as selfhash::HashFunctionT<mbx::MBHashStr>>::Hasher,
+    ) -> selfhash::Result<()> {
+        selfhash::write_digest_data_streaming_jcs(self, hasher)
+    }
+    fn self_hash_oi<'a, 'b: 'a>(
+        &'b self,
+    ) -> selfhash::Result<Box<dyn std::iter::Iterator<Item = Option<&'b mbx::MBHashStr>> + 'a>>
+    {
+        Ok(Box::new(std::iter::once(
+            self.self_hash_o
+                .as_ref()
+                .map(|s| -> &'b mbx::MBHashStr { s }),
+        )))
+    }
+    fn set_self_hash_slots_to(&mut self, hash: &mbx::MBHashStr) -> selfhash::Result<()> {
+        *self.self_hash_o = Some(hash.to_owned());
+        Ok(())
+    }
+}
+
+#[cfg(all(feature = "jcs", feature = "mbx", feature = "streaming"))]
+#[test]
+fn test_self_hashable_streaming_jcs() {
+    use selfhash::{HashFunctionT, SelfHashableT};
+
+    let mb_hash_function =
+        selfhash::MBHashFunction::new(mbx::Base::Base64Url, ssi_multicodec::BLAKE3)
+            .expect("programmer error");
+
+    let mut streamed_simple_data = StreamedSimpleDataMBHash {
+        previous_o: None,
+        name: "hippodonkey".to_string(),
+        stuff_count: 42,
+        data_byte_v: vec![0x01, 0x02, 0x03],
+        self_hash_o: selfhash::SelfHashSlot(None),
+    };
+    let self_hash = streamed_simple_data
+        .self_hash(mb_hash_function.new_hasher())
+        .expect("pass")
+        .to_owned();
+    streamed_simple_data.verify_self_hashes().expect("pass");
+
+    // Streaming produces the same digest data as cloning and setting the self-hash slot.
+    let mut simple_data = SimpleDataMBHash {
+        previous_o: None,
+        name: "hippodonkey".to_string(),
+        stuff_count: 42,
+        data_byte_v: vec![0x01, 0x02, 0x03],
+        self_hash_o: None,
+    };
+    simple_data
+        .self_hash(mb_hash_function.new_hasher())
+        .expect("pass");
+    assert_eq!(simple_data.self_hash_o, Some(self_hash));
+
+    streamed_simple_data.stuff_count = 43;
+    assert!(streamed_simple_data.verify_self_hashes().is_err());
+}
+
+#[cfg(feature = "self-hashable-yaml")]
+#[test]
+fn test_self_hashable_yaml() {
+    use selfhash::{HashFunctionT, SelfHashableJSON, SelfHashableT, SelfHashableYAML};
+    use std::{borrow::Cow, collections::HashSet};
+
+    let manifest = r#"# Deployment manifest
+apiVersion: apps/v1
+kind: Deployment
+metadata:
+  name: billing
+  selfHash: ~
+spec:
+  replicas: 3
+  paused: false
+  cpu: 0.5
+  version: "1.10"
+  ports:
+    - 8080
+    - 8443
+  command: |
+    run --verbose
+"#;
+    let mb_hash_function =
+        selfhash::MBHashFunction::new(mbx::Base::Base64Url, ssi_multicodec::BLAKE3)
+            .expect("programmer error");
+
+    let mut self_hashable_yaml = SelfHashableYAML::new(
+        manifest,
+        Cow::Owned(maplit::hashset! { Cow::Borrowed("$.metadata.selfHash") }),
+        Cow::Owned(HashSet::new()),
+    )
+    .expect("pass");
+    assert_eq!(self_hashable_yaml.value()["spec"]["replicas"], 3);
+    assert_eq!(self_hashable_yaml.value()["spec"]["version"], "1.10");
+    assert_eq!(
+        self_hashable_yaml.value()["spec"]["command"],
+        "run --verbose\n"
+    );
+    let self_hash = self_hashable_yaml
+        .self_hash(mb_hash_function.new_hasher())
+        .expect("pass")
+        .to_owned();
+    self_hashable_yaml.verify_self_hashes().expect("pass");
+    let hashed_manifest = self_hashable_yaml.to_yaml_string().expect("pass");
+    println!("yaml after self-hashing:\n{}", hashed_manifest);
+
+    // The written-back YAML verifies.
+    let reparsed_self_hashable_yaml = SelfHashableYAML::new(
+        &hashed_manifest,
+        Cow::Owned(maplit::hashset! { Cow::Borrowed("$.metadata.selfHash") }),
+        Cow::Owned(HashSet::new()),
+    )
+    .expect("pass");
+    assert_eq!(
+        reparsed_self_hashable_yaml
+            .verify_self_hashes()
+            .expect("pass")
+            .to_owned(),
+        self_hash
+    );
+
+    // The YAML has the same self-hash as its JSON counterpart.
+    let mut self_hashable_json = SelfHashableJSON::new(
+        selfhash::yaml_to_json_value(manifest).expect("pass"),
+        Cow::Owned(maplit::hashset! { Cow::Borrowed("$.metadata.selfHash") }),
+        Cow::Owned(HashSet::new()),
+    )
+    .expect("pass");
+    assert_eq!(
+        self_hashable_json
+            .self_hash(mb_hash_function.new_hasher())
+            .expect("pass")
+            .to_owned(),
+        self_hash
+    );
+
+    // Tampering is detected.
+    let tampered_manifest = hashed_manifest.replace("replicas: 3", "replicas: 4");
+    assert_ne!(tampered_manifest, hashed_manifest);
+    assert!(SelfHashableYAML::new(
+        &tampered_manifest,
+        Cow::Owned(maplit::hashset! { Cow::Borrowed("$.metadata.selfHash") }),
+        Cow::Owned(HashSet::new()),
+    )
+    .expect("pass")
+    .verify_self_hashes()
+    .is_err());
+
+    // Tags, anchors, aliases, non-string keys, and multiple documents are rejected.
+    for yaml in [
+        "selfHash: ~\nport: !!str 8080\n",
+        "selfHash: ~\n!custom {a: 1}: x\n",
+        "selfHash: ~\ndefaults: &defaults {a: 1}\n",
+        "selfHash: ~\na: *defaults\n",
+        "selfHash: ~\n8080: http\n",
+        "selfHash: ~\ntrue: x\n",
+        "selfHash: ~\n[a, b]: x\n",
+        "selfHash: ~\na: 1\na: 2\n",
+        "selfHash: ~\nratio: .nan\n",
+        "selfHash: ~\n---\nselfHash: ~\n",
+    ] {
+        let result = selfhash::yaml_to_json_value(yaml);
+        println!("{:?} -> {:?}", yaml, result);
+        assert!(result.is_err());
+    }
+}
+
+#[cfg(feature = "self-hashable-toml")]
+#[test]
+fn test_self_hashable_toml() {
+    use selfhash::{HashFunctionT, SelfHashableT, SelfHashableTOML};
+    use std::{borrow::Cow, collections::HashSet};
+
+    let config = r#"# Billing service configuration.
+name = "billing" # the service name
+started = 1979-05-27 07:32:00.500+00:00
+
+[limits]
+max-connections = 100
+timeout = 2.5
+
+[service]
+selfHash = "" # set by selfhash
+"#;
+    let mb_hash_function =
+        selfhash::MBHashFunction::new(mbx::Base::Base64Url, ssi_multicodec::BLAKE3)
+            .expect("programmer error");
+    let new_self_hashable_toml = |toml: &str| {
+        SelfHashableTOML::new(
+            toml,
+            Cow::Owned(maplit::hashset! { Cow::Borrowed("service.selfHash") }),
+            Cow::Owned(HashSet::new()),
+        )
+    };
+
+    let mut self_hashable_toml = new_self_hashable_toml(config).expect("pass");
+    let self_hash = self_hashable_toml
+        .self_hash(mb_hash_function.new_hasher())
+        .expect("pass")
+        .to_owned();
+    self_hashable_toml.verify_self_hashes().expect("pass");
+    let hashed_config = self_hashable_toml.to_toml_string();
+    println!("toml after self-hashing:\n{}", hashed_config);
+
+    // The layout and comments are preserved, and only the self-hash slot changed.
+    assert_eq!(
+        hashed_config,
+        config.replace(
+            "selfHash = \"\" # set by selfhash",
+            &format!("selfHash = \"{}\" # set by selfhash", self_hash)
+        )
+    );
+    assert_eq!(
+        new_self_hashable_toml(&hashed_config)
+            .expect("pass")
+            .verify_self_hashes()
+            .expect("pass")
+            .to_owned(),
+        self_hash
+    );
+
+    // Reformatting (reordering keys, using dotted keys and inline tables, changing quoting, datetime
+    // syntax, and comments) doesn't change the self-hash, since it doesn't change the data.
+    let reformatted_config = format!(
+        r#"started = 1979-05-27T07:32:00.5Z
+name = 'billing'
+limits = {{ timeout = 2.5, max-connections = 100 }}
+service.selfHash = "{}"
+"#,
+        self_hash
+    );
+    new_self_hashable_toml(&reformatted_config)
+        .expect("pass")
+        .verify_self_hashes()
+        .expect("pass");
+
+    // Integers and floats are distinct, as are datetimes and strings.
+    for tampered_config in [
+        hashed_config.replace("max-connections = 100", "max-connections = 101"),
+        hashed_config.replace("max-connections = 100", "max-connections = 100.0"),
+        hashed_config.replace(
+            "started = 1979-05-27 07:32:00.500+00:00",
+            "started = \"1979-05-27T07:32:00.5Z\"",
+        ),
+        hashed_config.replace(
+            "started = 1979-05-27 07:32:00.500+00:00",
+            "started = 1979-05-27 07:32:00.500",
+        ),
+    ] {
+        assert_ne!(tampered_config, hashed_config);
+        assert!(new_self_hashable_toml(&tampered_config)
+            .expect("pass")
+            .verify_self_hashes()
+            .is_err());
+    }
+
+    // A missing self-hash field is inserted at the end of its table.
+    let mut self_hashable_toml =
+        new_self_hashable_toml("[service]\nname = \"billing\"\n").expect("pass");
+    self_hashable_toml
+        .self_hash(mb_hash_function.new_hasher())
+        .expect("pass");
+    self_hashable_toml.verify_self_hashes().expect("pass");
+    println!(
+        "toml after self-hashing:\n{}",
+        self_hashable_toml.to_toml_string()
+    );
+
+    // The self-hash field must be a string, and its parent table must exist.
+    assert!(new_self_hashable_toml("[service]\nselfHash = 3\n").is_err());
+    assert!(new_self_hashable_toml("name = \"billing\"\n").is_err());
+}
+
+/// Returns the descriptor pool for the protobuf messages used in the protobuf tests, which corresponds to:
+///
+/// ```proto
+/// syntax = "proto3";
+/// package selfhash.test;
+/// message Header { string self_hash_url = 1; }
+/// message Record {
+///     string self_hash = 1;
+///     string name = 2;
+///     repeated sint64 values = 3;
+///     map<string, int32> attributes = 4;
+///     Header header = 5;
+/// }
+/// message Reading {
+///     string self_hash = 1;
+///     string sensor = 2;
+///     map<string, sint64> counters = 3;
+/// }
+/// ```
+#[cfg(feature = "protobuf")]
+fn protobuf_test_descriptor_pool() -> &'static prost_reflect::DescriptorPool {
+    use prost_reflect::prost_types::{
+        field_descriptor_proto::{Label, Type},
+        DescriptorProto, FieldDescriptorProto, FileDescriptorProto, FileDescriptorSet,
+        MessageOptions,
+    };
+
+    fn field(
+        name: &str,
+        number: i32,
+        label: Label,
+        r#type: Type,
+        type_name_o: Option<&str>,
+    ) -> FieldDescriptorProto {
+        FieldDescriptorProto {
+            name: Some(name.to_string()),
+            number: Some(number),
+            label: Some(label as i32),
+            r#type: Some(r#type as i32),
+            type_name: type_name_o.map(str::to_string),
+            ..Default::default()
+        }
+    }
+    fn map_entry(name: &str, value_type: Type) -> DescriptorProto {
+        DescriptorProto {
+            name: Some(name.to_string()),
+            field: vec![
+                field("key", 1, Label::Optional, Type::String, None),
+                field("value", 2, Label::Optional, value_type, None),
+            ],
+            options: Some(MessageOptions {
+                map_entry: Some(true),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    static DESCRIPTOR_POOL: std::sync::OnceLock<prost_reflect::DescriptorPool> =
+        std::sync::OnceLock::new();
+    DESCRIPTOR_POOL.get_or_init(|| {
+        let file_descriptor_proto = FileDescriptorProto {
+            name: Some("selfhash_test.proto".to_string()),
+            package: Some("selfhash.test".to_string()),
+            syntax: Some("proto3".to_string()),
+            message_type: vec![
+                DescriptorProto {
+                    name: Some("Header".to_string()),
+                    field: vec![field(
+                        "self_hash_url",
+                        1,
+                        Label::Optional,
+                        Type::String,
+                        None,
+                    )],
+                    ..Default::default()
+                },
+                DescriptorProto {
+                    name: Some("Record".to_string()),
+                    field: vec![
+                        field("self_hash", 1, Label::Optional, Type::String, None),
+                        field("name", 2, Label::Optional, Type::String, None),
+                        field("values", 3, Label::Repeated, Type::Sint64, None),
+                        field(
+                            "attributes",
+                            4,
+                            Label::Repeated,
+                            Type::Message,
+                            Some(".selfhash.test.Record.AttributesEntry"),
+                        ),
+                        field(
+                            "header",
+                            5,
+                            Label::Optional,
+                            Type::Message,
+                            Some(".selfhash.test.Header"),
+                        ),
+                    ],
+                    nested_type: vec![map_entry("AttributesEntry", Type::Int32)],
+                    ..Default::default()
+                },
+                DescriptorProto {
+                    name: Some("Reading".to_string()),
+                    field: vec![
+                        field("self_hash", 1, Label::Optional, Type::String, None),
+                        field("sensor", 2, Label::Optional, Type::String, None),
+                        field(
+                            "counters",
+                            3,
+                            Label::Repeated,
+                            Type::Message,
+                            Some(".selfhash.test.Reading.CountersEntry"),
+                        ),
+                    ],
+                    nested_type: vec![map_entry("CountersEntry", Type::Sint64)],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        prost_reflect::DescriptorPool::from_file_descriptor_set(FileDescriptorSet {
+            file: vec![file_descriptor_proto],
+        })
+        .expect("programmer error")
+    })
+}
+
+#[cfg(feature = "protobuf")]
+#[test]
+fn test_self_hashable_protobuf() {
+    use prost::Message;
+    use prost_reflect::{DynamicMessage, MapKey, Value};
+    use selfhash::{protobuf::SelfHashableProtobuf, HashFunctionT, SelfHashableT};
+    use std::borrow::Cow;
+
+    let record_descriptor = protobuf_test_descriptor_pool()
+        .get_message_by_name("selfhash.test.Record")
+        .expect("pass");
+    let header_descriptor = protobuf_test_descriptor_pool()
+        .get_message_by_name("selfhash.test.Header")
+        .expect("pass");
+    let mb_hash_function =
+        selfhash::MBHashFunction::new(mbx::Base::Base64Url, ssi_multicodec::BLAKE3)
+            .expect("programmer error");
+    let self_hash_field_path_s = maplit::hashset! { Cow::Borrowed("self_hash") };
+    let self_hash_url_field_path_s = maplit::hashset! { Cow::Borrowed("header.self_hash_url") };
+    let new_self_hashable_protobuf = |encoded: &[u8]| {
+        SelfHashableProtobuf::new(
+            DynamicMessage::decode(record_descriptor.clone(), encoded).expect("pass"),
+            Cow::Borrowed(&self_hash_field_path_s),
+            Cow::Borrowed(&self_hash_url_field_path_s),
+        )
+    };
+    // Returns the encoding of a Record having only the given field set.
+    let encode_record_field = |field_name: &str, value: Value| {
+        let mut record = DynamicMessage::new(record_descriptor.clone());
+        record.set_field_by_name(field_name, value);
+        record.encode_to_vec()
+    };
+
+    let mut header = DynamicMessage::new(header_descriptor.clone());
+    header.set_field_by_name("self_hash_url", Value::String("vjson:///".to_string()));
+    let header = Value::Message(header);
+    let attributes = Value::Map(
+        [("zeta", 26), ("alpha", 1), ("mu", 12), ("beta", 2)]
+            .into_iter()
+            .map(|(key, value)| (MapKey::String(key.to_string()), Value::I32(value)))
+            .collect(),
+    );
+    let value_v = vec![3i64, -1, 4, -1, 5];
+
+    // The straightforward encoding (fields in order, packed repeated scalars, map entries in the order
+    // the HashMap iterates them).
+    let encoded_0 = {
+        let mut record = DynamicMessage::new(record_descriptor.clone());
+        record.set_field_by_name("name", Value::String("billing".to_string()));
+        record.set_field_by_name(
+            "values",
+            Value::List(value_v.iter().copied().map(Value::I64).collect()),
+        );
+        record.set_field_by_name("attributes", attributes.clone());
+        record.set_field_by_name("header", header.clone());
+        record.encode_to_vec()
+    };
+    // An equivalent encoding, with fields in reverse order, unpacked repeated scalars, map entries in
+    // the reverse of their key order, and a field that's overwritten (the last value wins).
+    let encoded_1 = {
+        let mut encoded = encode_record_field("header", header.clone());
+        let mut attribute_v = attributes
+            .as_map()
+            .unwrap()
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect::<Vec<_>>();
+        attribute_v.sort_by(|a, b| b.0.cmp(&a.0));
+        for (key, value) in attribute_v {
+            encoded.extend(encode_record_field(
+                "attributes",
+                Value::Map(maplit::hashmap! { key => value }),
+            ));
+        }
+        prost::encoding::sint64::encode_repeated(3, &value_v, &mut encoded);
+        encoded.extend(encode_record_field(
+            "name",
+            Value::String("stale".to_string()),
+        ));
+        encoded.extend(encode_record_field(
+            "name",
+            Value::String("billing".to_string()),
+        ));
+        encoded
+    };
+    assert_ne!(encoded_0, encoded_1);
+
+    // The canonical encodings are identical, and therefore so are the self-hashes.
+    let mut self_hashable_protobuf_0 = new_self_hashable_protobuf(&encoded_0).expect("pass");
+    let mut self_hashable_protobuf_1 = new_self_hashable_protobuf(&encoded_1).expect("pass");
+    assert_eq!(
+        self_hashable_protobuf_0
+            .to_canonical_protobuf()
+            .expect("pass"),
+        self_hashable_protobuf_1
+            .to_canonical_protobuf()
+            .expect("pass")
+    );
+    let self_hash = self_hashable_protobuf_0
+        .self_hash(mb_hash_function.new_hasher())
+        .expect("pass")
+        .to_owned();
+    assert_eq!(
+        self_hashable_protobuf_1
+            .self_hash(mb_hash_function.new_hasher())
+            .expect("pass")
+            .to_owned(),
+        self_hash
+    );
+    println!("protobuf self-hash: {}", self_hash);
+    let self_hash_url = format!("vjson:///{}", self_hash);
+    assert_eq!(
+        self_hashable_protobuf_0
+            .message()
+            .get_field_by_name("header")
+            .expect("pass")
+            .as_message()
+            .expect("pass")
+            .get_field_by_name("self_hash_url")
+            .expect("pass")
+            .as_str(),
+        Some(self_hash_url.as_str())
+    );
+
+    // The self-hashed message verifies after being re-encoded and decoded.
+    let hashed_encoded = self_hashable_protobuf_0.message().encode_to_vec();
+    assert_eq!(
+        new_self_hashable_protobuf(&hashed_encoded)
+            .expect("pass")
+            .verify_self_hashes()
+            .expect("pass")
+            .to_owned(),
+        self_hash
+    );
+
+    // Tampering with any field causes verification to fail.
+    for (field_name, value) in [
+        ("name", Value::String("billing!".to_string())),
+        ("values", Value::List(vec![Value::I64(3)])),
+        (
+            "attributes",
+            Value::Map(maplit::hashmap! { MapKey::String("alpha".to_string()) => Value::I32(2) }),
+        ),
+    ] {
+        let mut tampered_encoded = hashed_encoded.clone();
+        tampered_encoded.extend(encode_record_field(field_name, value));
+        assert!(new_self_hashable_protobuf(&tampered_encoded)
+            .expect("pass")
+            .verify_self_hashes()
+            .is_err());
+    }
+
+    // Unknown fields can't be canonically encoded, so they're rejected.
+    {
+        let mut encoded_with_unknown_field = encoded_0.clone();
+        prost::encoding::string::encode(
+            99,
+            &"unknown".to_string(),
+            &mut encoded_with_unknown_field,
+        );
+        assert!(new_self_hashable_protobuf(&encoded_with_unknown_field).is_err());
+    }
+
+    // Each field path must designate a singular string field, via singular message fields.
+    for bad_field_path in [
+        "nonexistent",
+        "values",
+        "attributes",
+        "header",
+        "name.self_hash",
+    ] {
+        assert!(SelfHashableProtobuf::new(
+            DynamicMessage::new(record_descriptor.clone()),
+            Cow::Owned(maplit::hashset! { Cow::Borrowed(bad_field_path) }),
+            Cow::Owned(maplit::hashset! {}),
+        )
+        .is_err());
+    }
+    // The self-hash URL slot must already be a valid self-hash URL.
+    assert!(new_self_hashable_protobuf(&encode_record_field(
+        "name",
+        Value::String("billing".to_string())
+    ))
+    .is_err());
+}
+
+#[cfg(feature = "protobuf")]
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ReadingMBHash {
+    #[prost(string, tag = "1")]
+    pub self_hash: String,
+    #[prost(string, tag = "2")]
+    pub sensor: String,
+    #[prost(map = "string, sint64", tag = "3")]
+    pub counters: std::collections::HashMap<String, i64>,
+}
+
+#[cfg(feature = "protobuf")]
+impl prost_reflect::ReflectMessage for ReadingMBHash {
+    fn descriptor(&self) -> prost_reflect::MessageDescriptor {
+        protobuf_test_descriptor_pool()
+            .get_message_by_name("selfhash.test.Reading")
+            .expect("programmer error")
+    }
+}
+
+#[cfg(feature = "protobuf")]
+impl selfhash::SelfHashableT<mbx::MBHashStr> for ReadingMBHash {
+    fn write_digest_data(
+        &self,
+        hasher: &mut <<mbx::MBHashStr as selfhash::HashRefT>::HashFunction as selfhash::HashFunctionT<mbx::MBHashStr>>::Hasher,
+    ) -> selfhash::Result<()> {
+        selfhash::protobuf::write_digest_data_using_protobuf(self, hasher)
+    }
+    fn self_hash_oi<'a, 'b: 'a>(
+        &'b self,
+    ) -> selfhash::Result<Box<dyn std::iter::Iterator<Item = Option<&'b mbx::MBHashStr>> + 'a>>
+    {
+        let self_hash_o = if self.self_hash.is_empty() {
+            None
+        } else {
+            Some(
+                mbx::MBHashStr::new_ref(self.self_hash.as_str())
+                    .map_err(|e| selfhash::Error::from(e.to_string()))?,
+            )
+        };
+        Ok(Box::new(std::iter::once(self_hash_o)))
+    }
+    fn set_self_hash_slots_to(&mut self, hash: &mbx::MBHashStr) -> selfhash::Result<()> {
+        self.self_hash = hash.as_str().to_string();
+        Ok(())
+    }
+}
+
+#[cfg(feature = "protobuf")]
+#[test]
+fn test_self_hashable_protobuf_prost_message() {
+    use selfhash::{protobuf::SelfHashableProtobuf, HashFunctionT, SelfHashableT};
+    use std::borrow::Cow;
+
+    let mb_hash_function =
+        selfhash::MBHashFunction::new(mbx::Base::Base64Url, ssi_multicodec::BLAKE3)
+            .expect("programmer error");
+    let counter_v = (0..20)
+        .map(|i| (format!("counter-{}", i), i * i - 100))
+        .collect::<Vec<_>>();
+
+    let mut reading = ReadingMBHash {
+        self_hash: String::new(),
+        sensor: "thermometer-7".to_string(),
+        counters: counter_v.iter().cloned().collect(),
+    };
+    let self_hash = reading
+        .self_hash(mb_hash_function.new_hasher())
+        .expect("pass")
+        .to_owned();
+    reading.verify_self_hashes().expect("pass");
+    println!("reading: {:?}", reading);
+
+    // A HashMap populated in a different order (and so generally iterated, and encoded by prost, in a
+    // different order) doesn't change the self-hash.
+    let reading_with_reordered_counters = ReadingMBHash {
+        counters: counter_v.iter().rev().cloned().collect(),
+        ..reading.clone()
+    };
+    assert_eq!(
+        reading_with_reordered_counters
+            .verify_self_hashes()
+            .expect("pass")
+            .to_owned(),
+        self_hash
+    );
+
+    // SelfHashableProtobuf computes the same self-hash from the field path.
+    let self_hashable_protobuf = SelfHashableProtobuf::from_message(
+        &reading,
+        Cow::Owned(maplit::hashset! { Cow::Borrowed("self_hash") }),
+        Cow::Owned(maplit::hashset! {}),
+    )
+    .expect("pass");
+    assert_eq!(
+        self_hashable_protobuf
+            .verify_self_hashes()
+            .expect("pass")
+            .to_owned(),
+        self_hash
+    );
+    assert_eq!(
+        self_hashable_protobuf
+            .to_message::<ReadingMBHash>()
+            .expect("pass"),
+        reading
+    );
+
+    // Tampering causes verification to fail.
+    let mut tampered_reading = reading.clone();
+    tampered_reading
+        .counters
+        .insert("counter-0".to_string(), -99);
+    assert!(tampered_reading.verify_self_hashes().is_err());
+}
+
+#[cfg(feature = "self-hashable-xml")]
+#[test]
+fn test_self_hashable_xml() {
+    use selfhash::{HashFunctionT, SelfHashableT, SelfHashableXML};
+    use std::{borrow::Cow, collections::HashSet};
+
+    let mb_hash_function =
+        selfhash::MBHashFunction::new(mbx::Base::Base64Url, ssi_multicodec::BLAKE3)
+            .expect("programmer error");
+
+    // A self-hash attribute of the document element, which is added if it's missing.
+    {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!-- Submitted by the regulator. -->
+<record id="r-17" xmlns="urn:example:records" xmlns:unused="urn:example:unused">
+  <amount currency="USD">125.00</amount>
+  <memo><![CDATA[Q3 <adjusted>]]></memo>
+  <approved/>
+</record>
+"#;
+        let self_hash_path_s = maplit::hashset! { Cow::Borrowed("/record/@selfHash") };
+        let new_self_hashable_xml = |xml: &str| {
+            SelfHashableXML::new(
+                xml,
+                Cow::Borrowed(&self_hash_path_s),
+                Cow::Owned(HashSet::new()),
+            )
+        };
+
+        let mut self_hashable_xml = new_self_hashable_xml(xml).expect("pass");
+        let self_hash = self_hashable_xml
+            .self_hash(mb_hash_function.new_hasher())
+            .expect("pass")
+            .to_owned();
+        self_hashable_xml.verify_self_hashes().expect("pass");
+        let hashed_xml = self_hashable_xml.to_c14n_string();
+        println!("xml after self-hashing:\n{}", hashed_xml);
+        // The output is canonical: no XML declaration or comments, attributes sorted, unused namespace
+        // declarations dropped, empty elements expanded, and CDATA replaced by escaped text.
+        assert_eq!(
+            hashed_xml,
+            format!(
+                r#"<record xmlns="urn:example:records" id="r-17" selfHash="{}">
+  <amount currency="USD">125.00</amount>
+  <memo>Q3 &lt;adjusted&gt;</memo>
+  <approved></approved>
+</record>"#,
+                self_hash
+            )
+        );
+        assert_eq!(
+            new_self_hashable_xml(&hashed_xml)
+                .expect("pass")
+                .verify_self_hashes()
+                .expect("pass")
+                .to_owned(),
+            self_hash
+        );
+
+        // Differences in serialization which don't change the canonical form don't change the self-hash.
+        let reformatted_xml = format!(
+            "<record selfHash='{}'   id='r-17' xmlns='urn:example:records'>\r\n  <amount currency=\"USD\">125.00</amount>\n  <memo>Q3 &lt;adjusted></memo>\n  <approved></approved>\n</record><!-- end -->",
+            self_hash
+        );
+        new_self_hashable_xml(&reformatted_xml)
+            .expect("pass")
+            .verify_self_hashes()
+            .expect("pass");
+
+        // Changes to the content do change the self-hash.
+        for tampered_xml in [
+            hashed_xml.replace("125.00", "125.01"),
+            hashed_xml.replace("currency=\"USD\"", "currency=\"EUR\""),
+            hashed_xml.replace("  <approved>", "<approved>"),
+            hashed_xml.replace("urn:example:records", "urn:example:other"),
+        ] {
+            assert_ne!(tampered_xml, hashed_xml);
+            assert!(new_self_hashable_xml(&tampered_xml)
+                .expect("pass")
+                .verify_self_hashes()
+                .is_err());
+        }
+    }
+
+    // A self-hash element and a self-hash URL attribute selected with an attribute predicate, in a document
+    // with namespace prefixes.
+    {
+        let xml = r#"<rpt:report xmlns:rpt="urn:example:report" xmlns:xlink="http://www.w3.org/1999/xlink">
+  <rpt:header><rpt:selfHash></rpt:selfHash></rpt:header>
+  <rpt:link rel="self" xlink:href="https://reports.example.com/report?selfHash="/>
+  <rpt:link rel="next" xlink:href="https://reports.example.com/next"/>
+</rpt:report>"#;
+        let self_hash_path_s =
+            maplit::hashset! { Cow::Borrowed("/rpt:report/rpt:header/rpt:selfHash") };
+        let self_hash_url_path_s =
+            maplit::hashset! { Cow::Borrowed("//rpt:link[@rel='self']/@xlink:href") };
+        let new_self_hashable_xml = |xml: &str| {
+            SelfHashableXML::new(
+                xml,
+                Cow::Borrowed(&self_hash_path_s),
+                Cow::Borrowed(&self_hash_url_path_s),
+            )
+        };
+
+        let mut self_hashable_xml = new_self_hashable_xml(xml).expect("pass");
+        let self_hash = self_hashable_xml
+            .self_hash(mb_hash_function.new_hasher())
+            .expect("pass")
+            .to_owned();
+        let hashed_xml = self_hashable_xml.to_c14n_string();
+        println!("xml after self-hashing:\n{}", hashed_xml);
+        assert!(hashed_xml.contains(&format!("<rpt:selfHash>{}</rpt:selfHash>", self_hash)));
+        assert!(hashed_xml.contains(&format!(
+            "xlink:href=\"https://reports.example.com/report?selfHash={}\"",
+            self_hash
+        )));
+        assert!(hashed_xml.contains("xlink:href=\"https://reports.example.com/next\""));
+        assert_eq!(
+            new_self_hashable_xml(&hashed_xml)
+                .expect("pass")
+                .verify_self_hashes()
+                .expect("pass")
+                .to_owned(),
+            self_hash
+        );
+
+        // Changing the prefix of a namespace changes the canonical form, and therefore the self-hash.
+        assert!(new_self_hashable_xml(&hashed_xml.replace("xlink", "xl"))
+            .expect("pass")
+            .verify_self_hashes()
+            .is_err());
+
+        // A self-hash element must contain only text.
+        assert!(new_self_hashable_xml(&xml.replace(
+            "<rpt:selfHash></rpt:selfHash>",
+            "<rpt:selfHash><rpt:x/></rpt:selfHash>"
+        ))
+        .is_err());
+        // A self-hash URL slot must exist and be a valid self-hash URL.
+        assert!(new_self_hashable_xml(&xml.replace("rel=\"self\"", "rel=\"alternate\"")).is_err());
+        assert!(new_self_hashable_xml(&xml.replace("report?selfHash=", "report")).is_err());
+    }
+
+    // Malformed input and selectors are rejected.
+    for (xml, self_hash_path) in [
+        // DTDs aren't allowed, since they could affect the canonical form.
+        (
+            "<!DOCTYPE record [<!ENTITY e \"x\">]><record>&e;</record>",
+            "/record/@selfHash",
+        ),
+        ("<record>", "/record/@selfHash"),
+        ("<record/>", "record/@selfHash"),
+        ("<record/>", "/record/@selfHash/x"),
+        ("<record/>", "/record[0]/@selfHash"),
+        // A missing self-hash element can't be added.
+        ("<record/>", "/record/selfHash"),
+        // A self-hash attribute can only be added to an unambiguous element.
+        (
+            "<records><record/><record/></records>",
+            "//record/@selfHash",
+        ),
+        ("<record selfHash=\"not a hash\"/>", "/record/@selfHash"),
+    ] {
+        assert!(SelfHashableXML::new(
+            xml,
+            Cow::Owned(maplit::hashset! { Cow::Borrowed(self_hash_path) }),
+            Cow::Owned(HashSet::new()),
+        )
+        .and_then(|mut self_hashable_xml| {
+            self_hashable_xml
+                .self_hash(mb_hash_function.new_hasher())
+                .map(|_| ())
+        })
+        .is_err());
+    }
+}
+
+#[cfg(feature = "self-hashable-text")]
+#[test]
+fn test_self_hashable_text() {
+    use selfhash::{
+        HashFunctionT, LineEndingNormalization, SelfHashSlotCardinality, SelfHashableT,
+        SelfHashableText,
+    };
+    use std::{borrow::Cow, collections::HashSet};
+
+    let mb_hash_function =
+        selfhash::MBHashFunction::new(mbx::Base::Base64Url, ssi_multicodec::BLAKE3)
+            .expect("programmer error");
+
+    // A Markdown policy with a header line given by a template, and a footer line given by a regex.
+    let text = "selfhash: \n\n# Retention Policy\n\nRecords are kept for 7 years.\n\n<!-- self-hash:  -->\n";
+    let self_hash_template_s = maplit::hashset! { Cow::Borrowed("selfhash: {selfHash}") };
+    let self_hash_regex_s =
+        maplit::hashset! { Cow::Borrowed(r"<!-- self-hash: (?P<selfHash>[^ ]*) -->") };
+    let new_self_hashable_text = |text: &str| {
+        SelfHashableText::new(
+            text,
+            Cow::Borrowed(&self_hash_regex_s),
+            Cow::Borrowed(&self_hash_template_s),
+        )
+    };
+
+    let mut self_hashable_text = new_self_hashable_text(text).expect("pass");
+    assert!(self_hashable_text.verify_self_hashes().is_err());
+    let self_hash = self_hashable_text
+        .self_hash(mb_hash_function.new_hasher())
+        .expect("pass")
+        .to_owned();
+    self_hashable_text.verify_self_hashes().expect("pass");
+    let hashed_text = String::from_utf8(self_hashable_text.as_bytes().to_vec()).expect("pass");
+    println!("text after self-hashing:\n{}", hashed_text);
+    // Nothing other than the self-hash slots is changed.
+    assert_eq!(
+        hashed_text,
+        format!(
+            "selfhash: {}\n\n# Retention Policy\n\nRecords are kept for 7 years.\n\n<!-- self-hash: {} -->\n",
+            self_hash, self_hash
+        )
+    );
+    assert_eq!(
+        new_self_hashable_text(&hashed_text)
+            .expect("pass")
+            .verify_self_hashes()
+            .expect("pass")
+            .to_owned(),
+        self_hash
+    );
+
+    // The raw bytes are hashed, so any change (including to whitespace or line endings) changes the
+    // self-hash.
+    for tampered_text in [
+        hashed_text.replace("7 years", "8 years"),
+        hashed_text.replace("Policy\n", "Policy \n"),
+        hashed_text.replace('\n', "\r\n"),
+    ] {
+        assert!(new_self_hashable_text(&tampered_text)
+            .expect("pass")
+            .verify_self_hashes()
+            .is_err());
+    }
+
+    // With LF normalization, converting line endings doesn't change the self-hash, but the text is still
+    // output as it was given.
+    {
+        let mut self_hashable_text = new_self_hashable_text(text)
+            .expect("pass")
+            .with_line_ending_normalization(LineEndingNormalization::LF);
+        let self_hash = self_hashable_text
+            .self_hash(mb_hash_function.new_hasher())
+            .expect("pass")
+            .to_owned();
+        let hashed_text = String::from_utf8(self_hashable_text.into_bytes()).expect("pass");
+        assert!(!hashed_text.contains('\r'));
+        for converted_text in [hashed_text.clone(), hashed_text.replace('\n', "\r\n")] {
+            assert_eq!(
+                new_self_hashable_text(&converted_text)
+                    .expect("pass")
+                    .with_line_ending_normalization(LineEndingNormalization::LF)
+                    .verify_self_hashes()
+                    .expect("pass")
+                    .to_owned(),
+                self_hash
+            );
+        }
+    }
+
+    // A template matching several slots is fine by default, but can be constrained.
+    let self_hash_template_s = maplit::hashset! { Cow::Borrowed("[{selfHash}]") };
+    let new_self_hashable_text = |text: &str| {
+        SelfHashableText::new(
+            text,
+            Cow::Owned(HashSet::new()),
+            Cow::Borrowed(&self_hash_template_s),
+        )
+    };
+    let mut self_hashable_text = new_self_hashable_text("id [] and copy []").expect("pass");
+    let self_hash = self_hashable_text
+        .self_hash(mb_hash_function.new_hasher())
+        .expect("pass")
+        .to_owned();
+    assert_eq!(
+        self_hashable_text.as_bytes(),
+        format!("id [{}] and copy [{}]", self_hash, self_hash).as_bytes()
+    );
+    assert!(new_self_hashable_text("id [] and copy []")
+        .expect("pass")
+        .with_self_hash_slot_cardinality("[{selfHash}]", SelfHashSlotCardinality::Exactly(1))
+        .is_err());
+    // A marker which matches nothing is an error by default, since slots can't be inserted into text.
+    assert!(new_self_hashable_text("no slots here").is_err());
+    // Slots must be empty or valid self-hashes.
+    assert!(new_self_hashable_text("id [xyz]").is_err());
+
+    // Malformed markers and overlapping slots are rejected.
+    for (self_hash_regex, self_hash_template) in [
+        (Some("selfhash: (unclosed"), None),
+        (Some("selfhash: \\S*"), None),
+        (Some("(a)(b)"), None),
+        (None, Some("selfhash: no placeholder")),
+        (
+            Some("selfhash: (?P<selfHash>\\S*)"),
+            Some("selfhash: {selfHash}"),
+        ),
+    ] {
+        assert!(SelfHashableText::new(
+            "selfhash: \n",
+            Cow::Owned(self_hash_regex.map(Cow::Borrowed).into_iter().collect()),
+            Cow::Owned(self_hash_template.map(Cow::Borrowed).into_iter().collect()),
+        )
+        .is_err());
+    }
+}
+
+#[cfg(all(
+    feature = "self-hashable-binary",
+    feature = "blake3",
+    feature = "sha-256"
+))]
+#[test]
+fn test_self_hashable_binary() {
+    use selfhash::{
+        BinarySlot, BinarySlotEncoding, BinarySlotFill, NamedHashFunction, SelfHashableBinary,
+    };
+
+    // A firmware image with a 16-byte magic header followed by a 32-byte self-hash slot, and a payload
+    // that's larger than the hashing chunk size, with a second copy of the self-hash in a trailer.
+    let mut image = b"FWIMAGE\0v1.2.3\0\0".to_vec();
+    let header_slot = BinarySlot::new(image.len() as u64, 32);
+    image.extend_from_slice(&[0u8; 32]);
+    image.extend((0..200_000u32).map(|i| (i % 251) as u8));
+    let trailer_slot = BinarySlot::new(image.len() as u64, 32);
+    image.extend_from_slice(&[0u8; 32]);
+    image.extend_from_slice(b"END");
+
+    let self_hashable_binary = SelfHashableBinary::new(
+        NamedHashFunction::BLAKE3,
+        BinarySlotEncoding::RawDigest,
+        vec![trailer_slot, header_slot],
+    )
+    .expect("pass");
+    assert_eq!(
+        self_hashable_binary
+            .verify_self_hashes_of_bytes(&image)
+            .err()
+            .map(|e| e.kind()),
+        Some(selfhash::ErrorKind::MissingSlot)
+    );
+    let self_hash = self_hashable_binary
+        .self_hash_bytes(&mut image)
+        .expect("pass");
+    assert_eq!(self_hash.named_hash_function(), NamedHashFunction::BLAKE3);
+    assert_eq!(&image[16..48], self_hash.bytes());
+    assert_eq!(
+        &image[trailer_slot.offset() as usize..trailer_slot.end() as usize],
+        self_hash.bytes()
+    );
+    assert_eq!(
+        self_hashable_binary
+            .verify_self_hashes_of_bytes(&image)
+            .expect("pass"),
+        self_hash
+    );
+    // The digest is over the image with the slots zeroed.
+    let mut zeroed_image = image.clone();
+    zeroed_image[16..48].fill(0);
+    zeroed_image[trailer_slot.offset() as usize..trailer_slot.end() as usize].fill(0);
+    assert_eq!(blake3::hash(&zeroed_image).as_bytes(), self_hash.bytes());
+
+    // Tampering with the payload or with one of the slots is detected.
+    for i in [0, 20, 100_000, image.len() - 1] {
+        let mut tampered_image = image.clone();
+        tampered_image[i] ^= 0x01;
+        assert_eq!(
+            self_hashable_binary
+                .verify_self_hashes_of_bytes(&tampered_image)
+                .err()
+                .map(|e| e.kind()),
+            Some(selfhash::ErrorKind::HashMismatch)
+        );
+    }
+
+    // Files are self-hashed and verified through Read + Seek, without being loaded into memory.
+    {
+        let path =
+            std::env::temp_dir().join(format!("selfhash-test-binary-{}.img", std::process::id()));
+        let mut unhashed_image = image.clone();
+        unhashed_image[16..48].fill(0);
+        unhashed_image[trailer_slot.offset() as usize..trailer_slot.end() as usize].fill(0);
+        std::fs::write(&path, &unhashed_image).expect("pass");
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .expect("pass");
+        assert_eq!(
+            self_hashable_binary.self_hash(&mut file).expect("pass"),
+            self_hash
+        );
+        drop(file);
+        assert_eq!(std::fs::read(&path).expect("pass"), image);
+        let mut file = std::fs::File::open(&path).expect("pass");
+        assert_eq!(
+            self_hashable_binary
+                .verify_self_hashes(&mut file)
+                .expect("pass"),
+            self_hash
+        );
+        std::fs::remove_file(&path).expect("pass");
+    }
+
+    // Multihash slots carry the multicodec code and digest length, and can be filled with the placeholder
+    // (which keeps the multihash header) instead of zeros.
+    for slot_fill in [BinarySlotFill::Zeros, BinarySlotFill::Placeholder] {
+        let self_hashable_binary = SelfHashableBinary::new(
+            NamedHashFunction::SHA256,
+            BinarySlotEncoding::Multihash,
+            vec![BinarySlot::new(4, 34)],
+        )
+        .expect("pass")
+        .with_slot_fill(slot_fill);
+        assert_eq!(self_hashable_binary.slot_length(), 34);
+        let mut image = b"HDR:".to_vec();
+        image.extend_from_slice(&[0u8; 34]);
+        image.extend_from_slice(b"payload");
+        let self_hash = self_hashable_binary
+            .self_hash_bytes(&mut image)
+            .expect("pass");
+        assert_eq!(&image[4..6], &[0x12, 0x20]);
+        assert_eq!(&image[6..38], self_hash.bytes());
+        let mut preimage = image.clone();
+        match slot_fill {
+            BinarySlotFill::Zeros => preimage[4..38].fill(0),
+            BinarySlotFill::Placeholder => preimage[6..38].fill(0),
+        }
+        use sha2::Digest;
+        assert_eq!(
+            sha2::Sha256::digest(&preimage).as_slice(),
+            self_hash.bytes()
+        );
+        self_hashable_binary
+            .verify_self_hashes_of_bytes(&image)
+            .expect("pass");
+        // A slot which doesn't hold a SHA-256 multihash is rejected.
+        let mut tampered_image = image.clone();
+        tampered_image[4] = 0x1e;
+        assert!(self_hashable_binary
+            .verify_self_hashes_of_bytes(&tampered_image)
+            .is_err());
+    }
+
+    // Slots must have the right length, must not overlap, and must lie within the image.
+    assert!(SelfHashableBinary::new(
+        NamedHashFunction::BLAKE3,
+        BinarySlotEncoding::RawDigest,
+        vec![]
+    )
+    .is_err());
+    assert!(SelfHashableBinary::new(
+        NamedHashFunction::BLAKE3,
+        BinarySlotEncoding::RawDigest,
+        vec![BinarySlot::new(0, 31)]
+    )
+    .is_err());
+    assert!(SelfHashableBinary::new(
+        NamedHashFunction::BLAKE3,
+        BinarySlotEncoding::RawDigest,
+        vec![BinarySlot::new(0, 32), BinarySlot::new(31, 32)]
+    )
+    .is_err());
+    // A slot whose end overflows is rejected (rather than panicking), whatever its length.
+    for length in [32, u64::MAX] {
+        assert!(SelfHashableBinary::new(
+            NamedHashFunction::BLAKE3,
+            BinarySlotEncoding::RawDigest,
+            vec![BinarySlot::new(u64::MAX - 1, length)]
+        )
+        .is_err());
+    }
+    assert_eq!(BinarySlot::new(u64::MAX - 1, 32).end(), u64::MAX);
+    assert!(SelfHashableBinary::new(
+        NamedHashFunction::BLAKE3,
+        BinarySlotEncoding::RawDigest,
+        vec![BinarySlot::new(16, 32)]
+    )
+    .expect("pass")
+    .self_hash_bytes(&mut [0u8; 40])
+    .is_err());
+}
+
+#[cfg(all(feature = "tree", feature = "blake3"))]
+#[test]
+fn test_tree_manifest() {
+    use selfhash::tree::{
+        compute_tree_manifest, verify_tree_manifest, write_tree_manifest, TreeDiff, TreeManifest,
+        MANIFEST_FILE_NAME,
+    };
+
+    let mb_hash_function = selfhash::MBHashFunction::blake3(mbx::Base::Base64Url);
+    let root_dir = std::env::temp_dir().join(format!("selfhash-test-tree-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root_dir);
+    std::fs::create_dir_all(root_dir.join("bin")).expect("pass");
+    std::fs::create_dir_all(root_dir.join("docs/api")).expect("pass");
+    std::fs::write(root_dir.join("README.md"), "# Release 1.0\n").expect("pass");
+    std::fs::write(root_dir.join("bin/tool"), [0x7f, b'E', b'L', b'F']).expect("pass");
+    std::fs::write(root_dir.join("docs/api/index.html"), "<html></html>").expect("pass");
+    std::fs::write(root_dir.join("docs/changelog.txt"), "").expect("pass");
+
+    let tree_manifest = write_tree_manifest(&root_dir, &mb_hash_function).expect("pass");
+    let self_hash = tree_manifest.self_hash.clone().expect("pass");
+    println!(
+        "tree manifest: {}",
+        tree_manifest.to_json_string().expect("pass")
+    );
+    // The entries are sorted by path and don't include the manifest itself.
+    assert_eq!(
+        tree_manifest
+            .entries
+            .iter()
+            .map(|entry| entry.path.as_str())
+            .collect::<Vec<_>>(),
+        vec![
+            "README.md",
+            "bin/tool",
+            "docs/api/index.html",
+            "docs/changelog.txt"
+        ]
+    );
+    assert_eq!(tree_manifest.entries[0].size, 14);
+    assert_eq!(
+        tree_manifest.entries[0].digest,
+        mb_hash_function.hash(b"# Release 1.0\n")
+    );
+    assert_eq!(tree_manifest.verify_self_hash().expect("pass"), self_hash);
+    // The manifest is deterministic.
+    assert_eq!(
+        compute_tree_manifest(&root_dir, &mb_hash_function).expect("pass"),
+        tree_manifest
+    );
+    // The manifest file is self-hashed JSON.
+    let manifest_json = std::fs::read_to_string(root_dir.join(MANIFEST_FILE_NAME)).expect("pass");
+    assert_eq!(
+        TreeManifest::from_json_str(&manifest_json).expect("pass"),
+        tree_manifest
+    );
+    // A manifest must have the invariants enforced by TreeManifest::new: its entries are sorted by path,
+    // no path is listed more than once, and every digest uses the manifest's hash function.
+    {
+        let manifest_value =
+            serde_json::from_str::<serde_json::Value>(&manifest_json).expect("pass");
+        let mut unsorted_value = manifest_value.clone();
+        unsorted_value["entries"]
+            .as_array_mut()
+            .expect("pass")
+            .swap(0, 1);
+        let mut duplicated_value = manifest_value.clone();
+        let entry_0 = duplicated_value["entries"][0].clone();
+        duplicated_value["entries"]
+            .as_array_mut()
+            .expect("pass")
+            .insert(0, entry_0);
+        let mut mismatched_value = manifest_value.clone();
+        mismatched_value["entries"][0]["digest"] = serde_json::to_value(
+            selfhash::MBHashFunction::blake3(mbx::Base::Base58Btc).hash(b"# Release 1.0\n"),
+        )
+        .expect("pass");
+        for invalid_value in [unsorted_value, duplicated_value, mismatched_value] {
+            let error = TreeManifest::from_json_str(&invalid_value.to_string()).expect_err("pass");
+            println!("expected error: {}", error);
+        }
+        let mut invalid_tree_manifest = tree_manifest.clone();
+        invalid_tree_manifest.entries.swap(0, 1);
+        assert!(invalid_tree_manifest.verify_self_hash().is_err());
+    }
+
+    let tree_verification = verify_tree_manifest(&root_dir).expect("pass");
+    assert!(tree_verification.is_verified());
+    assert_eq!(tree_verification.self_hash, self_hash);
+
+    // Changes to the directory tree are reported.
+    std::fs::write(root_dir.join("README.md"), "# Release 1.1\n").expect("pass");
+    std::fs::remove_file(root_dir.join("docs/changelog.txt")).expect("pass");
+    std::fs::write(root_dir.join("docs/api/extra.js"), "").expect("pass");
+    let tree_verification = verify_tree_manifest(&root_dir).expect("pass");
+    assert!(!tree_verification.is_verified());
+    assert_eq!(
+        tree_verification.diff,
+        TreeDiff {
+            added_v: vec!["docs/api/extra.js".to_string()],
+            removed_v: vec!["docs/changelog.txt".to_string()],
+            modified_v: vec!["README.md".to_string()],
+        }
+    );
+    assert_eq!(
+        tree_verification.diff.to_string(),
+        "added: docs/api/extra.js\nremoved: docs/changelog.txt\nmodified: README.md\n"
+    );
+
+    // Rewriting the manifest covers the new state of the directory tree.
+    let new_tree_manifest = write_tree_manifest(&root_dir, &mb_hash_function).expect("pass");
+    assert_ne!(new_tree_manifest.self_hash.as_ref(), Some(&self_hash));
+    assert_ne!(new_tree_manifest.merkle_root, tree_manifest.merkle_root);
+    assert!(verify_tree_manifest(&root_dir).expect("pass").is_verified());
+
+    // Tampering with the manifest itself is an error, even if its self-hash is unchanged.
+    let mut tampered_tree_manifest = new_tree_manifest.clone();
+    tampered_tree_manifest.entries[0].mode = "0777".to_string();
+    assert!(tampered_tree_manifest.verify_self_hash().is_err());
+    std::fs::write(
+        root_dir.join(MANIFEST_FILE_NAME),
+        tampered_tree_manifest.to_json_string().expect("pass"),
+    )
+    .expect("pass");
+    assert!(verify_tree_manifest(&root_dir).is_err());
+
+    std::fs::remove_dir_all(&root_dir).expect("pass");
+}
+
+#[cfg(all(feature = "archive", feature = "blake3"))]
+#[test]
+fn test_archive_manifest() {
+    use selfhash::{
+        archive::{
+            compute_tar_manifest, compute_zip_manifest, verify_tar_manifest, verify_zip_manifest,
+            write_tar_with_manifest, write_zip_with_manifest,
+        },
+        tree::MANIFEST_FILE_NAME,
+    };
+    use std::io::{Cursor, Write};
+
+    let mb_hash_function = selfhash::MBHashFunction::blake3(mbx::Base::Base64Url);
+    let file_v: &[(&str, u32, &[u8])] = &[
+        ("README.md", 0o644, b"# Release 1.0\n"),
+        ("bin/tool", 0o755, &[0x7f, b'E', b'L', b'F']),
+        ("docs/changelog.txt", 0o644, b""),
+    ];
+
+    let build_tar = |file_v: &[(&str, u32, &[u8])]| {
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Directory);
+        header.set_mode(0o755);
+        header.set_size(0);
+        builder
+            .append_data(&mut header, "./bin/", std::io::empty())
+            .expect("pass");
+        for (path, mode, content) in file_v.iter() {
+            let mut header = tar::Header::new_gnu();
+            header.set_mode(*mode);
+            header.set_size(content.len() as u64);
+            builder
+                .append_data(&mut header, path, *content)
+                .expect("pass");
+        }
+        builder.into_inner().expect("pass")
+    };
+    let build_zip = |file_v: &[(&str, u32, &[u8])]| {
+        let mut zip_writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        zip_writer
+            .add_directory("bin/", zip::write::SimpleFileOptions::default())
+            .expect("pass");
+        for (path, mode, content) in file_v.iter() {
+            zip_writer
+                .start_file(
+                    *path,
+                    zip::write::SimpleFileOptions::default().unix_permissions(*mode),
+                )
+                .expect("pass");
+            zip_writer.write_all(content).expect("pass");
+        }
+        zip_writer.finish().expect("pass").into_inner()
+    };
+
+    // tar
+    {
+        let tar_bytes = build_tar(file_v);
+        let tree_manifest =
+            compute_tar_manifest(tar_bytes.as_slice(), &mb_hash_function).expect("pass");
+        assert_eq!(
+            tree_manifest
+                .entries
+                .iter()
+                .map(|entry| (entry.path.as_str(), entry.mode.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("README.md", "0644"),
+                ("bin/tool", "0755"),
+                ("docs/changelog.txt", "0644")
+            ]
+        );
+        let mut self_hashed_tar_bytes = Vec::new();
+        let written_tree_manifest = write_tar_with_manifest(
+            Cursor::new(&tar_bytes),
+            &mut self_hashed_tar_bytes,
+            &mb_hash_function,
+        )
+        .expect("pass");
+        assert_eq!(written_tree_manifest, tree_manifest);
+        let self_hash = verify_tar_manifest(self_hashed_tar_bytes.as_slice()).expect("pass");
+        assert_eq!(Some(self_hash), tree_manifest.self_hash);
+        // The manifest entry is excluded from the manifest, so rewriting it is idempotent.
+        let mut rewritten_tar_bytes = Vec::new();
+        write_tar_with_manifest(
+            Cursor::new(&self_hashed_tar_bytes),
+            &mut rewritten_tar_bytes,
+            &mb_hash_function,
+        )
+        .expect("pass");
+        assert_eq!(rewritten_tar_bytes, self_hashed_tar_bytes);
+
+        // An unlisted entry is an error.
+        let mut builder = tar::Builder::new(Vec::new());
+        let manifest_json = tree_manifest.to_json_string().expect("pass");
+        let mut header = tar::Header::new_gnu();
+        header.set_mode(0o644);
+        header.set_size(manifest_json.len() as u64);
+        builder
+            .append_data(&mut header, MANIFEST_FILE_NAME, manifest_json.as_bytes())
+            .expect("pass");
+        for (path, mode, content) in
+            file_v
+                .iter()
+                .chain(std::iter::once(&("extra.txt", 0o644, b"extra".as_slice())))
+        {
+            let mut header = tar::Header::new_gnu();
+            header.set_mode(*mode);
+            header.set_size(content.len() as u64);
+            builder
+                .append_data(&mut header, path, *content)
+                .expect("pass");
+        }
+        let tampered_tar_bytes = builder.into_inner().expect("pass");
+        let error = verify_tar_manifest(tampered_tar_bytes.as_slice()).expect_err("pass");
+        println!("expected error: {}", error);
+        assert!(error.to_string().contains("not listed in the manifest"));
+        // A missing manifest is an error.
+        assert!(verify_tar_manifest(tar_bytes.as_slice()).is_err());
+    }
+
+    // zip
+    {
+        let zip_bytes = build_zip(file_v);
+        let tree_manifest =
+            compute_zip_manifest(Cursor::new(&zip_bytes), &mb_hash_function).expect("pass");
+        assert_eq!(tree_manifest.entries.len(), 3);
+        assert_eq!(tree_manifest.entries[1].mode, "0755");
+        let mut self_hashed_zip_bytes = Cursor::new(Vec::new());
+        write_zip_with_manifest(
+            Cursor::new(&zip_bytes),
+            &mut self_hashed_zip_bytes,
+            &mb_hash_function,
+        )
+        .expect("pass");
+        let self_hashed_zip_bytes = self_hashed_zip_bytes.into_inner();
+        let self_hash = verify_zip_manifest(Cursor::new(&self_hashed_zip_bytes)).expect("pass");
+        assert_eq!(Some(self_hash), tree_manifest.self_hash);
+
+        // Modified and unlisted entries are errors.
+        for (tampered_file_v, expected_error) in [
+            (
+                vec![
+                    ("README.md", 0o644, b"# Release 1.1\n".as_slice()),
+                    file_v[1],
+                    file_v[2],
+                ],
+                "doesn't match the manifest",
+            ),
+            (
+                vec![
+                    file_v[0],
+                    file_v[1],
+                    file_v[2],
+                    ("extra.txt", 0o644, b"extra".as_slice()),
+                ],
+                "not listed in the manifest",
+            ),
+            (vec![file_v[0], file_v[1]], "missing entries"),
+        ] {
+            let mut zip_writer =
+                zip::ZipWriter::new_append(Cursor::new(build_zip(&tampered_file_v))).expect("pass");
+            zip_writer
+                .start_file(MANIFEST_FILE_NAME, zip::write::SimpleFileOptions::default())
+                .expect("pass");
+            zip_writer
+                .write_all(tree_manifest.to_json_string().expect("pass").as_bytes())
+                .expect("pass");
+            let tampered_zip_bytes = zip_writer.finish().expect("pass").into_inner();
+            let error = verify_zip_manifest(Cursor::new(&tampered_zip_bytes)).expect_err("pass");
+            println!("expected error: {}", error);
+            assert!(error.to_string().contains(expected_error));
+        }
     }
 }